@@ -0,0 +1,72 @@
+//! Benchmarks encoding deeply nested messages, computing submessage lengths for each encoding or
+//! once up front, and decoding them.
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use prost::Message;
+
+/// A link in a chain of messages, each holding the next as a submessage.
+#[derive(Message)]
+struct Node {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(string, tag = "2")]
+    name: String,
+    #[prost(message, optional, boxed, tag = "3")]
+    child: Option<Box<Node>>,
+}
+
+fn chain(depth: u64) -> Node {
+    (0..depth).fold(Node::default(), |child, id| Node {
+        id,
+        name: format!("node {}", id),
+        child: Some(Box::new(child)),
+    })
+}
+
+fn main() {
+    let mut criterion = Criterion::default().configure_from_args();
+
+    let mut group = criterion.benchmark_group("nested_message");
+    for depth in [10, 100, 1000] {
+        let message = chain(depth);
+        group.throughput(Throughput::Bytes(message.encoded_len() as u64));
+
+        // Sizing the buffer and encoding each compute the lengths of all nested submessages.
+        group.bench_with_input(BenchmarkId::new("encode", depth), &message, |b, message| {
+            b.iter(|| {
+                let mut buf = Vec::with_capacity(message.encoded_len());
+                message.encode(&mut buf).unwrap();
+                criterion::black_box(&buf);
+            })
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("encode_with_sizes", depth),
+            &message,
+            |b, message| {
+                b.iter(|| {
+                    let sizes = message.encoded_sizes();
+                    let mut buf = Vec::with_capacity(sizes.encoded_len());
+                    message.encode_with_sizes(&sizes, &mut buf).unwrap();
+                    criterion::black_box(&buf);
+                })
+            },
+        );
+    }
+
+    // Decoding is bounded by the recursion limit, so it is measured on shallower chains.
+    for depth in [10, 50] {
+        let buf = chain(depth).encode_to_vec();
+        group.throughput(Throughput::Bytes(buf.len() as u64));
+        group.bench_with_input(BenchmarkId::new("decode", depth), &buf, |b, buf| {
+            b.iter(|| {
+                let result = Node::decode(buf.as_slice());
+                debug_assert!(result.is_ok());
+                criterion::black_box(&result);
+            })
+        });
+    }
+    group.finish();
+
+    criterion.final_summary();
+}
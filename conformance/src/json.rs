@@ -0,0 +1,58 @@
+//! Handling of conformance requests with JSON input or output, through `prost_types::json`.
+
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+
+use protobuf::conformance::conformance_response;
+use protobuf::test_messages::proto2::TestAllTypesProto2;
+use protobuf::test_messages::proto3::TestAllTypesProto3;
+
+use crate::Input;
+
+/// Parses the payload as the requested message type, and encodes it in the requested format.
+pub fn handle(
+    message_type: &str,
+    input: Input<'_>,
+    json_output: bool,
+) -> conformance_response::Result {
+    match message_type {
+        "protobuf_test_messages.proto2.TestAllTypesProto2" => {
+            convert::<TestAllTypesProto2>(input, json_output)
+        }
+        "protobuf_test_messages.proto3.TestAllTypesProto3" => {
+            convert::<TestAllTypesProto3>(input, json_output)
+        }
+        _ => conformance_response::Result::ParseError(format!(
+            "unknown message type: {}",
+            message_type
+        )),
+    }
+}
+
+fn convert<M>(input: Input<'_>, json_output: bool) -> conformance_response::Result
+where
+    M: Message + Default + Serialize + DeserializeOwned,
+{
+    let message = match input {
+        Input::Json(json) => {
+            let mut message = M::default();
+            if let Err(error) = prost_types::json::merge_json(&mut message, json) {
+                return conformance_response::Result::ParseError(error.to_string());
+            }
+            message
+        }
+        Input::Protobuf(buf) => match M::decode(buf) {
+            Ok(message) => message,
+            Err(error) => return conformance_response::Result::ParseError(error.to_string()),
+        },
+    };
+
+    if json_output {
+        match prost_types::json::to_json_value(&message) {
+            Ok(value) => conformance_response::Result::JsonPayload(value.to_string()),
+            Err(error) => conformance_response::Result::SerializeError(error.to_string()),
+        }
+    } else {
+        conformance_response::Result::ProtobufPayload(message.encode_to_vec())
+    }
+}
@@ -10,6 +10,9 @@ use protobuf::test_messages::proto2::TestAllTypesProto2;
 use protobuf::test_messages::proto3::TestAllTypesProto3;
 use tests::{roundtrip, RoundtripResult};
 
+#[cfg(feature = "json")]
+mod json;
+
 fn main() -> io::Result<()> {
     env_logger::init();
     let mut bytes = Vec::new();
@@ -49,17 +52,13 @@ fn main() -> io::Result<()> {
 }
 
 fn handle_request(request: ConformanceRequest) -> conformance_response::Result {
-    match request.requested_output_format() {
+    let json_output = match request.requested_output_format() {
         WireFormat::Unspecified => {
             return conformance_response::Result::ParseError(
                 "output format unspecified".to_string(),
             );
         }
-        WireFormat::Json => {
-            return conformance_response::Result::Skipped(
-                "JSON output is not supported".to_string(),
-            );
-        }
+        WireFormat::Json => true,
         WireFormat::Jspb => {
             return conformance_response::Result::Skipped(
                 "JSPB output is not supported".to_string(),
@@ -70,29 +69,31 @@ fn handle_request(request: ConformanceRequest) -> conformance_response::Result {
                 "TEXT_FORMAT output is not supported".to_string(),
             );
         }
-        WireFormat::Protobuf => (),
+        WireFormat::Protobuf => false,
     };
 
     let buf = match request.payload {
         None => return conformance_response::Result::ParseError("no payload".to_string()),
-        Some(conformance_request::Payload::JsonPayload(_)) => {
-            return conformance_response::Result::Skipped(
-                "JSON input is not supported".to_string(),
-            );
+        Some(conformance_request::Payload::JsonPayload(json)) => {
+            return handle_json(&request.message_type, Input::Json(&json), json_output);
         }
         Some(conformance_request::Payload::JspbPayload(_)) => {
             return conformance_response::Result::Skipped(
-                "JSON input is not supported".to_string(),
+                "JSPB input is not supported".to_string(),
             );
         }
         Some(conformance_request::Payload::TextPayload(_)) => {
             return conformance_response::Result::Skipped(
-                "JSON input is not supported".to_string(),
+                "TEXT_FORMAT input is not supported".to_string(),
             );
         }
         Some(conformance_request::Payload::ProtobufPayload(buf)) => buf,
     };
 
+    if json_output {
+        return handle_json(&request.message_type, Input::Protobuf(&buf), json_output);
+    }
+
     let roundtrip = match &*request.message_type {
         "protobuf_test_messages.proto2.TestAllTypesProto2" => roundtrip::<TestAllTypesProto2>(&buf),
         "protobuf_test_messages.proto3.TestAllTypesProto3" => roundtrip::<TestAllTypesProto3>(&buf),
@@ -114,3 +115,30 @@ fn handle_request(request: ConformanceRequest) -> conformance_response::Result {
         }
     }
 }
+
+/// The payload of a request, in either supported input format.
+#[cfg_attr(not(feature = "json"), allow(dead_code))]
+enum Input<'a> {
+    Json(&'a str),
+    Protobuf(&'a [u8]),
+}
+
+/// Handles a request with JSON input or output.
+#[cfg(feature = "json")]
+fn handle_json(
+    message_type: &str,
+    input: Input<'_>,
+    json_output: bool,
+) -> conformance_response::Result {
+    json::handle(message_type, input, json_output)
+}
+
+/// Skips a request with JSON input or output, which requires the `json` feature.
+#[cfg(not(feature = "json"))]
+fn handle_json(
+    _message_type: &str,
+    _input: Input<'_>,
+    _json_output: bool,
+) -> conformance_response::Result {
+    conformance_response::Result::Skipped("JSON requires the `json` feature".to_string())
+}
@@ -6,33 +6,39 @@ use std::iter;
 use itertools::{Either, Itertools};
 use log::debug;
 use multimap::MultiMap;
+use prost_types::feature_set::{FieldPresence, MessageEncoding, RepeatedFieldEncoding};
 use prost_types::field_descriptor_proto::{Label, Type};
 use prost_types::source_code_info::Location;
 use prost_types::{
-    DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
-    FieldOptions, FileDescriptorProto, OneofDescriptorProto, ServiceDescriptorProto,
-    SourceCodeInfo,
+    DescriptorProto, Edition, EnumDescriptorProto, EnumValueDescriptorProto, FeatureSet,
+    FieldDescriptorProto, FieldOptions, FileDescriptorProto, OneofDescriptorProto,
+    ServiceDescriptorProto, SourceCodeInfo,
 };
 
 use crate::ast::{Comments, Method, Service};
 use crate::extern_paths::ExternPaths;
 use crate::ident::{to_snake, to_upper_camel};
 use crate::message_graph::MessageGraph;
-use crate::{BytesType, Config, MapType};
+use crate::{BytesType, Config, FieldEmitOrder, MapType};
 
 #[derive(PartialEq)]
 enum Syntax {
     Proto2,
     Proto3,
+    Editions,
 }
 
 pub struct CodeGenerator<'a> {
     config: &'a mut Config,
     package: String,
+    /// The Protobuf package of the file, which unlike `package` excludes enclosing messages.
+    file_package: String,
     source_info: Option<SourceCodeInfo>,
     syntax: Syntax,
     message_graph: &'a MessageGraph,
     extern_paths: &'a ExternPaths,
+    /// The feature sets of the file and the enclosing messages, innermost last.
+    features: Vec<FeatureSet>,
     depth: u8,
     path: Vec<i32>,
     buf: &'a mut String,
@@ -50,7 +56,23 @@ impl<'a> CodeGenerator<'a> {
         extern_paths: &ExternPaths,
         file: FileDescriptorProto,
         buf: &mut String,
-    ) {
+    ) -> Result<(), String> {
+        let syntax = match file.syntax.as_ref().map(String::as_str) {
+            None | Some("proto2") => Syntax::Proto2,
+            Some("proto3") => Syntax::Proto3,
+            Some("editions") => match file.edition() {
+                Edition::Edition2023 => Syntax::Editions,
+                edition => {
+                    return Err(format!(
+                        "{} uses {}, which isn't supported",
+                        file.name(),
+                        edition.as_str_name()
+                    ))
+                }
+            },
+            Some(s) => panic!("unknown syntax: {}", s),
+        };
+
         let source_info = file.source_code_info.map(|mut s| {
             s.location.retain(|loc| {
                 let len = loc.path.len();
@@ -60,19 +82,22 @@ impl<'a> CodeGenerator<'a> {
             s
         });
 
-        let syntax = match file.syntax.as_ref().map(String::as_str) {
-            None | Some("proto2") => Syntax::Proto2,
-            Some("proto3") => Syntax::Proto3,
-            Some(s) => panic!("unknown syntax: {}", s),
-        };
+        let features = file
+            .options
+            .as_ref()
+            .and_then(|options| options.features.clone())
+            .unwrap_or_default();
 
+        let package = file.package.unwrap_or_default();
         let mut code_gen = CodeGenerator {
             config,
-            package: file.package.unwrap_or_default(),
+            file_package: package.clone(),
+            package,
             source_info,
             syntax,
             message_graph,
             extern_paths,
+            features: vec![features],
             depth: 0,
             path: Vec::new(),
             buf,
@@ -87,7 +112,7 @@ impl<'a> CodeGenerator<'a> {
         code_gen.path.push(4);
         for (idx, message) in file.message_type.into_iter().enumerate() {
             code_gen.path.push(idx as i32);
-            code_gen.append_message(message);
+            code_gen.append_message(message)?;
             code_gen.path.pop();
         }
         code_gen.path.pop();
@@ -95,7 +120,7 @@ impl<'a> CodeGenerator<'a> {
         code_gen.path.push(5);
         for (idx, desc) in file.enum_type.into_iter().enumerate() {
             code_gen.path.push(idx as i32);
-            code_gen.append_enum(desc);
+            code_gen.append_enum(desc)?;
             code_gen.path.pop();
         }
         code_gen.path.pop();
@@ -114,9 +139,11 @@ impl<'a> CodeGenerator<'a> {
 
             code_gen.path.pop();
         }
+
+        Ok(())
     }
 
-    fn append_message(&mut self, message: DescriptorProto) {
+    fn append_message(&mut self, message: DescriptorProto) -> Result<(), String> {
         debug!("  message: {:?}", message.name());
 
         let message_name = message.name().to_string();
@@ -129,9 +156,17 @@ impl<'a> CodeGenerator<'a> {
 
         // Skip external types.
         if self.extern_paths.resolve_ident(&fq_message_name).is_some() {
-            return;
+            return Ok(());
         }
 
+        self.features.push(
+            message
+                .options
+                .as_ref()
+                .and_then(|options| options.features.clone())
+                .unwrap_or_default(),
+        );
+
         // Split the nested message types into a vector of normal nested message types, and a map
         // of the map field entry types. The path index of the nested message types is preserved so
         // that comments can be retrieved.
@@ -168,6 +203,7 @@ impl<'a> CodeGenerator<'a> {
             .field
             .into_iter()
             .enumerate()
+            .map(|(idx, field)| (idx, self.lower_delimited(field)))
             .partition_map(|(idx, field)| {
                 if field.proto3_optional.unwrap_or(false) {
                     Either::Left((field, idx))
@@ -178,6 +214,108 @@ impl<'a> CodeGenerator<'a> {
                 }
             });
 
+        if self.config.arena.get_first(&fq_message_name).is_some() {
+            if !oneof_fields.is_empty() || !map_types.is_empty() {
+                panic!(
+                    "arena message {} has oneof or map fields, which aren't supported",
+                    fq_message_name
+                );
+            }
+            self.append_arena_message(&message_name, &fq_message_name, fields);
+
+            if !message.enum_type.is_empty() || !nested_types.is_empty() {
+                self.push_mod(&message_name);
+                self.path.push(3);
+                for (nested_type, idx) in nested_types {
+                    self.path.push(idx as i32);
+                    self.append_message(nested_type)?;
+                    self.path.pop();
+                }
+                self.path.pop();
+
+                self.path.push(4);
+                for (idx, nested_enum) in message.enum_type.into_iter().enumerate() {
+                    self.path.push(idx as i32);
+                    self.append_enum(nested_enum)?;
+                    self.path.pop();
+                }
+                self.path.pop();
+                self.pop_mod();
+            }
+
+            self.features.pop();
+            return Ok(());
+        }
+
+        let preserve_unknown_fields = self
+            .config
+            .preserve_unknown_fields
+            .get_first(&fq_message_name)
+            .is_some();
+        if preserve_unknown_fields {
+            let oneof_names = message.oneof_decl.iter().map(|oneof| oneof.name());
+            if fields
+                .iter()
+                .map(|(field, _)| field.name())
+                .chain(oneof_names)
+                .any(|name| to_snake(name) == "unknown_fields")
+            {
+                panic!(
+                    "message {} preserves unknown fields, but has a field named unknown_fields",
+                    fq_message_name
+                );
+            }
+            if self.config.columnar.get_first(&fq_message_name).is_some() {
+                panic!(
+                    "columnar message {} can't preserve unknown fields",
+                    fq_message_name
+                );
+            }
+        }
+
+        // A message with a single scalar field can be laid out exactly like that field.
+        let transparent = match fields.as_slice() {
+            [(field, _)] => {
+                self.config
+                    .transparent
+                    .get_first(&fq_message_name)
+                    .is_some()
+                    && self.config.columnar.get_first(&fq_message_name).is_none()
+                    && !preserve_unknown_fields
+                    && oneof_fields.is_empty()
+                    && field.label() != Label::Repeated
+                    && !self.optional(field)
+                    && !matches!(field.r#type(), Type::Message | Type::Group)
+            }
+            _ => false,
+        };
+        // Large messages implement `Clone` by hand, which compiles faster than deriving it.
+        let manual_clone_fields = match self.config.manual_clone_threshold {
+            Some(threshold) if !transparent && fields.len() + oneof_fields.len() > threshold => {
+                let oneof_names = message
+                    .oneof_decl
+                    .iter()
+                    .enumerate()
+                    .filter(|&(idx, _)| oneof_fields.contains_key(&(idx as i32)))
+                    .map(|(_, oneof)| oneof.name());
+                let unknown_fields = if preserve_unknown_fields {
+                    Some("unknown_fields")
+                } else {
+                    None
+                };
+                Some(
+                    fields
+                        .iter()
+                        .map(|(field, _)| field.name())
+                        .chain(oneof_names)
+                        .map(to_snake)
+                        .chain(unknown_fields.map(str::to_owned))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            _ => None,
+        };
+
         self.append_doc(&fq_message_name, None);
         self.append_type_attributes(&fq_message_name);
         self.append_message_attributes(&fq_message_name);
@@ -185,13 +323,129 @@ impl<'a> CodeGenerator<'a> {
         self.buf
             .push_str("#[allow(clippy::derive_partial_eq_without_eq)]\n");
         self.buf.push_str(&format!(
-            "#[derive(Clone, PartialEq, {}::Message)]\n",
+            "#[derive({}PartialEq, {}::Message)]\n",
+            if manual_clone_fields.is_some() {
+                ""
+            } else {
+                "Clone, "
+            },
             self.config.prost_path.as_deref().unwrap_or("::prost")
         ));
+        if self.derive_serde(&fq_message_name) {
+            self.append_serde_derive();
+        }
+        if self
+            .config
+            .skip_default
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(skip_default)]\n");
+        }
+        if self.config.field_emit_order == FieldEmitOrder::Declaration {
+            self.push_indent();
+            self.buf.push_str("#[prost(declaration_order)]\n");
+        }
+        if self.serde_default(&fq_message_name) {
+            self.push_indent();
+            self.buf.push_str("#[serde(default)]\n");
+        }
+        // Columns can't track the length of a batch of messages without fields.
+        if self.config.columnar.get_first(&fq_message_name).is_some()
+            && !(fields.is_empty() && oneof_fields.is_empty())
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(columnar)]\n");
+        }
+        if self
+            .config
+            .option_accessors
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(option_accessors)]\n");
+        }
+        if self
+            .config
+            .trace_field_access
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(trace_field_access)]\n");
+        }
+        if self
+            .config
+            .extend_methods
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(extend_methods)]\n");
+        }
+        if self
+            .config
+            .reserve_methods
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(reserve_methods)]\n");
+        }
+        if self
+            .config
+            .field_accessors
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.push_indent();
+            self.buf.push_str("#[prost(field_accessors)]\n");
+        }
+        // A transparent wrapper around a string can be compared with string slices.
+        let string_newtype = transparent && fields[0].0.r#type() == Type::String;
+        // A transparent wrapper around a `Bytes` field can be consumed as a buffer.
+        let bytes_newtype = transparent
+            && fields[0].0.r#type() == Type::Bytes
+            && self
+                .config
+                .bytes_type
+                .get_first_field(&fq_message_name, fields[0].0.name())
+                .copied()
+                .unwrap_or_default()
+                == BytesType::Bytes;
+        if transparent {
+            self.push_indent();
+            self.buf.push_str("#[repr(transparent)]\n");
+        }
         self.push_indent();
         self.buf.push_str("pub struct ");
         self.buf.push_str(&to_upper_camel(&message_name));
-        self.buf.push_str(" {\n");
+        self.buf.push_str(if transparent { "(\n" } else { " {\n" });
+
+        // A message with a single repeated field, other than a map, wraps a collection.
+        let collection = match fields.as_slice() {
+            [(field, _)]
+                if self.config.collection_wrappers
+                    && !preserve_unknown_fields
+                    && oneof_fields.is_empty()
+                    && field.label() == Label::Repeated
+                    && !map_types.contains_key(field.type_name()) =>
+            {
+                Some((
+                    to_snake(field.name()),
+                    self.field_type(&fq_message_name, field),
+                ))
+            }
+            _ => None,
+        };
+
+        let size_hint = if self.config.size_hints {
+            Some(size_hint(&fields, &oneof_fields, &map_types))
+        } else {
+            None
+        };
 
         self.depth += 1;
         self.path.push(2);
@@ -205,7 +459,7 @@ impl<'a> CodeGenerator<'a> {
                 Some(&(ref key, ref value)) => {
                     self.append_map_field(&fq_message_name, field, key, value)
                 }
-                None => self.append_field(&fq_message_name, field),
+                None => self.append_field(&fq_message_name, field, !transparent),
             }
             self.path.pop();
         }
@@ -226,16 +480,57 @@ impl<'a> CodeGenerator<'a> {
         }
         self.path.pop();
 
+        if preserve_unknown_fields {
+            self.append_unknown_fields_field(&fq_message_name);
+        }
+
         self.depth -= 1;
         self.push_indent();
-        self.buf.push_str("}\n");
+        self.buf.push_str(if transparent { ");\n" } else { "}\n" });
+
+        let oneof_cases = message
+            .oneof_decl
+            .iter()
+            .enumerate()
+            .filter(|&(idx, oneof)| {
+                oneof_fields.contains_key(&(idx as i32)) && self.oneof_case(&fq_message_name, oneof)
+            })
+            .map(|(_, oneof)| oneof.name())
+            .collect::<Vec<_>>();
+        if !oneof_cases.is_empty() {
+            self.append_oneof_which(&message_name, &oneof_cases);
+        }
+
+        if let Some((field_name, item_type)) = collection {
+            self.append_collection_impls(&message_name, &field_name, &item_type);
+        }
+
+        if string_newtype {
+            self.append_str_eq_impls(&message_name);
+        }
+
+        if bytes_newtype {
+            self.append_buf_impl(&message_name);
+        }
+
+        if let Some(field_names) = manual_clone_fields {
+            self.append_clone_impl(&message_name, &field_names);
+        }
+
+        if self.config.any_conversions {
+            self.append_any_conversions(&message_name, &fq_message_name);
+        }
+
+        if let Some(size_hint) = size_hint {
+            self.append_size_hint(&message_name, size_hint);
+        }
 
         if !message.enum_type.is_empty() || !nested_types.is_empty() || !oneof_fields.is_empty() {
             self.push_mod(&message_name);
             self.path.push(3);
             for (nested_type, idx) in nested_types {
                 self.path.push(idx as i32);
-                self.append_message(nested_type);
+                self.append_message(nested_type)?;
                 self.path.pop();
             }
             self.path.pop();
@@ -243,7 +538,7 @@ impl<'a> CodeGenerator<'a> {
             self.path.push(4);
             for (idx, nested_enum) in message.enum_type.into_iter().enumerate() {
                 self.path.push(idx as i32);
-                self.append_enum(nested_enum);
+                self.append_enum(nested_enum)?;
                 self.path.pop();
             }
             self.path.pop();
@@ -260,6 +555,9 @@ impl<'a> CodeGenerator<'a> {
 
             self.pop_mod();
         }
+
+        self.features.pop();
+        Ok(())
     }
 
     fn append_type_attributes(&mut self, fq_message_name: &str) {
@@ -302,23 +600,31 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
-    fn append_field(&mut self, fq_message_name: &str, field: FieldDescriptorProto) {
+    fn append_field(&mut self, fq_message_name: &str, field: FieldDescriptorProto, named: bool) {
         let type_ = field.r#type();
         let repeated = field.label == Some(Label::Repeated as i32);
         let deprecated = self.deprecated(&field);
-        let optional = self.optional(&field);
-        let ty = self.resolve_type(&field, fq_message_name);
+        let nonzero = self.nonzero(fq_message_name, &field);
+        // Zero can't be represented, so singular non-zero fields always have presence.
+        let optional = self.optional(&field) || (nonzero && !repeated);
+        let raw = self.raw(fq_message_name, &field);
+        let ty = if nonzero {
+            nonzero_type(type_).to_string()
+        } else {
+            self.field_type(fq_message_name, &field)
+        };
 
-        let boxed = !repeated
-            && ((type_ == Type::Message || type_ == Type::Group)
-                && self
-                    .message_graph
-                    .is_nested(field.type_name(), fq_message_name))
-            || (self
-                .config
-                .boxed
-                .get_first_field(&fq_message_name, field.name())
-                .is_some());
+        let boxed = !raw
+            && (!repeated
+                && ((type_ == Type::Message || type_ == Type::Group)
+                    && self
+                        .message_graph
+                        .is_nested(field.type_name(), fq_message_name))
+                || (self
+                    .config
+                    .boxed
+                    .get_first_field(fq_message_name, field.name())
+                    .is_some()));
 
         debug!(
             "    field: {:?}, type: {:?}, boxed: {}",
@@ -336,7 +642,11 @@ impl<'a> CodeGenerator<'a> {
 
         self.push_indent();
         self.buf.push_str("#[prost(");
-        let type_tag = self.field_type_tag(&field);
+        let type_tag = if raw {
+            Cow::Borrowed("bytes=\"raw\"")
+        } else {
+            self.field_type_tag(&field)
+        };
         self.buf.push_str(&type_tag);
 
         if type_ == Type::Bytes {
@@ -354,17 +664,14 @@ impl<'a> CodeGenerator<'a> {
             Label::Optional => {
                 if optional {
                     self.buf.push_str(", optional");
+                } else if self.field_presence(&field) == FieldPresence::LegacyRequired {
+                    self.buf.push_str(", required");
                 }
             }
             Label::Required => self.buf.push_str(", required"),
             Label::Repeated => {
                 self.buf.push_str(", repeated");
-                if can_pack(&field)
-                    && !field
-                        .options
-                        .as_ref()
-                        .map_or(self.syntax == Syntax::Proto3, |options| options.packed())
-                {
+                if can_pack(&field) && !self.packed(&field) {
                     self.buf.push_str(", packed=\"false\"");
                 }
             }
@@ -373,6 +680,12 @@ impl<'a> CodeGenerator<'a> {
         if boxed {
             self.buf.push_str(", boxed");
         }
+        if self.lossy_string(fq_message_name, &field) {
+            self.buf.push_str(", lossy");
+        }
+        if nonzero {
+            self.buf.push_str(", nonzero");
+        }
         self.buf.push_str(", tag=\"");
         self.buf.push_str(&field.number().to_string());
 
@@ -407,11 +720,50 @@ impl<'a> CodeGenerator<'a> {
         }
 
         self.buf.push_str("\")]\n");
+        if self.serde_skip(fq_message_name, field.name()) {
+            self.append_serde_skip();
+        } else if repeated && self.serde_skip_empty(fq_message_name, field.name()) {
+            self.append_skip_serializing_if(&format!(
+                "{}::alloc::vec::Vec::is_empty",
+                self.config.prost_path.as_deref().unwrap_or("::prost")
+            ));
+        } else if self.serde_default(fq_message_name) {
+            let required = field.label() == Label::Required
+                || (field.label() == Label::Optional
+                    && !optional
+                    && self.field_presence(&field) == FieldPresence::LegacyRequired);
+            if optional {
+                self.append_skip_serializing_if("::core::option::Option::is_none");
+            } else if !required {
+                self.append_skip_serializing_if(&format!(
+                    "{}::encoding::is_default",
+                    self.config.prost_path.as_deref().unwrap_or("::prost")
+                ));
+            }
+        }
+        if self.derive_serde(fq_message_name)
+            && !boxed
+            && !self.serde_skip(fq_message_name, field.name())
+        {
+            let helper = if repeated {
+                "::repeated"
+            } else if optional {
+                "::optional"
+            } else {
+                ""
+            };
+            if let Some(with) = self.serde_with(&field, helper) {
+                self.push_indent();
+                self.buf.push_str(&format!("#[serde({})]\n", with));
+            }
+        }
         self.append_field_attributes(fq_message_name, field.name());
         self.push_indent();
         self.buf.push_str("pub ");
-        self.buf.push_str(&to_snake(field.name()));
-        self.buf.push_str(": ");
+        if named {
+            self.buf.push_str(&to_snake(field.name()));
+            self.buf.push_str(": ");
+        }
 
         let prost_path = self.config.prost_path.as_deref().unwrap_or("::prost");
 
@@ -435,6 +787,54 @@ impl<'a> CodeGenerator<'a> {
         self.buf.push_str(",\n");
     }
 
+    /// Returns whether the field is a message field passed through as a `RawMessage`.
+    fn raw(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> bool {
+        field.r#type() == Type::Message
+            && self
+                .config
+                .raw
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+    }
+
+    /// Returns `true` if invalid UTF-8 in the string field is replaced rather than rejected.
+    fn lossy_string(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> bool {
+        field.r#type() == Type::String
+            && self
+                .config
+                .lossy_string
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+    }
+
+    /// Returns `true` if the integer field is represented by a `NonZero*` type.
+    ///
+    /// Required fields and fields with a default value keep the plain type, as do fields in a
+    /// oneof, which aren't appended through `append_field`.
+    fn nonzero(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> bool {
+        is_integer(field.r#type())
+            && field.label() != Label::Required
+            && self.field_presence(field) != FieldPresence::LegacyRequired
+            && field.default_value.is_none()
+            && self
+                .config
+                .nonzero
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+    }
+
+    /// Returns the Rust type of a single value of the field.
+    fn field_type(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> String {
+        if self.raw(fq_message_name, field) {
+            format!(
+                "{}::RawMessage",
+                self.config.prost_path.as_deref().unwrap_or("::prost")
+            )
+        } else {
+            self.resolve_type(field, fq_message_name)
+        }
+    }
+
     fn append_map_field(
         &mut self,
         fq_message_name: &str,
@@ -442,8 +842,30 @@ impl<'a> CodeGenerator<'a> {
         key: &FieldDescriptorProto,
         value: &FieldDescriptorProto,
     ) {
-        let key_ty = self.resolve_type(key, fq_message_name);
-        let value_ty = self.resolve_type(value, fq_message_name);
+        let key_enum = self
+            .config
+            .enum_map_keys
+            .get_first_field(fq_message_name, field.name())
+            .filter(|_| key.r#type() == Type::Int32)
+            .map(|enum_name| self.resolve_ident(enum_name));
+        let key_ty = match key_enum {
+            Some(ref key_enum) => key_enum.clone(),
+            None => self.resolve_type(key, fq_message_name),
+        };
+        let mut value_ty = self.resolve_type(value, fq_message_name);
+        if value.r#type() == Type::Message
+            && self
+                .config
+                .boxed_map_value
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+        {
+            value_ty = format!(
+                "{}::alloc::boxed::Box<{}>",
+                self.config.prost_path.as_deref().unwrap_or("::prost"),
+                value_ty
+            );
+        }
 
         debug!(
             "    map field: {:?}, key type: {:?}, value type: {:?}",
@@ -461,7 +883,10 @@ impl<'a> CodeGenerator<'a> {
             .get_first_field(fq_message_name, field.name())
             .copied()
             .unwrap_or_default();
-        let key_tag = self.field_type_tag(key);
+        let key_tag = match key_enum {
+            Some(key_enum) => Cow::Owned(format!("enumeration({})", key_enum)),
+            None => self.field_type_tag(key),
+        };
         let value_tag = self.map_value_type_tag(value);
 
         self.buf.push_str(&format!(
@@ -471,6 +896,16 @@ impl<'a> CodeGenerator<'a> {
             value_tag,
             field.number()
         ));
+        if self.serde_skip(fq_message_name, field.name()) {
+            self.append_serde_skip();
+        } else if self.serde_skip_empty(fq_message_name, field.name()) {
+            self.append_skip_serializing_if(&format!("{}::is_empty", map_type.rust_type()));
+        } else if self.serde_default(fq_message_name) {
+            self.append_skip_serializing_if(&format!(
+                "{}::encoding::is_default",
+                self.config.prost_path.as_deref().unwrap_or("::prost")
+            ));
+        }
         self.append_field_attributes(fq_message_name, field.name());
         self.push_indent();
         self.buf.push_str(&format!(
@@ -504,6 +939,15 @@ impl<'a> CodeGenerator<'a> {
                 .map(|&(ref field, _)| field.number())
                 .join(", ")
         ));
+        if self.serde_skip(fq_message_name, oneof.name()) {
+            self.append_serde_skip();
+        } else if self.derive_serde(fq_message_name) {
+            // The set field appears under its own name, alongside the fields of the message.
+            self.push_indent();
+            self.buf.push_str("#[serde(flatten)]\n");
+        } else if self.serde_default(fq_message_name) {
+            self.append_skip_serializing_if("::core::option::Option::is_none");
+        }
         self.append_field_attributes(fq_message_name, oneof.name());
         self.push_indent();
         self.buf.push_str(&format!(
@@ -513,60 +957,765 @@ impl<'a> CodeGenerator<'a> {
         ));
     }
 
-    fn append_oneof(
-        &mut self,
-        fq_message_name: &str,
-        oneof: OneofDescriptorProto,
-        idx: i32,
-        fields: Vec<(FieldDescriptorProto, usize)>,
-    ) {
-        self.path.push(8);
-        self.path.push(idx);
-        self.append_doc(fq_message_name, None);
-        self.path.pop();
-        self.path.pop();
+    fn derive_serde(&self, fq_name: &str) -> bool {
+        self.config.derive_serde.get_first(fq_name).is_some()
+    }
 
-        let oneof_name = format!("{}.{}", fq_message_name, oneof.name());
-        self.append_type_attributes(&oneof_name);
-        self.append_enum_attributes(&oneof_name);
+    fn append_serde_derive(&mut self) {
         self.push_indent();
         self.buf
-            .push_str("#[allow(clippy::derive_partial_eq_without_eq)]\n");
+            .push_str("#[derive(serde::Serialize, serde::Deserialize)]\n");
+    }
+
+    /// Returns the arguments of a serde attribute serializing a `bytes` or enumeration field with
+    /// the helpers in `prost::serde`, using the `helper` submodule for `optional` and `repeated`
+    /// fields.
+    fn serde_with(&self, field: &FieldDescriptorProto, helper: &str) -> Option<String> {
+        let prost_path = self.config.prost_path.as_deref().unwrap_or("::prost");
+        match field.r#type() {
+            Type::Bytes => Some(format!("with = \"{}::serde::bytes{}\"", prost_path, helper)),
+            Type::Enum => {
+                let module = format!("{}::serde::enumeration{}", prost_path, helper);
+                let enum_type = self.resolve_ident(field.type_name());
+                Some(format!(
+                    "serialize_with = \"{module}::serialize::<{enum_type}, _>\", \
+                     deserialize_with = \"{module}::deserialize::<{enum_type}, _>\"",
+                    module = module,
+                    enum_type = enum_type,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn serde_default(&self, fq_message_name: &str) -> bool {
+        self.config
+            .serde_default
+            .get_first(fq_message_name)
+            .is_some()
+    }
+
+    fn append_skip_serializing_if(&mut self, predicate: &str) {
+        self.push_indent();
         self.buf.push_str(&format!(
-            "#[derive(Clone, PartialEq, {}::Oneof)]\n",
-            self.config.prost_path.as_deref().unwrap_or("::prost")
+            "#[serde(skip_serializing_if = \"{}\")]\n",
+            predicate
         ));
+    }
+
+    fn serde_skip(&self, fq_message_name: &str, field_name: &str) -> bool {
+        self.config
+            .serde_skip
+            .get_first_field(fq_message_name, field_name)
+            .is_some()
+    }
+
+    fn serde_skip_empty(&self, fq_message_name: &str, field_name: &str) -> bool {
+        self.config
+            .serde_skip_empty
+            .get_first_field(fq_message_name, field_name)
+            .is_some()
+    }
+
+    fn append_unknown_fields_field(&mut self, fq_message_name: &str) {
+        // Unknown fields have no JSON representation.
+        if self.serde_default(fq_message_name) {
+            self.append_serde_skip();
+        }
         self.push_indent();
-        self.buf.push_str("pub enum ");
-        self.buf.push_str(&to_upper_camel(oneof.name()));
-        self.buf.push_str(" {\n");
+        self.buf.push_str("#[prost(unknown_fields)]\n");
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "pub unknown_fields: {}::UnknownFields,\n",
+            self.config.prost_path.as_deref().unwrap_or("::prost")
+        ));
+    }
 
-        self.path.push(2);
-        self.depth += 1;
-        for (field, idx) in fields {
-            let type_ = field.r#type();
+    fn append_serde_skip(&mut self) {
+        self.push_indent();
+        self.buf.push_str("#[serde(skip)]\n");
+    }
 
-            self.path.push(idx as i32);
-            self.append_doc(fq_message_name, Some(field.name()));
-            self.path.pop();
+    fn append_collection_impls(&mut self, message_name: &str, field_name: &str, item_type: &str) {
+        let prost_path = self.config.prost_path.as_deref().unwrap_or("::prost");
+        let message_name = to_upper_camel(message_name);
+
+        let impls = [
+            format!(
+                "impl ::core::iter::IntoIterator for {message} {{\n\
+                 \x20   type Item = {item};\n\
+                 \x20   type IntoIter = {prost}::alloc::vec::IntoIter<{item}>;\n\
+                 \x20   fn into_iter(self) -> Self::IntoIter {{\n\
+                 \x20       ::core::iter::IntoIterator::into_iter(self.{field})\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+                item = item_type,
+                prost = prost_path,
+                field = field_name,
+            ),
+            format!(
+                "impl<'a> ::core::iter::IntoIterator for &'a {message} {{\n\
+                 \x20   type Item = &'a {item};\n\
+                 \x20   type IntoIter = ::core::slice::Iter<'a, {item}>;\n\
+                 \x20   fn into_iter(self) -> Self::IntoIter {{\n\
+                 \x20       self.{field}.iter()\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+                item = item_type,
+                field = field_name,
+            ),
+            format!(
+                "impl ::core::iter::FromIterator<{item}> for {message} {{\n\
+                 \x20   fn from_iter<I: ::core::iter::IntoIterator<Item = {item}>>(iter: I) -> Self {{\n\
+                 \x20       Self {{\n\
+                 \x20           {field}: ::core::iter::FromIterator::from_iter(iter),\n\
+                 \x20       }}\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+                item = item_type,
+                field = field_name,
+            ),
+            format!(
+                "impl ::core::iter::Extend<{item}> for {message} {{\n\
+                 \x20   fn extend<I: ::core::iter::IntoIterator<Item = {item}>>(&mut self, iter: I) {{\n\
+                 \x20       ::core::iter::Extend::extend(&mut self.{field}, iter)\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+                item = item_type,
+                field = field_name,
+            ),
+        ];
+        for line in impls.iter().flat_map(|code| code.lines()) {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
 
+    fn append_clone_impl(&mut self, message_name: &str, field_names: &[String]) {
+        let mut lines = vec![
+            format!(
+                "impl ::core::clone::Clone for {} {{",
+                to_upper_camel(message_name)
+            ),
+            "    fn clone(&self) -> Self {".to_owned(),
+            "        Self {".to_owned(),
+        ];
+        lines.extend(field_names.iter().map(|name| {
+            format!(
+                "            {name}: ::core::clone::Clone::clone(&self.{name}),",
+                name = name
+            )
+        }));
+        lines.push("        }".to_owned());
+        lines.push("    }".to_owned());
+        lines.push("}".to_owned());
+        for line in lines {
             self.push_indent();
-            let ty_tag = self.field_type_tag(&field);
-            self.buf.push_str(&format!(
-                "#[prost({}, tag=\"{}\")]\n",
-                ty_tag,
-                field.number()
-            ));
-            self.append_field_attributes(&oneof_name, field.name());
+            self.buf.push_str(&line);
+            self.buf.push('\n');
+        }
+    }
 
+    fn append_str_eq_impls(&mut self, message_name: &str) {
+        let message_name = to_upper_camel(message_name);
+
+        let impls = [
+            format!(
+                "impl ::core::cmp::PartialEq<str> for {message} {{\n\
+                 \x20   fn eq(&self, other: &str) -> bool {{\n\
+                 \x20       self.0 == *other\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+            ),
+            format!(
+                "impl<'a> ::core::cmp::PartialEq<&'a str> for {message} {{\n\
+                 \x20   fn eq(&self, other: &&'a str) -> bool {{\n\
+                 \x20       self.0 == **other\n\
+                 \x20   }}\n\
+                 }}\n",
+                message = message_name,
+            ),
+        ];
+        for line in impls.iter().flat_map(|code| code.lines()) {
             self.push_indent();
-            let ty = self.resolve_type(&field, fq_message_name);
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
 
-            let boxed = ((type_ == Type::Message || type_ == Type::Group)
-                && self
-                    .message_graph
-                    .is_nested(field.type_name(), fq_message_name))
-                || (self
+    /// Appends a `Buf` impl for a transparent wrapper around `Bytes`, delegating to the field.
+    fn append_buf_impl(&mut self, message_name: &str) {
+        let code = format!(
+            "impl {prost}::bytes::Buf for {message} {{\n\
+             \x20   fn remaining(&self) -> usize {{\n\
+             \x20       {prost}::bytes::Buf::remaining(&self.0)\n\
+             \x20   }}\n\
+             \x20   fn chunk(&self) -> &[u8] {{\n\
+             \x20       {prost}::bytes::Buf::chunk(&self.0)\n\
+             \x20   }}\n\
+             \x20   fn advance(&mut self, cnt: usize) {{\n\
+             \x20       {prost}::bytes::Buf::advance(&mut self.0, cnt)\n\
+             \x20   }}\n\
+             \x20   fn copy_to_bytes(&mut self, len: usize) -> {prost}::bytes::Bytes {{\n\
+             \x20       {prost}::bytes::Buf::copy_to_bytes(&mut self.0, len)\n\
+             \x20   }}\n\
+             }}\n",
+            prost = self.config.prost_path.as_deref().unwrap_or("::prost"),
+            message = to_upper_camel(message_name),
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Appends the `Name` impl of a message, and its conversion from `google.protobuf.Any`.
+    fn append_any_conversions(&mut self, message_name: &str, fq_message_name: &str) {
+        let message = to_upper_camel(message_name);
+        let prost_path = self.config.prost_path.as_deref().unwrap_or("::prost");
+        let any = self.resolve_ident(".google.protobuf.Any");
+        // Nested messages are named after their enclosing messages, as in `Outer.Inner`.
+        let name = if self.file_package.is_empty() {
+            &fq_message_name[1..]
+        } else {
+            &fq_message_name[self.file_package.len() + 2..]
+        };
+
+        let code = format!(
+            "impl {prost}::Name for {message} {{\n\
+             \x20   const NAME: &'static str = \"{name}\";\n\
+             \x20   const PACKAGE: &'static str = \"{package}\";\n\
+             }}\n\
+             impl ::core::convert::TryFrom<&{any}> for {message} {{\n\
+             \x20   type Error = {prost}::DecodeError;\n\
+             \x20   fn try_from(any: &{any}) -> ::core::result::Result<Self, Self::Error> {{\n\
+             \x20       any.to_msg()\n\
+             \x20   }}\n\
+             }}\n",
+            prost = prost_path,
+            message = message,
+            name = name,
+            package = self.file_package,
+            any = any,
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Appends the `TYPICAL_SIZE_HINT` constant of a message.
+    fn append_size_hint(&mut self, message_name: &str, size_hint: usize) {
+        let code = format!(
+            "impl {message} {{\n\
+             \x20   /// A typical encoded length of this message in bytes.\n\
+             \x20   ///\n\
+             \x20   /// This is estimated from the field types, for sizing buffers, and isn't\n\
+             \x20   /// a bound: it assumes a few elements per repeated field, and short\n\
+             \x20   /// strings, bytes and nested messages.\n\
+             \x20   pub const TYPICAL_SIZE_HINT: usize = {size_hint};\n\
+             }}\n",
+            message = to_upper_camel(message_name),
+            size_hint = size_hint,
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Appends a message which allocates from an arena, along with its `ArenaMessage` impl.
+    fn append_arena_message(
+        &mut self,
+        message_name: &str,
+        fq_message_name: &str,
+        fields: Vec<(FieldDescriptorProto, usize)>,
+    ) {
+        let prost_path = self
+            .config
+            .prost_path
+            .clone()
+            .unwrap_or_else(|| "::prost".to_owned());
+        let arena_path = format!("{}::arena", prost_path);
+        let message_name = to_upper_camel(message_name);
+
+        let mut new_in = Vec::new();
+        let mut encode = Vec::new();
+        let mut merge = Vec::new();
+        let mut encoded_len = Vec::new();
+
+        self.append_doc(fq_message_name, None);
+        self.append_type_attributes(fq_message_name);
+        self.append_message_attributes(fq_message_name);
+        self.push_indent();
+        self.buf.push_str("#[derive(Clone, PartialEq, Debug)]\n");
+        self.push_indent();
+        self.buf
+            .push_str(&format!("pub struct {}<'arena> {{\n", message_name));
+        self.depth += 1;
+        self.path.push(2);
+        for (field, idx) in fields {
+            self.path.push(idx as i32);
+            let name = to_snake(field.name());
+            let tag = field.number();
+            let repeated = field.label() == Label::Repeated;
+            let optional = self.optional(&field);
+
+            // The Rust type of a single value, the module of its encoding functions, and its
+            // default value.
+            let (ty, module, default) = match field.r#type() {
+                Type::String => (
+                    "&'arena str".to_owned(),
+                    format!("{}::string", arena_path),
+                    "\"\"",
+                ),
+                Type::Bytes => (
+                    "&'arena [u8]".to_owned(),
+                    format!("{}::bytes", arena_path),
+                    "&[]",
+                ),
+                Type::Message => {
+                    if self.config.arena.get_first(field.type_name()).is_none()
+                        || self.extern_paths.resolve_ident(field.type_name()).is_some()
+                    {
+                        panic!(
+                            "field {} of arena message {} refers to {}, which isn't an arena message",
+                            field.name(),
+                            fq_message_name,
+                            field.type_name()
+                        );
+                    }
+                    if self
+                        .message_graph
+                        .is_nested(field.type_name(), fq_message_name)
+                    {
+                        panic!(
+                            "field {} of arena message {} is recursive, which isn't supported",
+                            field.name(),
+                            fq_message_name
+                        );
+                    }
+                    (
+                        format!("{}<'arena>", self.resolve_ident(field.type_name())),
+                        format!("{}::message", arena_path),
+                        "",
+                    )
+                }
+                Type::Group => panic!(
+                    "field {} of arena message {} is a group, which isn't supported",
+                    field.name(),
+                    fq_message_name
+                ),
+                Type::Enum => (
+                    "i32".to_owned(),
+                    format!("{}::encoding::int32", prost_path),
+                    "::core::default::Default::default()",
+                ),
+                _ => (
+                    self.resolve_type(&field, fq_message_name),
+                    format!("{}::encoding::{}", prost_path, self.field_type_tag(&field)),
+                    "::core::default::Default::default()",
+                ),
+            };
+            let borrowed = matches!(field.r#type(), Type::String | Type::Bytes);
+            let message = field.r#type() == Type::Message;
+            let merge_args = if borrowed || message {
+                "buf, arena, ctx"
+            } else {
+                "buf, ctx"
+            };
+
+            self.append_doc(fq_message_name, Some(field.name()));
+            if self.deprecated(&field) {
+                self.push_indent();
+                self.buf.push_str("#[deprecated]\n");
+            }
+            self.append_field_attributes(fq_message_name, field.name());
+            self.push_indent();
+            if repeated {
+                self.buf.push_str(&format!(
+                    "pub {}: {}::Vec<'arena, {}>,\n",
+                    name, arena_path, ty
+                ));
+                new_in.push(format!("{}: {}::Vec::new_in(arena),", name, arena_path));
+                if borrowed || message {
+                    encode.push(format!(
+                        "{}::encode_repeated({}, &self.{}, buf);",
+                        module, tag, name
+                    ));
+                    encoded_len.push(format!(
+                        "{}::encoded_len_repeated({}, &self.{})",
+                        module, tag, name
+                    ));
+                    merge.push((
+                        tag,
+                        name.clone(),
+                        format!(
+                            "{}::merge_repeated(wire_type, &mut self.{}, {})",
+                            module, name, merge_args
+                        ),
+                    ));
+                } else {
+                    let (encode_fn, encoded_len_fn) = if can_pack(&field) && self.packed(&field) {
+                        ("encode_packed", "encoded_len_packed")
+                    } else {
+                        ("encode_repeated", "encoded_len_repeated")
+                    };
+                    let wire_type = match field.r#type() {
+                        Type::Float | Type::Fixed32 | Type::Sfixed32 => "ThirtyTwoBit",
+                        Type::Double | Type::Fixed64 | Type::Sfixed64 => "SixtyFourBit",
+                        _ => "Varint",
+                    };
+                    encode.push(format!(
+                        "{}::{}({}, &self.{}, buf);",
+                        module, encode_fn, tag, name
+                    ));
+                    encoded_len.push(format!(
+                        "{}::{}({}, &self.{})",
+                        module, encoded_len_fn, tag, name
+                    ));
+                    merge.push((
+                        tag,
+                        name.clone(),
+                        format!(
+                            "{}::merge_repeated_scalar(wire_type, {}::encoding::WireType::{}, &mut self.{}, buf, ctx, {}::merge)",
+                            arena_path, prost_path, wire_type, name, module
+                        ),
+                    ));
+                }
+            } else if message {
+                self.buf
+                    .push_str(&format!("pub {}: ::core::option::Option<{}>,\n", name, ty));
+                new_in.push(format!("{}: ::core::option::Option::None,", name));
+                encode.push(format!(
+                    "{}::encode_optional({}, &self.{}, buf);",
+                    module, tag, name
+                ));
+                encoded_len.push(format!(
+                    "{}::encoded_len_optional({}, &self.{})",
+                    module, tag, name
+                ));
+                merge.push((
+                    tag,
+                    name.clone(),
+                    format!(
+                        "{}::merge_optional(wire_type, &mut self.{}, {})",
+                        module, name, merge_args
+                    ),
+                ));
+            } else if optional {
+                self.buf
+                    .push_str(&format!("pub {}: ::core::option::Option<{}>,\n", name, ty));
+                new_in.push(format!("{}: ::core::option::Option::None,", name));
+                let value = if borrowed { "value" } else { "&value" };
+                encode.push(format!(
+                    "if let ::core::option::Option::Some(value) = self.{} {{ {}::encode({}, {}, buf); }}",
+                    name, module, tag, value
+                ));
+                encoded_len.push(format!(
+                    "self.{}.map_or(0, |value| {}::encoded_len({}, {}))",
+                    name, module, tag, value
+                ));
+                merge.push((
+                    tag,
+                    name.clone(),
+                    format!(
+                        "{}::merge(wire_type, self.{}.get_or_insert({}), {})",
+                        module, name, default, merge_args
+                    ),
+                ));
+            } else {
+                self.buf.push_str(&format!("pub {}: {},\n", name, ty));
+                new_in.push(format!("{}: {},", name, default));
+                let value = if borrowed {
+                    format!("self.{}", name)
+                } else {
+                    format!("&self.{}", name)
+                };
+                let required = field.label() == Label::Required
+                    || self.field_presence(&field) == FieldPresence::LegacyRequired;
+                if required {
+                    encode.push(format!("{}::encode({}, {}, buf);", module, tag, value));
+                    encoded_len.push(format!("{}::encoded_len({}, {})", module, tag, value));
+                } else {
+                    let set = if borrowed {
+                        format!("!self.{}.is_empty()", name)
+                    } else {
+                        format!("self.{} != {}", name, default)
+                    };
+                    encode.push(format!(
+                        "if {} {{ {}::encode({}, {}, buf); }}",
+                        set, module, tag, value
+                    ));
+                    encoded_len.push(format!(
+                        "if {} {{ {}::encoded_len({}, {}) }} else {{ 0 }}",
+                        set, module, tag, value
+                    ));
+                }
+                merge.push((
+                    tag,
+                    name.clone(),
+                    format!(
+                        "{}::merge(wire_type, &mut self.{}, {})",
+                        module, name, merge_args
+                    ),
+                ));
+            }
+            self.path.pop();
+        }
+        self.path.pop();
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        let mut lines = vec![
+            format!(
+                "impl<'arena> {arena}::ArenaMessage<'arena> for {message}<'arena> {{",
+                arena = arena_path,
+                message = message_name,
+            ),
+            "#[allow(unused_variables)]".to_owned(),
+            format!("fn new_in(arena: &'arena {}::Bump) -> Self {{", arena_path),
+            "Self {".to_owned(),
+        ];
+        lines.extend(new_in);
+        lines.push("}".to_owned());
+        lines.push("}".to_owned());
+        lines.push("#[allow(unused_variables)]".to_owned());
+        lines.push(format!(
+            "fn encode_raw<B>(&self, buf: &mut B) where B: {}::bytes::BufMut {{",
+            prost_path
+        ));
+        lines.extend(encode);
+        lines.push("}".to_owned());
+        lines.push("#[allow(unused_variables)]".to_owned());
+        lines.push(format!(
+            "fn merge_field<B>(&mut self, tag: u32, wire_type: {prost}::encoding::WireType, buf: &mut B, \
+             arena: &'arena {arena}::Bump, ctx: {prost}::encoding::DecodeContext) \
+             -> ::core::result::Result<(), {prost}::DecodeError> where B: {prost}::bytes::Buf {{",
+            prost = prost_path,
+            arena = arena_path,
+        ));
+        lines.push(format!("const STRUCT_NAME: &str = {:?};", message_name));
+        lines.push("match tag {".to_owned());
+        for (tag, name, merge) in merge {
+            lines.push(format!(
+                "{} => {}.map_err(|mut error| {{ error.push(STRUCT_NAME, {:?}); error }}),",
+                tag, merge, name
+            ));
+        }
+        lines.push(format!(
+            "_ => {}::encoding::skip_field(wire_type, tag, buf, ctx),",
+            prost_path
+        ));
+        lines.push("}".to_owned());
+        lines.push("}".to_owned());
+        lines.push("fn encoded_len(&self) -> usize {".to_owned());
+        lines.push(format!(
+            "0{}",
+            encoded_len
+                .iter()
+                .map(|len| format!(" + {}", len))
+                .collect::<String>()
+        ));
+        lines.push("}".to_owned());
+        lines.push("}".to_owned());
+
+        for line in lines {
+            if line.starts_with('}') {
+                self.depth -= 1;
+            }
+            self.push_indent();
+            self.buf.push_str(&line);
+            self.buf.push('\n');
+            if line.ends_with('{') {
+                self.depth += 1;
+            }
+        }
+    }
+
+    fn oneof_case(&self, fq_message_name: &str, oneof: &OneofDescriptorProto) -> bool {
+        let oneof_name = format!("{}.{}", fq_message_name, oneof.name());
+        self.config.oneof_case.get_first(&oneof_name).is_some()
+    }
+
+    fn append_oneof_which(&mut self, message_name: &str, oneof_names: &[&str]) {
+        self.push_indent();
+        self.buf.push_str("impl ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+        for oneof_name in oneof_names {
+            let field_name = to_snake(oneof_name);
+            let enum_name = format!("{}::{}", to_snake(message_name), to_upper_camel(oneof_name));
+
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "/// Returns which case of the `{}` oneof is set, or `None` if it is unset.\n",
+                oneof_name
+            ));
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "pub fn which_{}(&self) -> ::core::option::Option<{}Case> {{\n",
+                field_name.trim_start_matches("r#"),
+                enum_name
+            ));
+            self.depth += 1;
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "self.{}.as_ref().map({}::case)\n",
+                field_name, enum_name
+            ));
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+        }
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_oneof_case(&mut self, oneof: &OneofDescriptorProto, variants: &[String]) {
+        let enum_name = to_upper_camel(oneof.name());
+
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "/// The case of [`{}`], without its payload.\n",
+            enum_name
+        ));
+        self.push_indent();
+        self.buf
+            .push_str("#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]\n");
+        self.push_indent();
+        self.buf
+            .push_str(&format!("pub enum {}Case {{\n", enum_name));
+        self.depth += 1;
+        for variant in variants {
+            self.push_indent();
+            self.buf.push_str(variant);
+            self.buf.push_str(",\n");
+        }
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        self.push_indent();
+        self.buf.push_str(&format!("impl {} {{\n", enum_name));
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str("/// Returns the case of the oneof, without borrowing its payload.\n");
+        self.push_indent();
+        self.buf
+            .push_str(&format!("pub fn case(&self) -> {}Case {{\n", enum_name));
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str("match self {\n");
+        self.depth += 1;
+        for variant in variants {
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "{}::{}(_) => {}Case::{},\n",
+                enum_name, variant, enum_name, variant
+            ));
+        }
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_oneof(
+        &mut self,
+        fq_message_name: &str,
+        oneof: OneofDescriptorProto,
+        idx: i32,
+        fields: Vec<(FieldDescriptorProto, usize)>,
+    ) {
+        self.path.push(8);
+        self.path.push(idx);
+        self.append_doc(fq_message_name, None);
+        self.path.pop();
+        self.path.pop();
+
+        let oneof_name = format!("{}.{}", fq_message_name, oneof.name());
+        self.append_type_attributes(&oneof_name);
+        self.append_enum_attributes(&oneof_name);
+        self.push_indent();
+        self.buf
+            .push_str("#[allow(clippy::derive_partial_eq_without_eq)]\n");
+        self.buf.push_str(&format!(
+            "#[derive(Clone, PartialEq, {}::Oneof)]\n",
+            self.config.prost_path.as_deref().unwrap_or("::prost")
+        ));
+        let serde = self.derive_serde(fq_message_name);
+        if serde {
+            self.append_serde_derive();
+        }
+        self.push_indent();
+        self.buf.push_str("pub enum ");
+        self.buf.push_str(&to_upper_camel(oneof.name()));
+        self.buf.push_str(" {\n");
+
+        let case = self.oneof_case(fq_message_name, &oneof);
+        let variants = fields
+            .iter()
+            .map(|&(ref field, _)| to_upper_camel(field.name()))
+            .collect::<Vec<_>>();
+        let from = self.config.oneof_from.get_first(&oneof_name).is_some();
+        let mut payloads = Vec::new();
+
+        self.path.push(2);
+        self.depth += 1;
+        for (field, idx) in fields {
+            let type_ = field.r#type();
+
+            self.path.push(idx as i32);
+            self.append_doc(fq_message_name, Some(field.name()));
+            self.path.pop();
+
+            self.push_indent();
+            let ty_tag = self.field_type_tag(&field);
+            self.buf.push_str(&format!(
+                "#[prost({}{}, tag=\"{}\")]\n",
+                ty_tag,
+                if self.lossy_string(&oneof_name, &field) {
+                    ", lossy"
+                } else {
+                    ""
+                },
+                field.number()
+            ));
+            self.append_field_attributes(&oneof_name, field.name());
+            if serde {
+                self.push_indent();
+                self.buf
+                    .push_str(&format!("#[serde(rename = \"{}\")]\n", field.name()));
+            }
+
+            self.push_indent();
+            let ty = self.resolve_type(&field, fq_message_name);
+
+            let boxed = ((type_ == Type::Message || type_ == Type::Group)
+                && (self
+                    .message_graph
+                    .is_nested(field.type_name(), fq_message_name)
+                    || self.config.boxed_oneof.get_first(&oneof_name).is_some()))
+                || (self
                     .config
                     .boxed
                     .get_first_field(&oneof_name, field.name())
@@ -585,16 +1734,76 @@ impl<'a> CodeGenerator<'a> {
                     to_upper_camel(field.name()),
                     ty
                 ));
+            } else if let Some(with) = self.serde_with(&field, "").filter(|_| serde) {
+                self.buf.push_str(&format!(
+                    "{}(#[serde({})] {}),\n",
+                    to_upper_camel(field.name()),
+                    with,
+                    ty
+                ));
             } else {
                 self.buf
                     .push_str(&format!("{}({}),\n", to_upper_camel(field.name()), ty));
             }
+
+            if type_ != Type::Enum {
+                payloads.push((to_upper_camel(field.name()), ty, boxed));
+            }
         }
         self.depth -= 1;
         self.path.pop();
 
         self.push_indent();
         self.buf.push_str("}\n");
+
+        if case {
+            self.append_oneof_case(&oneof, &variants);
+        }
+        if from {
+            self.append_oneof_from(&oneof, &payloads);
+        }
+    }
+
+    fn append_oneof_from(
+        &mut self,
+        oneof: &OneofDescriptorProto,
+        payloads: &[(String, String, bool)],
+    ) {
+        let enum_name = to_upper_camel(oneof.name());
+
+        for (variant, ty, boxed) in payloads {
+            // A payload type shared by several variants can't pick a variant unambiguously.
+            if payloads.iter().filter(|(_, other, _)| other == ty).count() > 1 {
+                continue;
+            }
+
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "impl ::core::convert::From<{}> for {} {{\n",
+                ty, enum_name
+            ));
+            self.depth += 1;
+            self.push_indent();
+            self.buf
+                .push_str(&format!("fn from(value: {}) -> Self {{\n", ty));
+            self.depth += 1;
+            self.push_indent();
+            if *boxed {
+                self.buf.push_str(&format!(
+                    "{}::{}(::prost::alloc::boxed::Box::new(value))\n",
+                    enum_name, variant
+                ));
+            } else {
+                self.buf
+                    .push_str(&format!("{}::{}(value)\n", enum_name, variant));
+            }
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+        }
     }
 
     fn location(&self) -> Option<&Location> {
@@ -622,7 +1831,7 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
-    fn append_enum(&mut self, desc: EnumDescriptorProto) {
+    fn append_enum(&mut self, desc: EnumDescriptorProto) -> Result<(), String> {
         debug!("  enum: {:?}", desc.name());
 
         let proto_enum_name = desc.name();
@@ -640,26 +1849,79 @@ impl<'a> CodeGenerator<'a> {
             .resolve_ident(&fq_proto_enum_name)
             .is_some()
         {
-            return;
+            return Ok(());
+        }
+
+        let variant_mappings =
+            build_enum_value_mappings(&enum_name, self.config.strip_enum_prefix, enum_values);
+
+        // An open enum keeps values outside of its known variants in an `Unknown` variant.
+        let open = self
+            .config
+            .open_enums
+            .get_first(&fq_proto_enum_name)
+            .is_some();
+        if open {
+            if variant_mappings
+                .iter()
+                .any(|variant| variant.generated_variant_name == "Unknown")
+            {
+                return Err(format!(
+                    "open enum {} has a value named `Unknown`, which clashes with the variant \
+                     holding unknown values",
+                    fq_proto_enum_name
+                ));
+            }
+            if self
+                .config
+                .enum_extern
+                .get_first(&fq_proto_enum_name)
+                .is_some()
+            {
+                return Err(format!(
+                    "open enum {} can't also be an extern enum",
+                    fq_proto_enum_name
+                ));
+            }
         }
 
         self.append_doc(&fq_proto_enum_name, None);
         self.append_type_attributes(&fq_proto_enum_name);
         self.append_enum_attributes(&fq_proto_enum_name);
         self.push_indent();
-        self.buf.push_str(
-            &format!("#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, {}::Enumeration)]\n",self.config.prost_path.as_deref().unwrap_or("::prost")),
-        );
-        self.push_indent();
-        self.buf.push_str("#[repr(i32)]\n");
+        if open {
+            self.buf.push_str("#[derive(Clone, Copy, Debug, Eq)]\n");
+        } else {
+            self.buf.push_str(&format!(
+                "#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, {}::Enumeration)]\n",
+                self.config.prost_path.as_deref().unwrap_or("::prost")
+            ));
+            self.push_indent();
+            self.buf.push_str("#[repr(i32)]\n");
+            if self
+                .config
+                .enum_try_from
+                .get_first(&fq_proto_enum_name)
+                .is_some()
+            {
+                self.push_indent();
+                self.buf.push_str("#[prost(try_from_i32)]\n");
+                if let Some(prost_path) = self.config.prost_path.clone() {
+                    self.push_indent();
+                    self.buf
+                        .push_str(&format!("#[prost(prost_path = \"{}\")]\n", prost_path));
+                }
+            }
+        }
+        let serde = self.derive_serde(&fq_proto_enum_name);
+        if serde {
+            self.append_serde_derive();
+        }
         self.push_indent();
         self.buf.push_str("pub enum ");
         self.buf.push_str(&enum_name);
         self.buf.push_str(" {\n");
 
-        let variant_mappings =
-            build_enum_value_mappings(&enum_name, self.config.strip_enum_prefix, enum_values);
-
         self.depth += 1;
         self.path.push(2);
         for variant in variant_mappings.iter() {
@@ -667,14 +1929,32 @@ impl<'a> CodeGenerator<'a> {
 
             self.append_doc(&fq_proto_enum_name, Some(variant.proto_name));
             self.append_field_attributes(&fq_proto_enum_name, variant.proto_name);
+            if serde {
+                self.push_indent();
+                self.buf
+                    .push_str(&format!("#[serde(rename = \"{}\")]\n", variant.proto_name));
+            }
             self.push_indent();
             self.buf.push_str(&variant.generated_variant_name);
-            self.buf.push_str(" = ");
-            self.buf.push_str(&variant.proto_number.to_string());
+            if !open {
+                self.buf.push_str(" = ");
+                self.buf.push_str(&variant.proto_number.to_string());
+            }
             self.buf.push_str(",\n");
 
             self.path.pop();
         }
+        if open {
+            self.push_indent();
+            self.buf
+                .push_str("/// A value which isn't one of the known variants.\n");
+            if serde {
+                self.push_indent();
+                self.buf.push_str("#[serde(skip)]\n");
+            }
+            self.push_indent();
+            self.buf.push_str("Unknown(i32),\n");
+        }
 
         self.path.pop();
         self.depth -= 1;
@@ -721,6 +2001,11 @@ impl<'a> CodeGenerator<'a> {
             self.buf.push_str(variant.proto_name);
             self.buf.push_str("\",\n");
         }
+        if open {
+            self.push_indent();
+            self.buf.push_str(&enum_name);
+            self.buf.push_str("::Unknown(_) => \"\",\n");
+        }
 
         self.depth -= 1;
         self.push_indent();
@@ -740,23 +2025,27 @@ impl<'a> CodeGenerator<'a> {
         self.depth += 1;
 
         self.push_indent();
-        self.buf.push_str("match value {\n");
+        self.buf.push_str("let variant = match value {\n");
         self.depth += 1;
 
         for variant in variant_mappings.iter() {
             self.push_indent();
             self.buf.push('\"');
             self.buf.push_str(variant.proto_name);
-            self.buf.push_str("\" => Some(Self::");
+            self.buf.push_str("\" => Self::");
             self.buf.push_str(&variant.generated_variant_name);
-            self.buf.push_str("),\n");
+            self.buf.push_str(",\n");
         }
         self.push_indent();
-        self.buf.push_str("_ => None,\n");
+        self.buf
+            .push_str("_ => return ::core::option::Option::None,\n");
 
         self.depth -= 1;
         self.push_indent();
-        self.buf.push_str("}\n"); // End of match
+        self.buf.push_str("};\n"); // End of match
+
+        self.push_indent();
+        self.buf.push_str("::core::option::Option::Some(variant)\n");
 
         self.depth -= 1;
         self.push_indent();
@@ -766,6 +2055,343 @@ impl<'a> CodeGenerator<'a> {
         self.depth -= 1;
         self.push_indent();
         self.buf.push_str("}\n"); // End of impl
+
+        if open {
+            self.append_open_enum_impls(&enum_name, &variant_mappings);
+        } else {
+            self.append_enum_hash(&enum_name);
+        }
+
+        if self.config.enum_string_impls {
+            self.append_enum_display(&enum_name);
+            self.append_enum_from_str(&enum_name);
+        }
+
+        if let Some(rust_enum) = self
+            .config
+            .enum_extern
+            .get_first(&fq_proto_enum_name)
+            .cloned()
+        {
+            self.append_enum_extern(&enum_name, &rust_enum, &variant_mappings);
+        }
+
+        if serde {
+            self.append_named_enum(&enum_name, &variant_mappings);
+        }
+        Ok(())
+    }
+
+    /// Appends the impls of an open enum, which the `Enumeration` derive provides for closed
+    /// enums. Open enums are compared, ordered and hashed by their numbers, so an `Unknown`
+    /// variant holding the number of a known variant is equal to that variant.
+    fn append_open_enum_impls(&mut self, enum_name: &str, variant_mappings: &[EnumVariantMapping]) {
+        let into = "::core::convert::Into::<i32>::into";
+        let is_valid = variant_mappings
+            .iter()
+            .map(|variant| format!("            {} => true,\n", variant.proto_number))
+            .collect::<String>();
+        let to_i32 = variant_mappings
+            .iter()
+            .map(|variant| {
+                format!(
+                    "            {}::{} => {},\n",
+                    enum_name, variant.generated_variant_name, variant.proto_number
+                )
+            })
+            .collect::<String>();
+        let from_i32 = variant_mappings
+            .iter()
+            .map(|variant| {
+                format!(
+                    "            {} => {}::{},\n",
+                    variant.proto_number, enum_name, variant.generated_variant_name
+                )
+            })
+            .collect::<String>();
+
+        let code = format!(
+            "impl {enum_name} {{\n\
+             \x20   /// Returns `true` if `value` is a known variant of `{enum_name}`.\n\
+             \x20   pub fn is_valid(value: i32) -> bool {{\n\
+             \x20       match value {{\n\
+             {is_valid}\
+             \x20           _ => false,\n\
+             \x20       }}\n\
+             \x20   }}\n\
+             \x20   /// Converts an `i32` to a `{enum_name}`, which is never `None`: unknown values are\n\
+             \x20   /// kept in `{enum_name}::Unknown`.\n\
+             \x20   pub fn from_i32(value: i32) -> ::core::option::Option<{enum_name}> {{\n\
+             \x20       ::core::option::Option::Some(<Self as ::core::convert::From<i32>>::from(value))\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::default::Default for {enum_name} {{\n\
+             \x20   fn default() -> {enum_name} {{\n\
+             \x20       {enum_name}::{default}\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::convert::From<{enum_name}> for i32 {{\n\
+             \x20   fn from(value: {enum_name}) -> i32 {{\n\
+             \x20       match value {{\n\
+             {to_i32}\
+             \x20           {enum_name}::Unknown(value) => value,\n\
+             \x20       }}\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::convert::From<i32> for {enum_name} {{\n\
+             \x20   fn from(value: i32) -> {enum_name} {{\n\
+             \x20       match value {{\n\
+             {from_i32}\
+             \x20           value => {enum_name}::Unknown(value),\n\
+             \x20       }}\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::cmp::PartialEq for {enum_name} {{\n\
+             \x20   fn eq(&self, other: &Self) -> bool {{\n\
+             \x20       {into}(*self) == {into}(*other)\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::cmp::PartialOrd for {enum_name} {{\n\
+             \x20   fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {{\n\
+             \x20       ::core::option::Option::Some(::core::cmp::Ord::cmp(self, other))\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::cmp::Ord for {enum_name} {{\n\
+             \x20   fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {{\n\
+             \x20       ::core::cmp::Ord::cmp(&{into}(*self), &{into}(*other))\n\
+             \x20   }}\n\
+             }}\n\
+             impl ::core::hash::Hash for {enum_name} {{\n\
+             \x20   /// Hashes the `i32` number of the value, like closed enums do.\n\
+             \x20   fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {{\n\
+             \x20       ::core::hash::Hash::hash(&{into}(*self), state);\n\
+             \x20   }}\n\
+             }}\n",
+            enum_name = enum_name,
+            default = variant_mappings[0].generated_variant_name,
+            is_valid = is_valid,
+            to_i32 = to_i32,
+            from_i32 = from_i32,
+            into = into,
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    fn append_enum_extern(
+        &mut self,
+        enum_name: &str,
+        rust_enum: &str,
+        variant_mappings: &[EnumVariantMapping],
+    ) {
+        let prost_path = self
+            .config
+            .prost_path
+            .as_deref()
+            .unwrap_or("::prost")
+            .to_owned();
+
+        for (from, to) in [(enum_name, rust_enum), (rust_enum, enum_name)] {
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "impl ::core::convert::From<{}> for {} {{\n",
+                from, to
+            ));
+            self.depth += 1;
+            self.push_indent();
+            self.buf
+                .push_str(&format!("fn from(value: {}) -> Self {{\n", from));
+            self.depth += 1;
+            self.push_indent();
+            self.buf.push_str("match value {\n");
+            self.depth += 1;
+            for variant in variant_mappings {
+                self.push_indent();
+                self.buf.push_str(&format!(
+                    "{}::{} => Self::{},\n",
+                    from, variant.generated_variant_name, variant.generated_variant_name
+                ));
+            }
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+            self.depth -= 1;
+            self.push_indent();
+            self.buf.push_str("}\n");
+        }
+
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "impl ::core::convert::From<{}> for i32 {{\n",
+            rust_enum
+        ));
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str(&format!("fn from(value: {}) -> Self {{\n", rust_enum));
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str(&format!("{}::from(value) as i32\n", enum_name));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "impl ::core::convert::TryFrom<i32> for {} {{\n",
+            rust_enum
+        ));
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str(&format!("type Error = {}::DecodeError;\n", prost_path));
+        self.push_indent();
+        self.buf
+            .push_str("fn try_from(value: i32) -> ::core::result::Result<Self, Self::Error> {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "{}::from_i32(value).map(Self::from).ok_or_else(|| {}::DecodeError::new(\"invalid enumeration value\"))\n",
+            enum_name, prost_path
+        ));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    /// Appends the `Hash` impl of an enum, which hashes the number of the value rather than its
+    /// discriminant, so that hashes don't depend on how the compiler represents the enum.
+    fn append_enum_hash(&mut self, enum_name: &str) {
+        let code = format!(
+            "impl ::core::hash::Hash for {enum_name} {{\n\
+             \x20   /// Hashes the `i32` number of the value, as returned by `as i32`.\n\
+             \x20   ///\n\
+             \x20   /// The hash doesn't depend on the names or order of the values, so it's as stable\n\
+             \x20   /// across builds and versions as the hasher, e.g. for use as a shard key.\n\
+             \x20   fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {{\n\
+             \x20       ::core::hash::Hash::hash(&(*self as i32), state);\n\
+             \x20   }}\n\
+             }}\n",
+            enum_name = enum_name,
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Appends the `prost::serde::NamedEnum` impl, which serializes enumeration fields by name.
+    fn append_named_enum(&mut self, enum_name: &str, variant_mappings: &[EnumVariantMapping]) {
+        let names = variant_mappings
+            .iter()
+            .map(|variant| {
+                format!(
+                    "            {} => ::core::option::Option::Some(\"{}\"),\n",
+                    variant.proto_number, variant.proto_name
+                )
+            })
+            .collect::<String>();
+        let values = variant_mappings
+            .iter()
+            .map(|variant| {
+                format!(
+                    "            \"{}\" => ::core::option::Option::Some({}),\n",
+                    variant.proto_name, variant.proto_number
+                )
+            })
+            .collect::<String>();
+
+        let code = format!(
+            "impl {prost_path}::serde::NamedEnum for {enum_name} {{\n\
+             \x20   fn name(value: i32) -> ::core::option::Option<&'static str> {{\n\
+             \x20       match value {{\n\
+             {names}\
+             \x20           _ => ::core::option::Option::None,\n\
+             \x20       }}\n\
+             \x20   }}\n\
+             \x20   fn value(name: &str) -> ::core::option::Option<i32> {{\n\
+             \x20       match name {{\n\
+             {values}\
+             \x20           _ => ::core::option::Option::None,\n\
+             \x20       }}\n\
+             \x20   }}\n\
+             }}\n",
+            prost_path = self.config.prost_path.as_deref().unwrap_or("::prost"),
+            enum_name = enum_name,
+            names = names,
+            values = values,
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    fn append_enum_display(&mut self, enum_name: &str) {
+        self.push_indent();
+        self.buf
+            .push_str(&format!("impl ::core::fmt::Display for {} {{\n", enum_name));
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str(
+            "fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {\n",
+        );
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str("f.write_str(self.as_str_name())\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_enum_from_str(&mut self, enum_name: &str) {
+        let prost_path = self
+            .config
+            .prost_path
+            .as_deref()
+            .unwrap_or("::prost")
+            .to_owned();
+
+        self.push_indent();
+        self.buf
+            .push_str(&format!("impl ::core::str::FromStr for {} {{\n", enum_name));
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str(&format!("type Err = {}::ParseEnumError;\n", prost_path));
+        self.push_indent();
+        self.buf
+            .push_str("fn from_str(value: &str) -> ::core::result::Result<Self, Self::Err> {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "Self::from_str_name(value).ok_or_else({}::ParseEnumError::new)\n",
+            prost_path
+        ));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
     }
 
     fn push_service(&mut self, service: ServiceDescriptorProto) {
@@ -825,8 +2451,32 @@ impl<'a> CodeGenerator<'a> {
             options: service.options.unwrap_or_default(),
         };
 
+        let service_features = self.config.service_features.clone();
         if let Some(service_generator) = self.config.service_generator.as_mut() {
-            service_generator.generate(service, self.buf)
+            let sides = service_features.map(|(client_feature, server_feature)| {
+                let mut client = String::new();
+                service_generator.generate_client(&service, &mut client);
+                let mut server = String::new();
+                service_generator.generate_server(&service, &mut server);
+                [
+                    (client_feature, "client", client),
+                    (server_feature, "server", server),
+                ]
+            });
+            let module = to_snake(&service.proto_name);
+            service_generator.generate(service, self.buf);
+
+            for (feature, side, code) in sides.into_iter().flatten() {
+                if code.is_empty() {
+                    continue;
+                }
+                self.buf.push_str(&format!(
+                    "#[cfg(feature = {:?})]\npub mod {}_{} {{\n",
+                    feature, module, side
+                ));
+                self.buf.push_str(&code);
+                self.buf.push_str("}\n");
+            }
         }
     }
 
@@ -893,28 +2543,26 @@ impl<'a> CodeGenerator<'a> {
             return proto_ident;
         }
 
-        let mut local_path = self.package.split('.').peekable();
-
-        // If no package is specified the start of the package name will be '.'
-        // and split will return an empty string ("") which breaks resolution
-        // The fix to this is to ignore the first item if it is empty.
-        if local_path.peek().map_or(false, |s| s.is_empty()) {
-            local_path.next();
-        }
+        let local_path = self.config.module_for_path(&self.package);
+        let local_path: Vec<&str> = local_path.parts().collect();
 
-        let mut ident_path = pb_ident[1..].split('.');
-        let ident_type = ident_path.next_back().unwrap();
-        let mut ident_path = ident_path.peekable();
+        let (ident_path, ident_type) = pb_ident[1..]
+            .rsplit_once('.')
+            .unwrap_or(("", &pb_ident[1..]));
+        let ident_path = self.config.module_for_path(ident_path);
+        let ident_path: Vec<&str> = ident_path.parts().collect();
 
         // Skip path elements in common.
-        while local_path.peek().is_some() && local_path.peek() == ident_path.peek() {
-            local_path.next();
-            ident_path.next();
-        }
-
-        local_path
+        let common = local_path
+            .iter()
+            .zip(&ident_path)
+            .take_while(|(local, ident)| local == ident)
+            .count();
+
+        local_path[common..]
+            .iter()
             .map(|_| "super".to_string())
-            .chain(ident_path.map(to_snake))
+            .chain(ident_path[common..].iter().map(|s| s.to_string()))
             .chain(iter::once(to_upper_camel(ident_type)))
             .join("::")
     }
@@ -965,9 +2613,68 @@ impl<'a> CodeGenerator<'a> {
         }
 
         match field.r#type() {
-            Type::Message => true,
-            _ => self.syntax == Syntax::Proto2,
+            Type::Message | Type::Group => {
+                self.field_presence(field) != FieldPresence::LegacyRequired
+            }
+            _ => match self.syntax {
+                Syntax::Proto2 => true,
+                Syntax::Proto3 => false,
+                Syntax::Editions => self.field_presence(field) == FieldPresence::Explicit,
+            },
+        }
+    }
+
+    /// Returns `true` if the repeated field uses the packed encoding.
+    fn packed(&self, field: &FieldDescriptorProto) -> bool {
+        if self.syntax != Syntax::Editions {
+            return field
+                .options
+                .as_ref()
+                .map_or(self.syntax == Syntax::Proto3, |options| options.packed());
+        }
+
+        self.resolve_feature(field, |features| features.repeated_field_encoding)
+            != Some(RepeatedFieldEncoding::Expanded as i32)
+    }
+
+    /// Returns the field presence of the field in a file using editions.
+    ///
+    /// The edition 2023 default of explicit presence is used if no feature set overrides it.
+    fn field_presence(&self, field: &FieldDescriptorProto) -> FieldPresence {
+        if self.syntax != Syntax::Editions {
+            return FieldPresence::Unknown;
         }
+
+        self.resolve_feature(field, |features| features.field_presence)
+            .and_then(FieldPresence::from_i32)
+            .unwrap_or(FieldPresence::Explicit)
+    }
+
+    /// Turns a message field using the delimited encoding of editions into a group field, which
+    /// has the same encoding.
+    fn lower_delimited(&self, mut field: FieldDescriptorProto) -> FieldDescriptorProto {
+        if self.syntax == Syntax::Editions
+            && field.r#type() == Type::Message
+            && self.resolve_feature(&field, |features| features.message_encoding)
+                == Some(MessageEncoding::Delimited as i32)
+        {
+            field.set_type(Type::Group);
+        }
+        field
+    }
+
+    /// Resolves a feature of the field, falling back to the features of the enclosing messages
+    /// and the file.
+    fn resolve_feature<F>(&self, field: &FieldDescriptorProto, feature: F) -> Option<i32>
+    where
+        F: Fn(&FeatureSet) -> Option<i32>,
+    {
+        field
+            .options
+            .as_ref()
+            .and_then(|options| options.features.as_ref())
+            .and_then(&feature)
+            .or_else(|| self.features.iter().rev().find_map(&feature))
     }
 
     /// Returns `true` if the field options includes the `deprecated` option.
@@ -979,6 +2686,34 @@ impl<'a> CodeGenerator<'a> {
     }
 }
 
+/// Returns `true` if the field type is an integer type, excluding enumerations.
+fn is_integer(ty: Type) -> bool {
+    matches!(
+        ty,
+        Type::Int32
+            | Type::Int64
+            | Type::Uint32
+            | Type::Uint64
+            | Type::Sint32
+            | Type::Sint64
+            | Type::Fixed32
+            | Type::Fixed64
+            | Type::Sfixed32
+            | Type::Sfixed64
+    )
+}
+
+/// Returns the `NonZero*` type representing the integer field type.
+fn nonzero_type(ty: Type) -> &'static str {
+    match ty {
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 => "::core::num::NonZeroI32",
+        Type::Int64 | Type::Sint64 | Type::Sfixed64 => "::core::num::NonZeroI64",
+        Type::Uint32 | Type::Fixed32 => "::core::num::NonZeroU32",
+        Type::Uint64 | Type::Fixed64 => "::core::num::NonZeroU64",
+        _ => panic!("{:?} is not an integer type", ty),
+    }
+}
+
 /// Returns `true` if the repeated field type can be packed.
 fn can_pack(field: &FieldDescriptorProto) -> bool {
     matches!(
@@ -1000,6 +2735,64 @@ fn can_pack(field: &FieldDescriptorProto) -> bool {
     )
 }
 
+/// The number of elements assumed for repeated and map fields when estimating message sizes.
+const SIZE_HINT_ELEMENTS: usize = 4;
+/// The length assumed for strings, bytes and nested messages when estimating message sizes.
+const SIZE_HINT_LENGTH: usize = 16;
+
+/// Estimates the typical encoded length of a message from the types of its fields.
+///
+/// Fixed-width fields contribute their exact size, and each oneof contributes its largest
+/// variant.
+fn size_hint(
+    fields: &[(FieldDescriptorProto, usize)],
+    oneof_fields: &MultiMap<i32, (FieldDescriptorProto, usize)>,
+    map_types: &HashMap<String, (FieldDescriptorProto, FieldDescriptorProto)>,
+) -> usize {
+    let field_hint = |field: &FieldDescriptorProto| {
+        let key_len = prost::encoding::key_len(field.number() as u32);
+        if let Some((key, value)) = field
+            .type_name
+            .as_ref()
+            .and_then(|type_name| map_types.get(type_name))
+        {
+            let entry_len = 2 + value_size_hint(key) + value_size_hint(value);
+            SIZE_HINT_ELEMENTS * (key_len + 1 + entry_len)
+        } else if field.label() == Label::Repeated {
+            SIZE_HINT_ELEMENTS * (key_len + value_size_hint(field))
+        } else {
+            key_len + value_size_hint(field)
+        }
+    };
+
+    let oneofs = oneof_fields.iter_all().map(|(_, variants)| {
+        variants
+            .iter()
+            .map(|(field, _)| field_hint(field))
+            .max()
+            .unwrap_or(0)
+    });
+    fields
+        .iter()
+        .map(|(field, _)| field_hint(field))
+        .chain(oneofs)
+        .sum()
+}
+
+/// Estimates the encoded length of a single value of a field, excluding its key.
+fn value_size_hint(field: &FieldDescriptorProto) -> usize {
+    match field.r#type() {
+        Type::Bool => 1,
+        Type::Fixed32 | Type::Sfixed32 | Type::Float => 4,
+        Type::Fixed64 | Type::Sfixed64 | Type::Double => 8,
+        Type::Int32 | Type::Uint32 | Type::Sint32 | Type::Enum => 5,
+        Type::Int64 | Type::Uint64 | Type::Sint64 => 10,
+        Type::String | Type::Bytes | Type::Message => 1 + SIZE_HINT_LENGTH,
+        // A group is closed by an end-group key rather than prefixed with its length.
+        Type::Group => SIZE_HINT_LENGTH + 1,
+    }
+}
+
 /// Based on [`google::protobuf::UnescapeCEscapeString`][1]
 /// [1]: https://github.com/google/protobuf/blob/3.3.x/src/google/protobuf/stubs/strutil.cc#L312-L322
 fn unescape_c_escape_string(s: &str) -> Vec<u8> {
@@ -1200,6 +2993,7 @@ impl BytesType {
         match self {
             BytesType::Vec => "vec",
             BytesType::Bytes => "bytes",
+            BytesType::Shared => "shared",
         }
     }
 
@@ -1208,6 +3002,7 @@ impl BytesType {
         match self {
             BytesType::Vec => "::prost::alloc::vec::Vec<u8>",
             BytesType::Bytes => "::prost::bytes::Bytes",
+            BytesType::Shared => "::prost::SharedBytes",
         }
     }
 }
@@ -1215,6 +3010,7 @@ impl BytesType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Module;
 
     #[test]
     fn test_unescape_c_escape_string() {
@@ -1251,4 +3047,1315 @@ mod tests {
         assert_eq!(strip_enum_prefix("Foo", "Bar"), "Bar");
         assert_eq!(strip_enum_prefix("Foo", "Foo1"), "Foo1");
     }
+
+    #[test]
+    fn test_editions_features() {
+        let field = |name: &str, number: i32, label: Label, features: Option<FeatureSet>| {
+            FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(label as i32),
+                r#type: Some(Type::Int32 as i32),
+                options: features.map(|features| FieldOptions {
+                    features: Some(features),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        };
+        let presence = |presence: FieldPresence| FeatureSet {
+            field_presence: Some(presence as i32),
+            ..Default::default()
+        };
+
+        let file = FileDescriptorProto {
+            name: Some("editions.proto".to_string()),
+            package: Some("editions".to_string()),
+            syntax: Some("editions".to_string()),
+            edition: Some(Edition::Edition2023 as i32),
+            message_type: vec![DescriptorProto {
+                name: Some("Msg".to_string()),
+                field: vec![
+                    field("explicit", 1, Label::Optional, None),
+                    field(
+                        "implicit",
+                        2,
+                        Label::Optional,
+                        Some(presence(FieldPresence::Implicit)),
+                    ),
+                    field(
+                        "required",
+                        3,
+                        Label::Optional,
+                        Some(presence(FieldPresence::LegacyRequired)),
+                    ),
+                    field("packed", 4, Label::Repeated, None),
+                    field(
+                        "expanded",
+                        5,
+                        Label::Repeated,
+                        Some(FeatureSet {
+                            repeated_field_encoding: Some(RepeatedFieldEncoding::Expanded as i32),
+                            ..Default::default()
+                        }),
+                    ),
+                    FieldDescriptorProto {
+                        name: Some("delimited".to_string()),
+                        number: Some(6),
+                        label: Some(Label::Optional as i32),
+                        r#type: Some(Type::Message as i32),
+                        type_name: Some(".editions.Msg".to_string()),
+                        options: Some(FieldOptions {
+                            features: Some(FeatureSet {
+                                message_encoding: Some(MessageEncoding::Delimited as i32),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("editions");
+        let modules = Config::new()
+            .format(false)
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[prost(int32, optional, tag=\"1\")]"));
+        assert!(code.contains("pub explicit: ::core::option::Option<i32>,"));
+        assert!(code.contains("#[prost(int32, tag=\"2\")]"));
+        assert!(code.contains("pub implicit: i32,"));
+        assert!(code.contains("#[prost(int32, required, tag=\"3\")]"));
+        assert!(code.contains("pub required: i32,"));
+        assert!(code.contains("#[prost(int32, repeated, tag=\"4\")]"));
+        assert!(code.contains("#[prost(int32, repeated, packed=\"false\", tag=\"5\")]"));
+        assert!(code.contains("#[prost(group, optional, boxed, tag=\"6\")]"));
+    }
+
+    #[test]
+    fn test_unsupported_edition() {
+        let file = FileDescriptorProto {
+            name: Some("future.proto".to_string()),
+            package: Some("future".to_string()),
+            syntax: Some("editions".to_string()),
+            edition: Some(Edition::Edition2024 as i32),
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("future");
+        let error = Config::new()
+            .format(false)
+            .generate(vec![(module, file)])
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("EDITION_2024"));
+    }
+
+    #[test]
+    fn test_oneof_from_skips_ambiguous_payloads() {
+        let field = |name: &str, number: i32, r#type: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            oneof_index: Some(0),
+            ..Default::default()
+        };
+
+        let file = FileDescriptorProto {
+            name: Some("oneof.proto".to_string()),
+            package: Some("oneof".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Msg".to_string()),
+                field: vec![
+                    field("first", 1, Type::String),
+                    field("second", 2, Type::String),
+                    field("number", 3, Type::Int32),
+                ],
+                oneof_decl: vec![OneofDescriptorProto {
+                    name: Some("kind".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("oneof");
+        let modules = Config::new()
+            .format(false)
+            .oneof_from(".oneof.Msg.kind")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("impl ::core::convert::From<i32> for Kind {"));
+        assert!(code.contains("Kind::Number(value)"));
+        assert!(!code.contains("::core::convert::From<::prost::alloc::string::String>"));
+    }
+
+    #[test]
+    fn test_boxed_oneof() {
+        let field =
+            |name: &str, number: i32, r#type: Type, type_name: Option<&str>| FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(Label::Optional as i32),
+                r#type: Some(r#type as i32),
+                type_name: type_name.map(str::to_string),
+                oneof_index: Some(0),
+                ..Default::default()
+            };
+        let file = FileDescriptorProto {
+            name: Some("oneof.proto".to_string()),
+            package: Some("oneof".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Msg".to_string()),
+                    field: vec![
+                        field("text", 1, Type::String, None),
+                        field("big", 2, Type::Message, Some(".oneof.Big")),
+                    ],
+                    oneof_decl: vec![OneofDescriptorProto {
+                        name: Some("kind".to_string()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Big".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("oneof");
+        let modules = Config::new()
+            .format(false)
+            .boxed_oneof(".oneof.Msg.kind")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("Text(::prost::alloc::string::String),"));
+        assert!(code.contains("Big(::prost::alloc::boxed::Box<super::Big>),"));
+    }
+
+    #[test]
+    fn test_service_features() {
+        use crate::ServiceGenerator;
+
+        struct SplitGenerator;
+
+        impl ServiceGenerator for SplitGenerator {
+            fn generate(&mut self, service: Service, buf: &mut String) {
+                buf.push_str(&format!("pub struct {}Shared;\n", service.name));
+            }
+            fn generate_client(&mut self, service: &Service, buf: &mut String) {
+                buf.push_str(&format!("pub struct {}Client;\n", service.name));
+            }
+        }
+
+        let file = FileDescriptorProto {
+            name: Some("greeter.proto".to_string()),
+            package: Some("greeter".to_string()),
+            syntax: Some("proto3".to_string()),
+            service: vec![ServiceDescriptorProto {
+                name: Some("Greeter".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("greeter");
+        let modules = Config::new()
+            .format(false)
+            .service_generator(Box::new(SplitGenerator))
+            .service_features("greeter-client", "greeter-server")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "pub struct GreeterShared;\n\
+             #[cfg(feature = \"greeter-client\")]\n\
+             pub mod greeter_client {\n\
+             pub struct GreeterClient;\n\
+             }\n"
+        ));
+        // Sides without code get no module.
+        assert!(!code.contains("greeter_server"));
+    }
+
+    #[test]
+    fn test_transparent() {
+        let file = FileDescriptorProto {
+            name: Some("id.proto".to_string()),
+            package: Some("id".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("UserId".to_string()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("id".to_string()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::Uint64 as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("id");
+        let modules = Config::new()
+            .format(false)
+            .transparent(".id.UserId")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[repr(transparent)]\npub struct UserId(\n"));
+        assert!(code.contains("    pub u64,\n);\n"));
+    }
+
+    #[test]
+    fn test_bytes_type() {
+        let field = |name: &str, number, label: Label| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(Type::Bytes as i32),
+            ..Default::default()
+        };
+        let message = |name: &str| DescriptorProto {
+            name: Some(name.to_string()),
+            field: vec![
+                field("blob", 1, Label::Optional),
+                field("chunks", 2, Label::Repeated),
+            ],
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("blobs.proto".to_string()),
+            package: Some("blobs".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![message("Upload"), message("Download")],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("blobs");
+        let modules = Config::new()
+            .format(false)
+            .bytes([".blobs.Upload.blob", ".blobs.Download"])
+            .shared_bytes([".blobs.Upload.chunks"])
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        // A field path matches that field only, and a message path all of its fields.
+        assert!(code.contains(
+            "pub struct Upload {\n\
+             \x20   #[prost(bytes=\"bytes\", tag=\"1\")]\n\
+             \x20   pub blob: ::prost::bytes::Bytes,\n\
+             \x20   #[prost(bytes=\"shared\", repeated, tag=\"2\")]\n\
+             \x20   pub chunks: ::prost::alloc::vec::Vec<::prost::SharedBytes>,\n"
+        ));
+        assert!(code.contains(
+            "pub struct Download {\n\
+             \x20   #[prost(bytes=\"bytes\", tag=\"1\")]\n\
+             \x20   pub blob: ::prost::bytes::Bytes,\n\
+             \x20   #[prost(bytes=\"bytes\", repeated, tag=\"2\")]\n\
+             \x20   pub chunks: ::prost::alloc::vec::Vec<::prost::bytes::Bytes>,\n"
+        ));
+    }
+
+    #[test]
+    fn test_transparent_buf() {
+        let file = FileDescriptorProto {
+            name: Some("blob.proto".to_string()),
+            package: Some("blob".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Blob".to_string()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("data".to_string()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::Bytes as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("blob");
+        let code = &Config::new()
+            .format(false)
+            .transparent(".blob.Blob")
+            .generate(vec![(module.clone(), file.clone())])
+            .unwrap()[&module];
+        assert!(!code.contains("Buf for Blob"));
+
+        let code = &Config::new()
+            .format(false)
+            .transparent(".blob.Blob")
+            .bytes([".blob.Blob.data"])
+            .generate(vec![(module.clone(), file)])
+            .unwrap()[&module];
+        assert!(code.contains("impl ::prost::bytes::Buf for Blob {\n"));
+        assert!(code.contains("        ::prost::bytes::Buf::advance(&mut self.0, cnt)\n"));
+    }
+
+    #[test]
+    fn test_arena() {
+        let field = |name: &str, number: i32, label: Label, ty: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(ty as i32),
+            type_name: (ty == Type::Message).then(|| ".batch.Inner".to_string()),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("batch.proto".to_string()),
+            package: Some("batch".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Inner".to_string()),
+                    field: vec![field("id", 1, Label::Optional, Type::Int64)],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Outer".to_string()),
+                    field: vec![
+                        field("name", 1, Label::Optional, Type::String),
+                        field("tags", 2, Label::Repeated, Type::String),
+                        field("values", 3, Label::Repeated, Type::Sint32),
+                        field("inner", 4, Label::Optional, Type::Message),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("batch");
+        let modules = Config::new()
+            .format(false)
+            .arena(".batch")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("pub struct Outer<'arena> {\n"));
+        assert!(code.contains("pub name: &'arena str,\n"));
+        assert!(code.contains("pub tags: ::prost::arena::Vec<'arena, &'arena str>,\n"));
+        assert!(code.contains("pub values: ::prost::arena::Vec<'arena, i32>,\n"));
+        assert!(code.contains("pub inner: ::core::option::Option<Inner<'arena>>,\n"));
+        assert!(
+            code.contains("impl<'arena> ::prost::arena::ArenaMessage<'arena> for Outer<'arena> {")
+        );
+        assert!(code.contains("::prost::encoding::sint32::encode_packed(3, &self.values, buf);"));
+        assert!(!code.contains("::prost::Message)]"));
+    }
+
+    #[test]
+    fn test_manual_clone_threshold() {
+        let field = |name: &str, number: i32| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(Type::String as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("clone.proto".to_string()),
+            package: Some("clone".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Small".to_string()),
+                    field: vec![field("a", 1)],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Large".to_string()),
+                    field: vec![field("a", 1), field("b", 2)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("clone");
+        let modules = Config::new()
+            .format(false)
+            .manual_clone_threshold(1)
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[derive(Clone, PartialEq, ::prost::Message)]\npub struct Small {"));
+        assert!(code.contains("#[derive(PartialEq, ::prost::Message)]\npub struct Large {"));
+        assert!(code.contains(
+            "impl ::core::clone::Clone for Large {\n\
+             \x20   fn clone(&self) -> Self {\n\
+             \x20       Self {\n\
+             \x20           a: ::core::clone::Clone::clone(&self.a),\n\
+             \x20           b: ::core::clone::Clone::clone(&self.b),\n"
+        ));
+    }
+
+    #[test]
+    fn test_any_conversions() {
+        let file = FileDescriptorProto {
+            name: Some("events.proto".to_string()),
+            package: Some("events".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Outer".to_string()),
+                nested_type: vec![DescriptorProto {
+                    name: Some("Inner".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("events");
+        let modules = Config::new()
+            .format(false)
+            .any_conversions()
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "impl ::prost::Name for Outer {\n\
+             \x20   const NAME: &'static str = \"Outer\";\n\
+             \x20   const PACKAGE: &'static str = \"events\";\n"
+        ));
+        assert!(code.contains("    const NAME: &'static str = \"Outer.Inner\";\n"));
+        assert!(code.contains(
+            "impl ::core::convert::TryFrom<&::prost_types::Any> for Outer {\n\
+             \x20   type Error = ::prost::DecodeError;\n"
+        ));
+        assert!(code.contains("    impl ::core::convert::TryFrom<&::prost_types::Any> for Inner {"));
+    }
+
+    #[test]
+    fn test_size_hints() {
+        let field =
+            |name: &str, number, label: Label, r#type: Type, oneof_index| FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(label as i32),
+                r#type: Some(r#type as i32),
+                oneof_index,
+                ..Default::default()
+            };
+        let file = FileDescriptorProto {
+            name: Some("sizes.proto".to_string()),
+            package: Some("sizes".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Sample".to_string()),
+                field: vec![
+                    field("id", 1, Label::Optional, Type::Fixed32, None),
+                    field("name", 2, Label::Optional, Type::String, None),
+                    field("values", 3, Label::Repeated, Type::Double, None),
+                    field("count", 4, Label::Optional, Type::Int32, Some(0)),
+                    field("label", 5, Label::Optional, Type::String, Some(0)),
+                ],
+                oneof_decl: vec![OneofDescriptorProto {
+                    name: Some("kind".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("sizes");
+        let modules = Config::new()
+            .format(false)
+            .size_hints()
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        // 5 for `id`, 18 for `name`, 4 * 9 for `values` and 18 for the larger variant of `kind`.
+        assert!(code.contains(
+            "impl Sample {\n\
+             \x20   /// A typical encoded length of this message in bytes.\n"
+        ));
+        assert!(code.contains("    pub const TYPICAL_SIZE_HINT: usize = 77;\n"));
+    }
+
+    #[test]
+    fn test_preserve_unknown_fields() {
+        let file = FileDescriptorProto {
+            name: Some("unknown.proto".to_string()),
+            package: Some("unknown".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Wrapper".to_string()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("value".to_string()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::String as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("unknown");
+        let modules = Config::new()
+            .format(false)
+            .transparent(".unknown.Wrapper")
+            .preserve_unknown_fields(".unknown.Wrapper")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        // The unknown fields follow the declared fields, so the message isn't transparent.
+        assert!(!code.contains("#[repr(transparent)]"));
+        assert!(code.contains(
+            "    pub value: ::prost::alloc::string::String,\n\
+             \x20   #[prost(unknown_fields)]\n\
+             \x20   pub unknown_fields: ::prost::UnknownFields,\n\
+             }\n"
+        ));
+    }
+
+    #[test]
+    fn test_lossy_string() {
+        let string_field = |name: &str, number| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(Type::String as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("names.proto".to_string()),
+            package: Some("names".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Person".to_string()),
+                field: vec![string_field("id", 1), string_field("nickname", 2)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("names");
+        let modules = Config::new()
+            .format(false)
+            .lossy_string(".names.Person.nickname")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[prost(string, tag=\"1\")]\n    pub id:"));
+        assert!(code.contains("#[prost(string, lossy, tag=\"2\")]\n    pub nickname:"));
+    }
+
+    #[test]
+    fn test_nonzero() {
+        let field = |name: &str, number, label: Label, r#type: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(r#type as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("ids.proto".to_string()),
+            package: Some("ids".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Record".to_string()),
+                field: vec![
+                    field("id", 1, Label::Optional, Type::Uint64),
+                    field("parents", 2, Label::Repeated, Type::Sint32),
+                    field("name", 3, Label::Optional, Type::String),
+                    field("count", 4, Label::Optional, Type::Uint32),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("ids");
+        let modules = Config::new()
+            .format(false)
+            .nonzero(".ids.Record.id")
+            .nonzero(".ids.Record.parents")
+            .nonzero(".ids.Record.name")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "#[prost(uint64, optional, nonzero, tag=\"1\")]\n    \
+             pub id: ::core::option::Option<::core::num::NonZeroU64>,"
+        ));
+        assert!(code.contains(
+            "#[prost(sint32, repeated, nonzero, tag=\"2\")]\n    \
+             pub parents: ::prost::alloc::vec::Vec<::core::num::NonZeroI32>,"
+        ));
+        assert!(code.contains("#[prost(string, tag=\"3\")]\n    pub name:"));
+        assert!(code.contains("#[prost(uint32, tag=\"4\")]\n    pub count: u32,"));
+    }
+
+    #[test]
+    fn test_open_enums() {
+        let file = FileDescriptorProto {
+            name: Some("shades.proto".to_string()),
+            package: Some("shades".to_string()),
+            syntax: Some("proto3".to_string()),
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Shade".to_string()),
+                value: vec![
+                    EnumValueDescriptorProto {
+                        name: Some("SHADE_LIGHT".to_string()),
+                        number: Some(0),
+                        ..Default::default()
+                    },
+                    EnumValueDescriptorProto {
+                        name: Some("SHADE_DARK".to_string()),
+                        number: Some(1),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("shades");
+        let modules = Config::new()
+            .format(false)
+            .open_enums(".shades.Shade")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "#[derive(Clone, Copy, Debug, Eq)]\n\
+             pub enum Shade {\n\
+             \x20   Light,\n\
+             \x20   Dark,\n\
+             \x20   /// A value which isn't one of the known variants.\n\
+             \x20   Unknown(i32),\n\
+             }\n"
+        ));
+        assert!(!code.contains("Enumeration"));
+        assert!(code.contains("            Shade::Unknown(value) => value,\n"));
+        assert!(code.contains("            value => Shade::Unknown(value),\n"));
+    }
+
+    #[test]
+    fn test_enum_try_from() {
+        let file = FileDescriptorProto {
+            name: Some("shades.proto".to_string()),
+            package: Some("shades".to_string()),
+            syntax: Some("proto3".to_string()),
+            enum_type: vec![
+                EnumDescriptorProto {
+                    name: Some("Shade".to_string()),
+                    value: vec![EnumValueDescriptorProto {
+                        name: Some("SHADE_LIGHT".to_string()),
+                        number: Some(0),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                EnumDescriptorProto {
+                    name: Some("Tint".to_string()),
+                    value: vec![EnumValueDescriptorProto {
+                        name: Some("TINT_RED".to_string()),
+                        number: Some(0),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("shades");
+        let modules = Config::new()
+            .format(false)
+            .enum_try_from(".shades.Shade")
+            .generate(vec![(module.clone(), file.clone())])
+            .unwrap();
+        let code = &modules[&module];
+        assert!(code.contains("#[repr(i32)]\n#[prost(try_from_i32)]\npub enum Shade {"));
+        assert!(code.contains("#[repr(i32)]\npub enum Tint {"));
+        assert!(!code.contains("prost_path"));
+
+        let modules = Config::new()
+            .format(false)
+            .enum_try_from(".shades.Shade")
+            .prost_path("::my_prost")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+        assert!(code.contains(
+            "#[prost(try_from_i32)]\n\
+             #[prost(prost_path = \"::my_prost\")]\n\
+             pub enum Shade {"
+        ));
+    }
+
+    #[test]
+    fn test_open_enum_errors() {
+        let file = |value: &str| FileDescriptorProto {
+            name: Some("shades.proto".to_string()),
+            package: Some("shades".to_string()),
+            syntax: Some("proto3".to_string()),
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Shade".to_string()),
+                value: vec![EnumValueDescriptorProto {
+                    name: Some(value.to_string()),
+                    number: Some(0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("shades");
+        let error = Config::new()
+            .format(false)
+            .open_enums(".shades.Shade")
+            .generate(vec![(module.clone(), file("SHADE_UNKNOWN"))])
+            .unwrap_err();
+        assert!(error.to_string().contains("has a value named `Unknown`"));
+
+        let error = Config::new()
+            .format(false)
+            .open_enums(".shades.Shade")
+            .enum_extern(".shades.Shade", "crate::Shade")
+            .generate(vec![(module, file("SHADE_LIGHT"))])
+            .unwrap_err();
+        assert!(error.to_string().contains("can't also be an extern enum"));
+    }
+
+    #[test]
+    fn test_module_path_map() {
+        let message = |name: &str, type_name: &str| DescriptorProto {
+            name: Some(name.to_string()),
+            field: vec![FieldDescriptorProto {
+                name: Some("other".to_string()),
+                number: Some(1),
+                label: Some(Label::Optional as i32),
+                r#type: Some(Type::Message as i32),
+                type_name: Some(type_name.to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let file = |package: &str, message: DescriptorProto| FileDescriptorProto {
+            name: Some(format!("{}.proto", package)),
+            package: Some(package.to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![message],
+            ..Default::default()
+        };
+
+        let mut config = Config::new();
+        config
+            .format(false)
+            .module_path_map(".foo.bar", "my::custom::location");
+        let mapped = config.module_for_path("foo.bar");
+        let nested = config.module_for_path("foo.bar.baz");
+        let app = config.module_for_path("app");
+        assert_eq!(mapped, Module::from_parts(["my", "custom", "location"]));
+        assert_eq!(
+            nested,
+            Module::from_parts(["my", "custom", "location", "baz"])
+        );
+        assert_eq!(app, Module::from_parts(["app"]));
+        assert_eq!(mapped.to_file_name_or("_"), "my.custom.location.rs");
+
+        let modules = config
+            .generate(vec![
+                (mapped.clone(), file("foo.bar", message("Lib", ".app.App"))),
+                (app.clone(), file("app", message("App", ".foo.bar.Lib"))),
+            ])
+            .unwrap();
+
+        // The messages refer to each other, so both fields are boxed.
+        assert!(modules[&app].contains("Box<super::my::custom::location::Lib>>,"));
+        assert!(modules[&mapped].contains("Box<super::super::super::app::App>>,"));
+    }
+
+    #[test]
+    fn test_collection_wrappers() {
+        let field = |name: &str, number: i32, label: Label| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(Type::String as i32),
+            ..Default::default()
+        };
+        let message = |name: &str, field: Vec<FieldDescriptorProto>| DescriptorProto {
+            name: Some(name.to_string()),
+            field,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("list.proto".to_string()),
+            package: Some("list".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                message("Names", vec![field("names", 1, Label::Repeated)]),
+                message(
+                    "Tagged",
+                    vec![
+                        field("tag", 1, Label::Optional),
+                        field("names", 2, Label::Repeated),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("list");
+        let modules = Config::new()
+            .format(false)
+            .collection_wrappers()
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("impl ::core::iter::IntoIterator for Names {"));
+        assert!(code.contains("impl<'a> ::core::iter::IntoIterator for &'a Names {"));
+        assert!(code.contains(
+            "impl ::core::iter::FromIterator<::prost::alloc::string::String> for Names {"
+        ));
+        assert!(
+            code.contains("impl ::core::iter::Extend<::prost::alloc::string::String> for Names {")
+        );
+        assert!(!code.contains("for Tagged {"));
+    }
+
+    #[test]
+    fn test_map_entries_are_not_generated() {
+        let field = |name: &str, number: i32, label: Label, r#type: Type, type_name: &str| {
+            FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(label as i32),
+                r#type: Some(r#type as i32),
+                type_name: Some(type_name.to_string()).filter(|name| !name.is_empty()),
+                ..Default::default()
+            }
+        };
+        let entry = |name: &str, key: Type, value: Type, value_type_name: &str| DescriptorProto {
+            name: Some(name.to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, key, ""),
+                field("value", 2, Label::Optional, value, value_type_name),
+            ],
+            options: Some(prost_types::MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // A map at the top level, and a map nested in a message nested in another message.
+        let file = FileDescriptorProto {
+            name: Some("maps.proto".to_string()),
+            package: Some("maps".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Outer".to_string()),
+                field: vec![
+                    field(
+                        "counts",
+                        1,
+                        Label::Repeated,
+                        Type::Message,
+                        ".maps.Outer.CountsEntry",
+                    ),
+                    field(
+                        "inner",
+                        2,
+                        Label::Optional,
+                        Type::Message,
+                        ".maps.Outer.Inner",
+                    ),
+                ],
+                nested_type: vec![
+                    entry("CountsEntry", Type::String, Type::Int32, ""),
+                    DescriptorProto {
+                        name: Some("Inner".to_string()),
+                        field: vec![field(
+                            "children",
+                            1,
+                            Label::Repeated,
+                            Type::Message,
+                            ".maps.Outer.Inner.ChildrenEntry",
+                        )],
+                        nested_type: vec![entry(
+                            "ChildrenEntry",
+                            Type::Int32,
+                            Type::Message,
+                            ".maps.Outer.Inner",
+                        )],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("maps");
+        let modules = Config::new()
+            .format(false)
+            .btree_map([".maps.Outer.Inner"])
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("pub counts: ::std::collections::HashMap<"));
+        assert!(code.contains("pub children: ::prost::alloc::collections::BTreeMap<i32, "));
+        assert!(!code.contains("Entry"));
+        assert_eq!(code.matches("pub struct ").count(), 2);
+    }
+
+    #[test]
+    fn test_skip_default() {
+        let message = |name: &str| DescriptorProto {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("skip_default.proto".to_string()),
+            package: Some("skip_default".to_string()),
+            message_type: vec![message("Skipped"), message("Derived")],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("skip_default");
+        let modules = Config::new()
+            .format(false)
+            .skip_default(".skip_default.Skipped")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert_eq!(code.matches("#[prost(skip_default)]").count(), 1);
+        assert!(code.contains("#[prost(skip_default)]\npub struct Skipped {"));
+    }
+
+    #[test]
+    fn test_raw() {
+        let field = |name: &str, number: i32, label: Label| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(Type::Message as i32),
+            type_name: Some(".raw.Payload".to_string()),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("raw.proto".to_string()),
+            package: Some("raw".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Envelope".to_string()),
+                    field: vec![
+                        field("payload", 1, Label::Optional),
+                        field("extra", 2, Label::Repeated),
+                        field("decoded", 3, Label::Optional),
+                    ],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Payload".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("raw");
+        let modules = Config::new()
+            .format(false)
+            .raw(".raw.Envelope.payload")
+            .raw(".raw.Envelope.extra")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[prost(bytes=\"raw\", optional, tag=\"1\")]"));
+        assert!(code.contains("pub payload: ::core::option::Option<::prost::RawMessage>,"));
+        assert!(code.contains("#[prost(bytes=\"raw\", repeated, tag=\"2\")]"));
+        assert!(code.contains("pub extra: ::prost::alloc::vec::Vec<::prost::RawMessage>,"));
+        assert!(code.contains("pub decoded: ::core::option::Option<Payload>,"));
+    }
+
+    #[test]
+    fn test_derive_serde() {
+        let field =
+            |name: &str, number, label: Label, r#type: Type, oneof_index| FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(label as i32),
+                r#type: Some(r#type as i32),
+                type_name: if r#type == Type::Enum {
+                    Some(".serde.Kind".to_string())
+                } else {
+                    None
+                },
+                oneof_index,
+                ..Default::default()
+            };
+        let file = FileDescriptorProto {
+            name: Some("serde.proto".to_string()),
+            package: Some("serde".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Sample".to_string()),
+                field: vec![
+                    field("data", 1, Label::Optional, Type::Bytes, None),
+                    field("kinds", 2, Label::Repeated, Type::Enum, None),
+                    field("raw", 3, Label::Optional, Type::Bytes, Some(0)),
+                    field("kind", 4, Label::Optional, Type::Enum, Some(0)),
+                ],
+                oneof_decl: vec![OneofDescriptorProto {
+                    name: Some("choice".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Kind".to_string()),
+                value: vec![
+                    EnumValueDescriptorProto {
+                        name: Some("KIND_UNSPECIFIED".to_string()),
+                        number: Some(0),
+                        ..Default::default()
+                    },
+                    EnumValueDescriptorProto {
+                        name: Some("KIND_LARGE".to_string()),
+                        number: Some(1),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("serde");
+        let modules = Config::new()
+            .format(false)
+            .derive_serde(".")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "#[derive(Clone, PartialEq, ::prost::Message)]\n\
+             #[derive(serde::Serialize, serde::Deserialize)]\n\
+             pub struct Sample {\n"
+        ));
+        assert!(code.contains(
+            "    #[serde(with = \"::prost::serde::bytes\")]\n\
+             \x20   pub data: ::prost::alloc::vec::Vec<u8>,\n"
+        ));
+        assert!(code.contains(
+            "    #[serde(serialize_with = \
+             \"::prost::serde::enumeration::repeated::serialize::<Kind, _>\", \
+             deserialize_with = \
+             \"::prost::serde::enumeration::repeated::deserialize::<Kind, _>\")]\n\
+             \x20   pub kinds: ::prost::alloc::vec::Vec<i32>,\n"
+        ));
+        assert!(code.contains(
+            "    #[serde(flatten)]\n\
+             \x20   pub choice: ::core::option::Option<sample::Choice>,\n"
+        ));
+        assert!(code.contains(
+            "        #[serde(rename = \"raw\")]\n\
+             \x20       Raw(#[serde(with = \"::prost::serde::bytes\")] \
+             ::prost::alloc::vec::Vec<u8>),\n"
+        ));
+        assert!(code.contains("::serialize::<super::Kind, _>"));
+        assert!(code.contains(
+            "    #[serde(rename = \"KIND_LARGE\")]\n\
+             \x20   Large = 1,\n"
+        ));
+        assert!(code.contains("impl ::prost::serde::NamedEnum for Kind {\n"));
+        assert!(code.contains("            \"KIND_LARGE\" => ::core::option::Option::Some(1),\n"));
+    }
+
+    #[test]
+    fn test_serde_default() {
+        let field = |name: &str, number: i32, label: Label| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(Type::Int32 as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("serde.proto".to_string()),
+            package: Some("serde".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Msg".to_string()),
+                field: vec![
+                    field("optional", 1, Label::Optional),
+                    field("required", 2, Label::Required),
+                    field("repeated", 3, Label::Repeated),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("serde");
+        let modules = Config::new()
+            .format(false)
+            .serde_default(".serde.Msg")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains("#[serde(default)]\npub struct Msg {"));
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::core::option::Option::is_none\")]\n    pub optional:"
+        ));
+        assert!(code.contains("tag=\"2\")]\n    pub required:"));
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::prost::encoding::is_default\")]\n    pub repeated:"
+        ));
+    }
+
+    #[test]
+    fn test_serde_skip() {
+        let field = |name: &str, number: i32| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(Type::String as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("serde.proto".to_string()),
+            package: Some("serde".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("User".to_string()),
+                field: vec![field("name", 1), field("password_hash", 2)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("serde");
+        let modules = Config::new()
+            .format(false)
+            .serde_default(".serde.User")
+            .serde_skip(".serde.User.password_hash")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert_eq!(code.matches("#[serde(skip)]").count(), 1);
+        assert!(code.contains("#[serde(skip)]\n    pub password_hash:"));
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::core::option::Option::is_none\")]\n    pub name:"
+        ));
+    }
+
+    #[test]
+    fn test_serde_skip_empty() {
+        let field = |name: &str, number: i32, label: Label, r#type: Type, type_name: &str| {
+            FieldDescriptorProto {
+                name: Some(name.to_string()),
+                number: Some(number),
+                label: Some(label as i32),
+                r#type: Some(r#type as i32),
+                type_name: Some(type_name.to_string()).filter(|s| !s.is_empty()),
+                ..Default::default()
+            }
+        };
+        let file = FileDescriptorProto {
+            name: Some("serde.proto".to_string()),
+            package: Some("serde".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Msg".to_string()),
+                field: vec![
+                    field("scalar", 1, Label::Optional, Type::Int32, ""),
+                    field("list", 2, Label::Repeated, Type::String, ""),
+                    field(
+                        "counts",
+                        3,
+                        Label::Repeated,
+                        Type::Message,
+                        ".serde.Msg.CountsEntry",
+                    ),
+                ],
+                nested_type: vec![DescriptorProto {
+                    name: Some("CountsEntry".to_string()),
+                    field: vec![
+                        field("key", 1, Label::Optional, Type::String, ""),
+                        field("value", 2, Label::Optional, Type::Int32, ""),
+                    ],
+                    options: Some(prost_types::MessageOptions {
+                        map_entry: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = Module::from_protobuf_package_name("serde");
+        let modules = Config::new()
+            .format(false)
+            .serde_default(".serde.Msg")
+            .serde_skip_empty(".serde.Msg")
+            .generate(vec![(module.clone(), file)])
+            .unwrap();
+        let code = &modules[&module];
+
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::prost::encoding::is_default\")]\n    pub scalar:"
+        ));
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::prost::alloc::vec::Vec::is_empty\")]\n    pub list:"
+        ));
+        assert!(code.contains(
+            "#[serde(skip_serializing_if = \"::std::collections::HashMap::is_empty\")]\n    pub counts:"
+        ));
+    }
 }
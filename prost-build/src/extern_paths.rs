@@ -23,7 +23,11 @@ pub struct ExternPaths {
 }
 
 impl ExternPaths {
-    pub fn new(paths: &[(String, String)], prost_types: bool) -> Result<ExternPaths, String> {
+    pub fn new(
+        paths: &[(String, String)],
+        prost_types: bool,
+        empty_as_unit: bool,
+    ) -> Result<ExternPaths, String> {
         let mut extern_paths = ExternPaths {
             extern_paths: HashMap::new(),
         };
@@ -59,6 +63,8 @@ impl ExternPaths {
                 ".google.protobuf.UInt64Value".to_string(),
                 "u64".to_string(),
             )?;
+        } else if empty_as_unit {
+            extern_paths.insert(".google.protobuf.Empty".to_string(), "()".to_string())?;
         }
 
         Ok(extern_paths)
@@ -132,6 +138,7 @@ mod tests {
                 (".a.b.c.d.e.f".to_string(), "::abc::def".to_string()),
             ],
             false,
+            false,
         )
         .unwrap();
 
@@ -157,7 +164,7 @@ mod tests {
 
     #[test]
     fn test_well_known_types() {
-        let paths = ExternPaths::new(&[], true).unwrap();
+        let paths = ExternPaths::new(&[], true, false).unwrap();
 
         let case = |proto_ident: &str, resolved_ident: &str| {
             assert_eq!(paths.resolve_ident(proto_ident).unwrap(), resolved_ident);
@@ -167,4 +174,14 @@ mod tests {
         case(".google.protobuf.Duration", "::prost_types::Duration");
         case(".google.protobuf.Empty", "()");
     }
+
+    #[test]
+    fn test_empty_as_unit() {
+        let paths = ExternPaths::new(&[], false, false).unwrap();
+        assert!(paths.resolve_ident(".google.protobuf.Empty").is_none());
+
+        let paths = ExternPaths::new(&[], false, true).unwrap();
+        assert_eq!(paths.resolve_ident(".google.protobuf.Empty").unwrap(), "()");
+        assert!(paths.resolve_ident(".google.protobuf.Duration").is_none());
+    }
 }
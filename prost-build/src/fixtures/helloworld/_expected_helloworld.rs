@@ -13,7 +13,7 @@ pub struct Response {
     pub say: ::prost::alloc::string::String,
 }
 #[some_enum_attr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ServingStatus {
     Unknown = 0,
@@ -34,11 +34,21 @@ impl ServingStatus {
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
     pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "UNKNOWN" => Some(Self::Unknown),
-            "SERVING" => Some(Self::Serving),
-            "NOT_SERVING" => Some(Self::NotServing),
-            _ => None,
-        }
+        let variant = match value {
+            "UNKNOWN" => Self::Unknown,
+            "SERVING" => Self::Serving,
+            "NOT_SERVING" => Self::NotServing,
+            _ => return ::core::option::Option::None,
+        };
+        ::core::option::Option::Some(variant)
+    }
+}
+impl ::core::hash::Hash for ServingStatus {
+    /// Hashes the `i32` number of the value, as returned by `as i32`.
+    ///
+    /// The hash doesn't depend on the names or order of the values, so it's as stable
+    /// across builds and versions as the hasher, e.g. for use as a shard key.
+    fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+        ::core::hash::Hash::hash(&(*self as i32), state);
     }
 }
@@ -157,6 +157,7 @@ mod extern_paths;
 mod ident;
 mod message_graph;
 mod path;
+mod retention;
 
 /// A service generator takes a service descriptor and generates Rust code.
 ///
@@ -177,6 +178,24 @@ pub trait ServiceGenerator {
     /// result to `buf`.
     fn generate(&mut self, service: Service, buf: &mut String);
 
+    /// Generates the client side code for a service, writing the result to `buf`.
+    ///
+    /// Only called if [`Config::service_features`] is configured, in which case the output is
+    /// wrapped in a `<service>_client` module compiled only with the client feature. The module
+    /// is a child of the module containing the output of [`generate`](#method.generate), which is
+    /// called afterwards with the same service, for code shared by both sides.
+    ///
+    /// The default implementation is empty and does nothing.
+    fn generate_client(&mut self, _service: &Service, _buf: &mut String) {}
+
+    /// Generates the server side code for a service, writing the result to `buf`.
+    ///
+    /// Like [`generate_client`](#method.generate_client), but wrapped in a `<service>_server`
+    /// module compiled only with the server feature.
+    ///
+    /// The default implementation is empty and does nothing.
+    fn generate_server(&mut self, _service: &Service, _buf: &mut String) {}
+
     /// Finalizes the generation process.
     ///
     /// In case there's something that needs to be output at the end of the generation process, it
@@ -227,6 +246,8 @@ enum BytesType {
     Vec,
     /// The [`bytes::Bytes`] type.
     Bytes,
+    /// The `prost::SharedBytes` type.
+    Shared,
 }
 
 impl Default for BytesType {
@@ -235,12 +256,28 @@ impl Default for BytesType {
     }
 }
 
+/// The order in which the fields of generated messages are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldEmitOrder {
+    /// Ascending field number order, which is the canonical encoding.
+    FieldNumber,
+    /// The order in which the fields are declared in the `.proto` file.
+    Declaration,
+}
+
+impl Default for FieldEmitOrder {
+    fn default() -> FieldEmitOrder {
+        FieldEmitOrder::FieldNumber
+    }
+}
+
 /// Configuration options for Protobuf code generation.
 ///
 /// This configuration builder can be used to set non-default code generation options.
 pub struct Config {
     file_descriptor_set_path: Option<PathBuf>,
     service_generator: Option<Box<dyn ServiceGenerator>>,
+    service_features: Option<(String, String)>,
     map_type: PathMap<MapType>,
     bytes_type: PathMap<BytesType>,
     type_attributes: PathMap<String>,
@@ -248,15 +285,49 @@ pub struct Config {
     enum_attributes: PathMap<String>,
     field_attributes: PathMap<String>,
     boxed: PathMap<()>,
+    lossy_string: PathMap<()>,
+    nonzero: PathMap<()>,
+    boxed_oneof: PathMap<()>,
+    boxed_map_value: PathMap<()>,
+    oneof_case: PathMap<()>,
+    oneof_from: PathMap<()>,
+    skip_default: PathMap<()>,
+    columnar: PathMap<()>,
+    option_accessors: PathMap<()>,
+    trace_field_access: PathMap<()>,
+    extend_methods: PathMap<()>,
+    reserve_methods: PathMap<()>,
+    field_accessors: PathMap<()>,
+    preserve_unknown_fields: PathMap<()>,
+    transparent: PathMap<()>,
+    arena: PathMap<()>,
+    derive_serde: PathMap<()>,
+    serde_default: PathMap<()>,
+    serde_skip: PathMap<()>,
+    serde_skip_empty: PathMap<()>,
+    raw: PathMap<()>,
     prost_types: bool,
+    empty_as_unit: bool,
     strip_enum_prefix: bool,
+    enum_string_impls: bool,
+    collection_wrappers: bool,
+    any_conversions: bool,
+    size_hints: bool,
+    field_emit_order: FieldEmitOrder,
+    manual_clone_threshold: Option<usize>,
+    enum_extern: PathMap<String>,
+    open_enums: PathMap<()>,
+    enum_try_from: PathMap<()>,
+    enum_map_keys: PathMap<String>,
     out_dir: Option<PathBuf>,
     extern_paths: Vec<(String, String)>,
+    module_paths: Vec<(String, String)>,
     default_package_filename: String,
     protoc_args: Vec<OsString>,
     disable_comments: PathMap<()>,
     skip_protoc_run: bool,
     include_file: Option<PathBuf>,
+    generated_attributes: Vec<String>,
     prost_path: Option<String>,
     fmt: bool,
 }
@@ -331,6 +402,11 @@ impl Config {
     /// Configure the code generator to generate Rust [`bytes::Bytes`][1] fields for Protobuf
     /// [`bytes`][2] type fields.
     ///
+    /// When a message is decoded from a `Bytes` buffer, its `Bytes` fields are views into that
+    /// buffer rather than copies, so decoding many small fields from one large input does not
+    /// allocate per field. Fields decoded from other buffers, and `Vec<u8>` fields, are copied
+    /// out of the input once.
+    ///
     /// # Arguments
     ///
     /// **`paths`** - paths to specific fields, messages, or packages which should use a Rust
@@ -389,6 +465,46 @@ impl Config {
         self
     }
 
+    /// Configure the code generator to generate Rust [`prost::SharedBytes`][1] fields for
+    /// Protobuf `bytes` type fields.
+    ///
+    /// `SharedBytes` is used like a `Vec<u8>`, but is a slice of the buffer the message was
+    /// decoded from when decoding from a `Bytes` buffer, or with
+    /// [`DecodeOptions::share_input`][2]. Decoding many small fields from one large input then
+    /// allocates once, rather than once per field.
+    ///
+    /// Unlike [`bytes`](#method.bytes), this adds to the paths already configured, so it should
+    /// be called after `bytes`, which replaces them. A path configured by both selects the type of
+    /// the later call.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages which should use a Rust
+    /// `SharedBytes` for Protobuf `bytes` fields. For details about matching fields see
+    /// [`bytes`](#method.bytes).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Match all bytes fields in a message type.
+    /// config.shared_bytes(&[".my_messages.MyMessageType"]);
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.SharedBytes.html
+    /// [2]: https://docs.rs/prost/latest/prost/struct.DecodeOptions.html#method.share_input
+    pub fn shared_bytes<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for matcher in paths {
+            self.bytes_type
+                .insert(matcher.as_ref().to_string(), BytesType::Shared);
+        }
+        self
+    }
+
     /// Add additional attribute to matched fields.
     ///
     /// # Arguments
@@ -492,91 +608,704 @@ impl Config {
     ///
     /// ```rust
     /// # let mut config = prost_build::Config::new();
-    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
-    /// config.message_attribute(".", "#[derive(Eq)]");
-    /// // Some messages want to be serializable with serde as well.
-    /// config.message_attribute("my_messages.MyMessageType",
-    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
-    /// config.message_attribute("my_messages.MyMessageType.MyNestedMessageType",
-    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
+    /// config.message_attribute(".", "#[derive(Eq)]");
+    /// // Some messages want to be serializable with serde as well.
+    /// config.message_attribute("my_messages.MyMessageType",
+    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// config.message_attribute("my_messages.MyMessageType.MyNestedMessageType",
+    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// ```
+    pub fn message_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    where
+        P: AsRef<str>,
+        A: AsRef<str>,
+    {
+        self.message_attributes
+            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self
+    }
+
+    /// Add additional attribute to matched enums and one-ofs.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - a path matching any number of types. It works the same way as in
+    /// [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// **`attribute`** - an arbitrary string to be placed before each matched type. The
+    /// expected usage are additional attributes, but anything is allowed.
+    ///
+    /// The calls to this method are cumulative. They don't overwrite previous calls and if a
+    /// type is matched by multiple calls of the method, all relevant attributes are added to
+    /// it.
+    ///
+    /// For things like serde it might be needed to combine with [field
+    /// attributes](#method.field_attribute).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
+    /// config.enum_attribute(".", "#[derive(Eq)]");
+    /// // Some messages want to be serializable with serde as well.
+    /// config.enum_attribute("my_messages.MyEnumType",
+    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// config.enum_attribute("my_messages.MyMessageType.MyNestedEnumType",
+    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// ```
+    ///
+    /// # Oneof fields
+    ///
+    /// The `oneof` fields don't have a type name of their own inside Protobuf. Therefore, the
+    /// field name can be used both with `enum_attribute` and `field_attribute` ‒ the first is
+    /// placed before the `enum` type definition, the other before the field inside corresponding
+    /// message `struct`.
+    ///
+    /// In other words, to place an attribute on the `enum` implementing the `oneof`, the match
+    /// would look like `my_messages.MyNestedMessageType.oneofname`.
+    pub fn enum_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    where
+        P: AsRef<str>,
+        A: AsRef<str>,
+    {
+        self.enum_attributes
+            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self
+    }
+
+    /// Wrap matched fields in a `Box`.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.boxed(".my_messages.MyMessageType.my_field");
+    /// ```
+    pub fn boxed<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.boxed.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Decode matched string fields lossily.
+    ///
+    /// String fields reject data which isn't valid UTF-8 by default, failing the whole decode.
+    /// Matched fields instead replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`, as
+    /// `String::from_utf8_lossy` does, which suits fields filled in by untrusted senders while
+    /// the others stay strict. This composes with [`DecodeOptions::lossy_utf8`][1], which
+    /// decodes every string field of a message lossily. Fields other than strings are unaffected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.lossy_string(".my_messages.MyMessageType.my_field");
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.DecodeOptions.html#method.lossy_utf8
+    pub fn lossy_string<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.lossy_string.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Represent matched integer fields with `NonZero*` types.
+    ///
+    /// Matched fields hold `core::num::NonZeroU32` and the like instead of the plain integer
+    /// type, and decoding fails if a zero value is read for them. As zero can't be represented, a
+    /// singular field becomes an `Option`, which is `None` when the field isn't set, while a
+    /// repeated field becomes a `Vec` of non-zero values. Required fields, fields with a default
+    /// value, fields in a `oneof` and fields other than integers are unaffected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.nonzero(".my_messages.MyMessageType.my_field");
+    /// ```
+    pub fn nonzero<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.nonzero.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Wrap the message payloads of matched oneofs in a `Box`.
+    ///
+    /// The size of a oneof enum is that of its largest variant, so a single large message variant
+    /// increases the size of every containing message, even when that variant is rarely set.
+    /// Boxing the message and group variants of a oneof bounds the size of the enum, at the cost
+    /// of an allocation when such a variant is set. Scalar, string and bytes variants are not
+    /// boxed. Individual variants can also be boxed with [`boxed`](#method.boxed).
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of oneofs. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.boxed_oneof(".my_messages.MyMessageType.my_oneof");
+    /// ```
+    pub fn boxed_oneof<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.boxed_oneof.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Wrap the message values of matched map fields in a `Box`.
+    ///
+    /// A map field such as `map<string, Node> children` is generated with the value type
+    /// `Box<Node>` rather than `Node`. Maps allocate their entries, so this isn't needed for
+    /// recursive messages to compile, but it keeps entries small for large value messages, and
+    /// lets values be moved in and out of the map without copying them. Maps with scalar, string,
+    /// bytes or enumeration values are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of map fields. For details about matching fields
+    /// see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.boxed_map_value(".my_messages.Node.children");
+    /// ```
+    pub fn boxed_map_value<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.boxed_map_value.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate a payload-free case enum for matched `oneof` fields.
+    ///
+    /// For a matched oneof `kind` in message `Foo`, a `KindCase` enum with one unit variant per
+    /// oneof field is generated alongside the `Kind` enum, together with a `Kind::case` method
+    /// and a `Foo::which_kind` method returning `None` if the oneof is unset. This allows
+    /// matching on which variant is set without borrowing or moving its payload.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of oneofs. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate case enums for all oneofs.
+    /// config.oneof_case(".");
+    /// // Generate a case enum only for the `kind` oneof of `MyMessageType`.
+    /// config.oneof_case(".my_messages.MyMessageType.kind");
+    /// ```
+    pub fn oneof_case<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.oneof_case.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate `From` conversions from payload types into matched `oneof` enums.
+    ///
+    /// For a matched oneof `kind`, an `impl From<T> for Kind` is generated for each oneof field
+    /// with payload type `T`, so that a oneof can be set with `msg.kind = Some(value.into())`.
+    /// Fields sharing a payload type with another field of the same oneof would make the
+    /// conversion ambiguous, so no conversion is generated for them. Enumeration fields are
+    /// skipped as well, since their payload is a plain `i32`.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of oneofs. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate conversions for all oneofs.
+    /// config.oneof_from(".");
+    /// // Generate conversions only for the `kind` oneof of `MyMessageType`.
+    /// config.oneof_from(".my_messages.MyMessageType.kind");
+    /// ```
+    pub fn oneof_from<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.oneof_from.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Skip generating the `Default` implementation for matched messages.
+    ///
+    /// By default, every message implements `Default`, producing a value with all fields set to
+    /// their default. For messages where such a value is meaningless, skipping the implementation
+    /// forces callers to construct the message explicitly, e.g. through a hand-written
+    /// constructor.
+    ///
+    /// Note that decoding requires `Default`, so unless it is implemented by hand, a matched
+    /// message can not be decoded with [`Message::decode`][1], and can not be used as a field of
+    /// another message.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.skip_default(".my_messages.MyMessageType");
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/trait.Message.html#method.decode
+    pub fn skip_default<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.skip_default.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate a column-oriented batch type for matched messages.
+    ///
+    /// For a matched message `Foo`, a `FooColumns` struct is generated alongside it, with one
+    /// `Vec` per field of `Foo`. Messages are appended to the batch either with `push`, or with
+    /// `push_decoded`, which decodes a message from a buffer directly into the columns without
+    /// materializing a `Foo`. This layout suits analytical processing of many messages of the
+    /// same type.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.columnar(".my_messages.MyMessageType");
+    /// ```
+    pub fn columnar<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.columnar.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate borrowing `Option` accessors for the optional scalar fields of matched messages.
+    ///
+    /// For each optional scalar, string or bytes field `foo` of a matched message, a method
+    /// `foo_opt` is generated which returns the field's value as an `Option`, borrowing strings as
+    /// `&str` and bytes as `&[u8]`. This complements the `foo` accessor, which substitutes the
+    /// default value when the field is unset. Optional enumeration fields are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.option_accessors(".my_messages.MyMessageType");
+    /// ```
+    pub fn option_accessors<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.option_accessors.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Log each call to the generated getters of matched messages, in debug builds.
+    ///
+    /// The getters of matched messages, such as the enumeration getters and the getters of
+    /// optional fields, log every call at the trace level through the [`log`] crate, naming the
+    /// message and the field. This helps to audit which fields a code path touches. The logging
+    /// statements are compiled only with `debug_assertions`, so release builds are unaffected.
+    /// Direct accesses to the public fields are not logged.
+    ///
+    /// The logging goes through `prost`, which must be built with its `log` feature enabled.
+    ///
+    /// [`log`]: https://docs.rs/log
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.trace_field_access(".my_messages.MyMessageType");
+    /// ```
+    pub fn trace_field_access<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.trace_field_access
+            .insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate `extend_<field>` methods for the repeated fields of matched messages.
+    ///
+    /// For each repeated field `foo` of a matched message, a method `extend_foo` is generated which
+    /// appends the elements of an iterator to the field, as `Vec::extend` does. This keeps
+    /// builder-style construction from iterators fluent, alongside the `reserve_foo` methods of
+    /// [`reserve_methods`](#method.reserve_methods). Map fields are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.extend_methods(".my_messages.MyMessageType");
+    /// ```
+    pub fn extend_methods<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.extend_methods.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate `reserve_<field>` methods for the repeated and map fields of matched messages.
+    ///
+    /// For each repeated field or `HashMap` field `foo` of a matched message, a method
+    /// `reserve_foo` is generated which reserves capacity for at least `additional` more elements,
+    /// as `Vec::reserve` does. This avoids reallocations when the number of elements is known
+    /// before they are added. `BTreeMap` fields can't reserve capacity, and are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.reserve_methods(".my_messages.MyMessageType");
+    /// ```
+    pub fn reserve_methods<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.reserve_methods.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate `clear_<field>` and `has_<field>` methods for the fields of matched messages.
+    ///
+    /// For each field `foo` of a matched message, a method `clear_foo` is generated which resets
+    /// the field to its default value. Fields which track presence, namely optional scalars,
+    /// optional submessages and oneofs, also get a method `has_foo` which returns whether the field
+    /// is set. Together they allow updating messages field by field, for example as directed by a
+    /// field mask.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.field_accessors(".my_messages.MyMessageType");
+    /// ```
+    pub fn field_accessors<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.field_accessors.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Preserve the unknown fields of matched messages through a decode and encode round trip.
+    ///
+    /// Matched messages get an `unknown_fields` field of type [`prost::UnknownFields`], which
+    /// holds the fields that decoding doesn't recognize, rather than skipping them. Encoding writes
+    /// them again after the known fields, so that fields added by newer versions of a schema
+    /// survive a process which only knows an older one. Clearing a message empties its unknown
+    /// fields too.
+    ///
+    /// Matched messages aren't generated as `#[repr(transparent)]` tuple structs or collection
+    /// wrappers, and can't also be [`columnar`](#method.columnar).
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Preserve unknown fields in all messages.
+    /// config.preserve_unknown_fields(".");
+    /// ```
+    pub fn preserve_unknown_fields<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.preserve_unknown_fields
+            .insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate matched single-field messages as `#[repr(transparent)]` tuple structs.
+    ///
+    /// A matched message whose only field is a singular scalar, string, bytes or enumeration
+    /// field without explicit presence is generated as a tuple struct around the field's value,
+    /// such as `pub struct UserId(pub u64);`, which has the same layout as the value itself. This
+    /// suits strongly typed identifiers and FFI. The field is still encoded at its tag. Matched
+    /// messages with any other shape, as well as [`columnar`](#method.columnar) messages, are
+    /// generated as usual.
+    ///
+    /// Wrappers around a string field also implement `PartialEq<str>` and `PartialEq<&str>`, so
+    /// they can be compared with string literals directly.
+    /// Wrappers around a bytes field of type [`Bytes`](#method.bytes) implement `bytes::Buf` by
+    /// delegating to the field, so they can be passed to `Buf`-consuming APIs directly.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.transparent(".my_messages.UserId");
+    /// ```
+    pub fn transparent<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.transparent.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate matched messages which allocate from an arena.
+    ///
+    /// A matched message `Foo` is generated as `Foo<'arena>`, implementing
+    /// `prost::arena::ArenaMessage` instead of `prost::Message`. Its string and bytes fields are
+    /// `&'arena str` and `&'arena [u8]`, its repeated fields are `prost::arena::Vec<'arena, T>`,
+    /// and it is decoded with `Foo::decode_in(buf, &arena)`, copying all of its data into the
+    /// arena. This removes the per-message allocations of batch decoding, and requires the
+    /// `arena` feature of `prost`.
+    ///
+    /// Arena messages may contain scalar, enumeration, string, bytes and message fields, and their
+    /// message fields must refer to other arena messages. Oneofs, maps, groups, recursive
+    /// messages and the default values of proto2 fields are not supported.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.arena(".my_messages");
+    /// ```
+    pub fn arena<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.arena.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Derive `serde::Serialize` and `serde::Deserialize` for matched messages and enumerations.
+    ///
+    /// Fields are serialized under their Rust names. `bytes` fields are serialized as base64
+    /// strings, and enumeration fields by the names of their values, as in the Protobuf JSON
+    /// mapping; unknown enumeration values keep their numbers. The fields of a `oneof` are
+    /// flattened into the message, so that the set field appears under its own name. Map values
+    /// are serialized with their plain Rust types.
+    ///
+    /// The generated code uses the helpers in `prost::serde`, which require the `serde` feature
+    /// of `prost`, and the crate including it must depend on `serde` with the `derive` feature.
+    /// The enumerations used by matched messages must be matched too. Combine this with
+    /// [`serde_default`](#method.serde_default) to omit fields holding their default values and
+    /// to accept input missing them.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages and enumerations. For details about
+    /// matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.derive_serde(".");
+    /// config.serde_default(".");
+    /// ```
+    pub fn derive_serde<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.derive_serde.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate serde attributes giving matched messages Protobuf default value semantics.
+    ///
+    /// Matched messages are annotated with `#[serde(default)]`, so that fields absent from the
+    /// input take their Protobuf default when deserializing. Each field is annotated with
+    /// `#[serde(skip_serializing_if = "...")]`, so that unset optional fields and fields holding
+    /// their default value are omitted when serializing, as in the canonical Protobuf JSON
+    /// mapping. Required fields are always serialized.
+    ///
+    /// This does not derive the serde traits themselves; combine it with
+    /// [`message_attribute`](#method.message_attribute) to add the derives.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.message_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]");
+    /// config.serde_default(".");
+    /// ```
+    pub fn serde_default<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.serde_default.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Annotate matched fields with `#[serde(skip)]`.
+    ///
+    /// Matched fields are omitted when serializing, and take their default value when
+    /// deserializing. This is useful to keep implementation details or sensitive data out of
+    /// serialized output. A matched field is not annotated by
+    /// [`serde_default`](#method.serde_default), which would be redundant.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.serde_skip(".my_messages.User.password_hash");
     /// ```
-    pub fn message_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    pub fn serde_skip<P>(&mut self, path: P) -> &mut Self
     where
         P: AsRef<str>,
-        A: AsRef<str>,
     {
-        self.message_attributes
-            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self.serde_skip.insert(path.as_ref().to_string(), ());
         self
     }
 
-    /// Add additional attribute to matched enums and one-ofs.
-    ///
-    /// # Arguments
-    ///
-    /// **`paths`** - a path matching any number of types. It works the same way as in
-    /// [`btree_map`](#method.btree_map), just with the field name omitted.
+    /// Omit matched repeated and map fields from serialized output when they are empty.
     ///
-    /// **`attribute`** - an arbitrary string to be placed before each matched type. The
-    /// expected usage are additional attributes, but anything is allowed.
+    /// Matched repeated fields are annotated with
+    /// `#[serde(skip_serializing_if = "Vec::is_empty")]`, and matched map fields with the
+    /// `is_empty` method of their map type, matching the canonical Protobuf JSON mapping, which
+    /// never emits empty collections. Matched fields of any other kind are left untouched. This
+    /// takes precedence over the generic predicate added by
+    /// [`serde_default`](#method.serde_default), but not over [`serde_skip`](#method.serde_skip).
     ///
-    /// The calls to this method are cumulative. They don't overwrite previous calls and if a
-    /// type is matched by multiple calls of the method, all relevant attributes are added to
-    /// it.
+    /// # Arguments
     ///
-    /// For things like serde it might be needed to combine with [field
-    /// attributes](#method.field_attribute).
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
     ///
     /// # Examples
     ///
     /// ```rust
     /// # let mut config = prost_build::Config::new();
-    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
-    /// config.enum_attribute(".", "#[derive(Eq)]");
-    /// // Some messages want to be serializable with serde as well.
-    /// config.enum_attribute("my_messages.MyEnumType",
-    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
-    /// config.enum_attribute("my_messages.MyMessageType.MyNestedEnumType",
-    ///                       "#[derive(Serialize)] #[serde(rename_all = \"snake_case\")]");
+    /// // Omit every empty repeated and map field.
+    /// config.serde_skip_empty(".");
     /// ```
-    ///
-    /// # Oneof fields
-    ///
-    /// The `oneof` fields don't have a type name of their own inside Protobuf. Therefore, the
-    /// field name can be used both with `enum_attribute` and `field_attribute` ‒ the first is
-    /// placed before the `enum` type definition, the other before the field inside corresponding
-    /// message `struct`.
-    ///
-    /// In other words, to place an attribute on the `enum` implementing the `oneof`, the match
-    /// would look like `my_messages.MyNestedMessageType.oneofname`.
-    pub fn enum_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    pub fn serde_skip_empty<P>(&mut self, path: P) -> &mut Self
     where
         P: AsRef<str>,
-        A: AsRef<str>,
     {
-        self.enum_attributes
-            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self.serde_skip_empty.insert(path.as_ref().to_string(), ());
         self
     }
 
-    /// Wrap matched fields in a `Box`.
+    /// Keep matched message fields in their encoded form.
+    ///
+    /// Matched fields are generated with the [`prost::RawMessage`][1] type instead of the message
+    /// type. The encoded contents of the submessage are captured when decoding, and written back
+    /// verbatim when encoding, so that a submessage can be passed through without being decoded
+    /// and re-encoded. When decoding from a `Bytes` buffer, the contents are not copied.
+    ///
+    /// Only singular and repeated message fields are affected; map values and `oneof` fields are
+    /// always decoded.
     ///
     /// # Arguments
     ///
-    /// **`path`** - a path matching any number of fields. These fields get the attribute.
-    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    /// **`path`** - a path matching any number of fields. These fields get the raw type. For
+    /// details about matching fields see [`btree_map`](#method.btree_map).
     ///
     /// # Examples
     ///
     /// ```rust
     /// # let mut config = prost_build::Config::new();
-    /// config.boxed(".my_messages.MyMessageType.my_field");
+    /// config.raw(".my_messages.Envelope.payload");
     /// ```
-    pub fn boxed<P>(&mut self, path: P) -> &mut Self
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.RawMessage.html
+    pub fn raw<P>(&mut self, path: P) -> &mut Self
     where
         P: AsRef<str>,
     {
-        self.boxed.insert(path.as_ref().to_string(), ());
+        self.raw.insert(path.as_ref().to_string(), ());
         self
     }
 
@@ -586,6 +1315,29 @@ impl Config {
         self
     }
 
+    /// Split the code of services into client and server modules gated by Cargo features.
+    ///
+    /// For each service, the client and server code of the [`ServiceGenerator`] are written to
+    /// `<service>_client` and `<service>_server` modules, annotated with
+    /// `#[cfg(feature = "<client_feature>")]` and `#[cfg(feature = "<server_feature>")]`
+    /// respectively, so that one-sided consumers don't compile the code of the other side. See
+    /// [`ServiceGenerator::generate_client`] and [`ServiceGenerator::generate_server`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.service_features("client", "server");
+    /// ```
+    pub fn service_features<C, S>(&mut self, client_feature: C, server_feature: S) -> &mut Self
+    where
+        C: Into<String>,
+        S: Into<String>,
+    {
+        self.service_features = Some((client_feature.into(), server_feature.into()));
+        self
+    }
+
     /// Configures the code generator to not use the `prost_types` crate for Protobuf well-known
     /// types, and instead generate Protobuf well-known types from their `.proto` definitions.
     pub fn compile_well_known_types(&mut self) -> &mut Self {
@@ -593,6 +1345,17 @@ impl Config {
         self
     }
 
+    /// Configures the code generator to use `()` for `google.protobuf.Empty`, even when
+    /// [compiling the well-known types](#method.compile_well_known_types).
+    ///
+    /// `()` implements `Message` as the empty message, so it can be used wherever `Empty` appears,
+    /// most commonly as the input or output type of service methods. When the well-known types
+    /// are provided by `prost-types`, which is the default, `Empty` is always mapped to `()`.
+    pub fn empty_as_unit(&mut self) -> &mut Self {
+        self.empty_as_unit = true;
+        self
+    }
+
     /// Configures the code generator to omit documentation comments on generated Protobuf types.
     ///
     /// # Example
@@ -741,6 +1504,73 @@ impl Config {
         self
     }
 
+    /// Places the generated code for a Protobuf package at a custom Rust module path.
+    ///
+    /// By default the code for package `foo.bar` is generated into the module `foo::bar`, written
+    /// to `foo.bar.rs`. With a module path mapping the package's code is written to a file named
+    /// after the given module path instead, nested accordingly in the include file, and references
+    /// to its types from other packages resolve to the new location. Nested packages follow their
+    /// parent, so with the mapping below `foo.bar.baz` is generated into
+    /// `my::custom::location::baz`. When several mappings match a package the longest one wins.
+    ///
+    /// The Rust path is relative to the root module the generated code is included into, just
+    /// like the default module paths.
+    ///
+    /// # Arguments
+    ///
+    /// **`package`** - a fully-qualified Protobuf package name, with or without the leading `.`.
+    ///
+    /// **`module_path`** - the Rust module path, with components separated by `::`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.module_path_map("foo.bar", "my::custom::location");
+    /// ```
+    pub fn module_path_map<P1, P2>(&mut self, package: P1, module_path: P2) -> &mut Self
+    where
+        P1: AsRef<str>,
+        P2: Into<String>,
+    {
+        self.module_paths.push((
+            package.as_ref().trim_start_matches('.').to_owned(),
+            module_path.into(),
+        ));
+        self
+    }
+
+    /// Returns the Rust module for a Protobuf package, or for a message nested within one,
+    /// honoring the configured module path mappings.
+    fn module_for_path(&self, path: &str) -> Module {
+        let path = path.trim_start_matches('.');
+        let mapping = self
+            .module_paths
+            .iter()
+            .filter(|(package, _)| {
+                path.strip_prefix(package.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .max_by_key(|(package, _)| package.len());
+
+        match mapping {
+            Some((package, module_path)) => Module {
+                components: module_path
+                    .split("::")
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .chain(
+                        path[package.len()..]
+                            .split('.')
+                            .filter(|s| !s.is_empty())
+                            .map(to_snake),
+                    )
+                    .collect(),
+            },
+            None => Module::from_protobuf_package_name(path),
+        }
+    }
+
     /// When set, the `FileDescriptorSet` generated by `protoc` is written to the provided
     /// filesystem path.
     ///
@@ -803,6 +1633,227 @@ impl Config {
         self
     }
 
+    /// Configures the code generator to implement `Display` and `FromStr` for enums.
+    ///
+    /// The generated implementations use the enum value names as they appear in the Protobuf
+    /// definition, e.g. `FOO_BAR` rather than `FooBar`, matching the names used by other
+    /// languages and by the JSON mapping. `Display` prints the name, and `FromStr` parses it,
+    /// returning a [`prost::ParseEnumError`][1] for unknown names.
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.ParseEnumError.html
+    pub fn enum_string_impls(&mut self) -> &mut Self {
+        self.enum_string_impls = true;
+        self
+    }
+
+    /// Configures the code generator to implement collection traits for messages which wrap a
+    /// single repeated field.
+    ///
+    /// For a message whose only field is a repeated field of `T` (but not a map), such as
+    /// `message List { repeated Item items = 1; }`, the generated type implements
+    /// `IntoIterator` (both by value and by reference), `FromIterator<T>` and `Extend<T>`, by
+    /// delegating to the field. This lets such list messages be used much like a `Vec<T>`.
+    pub fn collection_wrappers(&mut self) -> &mut Self {
+        self.collection_wrappers = true;
+        self
+    }
+
+    /// Configures the code generator to implement conversions from `google.protobuf.Any` for
+    /// messages.
+    ///
+    /// Each generated message implements [`prost::Name`][1], naming its Protobuf package and type,
+    /// and `TryFrom<&prost_types::Any>`, which checks the `Any`'s type URL against the message's
+    /// name and decodes its value, as [`Any::to_msg`][2] does. Unpacking an `Any` into a concrete
+    /// message then takes a `try_into()` call.
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/trait.Name.html
+    /// [2]: https://docs.rs/prost-types/latest/prost_types/struct.Any.html#method.to_msg
+    pub fn any_conversions(&mut self) -> &mut Self {
+        self.any_conversions = true;
+        self
+    }
+
+    /// Configures the code generator to give each message a `TYPICAL_SIZE_HINT` constant.
+    ///
+    /// The constant is a typical encoded length of the message in bytes, computed from its field
+    /// types when the code is generated, which is handy for preallocating encode buffers. It's an
+    /// estimate, not a bound: fixed-width fields count their exact size, but varints, repeated
+    /// fields, maps, strings, bytes and nested messages count a default size, and each oneof counts
+    /// its largest variant.
+    pub fn size_hints(&mut self) -> &mut Self {
+        self.size_hints = true;
+        self
+    }
+
+    /// Configures the order in which generated messages encode their fields.
+    ///
+    /// Fields are encoded in ascending field number order by default, which is the canonical
+    /// order. With [`FieldEmitOrder::Declaration`], they are encoded in the order in which they
+    /// are declared in the `.proto` file instead, for interoperability with readers which depend
+    /// on it. Oneof fields are encoded after all other fields in that case. Decoding accepts
+    /// fields in any order either way.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.field_emit_order(prost_build::FieldEmitOrder::Declaration);
+    /// ```
+    pub fn field_emit_order(&mut self, order: FieldEmitOrder) -> &mut Self {
+        self.field_emit_order = order;
+        self
+    }
+
+    /// Configures the code generator to implement `Clone` by hand, rather than deriving it, for
+    /// messages with more than `threshold` fields.
+    ///
+    /// The generated implementation clones each field in turn, exactly like the derived one, but
+    /// may compile faster for messages with hundreds of fields. Oneofs count as a single field.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.manual_clone_threshold(100);
+    /// ```
+    pub fn manual_clone_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.manual_clone_threshold = Some(threshold);
+        self
+    }
+
+    /// Generate conversions between matched enums and hand-written Rust enums.
+    ///
+    /// For a matched enum, `From` conversions are generated in both directions between the
+    /// generated enum and the Rust enum, along with `From<RustEnum> for i32` and
+    /// `TryFrom<i32> for RustEnum`, so that the Rust enum can be used with the `i32` enumeration
+    /// fields of generated messages. `TryFrom` fails with a [`prost::DecodeError`][1] for values
+    /// which are not defined by the Protobuf enum.
+    ///
+    /// Variants are mapped by name: the Rust enum must have exactly one variant named after each
+    /// variant of the generated enum, i.e. after stripping the enum name prefix unless
+    /// [`retain_enum_prefix`](#method.retain_enum_prefix) is configured. A mismatch is reported
+    /// when compiling the generated code.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of enums. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// **`rust_enum`** - the fully-qualified path of the Rust enum, e.g. `crate::domain::Color`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enum_extern(".my_enums.Color", "crate::domain::Color");
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.DecodeError.html
+    pub fn enum_extern<P, R>(&mut self, path: P, rust_enum: R) -> &mut Self
+    where
+        P: AsRef<str>,
+        R: AsRef<str>,
+    {
+        self.enum_extern
+            .insert(path.as_ref().to_string(), rust_enum.as_ref().to_string());
+        self
+    }
+
+    /// Generate matched enums as open enums, with an `Unknown(i32)` variant.
+    ///
+    /// Enums are closed by default: a value which the Protobuf enum doesn't define has no Rust
+    /// variant, so `from_i32` returns `None` for it. A matched enum instead gets a trailing
+    /// `Unknown(i32)` variant holding any other value, so converting from an `i32` always
+    /// succeeds and converting back yields the original number. The enumeration getters of
+    /// message fields then return `Unknown` rather than the default for such values, and a value
+    /// set through `Unknown` is encoded unchanged. Unmatched enums stay closed.
+    ///
+    /// As its variants can't have discriminants, an open enum implements `From<i32>` and
+    /// `From<Enum> for i32` in place of `as` casts, and compares, orders and hashes its values by
+    /// number. Its `as_str_name` returns an empty string for unknown values. Generating code fails
+    /// if a matched enum has a value whose variant would be named `Unknown`, or is also an
+    /// [`enum_extern`](#method.enum_extern).
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of enums. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.open_enums(".my_enums.Color");
+    /// ```
+    pub fn open_enums<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.open_enums.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Implement `TryFrom<i32>` for matched enums.
+    ///
+    /// The `Enumeration` derive of a matched enum also implements `TryFrom<i32>`, failing with
+    /// [`prost::UnknownEnumValue`][1], which holds the original value, if it isn't one of the
+    /// enum's values. Unmatched enums only have `from_i32`, which returns `None` for such values.
+    /// Open enums are not affected, as they always implement `From<i32>`.
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.UnknownEnumValue.html
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of enums. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Implement `TryFrom<i32>` for all enums.
+    /// config.enum_try_from(".");
+    /// ```
+    pub fn enum_try_from<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<str>,
+    {
+        self.enum_try_from.insert(path.as_ref().to_string(), ());
+        self
+    }
+
+    /// Generate matched `int32`-keyed map fields with keys of a Protobuf enum type.
+    ///
+    /// Protobuf doesn't allow enum map keys, but `int32` keys are wire-compatible with them. A
+    /// matched `map<int32, V>` field is generated with the Rust enum as its key type, e.g.
+    /// `HashMap<Category, V>`, converting keys when encoding and decoding. Keys which are not a
+    /// value of the enum are kept in the `Unknown` variant of enums generated with
+    /// [`open_enums`](#method.open_enums), and fail decoding otherwise.
+    /// Matched map fields with other key types are generated as usual.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of map fields. For details about matching fields
+    /// see [`btree_map`](#method.btree_map).
+    ///
+    /// **`enum_name`** - the fully-qualified Protobuf name of the enum, e.g. `.my_enums.Category`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enum_map_keys(".my_messages.Inventory.counts", ".my_enums.Category");
+    /// ```
+    pub fn enum_map_keys<P, E>(&mut self, path: P, enum_name: E) -> &mut Self
+    where
+        P: AsRef<str>,
+        E: AsRef<str>,
+    {
+        self.enum_map_keys
+            .insert(path.as_ref().to_string(), enum_name.as_ref().to_string());
+        self
+    }
+
     /// Configures the output directory where generated Rust files will be written.
     ///
     /// If unset, defaults to the `OUT_DIR` environment variable. `OUT_DIR` is set by Cargo when
@@ -891,6 +1942,36 @@ impl Config {
         self
     }
 
+    /// Adds inner attributes, such as `#![allow(clippy::all)]`, to the modules of the include
+    /// file.
+    ///
+    /// The attributes are written at the start of each module of the
+    /// [include file](#method.include_file) which includes a generated file, so that they apply
+    /// precisely to the generated code, e.g. to exempt it from lints denied in the rest of the
+    /// crate. The attributes can't be written into the generated files themselves, since
+    /// `include!` does not permit inner attributes. Without an include file, this option has no
+    /// effect; add the attributes to the module which includes the generated code instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config
+    ///     .include_file("_includes.rs")
+    ///     .generated_attributes(["#![allow(clippy::all)]"]);
+    /// ```
+    pub fn generated_attributes<I, S>(&mut self, attributes: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.generated_attributes = attributes
+            .into_iter()
+            .map(|attribute| attribute.as_ref().to_string())
+            .collect();
+        self
+    }
+
     /// Configures the code generator to format the output code via `prettyplease`.
     ///
     /// By default, this is enabled but if the `format` feature is not enabled this does
@@ -909,12 +1990,14 @@ impl Config {
     /// # Example `build.rs`
     ///
     /// ```rust,no_run
+    /// # use prost_types::FileDescriptorSet;
     /// # fn fds() -> FileDescriptorSet { todo!() }
     /// fn main() -> std::io::Result<()> {
     ///   let file_descriptor_set = fds();
     ///
     ///   prost_build::Config::new()
     ///     .compile_fds(file_descriptor_set)?;
+    ///   Ok(())
     /// }
     /// ```
     pub fn compile_fds(&mut self, fds: FileDescriptorSet) -> Result<()> {
@@ -933,12 +2016,7 @@ impl Config {
         let requests = fds
             .file
             .into_iter()
-            .map(|descriptor| {
-                (
-                    Module::from_protobuf_package_name(descriptor.package()),
-                    descriptor,
-                )
-            })
+            .map(|descriptor| (self.module_for_path(descriptor.package()), descriptor))
             .collect::<Vec<_>>();
 
         let file_names = requests
@@ -1098,6 +2176,21 @@ impl Config {
             )
         })?;
 
+        // Options with source retention are not available in the descriptors embedded in
+        // generated code, so drop them from the descriptor set written for the user.
+        if !self.skip_protoc_run && self.file_descriptor_set_path.is_some() {
+            let stripped = retention::strip_source_retention_options(&buf, &file_descriptor_set)
+                .map_err(|error| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid FileDescriptorSet: {}", error),
+                    )
+                })?;
+            if let Some(stripped) = stripped {
+                fs::write(&file_descriptor_set_path, stripped)?;
+            }
+        }
+
         self.compile_fds(file_descriptor_set)
     }
 
@@ -1127,6 +2220,11 @@ impl Config {
                 entries = _temp;
             }
             self.write_line(outfile, depth, &format!("pub mod {} {{", modident))?;
+            if matching.iter().any(|v| v.len() == depth + 1) {
+                for attribute in &self.generated_attributes {
+                    self.write_line(outfile, depth + 1, attribute)?;
+                }
+            }
             let subwritten = self.write_includes(
                 matching
                     .iter()
@@ -1180,8 +2278,9 @@ impl Config {
 
         let message_graph = MessageGraph::new(requests.iter().map(|x| &x.1))
             .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
-        let extern_paths = ExternPaths::new(&self.extern_paths, self.prost_types)
-            .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
+        let extern_paths =
+            ExternPaths::new(&self.extern_paths, self.prost_types, self.empty_as_unit)
+                .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
 
         for (request_module, request_fd) in requests {
             // Only record packages that have services
@@ -1191,7 +2290,8 @@ impl Config {
             let buf = modules
                 .entry(request_module.clone())
                 .or_insert_with(String::new);
-            CodeGenerator::generate(self, &message_graph, &extern_paths, request_fd, buf);
+            CodeGenerator::generate(self, &message_graph, &extern_paths, request_fd, buf)
+                .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
             if buf.is_empty() {
                 // Did not generate any code, remove from list to avoid inclusion in include file or output file list
                 modules.remove(&request_module);
@@ -1230,6 +2330,7 @@ impl default::Default for Config {
         Config {
             file_descriptor_set_path: None,
             service_generator: None,
+            service_features: None,
             map_type: PathMap::default(),
             bytes_type: PathMap::default(),
             type_attributes: PathMap::default(),
@@ -1237,15 +2338,49 @@ impl default::Default for Config {
             enum_attributes: PathMap::default(),
             field_attributes: PathMap::default(),
             boxed: PathMap::default(),
+            lossy_string: PathMap::default(),
+            nonzero: PathMap::default(),
+            boxed_oneof: PathMap::default(),
+            boxed_map_value: PathMap::default(),
+            oneof_case: PathMap::default(),
+            oneof_from: PathMap::default(),
+            skip_default: PathMap::default(),
+            columnar: PathMap::default(),
+            option_accessors: PathMap::default(),
+            trace_field_access: PathMap::default(),
+            extend_methods: PathMap::default(),
+            reserve_methods: PathMap::default(),
+            field_accessors: PathMap::default(),
+            preserve_unknown_fields: PathMap::default(),
+            transparent: PathMap::default(),
+            arena: PathMap::default(),
+            derive_serde: PathMap::default(),
+            serde_default: PathMap::default(),
+            serde_skip: PathMap::default(),
+            serde_skip_empty: PathMap::default(),
+            raw: PathMap::default(),
             prost_types: true,
+            empty_as_unit: false,
             strip_enum_prefix: true,
+            enum_string_impls: false,
+            collection_wrappers: false,
+            any_conversions: false,
+            size_hints: false,
+            field_emit_order: FieldEmitOrder::default(),
+            manual_clone_threshold: None,
+            enum_extern: PathMap::default(),
+            open_enums: PathMap::default(),
+            enum_try_from: PathMap::default(),
+            enum_map_keys: PathMap::default(),
             out_dir: None,
             extern_paths: Vec::new(),
+            module_paths: Vec::new(),
             default_package_filename: "_".to_string(),
             protoc_args: Vec::new(),
             disable_comments: PathMap::default(),
             skip_protoc_run: false,
             include_file: None,
+            generated_attributes: Vec::new(),
             prost_path: None,
             fmt: true,
         }
@@ -1257,15 +2392,28 @@ impl fmt::Debug for Config {
         fmt.debug_struct("Config")
             .field("file_descriptor_set_path", &self.file_descriptor_set_path)
             .field("service_generator", &self.service_generator.is_some())
+            .field("service_features", &self.service_features)
             .field("map_type", &self.map_type)
             .field("bytes_type", &self.bytes_type)
             .field("type_attributes", &self.type_attributes)
             .field("field_attributes", &self.field_attributes)
             .field("prost_types", &self.prost_types)
+            .field("empty_as_unit", &self.empty_as_unit)
             .field("strip_enum_prefix", &self.strip_enum_prefix)
+            .field("enum_string_impls", &self.enum_string_impls)
+            .field("collection_wrappers", &self.collection_wrappers)
+            .field("any_conversions", &self.any_conversions)
+            .field("size_hints", &self.size_hints)
+            .field("field_emit_order", &self.field_emit_order)
+            .field("manual_clone_threshold", &self.manual_clone_threshold)
+            .field("enum_extern", &self.enum_extern)
+            .field("enum_try_from", &self.enum_try_from)
+            .field("enum_map_keys", &self.enum_map_keys)
             .field("out_dir", &self.out_dir)
             .field("extern_paths", &self.extern_paths)
+            .field("module_paths", &self.module_paths)
             .field("default_package_filename", &self.default_package_filename)
+            .field("generated_attributes", &self.generated_attributes)
             .field("protoc_args", &self.protoc_args)
             .field("disable_comments", &self.disable_comments)
             .field("prost_path", &self.prost_path)
@@ -1418,11 +2566,13 @@ pub fn compile_protos(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]
 ///
 /// # Example
 /// ```rust,no_run
+/// # use prost_types::FileDescriptorSet;
 /// # fn fds() -> FileDescriptorSet { todo!() }
 /// fn main() -> std::io::Result<()> {
 ///   let file_descriptor_set = fds();
 ///
 ///   prost_build::compile_fds(file_descriptor_set)?;
+///   Ok(())
 /// }
 /// ```
 pub fn compile_fds(fds: FileDescriptorSet) -> Result<()> {
@@ -1752,6 +2902,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compile_fds() {
+        let _ = env_logger::try_init();
+
+        // A descriptor set built in memory, as if produced by an external `protoc` run.
+        let fds = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("prebuilt.proto".to_string()),
+                package: Some("prebuilt".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![prost_types::DescriptorProto {
+                    name: Some("Msg".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let out_dir = tempfile::tempdir().unwrap();
+        Config::new()
+            .out_dir(out_dir.path())
+            .compile_fds(fds)
+            .unwrap();
+
+        let content = read_all_content(&out_dir.path().join("prebuilt.rs").display().to_string());
+        assert!(content.contains("pub struct Msg"));
+    }
+
+    #[test]
+    fn test_generated_attributes() {
+        let _ = env_logger::try_init();
+
+        let file = |package: &str| FileDescriptorProto {
+            name: Some(format!("{}.proto", package)),
+            package: Some(package.to_string()),
+            message_type: vec![prost_types::DescriptorProto {
+                name: Some("Msg".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let out_dir = tempfile::tempdir().unwrap();
+        Config::new()
+            .out_dir(out_dir.path())
+            .include_file("_include.rs")
+            .generated_attributes(["#![allow(clippy::all)]"])
+            .compile_fds(FileDescriptorSet {
+                file: vec![file("a"), file("a.b")],
+            })
+            .unwrap();
+
+        let content = read_all_content(&out_dir.path().join("_include.rs").display().to_string());
+        let expected = [
+            "pub mod a {",
+            "    #![allow(clippy::all)]",
+            "    pub mod b {",
+            "        #![allow(clippy::all)]",
+            "        include!(\"a.b.rs\");",
+            "    }",
+            "    include!(\"a.rs\");",
+            "}",
+            "",
+        ];
+        assert_eq!(content.replace("\r\n", "\n"), expected.join("\n"));
+    }
+
     fn read_all_content(filepath: &str) -> String {
         let mut f = File::open(filepath).unwrap();
         let mut content = String::new();
@@ -0,0 +1,270 @@
+//! Stripping of source-retention options from an encoded `FileDescriptorSet`.
+//!
+//! Custom options are extensions of the `google.protobuf.*Options` messages. Options declared with
+//! `retention = RETENTION_SOURCE` are only meaningful to tools operating on the `.proto` source,
+//! and are omitted from the descriptors `protoc` hands to code generators. `prost-types` does not
+//! represent extensions, so the options are removed from the encoded bytes directly.
+
+use std::collections::{HashMap, HashSet};
+
+use prost::bytes::Buf;
+use prost::encoding::{
+    decode_key, decode_varint, encode_key, encode_varint, skip_field, DecodeContext, WireType,
+};
+use prost::DecodeError;
+use prost_types::field_options::OptionRetention;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
+/// The descriptor message types which may contain options, directly or nested.
+#[derive(Clone, Copy, Debug)]
+enum Descriptor {
+    FileSet,
+    File,
+    Message,
+    Field,
+    Oneof,
+    Enum,
+    EnumValue,
+    Service,
+    Method,
+    ExtensionRange,
+    /// An options message, identified by its fully-qualified name.
+    Options(&'static str),
+}
+
+impl Descriptor {
+    /// Returns the type of the descriptor or options message nested in field `tag`, if any.
+    fn nested(self, tag: u32) -> Option<Descriptor> {
+        use Descriptor::*;
+        Some(match (self, tag) {
+            (FileSet, 1) => File,
+            (File, 4) => Message,
+            (File, 5) => Enum,
+            (File, 6) => Service,
+            (File, 7) => Field,
+            (File, 8) => Options(".google.protobuf.FileOptions"),
+            (Message, 2) | (Message, 6) => Field,
+            (Message, 3) => Message,
+            (Message, 4) => Enum,
+            (Message, 5) => ExtensionRange,
+            (Message, 7) => Options(".google.protobuf.MessageOptions"),
+            (Message, 8) => Oneof,
+            (Field, 8) => Options(".google.protobuf.FieldOptions"),
+            (Oneof, 2) => Options(".google.protobuf.OneofOptions"),
+            (Enum, 2) => EnumValue,
+            (Enum, 3) => Options(".google.protobuf.EnumOptions"),
+            (EnumValue, 3) => Options(".google.protobuf.EnumValueOptions"),
+            (Service, 2) => Method,
+            (Service, 3) => Options(".google.protobuf.ServiceOptions"),
+            (Method, 4) => Options(".google.protobuf.MethodOptions"),
+            (ExtensionRange, 3) => Options(".google.protobuf.ExtensionRangeOptions"),
+            _ => return None,
+        })
+    }
+}
+
+/// Removes all source-retention options from the encoded `FileDescriptorSet`.
+///
+/// Returns `None` if the descriptor set does not declare any source-retention options, in which
+/// case the encoded bytes are left as-is.
+pub(crate) fn strip_source_retention_options(
+    buf: &[u8],
+    file_descriptor_set: &FileDescriptorSet,
+) -> Result<Option<Vec<u8>>, DecodeError> {
+    let mut source_options = HashMap::new();
+    for file in &file_descriptor_set.file {
+        collect_source_options(&file.extension, &mut source_options);
+        for message in &file.message_type {
+            collect_message_source_options(message, &mut source_options);
+        }
+    }
+
+    if source_options.is_empty() {
+        return Ok(None);
+    }
+
+    let mut out = Vec::with_capacity(buf.len());
+    strip(Descriptor::FileSet, buf, &source_options, &mut out)?;
+    Ok(Some(out))
+}
+
+fn collect_message_source_options<'a>(
+    message: &'a DescriptorProto,
+    source_options: &mut HashMap<&'a str, HashSet<u32>>,
+) {
+    collect_source_options(&message.extension, source_options);
+    for nested in &message.nested_type {
+        collect_message_source_options(nested, source_options);
+    }
+}
+
+fn collect_source_options<'a>(
+    extensions: &'a [FieldDescriptorProto],
+    source_options: &mut HashMap<&'a str, HashSet<u32>>,
+) {
+    for extension in extensions {
+        let retention = extension
+            .options
+            .as_ref()
+            .map_or(OptionRetention::RetentionUnknown, |options| {
+                options.retention()
+            });
+        if retention == OptionRetention::RetentionSource {
+            source_options
+                .entry(extension.extendee())
+                .or_default()
+                .insert(extension.number() as u32);
+        }
+    }
+}
+
+fn strip(
+    descriptor: Descriptor,
+    mut buf: &[u8],
+    source_options: &HashMap<&str, HashSet<u32>>,
+    out: &mut Vec<u8>,
+) -> Result<(), DecodeError> {
+    while buf.has_remaining() {
+        let field = buf;
+        let (tag, wire_type) = decode_key(&mut buf)?;
+
+        match descriptor.nested(tag) {
+            Some(nested) if wire_type == WireType::LengthDelimited => {
+                let len = decode_varint(&mut buf)?;
+                if len > buf.remaining() as u64 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                let len = len as usize;
+
+                let mut nested_out = Vec::with_capacity(len);
+                strip(nested, &buf[..len], source_options, &mut nested_out)?;
+                buf.advance(len);
+
+                encode_key(tag, wire_type, out);
+                encode_varint(nested_out.len() as u64, out);
+                out.extend_from_slice(&nested_out);
+            }
+            _ => {
+                skip_field(wire_type, tag, &mut buf, DecodeContext::default())?;
+
+                let retained = match descriptor {
+                    Descriptor::Options(name) => !source_options
+                        .get(name)
+                        .map_or(false, |tags| tags.contains(&tag)),
+                    _ => true,
+                };
+                if retained {
+                    out.extend_from_slice(&field[..field.len() - buf.len()]);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use prost_types::{FieldOptions, FileDescriptorProto};
+
+    use super::*;
+
+    /// Encodes `value` as a length-delimited field.
+    fn field(tag: u32, value: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_key(tag, WireType::LengthDelimited, &mut buf);
+        encode_varint(value.len() as u64, &mut buf);
+        buf.extend_from_slice(value);
+        buf
+    }
+
+    /// Encodes `value` as a varint field.
+    fn varint(tag: u32, value: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_key(tag, WireType::Varint, &mut buf);
+        encode_varint(value, &mut buf);
+        buf
+    }
+
+    fn extension(name: &str, number: i32, retention: OptionRetention) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            extendee: Some(".google.protobuf.FieldOptions".to_string()),
+            options: Some(FieldOptions {
+                retention: Some(retention as i32),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Encodes a descriptor set with a single message field carrying the given options.
+    fn descriptor_set(field_options: &[u8]) -> Vec<u8> {
+        let mut message = DescriptorProto {
+            name: Some("Msg".to_string()),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut message_field = FieldDescriptorProto {
+            name: Some("field".to_string()),
+            number: Some(1),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        message_field.extend(field(8, field_options));
+        message.extend(field(2, &message_field));
+
+        let mut file = FileDescriptorProto {
+            name: Some("retention.proto".to_string()),
+            extension: vec![
+                extension("source", 50000, OptionRetention::RetentionSource),
+                extension("runtime", 50001, OptionRetention::RetentionRuntime),
+            ],
+            ..Default::default()
+        }
+        .encode_to_vec();
+        file.extend(field(4, &message));
+
+        field(1, &file)
+    }
+
+    #[test]
+    fn test_strip_source_retention_options() {
+        let mut options = FieldOptions {
+            deprecated: Some(true),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut expected_options = options.clone();
+        options.extend(varint(50000, 1));
+        options.extend(varint(50001, 2));
+        expected_options.extend(varint(50001, 2));
+
+        let buf = descriptor_set(&options);
+        let file_descriptor_set = FileDescriptorSet::decode(&*buf).unwrap();
+        let stripped = strip_source_retention_options(&buf, &file_descriptor_set)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stripped, descriptor_set(&expected_options));
+    }
+
+    #[test]
+    fn test_no_source_retention_options() {
+        let file_descriptor_set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                extension: vec![extension(
+                    "runtime",
+                    50001,
+                    OptionRetention::RetentionRuntime,
+                )],
+                ..Default::default()
+            }],
+        };
+        let buf = file_descriptor_set.encode_to_vec();
+        assert_eq!(
+            strip_source_retention_options(&buf, &file_descriptor_set).unwrap(),
+            None
+        );
+    }
+}
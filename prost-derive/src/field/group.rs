@@ -74,7 +74,7 @@ impl Field {
         let tag = self.tag;
         match self.label {
             Label::Optional => quote! {
-                if let Some(ref msg) = #ident {
+                if let ::core::option::Option::Some(ref msg) = #ident {
                     ::prost::encoding::group::encode(#tag, msg, buf);
                 }
             },
@@ -124,10 +124,30 @@ impl Field {
         }
     }
 
+    /// Returns a statement which merges the value `other` of the field in memory.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                if let ::core::option::Option::Some(other) = #other {
+                    match #ident {
+                        ::core::option::Option::Some(ref mut value) => {
+                            ::prost::Message::merge_from(value, other)
+                        }
+                        ::core::option::Option::None => {
+                            #ident = ::core::option::Option::Some(other)
+                        }
+                    }
+                }
+            },
+            Label::Required => quote!(::prost::Message::merge_from(&mut #ident, #other);),
+            Label::Repeated => quote!(::core::iter::Extend::extend(&mut #ident, #other);),
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.label {
             Label::Optional => quote!(#ident = ::core::option::Option::None),
-            Label::Required => quote!(#ident.clear()),
+            Label::Required => quote!(::prost::Message::clear(&mut #ident)),
             Label::Repeated => quote!(#ident.clear()),
         }
     }
@@ -41,6 +41,8 @@ fn fake_scalar(ty: scalar::Ty) -> scalar::Field {
         ty,
         kind,
         tag: 0, // Not used here
+        lossy: false,
+        nonzero: false,
     }
 }
 
@@ -134,9 +136,23 @@ impl Field {
         let ke = quote!(::prost::encoding::#key_mod::encode);
         let kl = quote!(::prost::encoding::#key_mod::encoded_len);
         let module = self.map_ty.module();
+        if let scalar::Ty::Enumeration(_) = self.key_ty {
+            let (val_mod, default) = self.value_ty.encoding();
+            return quote! {
+                ::prost::encoding::#module::encode_with_key_conversion(
+                    ::prost::encoding::EnumerationKey,
+                    ::prost::encoding::#val_mod::encode,
+                    ::prost::encoding::#val_mod::encoded_len,
+                    &(#default),
+                    #tag,
+                    &#ident,
+                    buf,
+                );
+            };
+        }
         match &self.value_ty {
             ValueTy::Scalar(scalar::Ty::Enumeration(ty)) => {
-                let default = quote!(#ty::default() as i32);
+                let default = quote!(::core::convert::Into::<i32>::into(<#ty as ::core::default::Default>::default()));
                 quote! {
                     ::prost::encoding::#module::encode_with_default(
                         #ke,
@@ -186,9 +202,23 @@ impl Field {
         let key_mod = self.key_ty.module();
         let km = quote!(::prost::encoding::#key_mod::merge);
         let module = self.map_ty.module();
+        if let scalar::Ty::Enumeration(key_ty) = &self.key_ty {
+            let (val_mod, default) = self.value_ty.encoding();
+            return quote! {
+                ::prost::encoding::#module::merge_with_key_conversion(
+                    #key_ty::from_i32,
+                    #km,
+                    ::prost::encoding::#val_mod::merge,
+                    #default,
+                    &mut #ident,
+                    buf,
+                    ctx,
+                )
+            };
+        }
         match &self.value_ty {
             ValueTy::Scalar(scalar::Ty::Enumeration(ty)) => {
-                let default = quote!(#ty::default() as i32);
+                let default = quote!(::core::convert::Into::<i32>::into(<#ty as ::core::default::Default>::default()));
                 quote! {
                     ::prost::encoding::#module::merge_with_default(
                         #km,
@@ -223,9 +253,21 @@ impl Field {
         let key_mod = self.key_ty.module();
         let kl = quote!(::prost::encoding::#key_mod::encoded_len);
         let module = self.map_ty.module();
+        if let scalar::Ty::Enumeration(_) = self.key_ty {
+            let (val_mod, default) = self.value_ty.encoding();
+            return quote! {
+                ::prost::encoding::#module::encoded_len_with_key_conversion(
+                    ::prost::encoding::EnumerationKey,
+                    ::prost::encoding::#val_mod::encoded_len,
+                    &(#default),
+                    #tag,
+                    &#ident,
+                )
+            };
+        }
         match &self.value_ty {
             ValueTy::Scalar(scalar::Ty::Enumeration(ty)) => {
-                let default = quote!(#ty::default() as i32);
+                let default = quote!(::core::convert::Into::<i32>::into(<#ty as ::core::default::Default>::default()));
                 quote! {
                     ::prost::encoding::#module::encoded_len_with_default(
                         #kl,
@@ -252,15 +294,32 @@ impl Field {
         }
     }
 
+    /// Returns a statement which merges the value `other` of the field in memory, replacing the
+    /// values of existing keys.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        quote!(::core::iter::Extend::extend(&mut #ident, #other);)
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident.clear())
     }
 
-    /// Returns methods to embed in the message.
-    pub fn methods(&self, ident: &TokenStream) -> Option<TokenStream> {
+    /// Returns the Rust type of the map's keys.
+    fn key_rust_type(&self) -> TokenStream {
+        match &self.key_ty {
+            scalar::Ty::Enumeration(ty) => quote!(#ty),
+            key_ty => key_ty.rust_type(),
+        }
+    }
+
+    /// Returns methods to embed in the message. Getters begin with the `trace` statements.
+    pub fn methods(&self, ident: &TokenStream, trace: &TokenStream) -> Option<TokenStream> {
         if let ValueTy::Scalar(scalar::Ty::Enumeration(ty)) = &self.value_ty {
-            let key_ty = self.key_ty.rust_type();
-            let key_ref_ty = self.key_ty.rust_ref_type();
+            let key_ty = self.key_rust_type();
+            let key_ref_ty = match &self.key_ty {
+                scalar::Ty::Enumeration(..) => self.key_rust_type(),
+                key_ty => key_ty.rust_ref_type(),
+            };
 
             let get = Ident::new(&format!("get_{}", ident), Span::call_site());
             let insert = Ident::new(&format!("insert_{}", ident), Span::call_site());
@@ -279,11 +338,12 @@ impl Field {
             Some(quote! {
                 #[doc=#get_doc]
                 pub fn #get(&self, key: #key_ref_ty) -> ::core::option::Option<#ty> {
+                    #trace
                     self.#ident.get(#take_ref key).cloned().and_then(#ty::from_i32)
                 }
                 #[doc=#insert_doc]
                 pub fn #insert(&mut self, key: #key_ty, value: #ty) -> ::core::option::Option<#ty> {
-                    self.#ident.insert(key, value as i32).and_then(#ty::from_i32)
+                    self.#ident.insert(key, ::core::convert::Into::<i32>::into(value)).and_then(#ty::from_i32)
                 }
             })
         } else {
@@ -302,8 +362,16 @@ impl Field {
         };
 
         // A fake field for generating the debug wrapper
-        let key_wrapper = fake_scalar(self.key_ty.clone()).debug(quote!(KeyWrapper));
-        let key = self.key_ty.rust_type();
+        let key_wrapper = match self.key_ty {
+            // Enumeration keys are stored as the enum type, which already implements Debug.
+            scalar::Ty::Enumeration(..) => quote!(
+                fn KeyWrapper<T>(v: T) -> T {
+                    v
+                }
+            ),
+            _ => fake_scalar(self.key_ty.clone()).debug(quote!(KeyWrapper)),
+        };
+        let key = self.key_rust_type();
         let value_wrapper = self.value_ty.debug();
         let libname = self.map_ty.lib();
         let fmt = quote! {
@@ -365,7 +433,8 @@ fn key_ty_from_str(s: &str) -> Result<scalar::Ty, Error> {
         | scalar::Ty::Sfixed32
         | scalar::Ty::Sfixed64
         | scalar::Ty::Bool
-        | scalar::Ty::String => Ok(ty),
+        | scalar::Ty::String
+        | scalar::Ty::Enumeration(..) => Ok(ty),
         _ => bail!("invalid map key type: {}", s),
     }
 }
@@ -388,6 +457,22 @@ impl ValueTy {
         }
     }
 
+    /// Returns the encoding module of the value type, and the default value of its encoded
+    /// representation.
+    fn encoding(&self) -> (Ident, TokenStream) {
+        match self {
+            ValueTy::Scalar(scalar::Ty::Enumeration(ty)) => (
+                Ident::new("int32", Span::call_site()),
+                quote!(::core::convert::Into::<i32>::into(<#ty as ::core::default::Default>::default())),
+            ),
+            ValueTy::Scalar(ty) => (ty.module(), quote!(::core::default::Default::default())),
+            ValueTy::Message => (
+                Ident::new("message", Span::call_site()),
+                quote!(::core::default::Default::default()),
+            ),
+        }
+    }
+
     /// Returns a newtype wrapper around the ValueTy for nicer debug.
     ///
     /// If the contained value is enumeration, it tries to convert it to the variant. If not, it
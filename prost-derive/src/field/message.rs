@@ -77,9 +77,7 @@ impl Field {
         let tag = self.tag;
         match self.label {
             Label::Optional => quote! {
-                if let Some(ref msg) = #ident {
-                    ::prost::encoding::message::encode(#tag, msg, buf);
-                }
+                ::prost::encoding::message::encode_optional(#tag, &#ident, buf);
             },
             Label::Required => quote! {
                 ::prost::encoding::message::encode(#tag, &#ident, buf);
@@ -95,10 +93,7 @@ impl Field {
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
         match self.label {
             Label::Optional => quote! {
-                ::prost::encoding::message::merge(wire_type,
-                                                 #ident.get_or_insert_with(::core::default::Default::default),
-                                                 buf,
-                                                 ctx)
+                ::prost::encoding::message::merge_optional(wire_type, #ident, buf, ctx)
             },
             Label::Required => quote! {
                 ::prost::encoding::message::merge(wire_type, #ident, buf, ctx)
@@ -113,7 +108,7 @@ impl Field {
         let tag = self.tag;
         match self.label {
             Label::Optional => quote! {
-                #ident.as_ref().map_or(0, |msg| ::prost::encoding::message::encoded_len(#tag, msg))
+                ::prost::encoding::message::encoded_len_optional(#tag, &#ident)
             },
             Label::Required => quote! {
                 ::prost::encoding::message::encoded_len(#tag, &#ident)
@@ -124,10 +119,64 @@ impl Field {
         }
     }
 
+    /// Returns a statement which encodes the field, taking the lengths of the submessages from
+    /// `sizes`.
+    pub fn encode_cached(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        match self.label {
+            Label::Optional => quote! {
+                ::prost::encoding::message::encode_optional_cached(#tag, &#ident, buf, sizes);
+            },
+            Label::Required => quote! {
+                ::prost::encoding::message::encode_cached(#tag, &#ident, buf, sizes);
+            },
+            Label::Repeated => quote! {
+                ::prost::encoding::message::encode_repeated_cached(#tag, &#ident, buf, sizes);
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the encoded length of the field, recording the
+    /// lengths of the submessages in `sizes`.
+    pub fn encoded_len_cached(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        match self.label {
+            Label::Optional => quote! {
+                ::prost::encoding::message::encoded_len_optional_cached(#tag, &#ident, sizes)
+            },
+            Label::Required => quote! {
+                ::prost::encoding::message::encoded_len_cached(#tag, &#ident, sizes)
+            },
+            Label::Repeated => quote! {
+                ::prost::encoding::message::encoded_len_repeated_cached(#tag, &#ident, sizes)
+            },
+        }
+    }
+
+    /// Returns a statement which merges the value `other` of the field in memory.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                if let ::core::option::Option::Some(other) = #other {
+                    match #ident {
+                        ::core::option::Option::Some(ref mut value) => {
+                            ::prost::Message::merge_from(value, other)
+                        }
+                        ::core::option::Option::None => {
+                            #ident = ::core::option::Option::Some(other)
+                        }
+                    }
+                }
+            },
+            Label::Required => quote!(::prost::Message::merge_from(&mut #ident, #other);),
+            Label::Repeated => quote!(::core::iter::Extend::extend(&mut #ident, #other);),
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.label {
             Label::Optional => quote!(#ident = ::core::option::Option::None),
-            Label::Required => quote!(#ident.clear()),
+            Label::Required => quote!(::prost::Message::clear(&mut #ident)),
             Label::Repeated => quote!(#ident.clear()),
         }
     }
@@ -8,9 +8,9 @@ use std::fmt;
 use std::slice;
 
 use anyhow::{bail, Error};
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{Attribute, Lit, LitBool, Meta, MetaList, MetaNameValue, NestedMeta};
+use syn::{Attribute, Ident, Lit, LitBool, Meta, MetaList, MetaNameValue, NestedMeta, Type};
 
 #[derive(Clone)]
 pub enum Field {
@@ -87,6 +87,16 @@ impl Field {
         }
     }
 
+    /// Returns whether the field is a required proto2 field.
+    pub fn is_required(&self) -> bool {
+        match *self {
+            Field::Scalar(ref scalar) => matches!(scalar.kind, scalar::Kind::Required(..)),
+            Field::Message(ref message) => message.label == Label::Required,
+            Field::Group(ref group) => group.label == Label::Required,
+            Field::Map(..) | Field::Oneof(..) => false,
+        }
+    }
+
     /// Returns a statement which encodes the field.
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
         match *self {
@@ -121,6 +131,38 @@ impl Field {
         }
     }
 
+    /// Returns a statement which encodes the field, taking the lengths of its submessages from
+    /// `sizes`, if the field holds length-delimited submessages.
+    pub fn encode_cached(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Message(ref msg) => Some(msg.encode_cached(ident)),
+            Field::Oneof(ref oneof) => Some(oneof.encode_cached(ident)),
+            _ => None,
+        }
+    }
+
+    /// Returns an expression which evaluates to the encoded length of the field, recording the
+    /// lengths of its submessages in `sizes`, if the field holds length-delimited submessages.
+    pub fn encoded_len_cached(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Message(ref msg) => Some(msg.encoded_len_cached(ident)),
+            Field::Oneof(ref oneof) => Some(oneof.encoded_len_cached(ident)),
+            _ => None,
+        }
+    }
+
+    /// Returns a statement which merges `other`, the value of the field in another message, into
+    /// the field in memory, following the semantics of merging encoded messages.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.merge_from(ident, other),
+            Field::Message(ref message) => message.merge_from(ident, other),
+            Field::Map(ref map) => map.merge_from(ident, other),
+            Field::Oneof(ref oneof) => oneof.merge_from(ident, other),
+            Field::Group(ref group) => group.merge_from(ident, other),
+        }
+    }
+
     /// Returns a statement which clears the field.
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match *self {
@@ -135,6 +177,13 @@ impl Field {
     pub fn default(&self) -> TokenStream {
         match *self {
             Field::Scalar(ref scalar) => scalar.default(),
+            // Optional submessages, including boxed recursive ones, are unset by default.
+            Field::Message(ref message) if message.label == Label::Optional => {
+                quote!(::core::option::Option::None)
+            }
+            Field::Group(ref group) if group.label == Label::Optional => {
+                quote!(::core::option::Option::None)
+            }
             _ => quote!(::core::default::Default::default()),
         }
     }
@@ -164,13 +213,214 @@ impl Field {
         }
     }
 
-    pub fn methods(&self, ident: &TokenStream) -> Option<TokenStream> {
+    /// Returns methods to embed in the message. Getters begin with the `trace` statements.
+    pub fn methods(&self, ident: &TokenStream, trace: &TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.methods(ident, trace),
+            Field::Map(ref map) => map.methods(ident, trace),
+            _ => None,
+        }
+    }
+
+    /// Returns a `reserve_<field>` method reserving capacity in a repeated or hash map field, if
+    /// the field is one.
+    pub fn reserve_method(&self, ident: &TokenStream) -> Option<TokenStream> {
+        let reservable = match *self {
+            Field::Scalar(ref scalar) => {
+                matches!(scalar.kind, scalar::Kind::Repeated | scalar::Kind::Packed)
+            }
+            Field::Message(ref message) => message.label == Label::Repeated,
+            Field::Group(ref group) => group.label == Label::Repeated,
+            // `BTreeMap` does not support reserving capacity.
+            Field::Map(ref map) => matches!(map.map_ty, map::MapTy::HashMap),
+            Field::Oneof(..) => false,
+        };
+        if !reservable {
+            return None;
+        }
+
+        let mut ident_str = ident.to_string();
+        if ident_str.starts_with("r#") {
+            ident_str = ident_str[2..].to_owned();
+        }
+        let reserve = Ident::new(&format!("reserve_{}", ident_str), Span::call_site());
+        let doc = format!(
+            "Reserves capacity for at least `additional` more elements in `{}`.",
+            ident_str,
+        );
+        Some(quote! {
+            #[doc=#doc]
+            pub fn #reserve(&mut self, additional: usize) {
+                self.#ident.reserve(additional);
+            }
+        })
+    }
+
+    /// Returns an `extend_<field>` method appending the items of an iterator to a repeated field, if
+    /// the field is one. `ty` is the Rust type of the field.
+    pub fn extend_method(&self, ident: &TokenStream, ty: &Type) -> Option<TokenStream> {
+        let repeated = match *self {
+            Field::Scalar(ref scalar) => {
+                matches!(scalar.kind, scalar::Kind::Repeated | scalar::Kind::Packed)
+            }
+            Field::Message(ref message) => message.label == Label::Repeated,
+            Field::Group(ref group) => group.label == Label::Repeated,
+            Field::Map(..) | Field::Oneof(..) => false,
+        };
+        if !repeated {
+            return None;
+        }
+
+        let mut ident_str = ident.to_string();
+        if ident_str.starts_with("r#") {
+            ident_str = ident_str[2..].to_owned();
+        }
+        let extend = Ident::new(&format!("extend_{}", ident_str), Span::call_site());
+        let doc = format!("Appends the elements of `iter` to `{}`.", ident_str,);
+        Some(quote! {
+            #[doc=#doc]
+            pub fn #extend<I>(&mut self, iter: I)
+            where
+                I: ::core::iter::IntoIterator<Item = <#ty as ::core::iter::IntoIterator>::Item>,
+            {
+                ::core::iter::Extend::extend(&mut self.#ident, iter);
+            }
+        })
+    }
+
+    /// Returns an expression which evaluates to whether the submessages of the field nest more
+    /// than `limit` levels deep, if the field can hold submessages.
+    pub fn exceeds_depth(&self, ident: TokenStream) -> Option<TokenStream> {
+        let check = |msg: TokenStream| {
+            quote! {
+                (limit == 0 || ::prost::Message::exceeds_depth(#msg, limit - 1))
+            }
+        };
+        let label = match *self {
+            Field::Message(ref message) => message.label,
+            Field::Group(ref group) => group.label,
+            // Map entries are messages themselves, and count as a level.
+            Field::Map(ref map) if map.value_ty == map::ValueTy::Message => {
+                return Some(quote! {
+                    ::core::iter::Iterator::any(&mut #ident.values(), |msg| {
+                        limit < 2 || ::prost::Message::exceeds_depth(msg, limit - 2)
+                    })
+                });
+            }
+            Field::Oneof(ref oneof) => {
+                let ty = &oneof.ty;
+                return Some(quote! {
+                    #ident.as_ref().map_or(false, |oneof| #ty::exceeds_depth(oneof, limit))
+                });
+            }
+            _ => return None,
+        };
+        Some(match label {
+            Label::Optional => {
+                let check = check(quote!(msg));
+                quote!(#ident.as_ref().map_or(false, |msg| #check))
+            }
+            Label::Required => check(quote!(&#ident)),
+            Label::Repeated => {
+                let check = check(quote!(msg));
+                quote!(::core::iter::Iterator::any(&mut #ident.iter(), |msg| #check))
+            }
+        })
+    }
+
+    /// Returns a statement which clears the unknown fields preserved by the submessages of the
+    /// field, if the field can hold submessages.
+    pub fn clear_unknown_fields(&self, ident: TokenStream) -> Option<TokenStream> {
+        let clear = |msg: TokenStream| quote!(::prost::Message::clear_unknown_fields(#msg););
+        let label = match *self {
+            Field::Message(ref message) => message.label,
+            Field::Group(ref group) => group.label,
+            Field::Map(ref map) if map.value_ty == map::ValueTy::Message => {
+                let clear = clear(quote!(msg));
+                return Some(quote! {
+                    for msg in #ident.values_mut() {
+                        #clear
+                    }
+                });
+            }
+            Field::Oneof(ref oneof) => {
+                let ty = &oneof.ty;
+                return Some(quote! {
+                    if let ::core::option::Option::Some(ref mut oneof) = #ident {
+                        #ty::clear_unknown_fields(oneof);
+                    }
+                });
+            }
+            _ => return None,
+        };
+        Some(match label {
+            Label::Optional => {
+                let clear = clear(quote!(msg));
+                quote! {
+                    if let ::core::option::Option::Some(ref mut msg) = #ident {
+                        #clear
+                    }
+                }
+            }
+            Label::Required => clear(quote!(&mut #ident)),
+            Label::Repeated => {
+                let clear = clear(quote!(msg));
+                quote! {
+                    for msg in #ident.iter_mut() {
+                        #clear
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns an accessor method borrowing the field as an `Option`, if the field is an optional
+    /// scalar.
+    pub fn option_accessor(&self, ident: &TokenStream, trace: &TokenStream) -> Option<TokenStream> {
         match *self {
-            Field::Scalar(ref scalar) => scalar.methods(ident),
-            Field::Map(ref map) => map.methods(ident),
+            Field::Scalar(ref scalar) => scalar.option_accessor(ident, trace),
             _ => None,
         }
     }
+
+    /// Returns a `clear_<field>` method resetting the field, and a `has_<field>` method if the
+    /// field tracks presence. The `has_<field>` getter begins with the `trace` statements.
+    pub fn field_accessors(&self, ident: &TokenStream, trace: &TokenStream) -> Vec<TokenStream> {
+        let mut ident_str = ident.to_string();
+        if ident_str.starts_with("r#") {
+            ident_str = ident_str[2..].to_owned();
+        }
+
+        let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+        let clear_doc = format!("Resets `{}` to its default value.", ident_str);
+        let reset = self.clear(quote!(self.#ident));
+        let mut methods = vec![quote! {
+            #[doc=#clear_doc]
+            pub fn #clear(&mut self) {
+                #reset;
+            }
+        }];
+
+        let presence = match *self {
+            Field::Scalar(ref scalar) => matches!(scalar.kind, scalar::Kind::Optional(..)),
+            Field::Message(ref message) => message.label == Label::Optional,
+            Field::Group(ref group) => group.label == Label::Optional,
+            Field::Oneof(..) => true,
+            Field::Map(..) => false,
+        };
+        if presence {
+            let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+            let has_doc = format!("Returns `true` if `{}` is set.", ident_str);
+            methods.push(quote! {
+                #[doc=#has_doc]
+                pub fn #has(&self) -> bool {
+                    #trace
+                    self.#ident.is_some()
+                }
+            });
+        }
+        methods
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -223,8 +473,15 @@ impl fmt::Display for Label {
     }
 }
 
+/// Returns `true` if the attributes declare the field holding the unknown fields of a message.
+pub fn is_unknown_fields(attrs: &[Attribute]) -> bool {
+    prost_attrs(attrs.to_vec())
+        .iter()
+        .any(|attr| word_attr("unknown_fields", attr))
+}
+
 /// Get the items belonging to the 'prost' list attribute, e.g. `#[prost(foo, bar="baz")]`.
-fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
+pub(super) fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
     attrs
         .iter()
         .flat_map(Attribute::parse_meta)
@@ -301,7 +558,7 @@ fn bool_attr(key: &str, attr: &Meta) -> Result<Option<bool>, Error> {
 }
 
 /// Checks if an attribute matches a word.
-fn word_attr(key: &str, attr: &Meta) -> bool {
+pub(super) fn word_attr(key: &str, attr: &Meta) -> bool {
     if let Meta::Path(ref path) = *attr {
         path.is_ident(key)
     } else {
@@ -71,7 +71,7 @@ impl Field {
     /// Returns a statement which encodes the oneof field.
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
         quote! {
-            if let Some(ref oneof) = #ident {
+            if let ::core::option::Option::Some(ref oneof) = #ident {
                 oneof.encode(buf)
             }
         }
@@ -93,6 +93,38 @@ impl Field {
         }
     }
 
+    /// Returns a statement which encodes the oneof field, taking the lengths of submessages from
+    /// `sizes`.
+    pub fn encode_cached(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let ::core::option::Option::Some(ref oneof) = #ident {
+                oneof.encode_cached(buf, sizes)
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the encoded length of the oneof field, recording
+    /// the lengths of submessages in `sizes`.
+    pub fn encoded_len_cached(&self, ident: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        quote! {
+            #ident.as_ref().map_or(0, |oneof| #ty::encoded_len_cached(oneof, sizes))
+        }
+    }
+
+    /// Returns a statement which merges the value `other` of the oneof field in memory.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        quote! {
+            if let ::core::option::Option::Some(other) = #other {
+                match #ident {
+                    ::core::option::Option::Some(ref mut value) => #ty::merge_from(value, other),
+                    ::core::option::Option::None => #ident = ::core::option::Option::Some(other),
+                }
+            }
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident = ::core::option::Option::None)
     }
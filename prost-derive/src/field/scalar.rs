@@ -8,7 +8,7 @@ use syn::{
     parse_str, Ident, Index, Lit, LitByteStr, Meta, MetaList, MetaNameValue, NestedMeta, Path,
 };
 
-use crate::field::{bool_attr, set_option, tag_attr, Label};
+use crate::field::{bool_attr, set_bool, set_option, tag_attr, word_attr, Label};
 
 /// A scalar protobuf field.
 #[derive(Clone)]
@@ -16,6 +16,10 @@ pub struct Field {
     pub ty: Ty,
     pub kind: Kind,
     pub tag: u32,
+    /// Whether invalid UTF-8 in a string field is replaced rather than rejected.
+    pub lossy: bool,
+    /// Whether an integer field is represented by a `NonZero*` type, rejecting zero values.
+    pub nonzero: bool,
 }
 
 impl Field {
@@ -25,6 +29,8 @@ impl Field {
         let mut packed = None;
         let mut default = None;
         let mut tag = None;
+        let mut lossy = false;
+        let mut nonzero = false;
 
         let mut unknown_attrs = Vec::new();
 
@@ -39,6 +45,10 @@ impl Field {
                 set_option(&mut label, l, "duplicate label attributes")?;
             } else if let Some(d) = DefaultValue::from_attr(attr)? {
                 set_option(&mut default, d, "duplicate default attributes")?;
+            } else if word_attr("lossy", attr) {
+                set_bool(&mut lossy, "duplicate lossy attributes")?;
+            } else if word_attr("nonzero", attr) {
+                set_bool(&mut nonzero, "duplicate nonzero attributes")?;
             } else {
                 unknown_attrs.push(attr);
             }
@@ -55,12 +65,22 @@ impl Field {
             _ => bail!("unknown attributes: {:?}", unknown_attrs),
         }
 
+        if lossy && ty != Ty::String {
+            bail!("lossy attribute may only be applied to string fields");
+        }
+        if nonzero && !ty.is_integer() {
+            bail!("nonzero attribute may only be applied to integer fields");
+        }
+
         let tag = match tag.or(inferred_tag) {
             Some(tag) => tag,
             None => bail!("missing tag attribute"),
         };
 
         let has_default = default.is_some();
+        if nonzero && has_default {
+            bail!("nonzero fields may not have a default value");
+        }
         let default = default.map_or_else(
             || Ok(DefaultValue::new(&ty)),
             |lit| DefaultValue::from_lit(&ty, lit),
@@ -79,6 +99,12 @@ impl Field {
                 bail!("repeated fields may not have a default value");
             }
 
+            (Some(Label::Required), _, _) if nonzero => {
+                bail!("nonzero attribute may not be applied to required fields");
+            }
+
+            // Zero can't be represented, so singular fields are held in an `Option` either way.
+            (None, _, _) if nonzero => Kind::Optional(default),
             (None, _, _) => Kind::Plain(default),
             (Some(Label::Optional), _, _) => Kind::Optional(default),
             (Some(Label::Required), _, _) => Kind::Required(default),
@@ -88,11 +114,20 @@ impl Field {
             (Some(Label::Repeated), _, false) => Kind::Repeated,
         };
 
-        Ok(Some(Field { ty, kind, tag }))
+        Ok(Some(Field {
+            ty,
+            kind,
+            tag,
+            lossy,
+            nonzero,
+        }))
     }
 
     pub fn new_oneof(attrs: &[Meta]) -> Result<Option<Field>, Error> {
         if let Some(mut field) = Field::new(attrs, None)? {
+            if field.nonzero {
+                bail!("invalid nonzero attribute on oneof field");
+            }
             match field.kind {
                 Kind::Plain(default) => {
                     field.kind = Kind::Required(default);
@@ -107,8 +142,18 @@ impl Field {
         }
     }
 
-    pub fn encode(&self, ident: TokenStream) -> TokenStream {
+    /// Returns the path of the module holding the encoding functions of the field.
+    fn module(&self) -> TokenStream {
         let module = self.ty.module();
+        if self.nonzero {
+            quote!(nonzero::#module)
+        } else {
+            quote!(#module)
+        }
+    }
+
+    pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
         let encode_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encode),
             Kind::Repeated => quote!(encode_repeated),
@@ -140,10 +185,22 @@ impl Field {
     /// Returns an expression which evaluates to the result of merging a decoded
     /// scalar value into the field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
-        let module = self.ty.module();
-        let merge_fn = match self.kind {
-            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(merge),
-            Kind::Repeated | Kind::Packed => quote!(merge_repeated),
+        let module = self.module();
+        // `Vec<u8>` values are copied straight out of the input, rather than through the `Bytes`
+        // which `merge` takes, so that they are copied once. `merge` doesn't copy `Bytes` values
+        // out of a `Bytes` input at all.
+        let one_copy = self.ty == Ty::Bytes(BytesTy::Vec);
+        let merge_fn = match (&self.kind, self.lossy) {
+            (Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..), false) if one_copy => {
+                quote!(merge_one_copy)
+            }
+            (Kind::Repeated | Kind::Packed, false) if one_copy => quote!(merge_repeated_one_copy),
+            (Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..), false) => quote!(merge),
+            (Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..), true) => {
+                quote!(merge_lossy)
+            }
+            (Kind::Repeated | Kind::Packed, false) => quote!(merge_repeated),
+            (Kind::Repeated | Kind::Packed, true) => quote!(merge_repeated_lossy),
         };
         let merge_fn = quote!(::prost::encoding::#module::#merge_fn);
 
@@ -151,6 +208,10 @@ impl Field {
             Kind::Plain(..) | Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
                 #merge_fn(wire_type, #ident, buf, ctx)
             },
+            // Non-zero values have no default to start from, so the `Option` is merged into.
+            Kind::Optional(..) if self.nonzero => quote! {
+                #merge_fn(wire_type, #ident, buf, ctx)
+            },
             Kind::Optional(..) => quote! {
                 #merge_fn(wire_type,
                           #ident.get_or_insert_with(::core::default::Default::default),
@@ -162,7 +223,7 @@ impl Field {
 
     /// Returns an expression which evaluates to the encoded length of the field.
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
-        let module = self.ty.module();
+        let module = self.module();
         let encoded_len_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encoded_len),
             Kind::Repeated => quote!(encoded_len_repeated),
@@ -191,6 +252,30 @@ impl Field {
         }
     }
 
+    /// Returns a statement which merges the value `other` of the field in memory. Like decoding,
+    /// this keeps the current value unless `other` would be encoded.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match self.kind {
+            Kind::Plain(ref default) => {
+                let default = default.typed();
+                quote! {
+                    if #other != #default {
+                        #ident = #other;
+                    }
+                }
+            }
+            Kind::Optional(..) => quote! {
+                if ::core::option::Option::is_some(&#other) {
+                    #ident = #other;
+                }
+            },
+            Kind::Required(..) => quote!(#ident = #other;),
+            Kind::Repeated | Kind::Packed => {
+                quote!(::core::iter::Extend::extend(&mut #ident, #other);)
+            }
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.kind {
             Kind::Plain(ref default) | Kind::Required(ref default) => {
@@ -222,8 +307,8 @@ impl Field {
                 impl<'a> ::core::fmt::Debug for #wrap_name<'a> {
                     fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                         match #ty::from_i32(*self.0) {
-                            None => ::core::fmt::Debug::fmt(&self.0, f),
-                            Some(en) => ::core::fmt::Debug::fmt(&en, f),
+                            ::core::option::Option::None => ::core::fmt::Debug::fmt(&self.0, f),
+                            ::core::option::Option::Some(en) => ::core::fmt::Debug::fmt(&en, f),
                         }
                     }
                 }
@@ -238,7 +323,11 @@ impl Field {
     /// Returns a fragment for formatting the field `ident` in `Debug`.
     pub fn debug(&self, wrapper_name: TokenStream) -> TokenStream {
         let wrapper = self.debug_inner(quote!(Inner));
-        let inner_ty = self.ty.rust_type();
+        let inner_ty = if self.nonzero {
+            self.ty.nonzero_type()
+        } else {
+            self.ty.rust_type()
+        };
         match self.kind {
             Kind::Plain(_) | Kind::Required(_) => self.debug_inner(wrapper_name),
             Kind::Optional(_) => quote! {
@@ -268,8 +357,8 @@ impl Field {
         }
     }
 
-    /// Returns methods to embed in the message.
-    pub fn methods(&self, ident: &TokenStream) -> Option<TokenStream> {
+    /// Returns methods to embed in the message. Getters begin with the `trace` statements.
+    pub fn methods(&self, ident: &TokenStream, trace: &TokenStream) -> Option<TokenStream> {
         let mut ident_str = ident.to_string();
         if ident_str.starts_with("r#") {
             ident_str = ident_str[2..].to_owned();
@@ -297,12 +386,13 @@ impl Field {
                     quote! {
                         #[doc=#get_doc]
                         pub fn #get(&self) -> #ty {
+                            #trace
                             #ty::from_i32(self.#ident).unwrap_or(#default)
                         }
 
                         #[doc=#set_doc]
                         pub fn #set(&mut self, value: #ty) {
-                            self.#ident = value as i32;
+                            self.#ident = ::core::convert::Into::<i32>::into(value);
                         }
                     }
                 }
@@ -315,12 +405,13 @@ impl Field {
                     quote! {
                         #[doc=#get_doc]
                         pub fn #get(&self) -> #ty {
+                            #trace
                             self.#ident.and_then(#ty::from_i32).unwrap_or(#default)
                         }
 
                         #[doc=#set_doc]
                         pub fn #set(&mut self, value: #ty) {
-                            self.#ident = ::core::option::Option::Some(value as i32);
+                            self.#ident = ::core::option::Option::Some(::core::convert::Into::<i32>::into(value));
                         }
                     }
                 }
@@ -337,15 +428,22 @@ impl Field {
                             ::core::iter::Cloned<::core::slice::Iter<i32>>,
                             fn(i32) -> ::core::option::Option<#ty>,
                         > {
-                            self.#ident.iter().cloned().filter_map(#ty::from_i32)
+                            #trace
+                            ::core::iter::Iterator::filter_map(
+                                ::core::iter::Iterator::cloned(self.#ident.iter()),
+                                #ty::from_i32,
+                            )
                         }
                         #[doc=#push_doc]
                         pub fn #push(&mut self, value: #ty) {
-                            self.#ident.push(value as i32);
+                            self.#ident.push(::core::convert::Into::<i32>::into(value));
                         }
                     }
                 }
             })
+        } else if self.nonzero {
+            // The field is already an `Option`, as there's no default to return when unset.
+            None
         } else if let Kind::Optional(ref default) = self.kind {
             let ty = self.ty.rust_ref_type();
 
@@ -363,6 +461,7 @@ impl Field {
             Some(quote! {
                 #[doc=#get_doc]
                 pub fn #get(&self) -> #ty {
+                    #trace
                     match self.#ident {
                         #match_some
                         ::core::option::Option::None => #default,
@@ -373,6 +472,45 @@ impl Field {
             None
         }
     }
+
+    /// Returns a `<field>_opt` method returning the value of an optional field, borrowing strings
+    /// and bytes.
+    pub fn option_accessor(&self, ident: &TokenStream, trace: &TokenStream) -> Option<TokenStream> {
+        if !matches!(self.kind, Kind::Optional(..))
+            || matches!(self.ty, Ty::Enumeration(..))
+            || self.nonzero
+        {
+            return None;
+        }
+
+        let mut ident_str = ident.to_string();
+        if ident_str.starts_with("r#") {
+            ident_str = ident_str[2..].to_owned();
+        }
+        let opt = match syn::parse_str::<Index>(&ident_str) {
+            Ok(index) => Ident::new(&format!("get_{}_opt", index.index), Span::call_site()),
+            Err(_) => Ident::new(&format!("{}_opt", ident_str), Span::call_site()),
+        };
+
+        let ty = self.ty.rust_ref_type();
+        let value = if self.ty.is_numeric() {
+            quote!(self.#ident)
+        } else {
+            quote!(::core::option::Option::as_deref(&self.#ident))
+        };
+
+        let doc = format!(
+            "Returns the value of `{0}`, or `None` if `{0}` is unset.",
+            ident_str,
+        );
+        Some(quote! {
+            #[doc=#doc]
+            pub fn #opt(&self) -> ::core::option::Option<#ty> {
+                #trace
+                #value
+            }
+        })
+    }
 }
 
 /// A scalar protobuf field type.
@@ -400,6 +538,8 @@ pub enum Ty {
 pub enum BytesTy {
     Vec,
     Bytes,
+    Raw,
+    Shared,
 }
 
 impl BytesTy {
@@ -407,6 +547,8 @@ impl BytesTy {
         match s {
             "vec" => Ok(BytesTy::Vec),
             "bytes" => Ok(BytesTy::Bytes),
+            "raw" => Ok(BytesTy::Raw),
+            "shared" => Ok(BytesTy::Shared),
             _ => bail!("Invalid bytes type: {}", s),
         }
     }
@@ -415,6 +557,8 @@ impl BytesTy {
         match self {
             BytesTy::Vec => quote! { ::prost::alloc::vec::Vec<u8> },
             BytesTy::Bytes => quote! { ::prost::bytes::Bytes },
+            BytesTy::Raw => quote! { ::prost::RawMessage },
+            BytesTy::Shared => quote! { ::prost::SharedBytes },
         }
     }
 }
@@ -558,6 +702,17 @@ impl Ty {
         }
     }
 
+    /// Returns the `NonZero*` type representing the integer type.
+    pub fn nonzero_type(&self) -> TokenStream {
+        match *self {
+            Ty::Int32 | Ty::Sint32 | Ty::Sfixed32 => quote!(::core::num::NonZeroI32),
+            Ty::Int64 | Ty::Sint64 | Ty::Sfixed64 => quote!(::core::num::NonZeroI64),
+            Ty::Uint32 | Ty::Fixed32 => quote!(::core::num::NonZeroU32),
+            Ty::Uint64 | Ty::Fixed64 => quote!(::core::num::NonZeroU64),
+            _ => panic!("{} is not an integer type", self),
+        }
+    }
+
     pub fn module(&self) -> Ident {
         match *self {
             Ty::Enumeration(..) => Ident::new("int32", Span::call_site()),
@@ -565,6 +720,23 @@ impl Ty {
         }
     }
 
+    /// Returns true if the scalar type is an integer type, excluding enumerations.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Ty::Int32
+                | Ty::Int64
+                | Ty::Uint32
+                | Ty::Uint64
+                | Ty::Sint32
+                | Ty::Sint64
+                | Ty::Fixed32
+                | Ty::Fixed64
+                | Ty::Sfixed32
+                | Ty::Sfixed64
+        )
+    }
+
     /// Returns false if the scalar type is length delimited (i.e., `string` or `bytes`).
     pub fn is_numeric(&self) -> bool {
         !matches!(self, Ty::String | Ty::Bytes(..))
@@ -661,7 +833,9 @@ impl DefaultValue {
             Lit::Bool(ref lit) if *ty == Ty::Bool => DefaultValue::Bool(lit.value),
             Lit::Str(ref lit) if *ty == Ty::String => DefaultValue::String(lit.value()),
             Lit::ByteStr(ref lit)
-                if *ty == Ty::Bytes(BytesTy::Bytes) || *ty == Ty::Bytes(BytesTy::Vec) =>
+                if *ty == Ty::Bytes(BytesTy::Bytes)
+                    || *ty == Ty::Bytes(BytesTy::Vec)
+                    || *ty == Ty::Bytes(BytesTy::Shared) =>
             {
                 DefaultValue::Bytes(lit.value())
             }
@@ -770,7 +944,9 @@ impl DefaultValue {
             Ty::Bool => DefaultValue::Bool(false),
             Ty::String => DefaultValue::String(String::new()),
             Ty::Bytes(..) => DefaultValue::Bytes(Vec::new()),
-            Ty::Enumeration(ref path) => DefaultValue::Enumeration(quote!(#path::default())),
+            Ty::Enumeration(ref path) => {
+                DefaultValue::Enumeration(quote!(<#path as ::core::default::Default>::default()))
+            }
         }
     }
 
@@ -779,13 +955,13 @@ impl DefaultValue {
             DefaultValue::String(ref value) if value.is_empty() => {
                 quote!(::prost::alloc::string::String::new())
             }
-            DefaultValue::String(ref value) => quote!(#value.into()),
+            DefaultValue::String(ref value) => quote!(::core::convert::Into::into(#value)),
             DefaultValue::Bytes(ref value) if value.is_empty() => {
                 quote!(::core::default::Default::default())
             }
             DefaultValue::Bytes(ref value) => {
                 let lit = LitByteStr::new(value, Span::call_site());
-                quote!(#lit.as_ref().into())
+                quote!(::core::convert::Into::into(&#lit[..]))
             }
 
             ref other => other.typed(),
@@ -794,7 +970,7 @@ impl DefaultValue {
 
     pub fn typed(&self) -> TokenStream {
         if let DefaultValue::Enumeration(_) = *self {
-            quote!(#self as i32)
+            quote!(::core::convert::Into::<i32>::into(#self))
         } else {
             quote!(#self)
         }
@@ -12,7 +12,8 @@ use proc_macro2::Span;
 use quote::quote;
 use syn::{
     punctuated::Punctuated, Data, DataEnum, DataStruct, DeriveInput, Expr, Fields, FieldsNamed,
-    FieldsUnnamed, Ident, Index, Variant,
+    FieldsUnnamed, Generics, Ident, Index, Lit, Meta, MetaNameValue, Path, Type, Variant,
+    Visibility,
 };
 
 mod field;
@@ -23,6 +24,45 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
 
     let ident = input.ident;
 
+    let mut skip_default = false;
+    let mut columnar = false;
+    let mut option_accessors = false;
+    let mut declaration_order = false;
+    let mut trace_field_access = false;
+    let mut extend_methods = false;
+    let mut field_accessors = false;
+    let mut reserve_methods = false;
+    for attr in field::prost_attrs(input.attrs) {
+        if field::word_attr("skip_default", &attr) {
+            field::set_bool(&mut skip_default, "duplicate skip_default attribute")?;
+        } else if field::word_attr("columnar", &attr) {
+            field::set_bool(&mut columnar, "duplicate columnar attribute")?;
+        } else if field::word_attr("option_accessors", &attr) {
+            field::set_bool(
+                &mut option_accessors,
+                "duplicate option_accessors attribute",
+            )?;
+        } else if field::word_attr("declaration_order", &attr) {
+            field::set_bool(
+                &mut declaration_order,
+                "duplicate declaration_order attribute",
+            )?;
+        } else if field::word_attr("trace_field_access", &attr) {
+            field::set_bool(
+                &mut trace_field_access,
+                "duplicate trace_field_access attribute",
+            )?;
+        } else if field::word_attr("extend_methods", &attr) {
+            field::set_bool(&mut extend_methods, "duplicate extend_methods attribute")?;
+        } else if field::word_attr("field_accessors", &attr) {
+            field::set_bool(&mut field_accessors, "duplicate field_accessors attribute")?;
+        } else if field::word_attr("reserve_methods", &attr) {
+            field::set_bool(&mut reserve_methods, "duplicate reserve_methods attribute")?;
+        } else {
+            bail!("unknown attribute for message {}: {:?}", ident, attr);
+        }
+    }
+
     let variant_data = match input.data {
         Data::Struct(variant_data) => variant_data,
         Data::Enum(..) => bail!("Message can not be derived for an enum"),
@@ -51,10 +91,12 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     };
 
     let mut next_tag: u32 = 1;
-    let mut fields = fields
+    let mut unknown_fields = None;
+    let fields = fields
         .into_iter()
         .enumerate()
         .flat_map(|(i, field)| {
+            let ty = field.ty;
             let field_ident = field.ident.map(|x| quote!(#x)).unwrap_or_else(|| {
                 let index = Index {
                     index: i as u32,
@@ -62,10 +104,19 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
                 };
                 quote!(#index)
             });
+            // The field holding unknown fields has no tag, and is handled apart from the others.
+            if field::is_unknown_fields(&field.attrs) {
+                return unknown_fields.replace(field_ident).map(|_| {
+                    Err(Error::msg(format!(
+                        "message {} has more than one unknown_fields field",
+                        ident
+                    )))
+                });
+            }
             match Field::new(field.attrs, Some(next_tag)) {
                 Ok(Some(field)) => {
                     next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
-                    Some(Ok((field_ident, field)))
+                    Some(Ok((field_ident, field, ty)))
                 }
                 Ok(None) => None,
                 Err(err) => Some(Err(
@@ -75,6 +126,40 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    if unknown_fields.is_some() && !is_struct {
+        bail!(
+            "message {} must have named fields to preserve unknown fields",
+            ident
+        );
+    }
+
+    let columns = if columnar {
+        if !is_struct || fields.is_empty() {
+            bail!("columnar message {} must have named fields", ident);
+        }
+        if unknown_fields.is_some() {
+            bail!("columnar message {} can't preserve unknown fields", ident);
+        }
+        columns(&ident, &input.vis, generics, &fields)
+    } else {
+        quote!()
+    };
+
+    // Extend methods name the Rust types of the fields, which are dropped below.
+    let extend_methods: Vec<_> = if extend_methods {
+        fields
+            .iter()
+            .flat_map(|(field_ident, field, ty)| field.extend_method(field_ident, ty))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut fields = fields
+        .into_iter()
+        .map(|(field_ident, field, _)| (field_ident, field))
+        .collect::<Vec<_>>();
+
     // We want Debug to be in declaration order
     let unsorted_fields = fields.clone();
 
@@ -82,7 +167,11 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     // TODO: This encodes oneof fields in the position of their lowest tag,
     // regardless of the currently occupied variant, is that consequential?
     // See: https://developers.google.com/protocol-buffers/docs/encoding#order
-    fields.sort_by_key(|&(_, ref field)| field.tags().into_iter().min().unwrap());
+    // Messages may opt into encoding fields in declaration order instead, for readers which
+    // depend on it.
+    if !declaration_order {
+        fields.sort_by_key(|&(_, ref field)| field.tags().into_iter().min().unwrap());
+    }
     let fields = fields;
 
     let mut tags = fields
@@ -95,14 +184,89 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     if tags.len() != num_tags {
         bail!("message {} has fields with duplicate tags", ident);
     }
+    if let Some(&tag) = tags
+        .iter()
+        .find(|&&tag| !(1..=(1 << 29) - 1).contains(&tag))
+    {
+        bail!("message {} has field with out of range tag: {}", ident, tag);
+    }
 
     let encoded_len = fields
         .iter()
         .map(|&(ref field_ident, ref field)| field.encoded_len(quote!(self.#field_ident)));
 
+    let encoded_len_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(+ self.#field_ident.encoded_len()));
+
+    // Unknown fields are encoded after the declared fields, as the last part.
     let encode = fields
         .iter()
-        .map(|&(ref field_ident, ref field)| field.encode(quote!(self.#field_ident)));
+        .map(|&(ref field_ident, ref field)| field.encode(quote!(self.#field_ident)))
+        .chain(
+            unknown_fields
+                .as_ref()
+                .map(|field_ident| quote!(self.#field_ident.encode_raw(buf);)),
+        )
+        .collect::<Vec<_>>();
+    // Messages without fields are encoded as a single empty part by the default implementation.
+    let encode_raw_part = if encode.is_empty() {
+        quote!()
+    } else {
+        let parts = 0..encode.len();
+        quote! {
+            #[allow(unused_variables)]
+            fn encode_raw_part<B>(&self, part: usize, buf: &mut B) -> bool
+            where B: ::prost::bytes::BufMut {
+                match part {
+                    #(#parts => { #encode })*
+                    _ => return false,
+                }
+                true
+            }
+        }
+    };
+
+    // Messages without submessages have no lengths to record, as the default implementations
+    // assume. Otherwise, fields are sized and encoded in the same order as above.
+    let encode_cached = fields
+        .iter()
+        .map(|(field_ident, field)| field.encode_cached(quote!(self.#field_ident)))
+        .collect::<Vec<_>>();
+    let cached = if encode_cached.iter().all(Option::is_none) {
+        quote!()
+    } else {
+        let encoded_len_cached = fields.iter().map(|(field_ident, field)| {
+            let ident = quote!(self.#field_ident);
+            field
+                .encoded_len_cached(ident.clone())
+                .unwrap_or_else(|| field.encoded_len(ident))
+        });
+        let encode_cached =
+            fields
+                .iter()
+                .zip(encode_cached)
+                .map(|((field_ident, field), encode_cached)| {
+                    encode_cached.unwrap_or_else(|| field.encode(quote!(self.#field_ident)))
+                });
+        let encode_unknown = unknown_fields
+            .as_ref()
+            .map(|field_ident| quote!(self.#field_ident.encode_raw(buf);));
+        quote! {
+            fn encoded_len_cached(&self, sizes: &mut ::prost::EncodedSizes) -> usize {
+                0 #(+ #encoded_len_cached)* #encoded_len_unknown
+            }
+
+            fn encode_raw_cached<B>(
+                &self,
+                buf: &mut B,
+                sizes: &mut ::core::slice::Iter<usize>,
+            ) where B: ::prost::bytes::BufMut {
+                #(#encode_cached)*
+                #encode_unknown
+            }
+        }
+    };
 
     let merge = fields.iter().map(|&(ref field_ident, ref field)| {
         let merge = field.merge(quote!(value));
@@ -120,6 +284,79 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         }
     });
 
+    // Messages without submessages never exceed a depth, as the default implementation reports.
+    let exceeds_depth = fields
+        .iter()
+        .flat_map(|(field_ident, field)| field.exceeds_depth(quote!(self.#field_ident)))
+        .collect::<Vec<_>>();
+    let exceeds_depth = if exceeds_depth.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn exceeds_depth(&self, limit: u32) -> bool {
+                false #(|| #exceeds_depth)*
+            }
+        }
+    };
+
+    // Messages without unknown fields or submessages have none to clear, as the default
+    // implementation assumes.
+    let clear_unknown_fields =
+        unknown_fields
+            .as_ref()
+            .map(|field_ident| quote!(self.#field_ident.clear();))
+            .into_iter()
+            .chain(fields.iter().flat_map(|(field_ident, field)| {
+                field.clear_unknown_fields(quote!(self.#field_ident))
+            }))
+            .collect::<Vec<_>>();
+    let clear_unknown_fields = if clear_unknown_fields.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn clear_unknown_fields(&mut self) {
+                #(#clear_unknown_fields)*
+            }
+        }
+    };
+
+    // Messages without required fields have nothing to check, as the default implementations
+    // report. The first missing field in declaration order is reported.
+    let required = fields
+        .iter()
+        .filter(|(_, field)| field.is_required())
+        .collect::<Vec<_>>();
+    let check_required = if required.is_empty() {
+        quote!()
+    } else {
+        let tags = required.iter().map(|(_, field)| field.tags()[0]);
+        let checks = required
+            .iter()
+            .enumerate()
+            .map(|(index, (field_ident, _))| {
+                quote! {
+                    if !present[#index] {
+                        let mut error = ::prost::DecodeError::missing_required_field();
+                        error.push(stringify!(#ident), stringify!(#field_ident));
+                        return ::core::result::Result::Err(error);
+                    }
+                }
+            });
+        quote! {
+            fn required_tags(&self) -> &'static [u32] {
+                &[#(#tags),*]
+            }
+
+            fn check_required(
+                &self,
+                present: &[bool],
+            ) -> ::core::result::Result<(), ::prost::DecodeError> {
+                #(#checks)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    };
+
     let struct_name = if fields.is_empty() {
         quote!()
     } else {
@@ -128,32 +365,102 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         )
     };
 
+    let merge_unknown = match unknown_fields {
+        Some(ref field_ident) => quote!(self.#field_ident.merge_field(tag, wire_type, buf, ctx)),
+        None => quote!(::prost::encoding::skip_field(wire_type, tag, buf, ctx)),
+    };
+
     let clear = fields
         .iter()
-        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)));
+        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)))
+        .chain(
+            unknown_fields
+                .as_ref()
+                .map(|field_ident| quote!(self.#field_ident.clear())),
+        );
 
-    let default = if is_struct {
-        let default = fields.iter().map(|(field_ident, field)| {
-            let value = field.default();
-            quote!(#field_ident: #value,)
-        });
-        quote! {#ident {
-            #(#default)*
-        }}
+    let merge_from = fields
+        .iter()
+        .map(|(field_ident, field)| {
+            field.merge_from(quote!(self.#field_ident), quote!(other.#field_ident))
+        })
+        .chain(
+            unknown_fields
+                .as_ref()
+                .map(|field_ident| quote!(self.#field_ident.merge_from(other.#field_ident);)),
+        );
+
+    let default = if skip_default {
+        quote!()
     } else {
-        let default = fields.iter().map(|(_, field)| {
-            let value = field.default();
-            quote!(#value,)
-        });
-        quote! {#ident (
-            #(#default)*
-        )}
+        let default = if is_struct {
+            let default_unknown = unknown_fields
+                .as_ref()
+                .map(|field_ident| quote!(#field_ident: ::core::default::Default::default(),));
+            let default = fields
+                .iter()
+                .map(|(field_ident, field)| {
+                    let value = field.default();
+                    quote!(#field_ident: #value,)
+                })
+                .chain(default_unknown);
+            quote! {#ident {
+                #(#default)*
+            }}
+        } else {
+            let default = fields.iter().map(|(_, field)| {
+                let value = field.default();
+                quote!(#value,)
+            });
+            quote! {#ident (
+                #(#default)*
+            )}
+        };
+        quote! {
+            impl #impl_generics ::core::default::Default for #ident #ty_generics #where_clause {
+                fn default() -> Self {
+                    #default
+                }
+            }
+        }
     };
 
-    let methods = fields
+    // Getters of messages tracing field access log each access in debug builds, leaving release
+    // builds untouched.
+    let trace = |field_ident: &proc_macro2::TokenStream| {
+        if trace_field_access {
+            let field_name = field_ident.to_string();
+            let message = format!("accessed {}.{}", ident, field_name.trim_start_matches("r#"));
+            quote! {
+                #[cfg(debug_assertions)]
+                ::prost::log::trace!("{}", #message);
+            }
+        } else {
+            quote!()
+        }
+    };
+    let mut methods = fields
         .iter()
-        .flat_map(|&(ref field_ident, ref field)| field.methods(field_ident))
+        .flat_map(|&(ref field_ident, ref field)| field.methods(field_ident, &trace(field_ident)))
         .collect::<Vec<_>>();
+    if reserve_methods {
+        methods.extend(
+            fields
+                .iter()
+                .flat_map(|(field_ident, field)| field.reserve_method(field_ident)),
+        );
+    }
+    methods.extend(extend_methods);
+    if option_accessors {
+        methods.extend(fields.iter().flat_map(|(field_ident, field)| {
+            field.option_accessor(field_ident, &trace(field_ident))
+        }));
+    }
+    if field_accessors {
+        methods.extend(fields.iter().flat_map(|(field_ident, field)| {
+            field.field_accessors(field_ident, &trace(field_ident))
+        }));
+    }
     let methods = if methods.is_empty() {
         quote!()
     } else {
@@ -179,6 +486,11 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
              };
         }
     });
+    let debug_unknown = unknown_fields.as_ref().map(|field_ident| {
+        quote! {
+            let builder = builder.field(stringify!(#field_ident), &self.#field_ident);
+        }
+    });
     let debug_builder = if is_struct {
         quote!(f.debug_struct(stringify!(#ident)))
     } else {
@@ -192,6 +504,8 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
                 #(#encode)*
             }
 
+            #encode_raw_part
+
             #[allow(unused_variables)]
             fn merge_field<B>(
                 &mut self,
@@ -202,42 +516,163 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             ) -> ::core::result::Result<(), ::prost::DecodeError>
             where B: ::prost::bytes::Buf {
                 #struct_name
+                // A single match on integer literals, which the compiler lowers to a jump table
+                // or a binary search, so dispatch doesn't grow linearly with the field count.
                 match tag {
                     #(#merge)*
-                    _ => ::prost::encoding::skip_field(wire_type, tag, buf, ctx),
+                    _ => #merge_unknown,
                 }
             }
 
             #[inline]
             fn encoded_len(&self) -> usize {
-                0 #(+ #encoded_len)*
+                0 #(+ #encoded_len)* #encoded_len_unknown
             }
 
             fn clear(&mut self) {
                 #(#clear;)*
             }
-        }
 
-        impl #impl_generics ::core::default::Default for #ident #ty_generics #where_clause {
-            fn default() -> Self {
-                #default
+            #[allow(unused_variables)]
+            fn merge_from(&mut self, other: Self) {
+                #(#merge_from)*
             }
+
+            #cached
+
+            #check_required
+
+            #exceeds_depth
+
+            #clear_unknown_fields
         }
 
+        #default
+
         impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
             fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 let mut builder = #debug_builder;
                 #(#debugs;)*
+                #debug_unknown
                 builder.finish()
             }
         }
 
         #methods
+
+        #columns
     };
 
     Ok(expanded.into())
 }
 
+/// Generates a `<Message>Columns` type holding a batch of messages in a column-oriented layout,
+/// with one `Vec` per field.
+fn columns(
+    ident: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    fields: &[(proc_macro2::TokenStream, Field, Type)],
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let columns_ident = Ident::new(&format!("{}Columns", ident), ident.span());
+    let doc = format!(
+        "Column-oriented storage for a batch of [`{}`] messages.",
+        ident
+    );
+
+    let field_idents = fields
+        .iter()
+        .map(|(field_ident, _, _)| field_ident)
+        .collect::<Vec<_>>();
+    let first_ident = field_idents[0];
+
+    let columns = fields
+        .iter()
+        .map(|(field_ident, _, ty)| quote!(#vis #field_ident: ::prost::alloc::vec::Vec<#ty>,));
+
+    let defaults = fields.iter().map(|(field_ident, field, _)| {
+        let default = field.default();
+        quote!(self.#field_ident.push(#default);)
+    });
+
+    let merge = fields.iter().map(|(field_ident, field, _)| {
+        let merge = field.merge(quote!(value));
+        let tags = field.tags().into_iter().map(|tag| quote!(#tag));
+        let tags = Itertools::intersperse(tags, quote!(|));
+
+        quote! {
+            #(#tags)* => {
+                let mut value = self.#field_ident.last_mut().expect("missing column value");
+                #merge.map_err(|mut error| {
+                    error.push(STRUCT_NAME, stringify!(#field_ident));
+                    error
+                })?;
+            },
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Debug, Default, PartialEq)]
+        #vis struct #columns_ident #generics #where_clause {
+            #(#columns)*
+        }
+
+        #[allow(dead_code)]
+        impl #impl_generics #columns_ident #ty_generics #where_clause {
+            /// Returns the number of messages in the batch.
+            pub fn len(&self) -> usize {
+                self.#first_ident.len()
+            }
+
+            /// Returns `true` if the batch contains no messages.
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Appends a message to the batch.
+            pub fn push(&mut self, msg: #ident #ty_generics) {
+                #(self.#field_idents.push(msg.#field_idents);)*
+            }
+
+            /// Shortens the batch, keeping the first `len` messages.
+            pub fn truncate(&mut self, len: usize) {
+                #(self.#field_idents.truncate(len);)*
+            }
+
+            /// Decodes a message from the buffer directly into the columns, appending it to the
+            /// batch. If decoding fails, the batch is left unchanged.
+            pub fn push_decoded<B>(&mut self, mut buf: B) -> ::core::result::Result<(), ::prost::DecodeError>
+            where B: ::prost::bytes::Buf {
+                let len = self.len();
+                #(#defaults)*
+                let result = self.merge_last(&mut buf);
+                if result.is_err() {
+                    self.truncate(len);
+                }
+                result
+            }
+
+            #[allow(unused_variables)]
+            fn merge_last<B>(&mut self, buf: &mut B) -> ::core::result::Result<(), ::prost::DecodeError>
+            where B: ::prost::bytes::Buf {
+                const STRUCT_NAME: &'static str = stringify!(#ident);
+                let root = ::prost::encoding::DecodeContext::default();
+                while ::prost::bytes::Buf::has_remaining(&*buf) {
+                    let (tag, wire_type) = ::prost::encoding::decode_key(buf)?;
+                    let ctx = root.clone();
+                    match tag {
+                        #(#merge)*
+                        _ => ::prost::encoding::skip_field(wire_type, tag, buf, ctx)?,
+                    }
+                }
+                ::core::result::Result::Ok(())
+            }
+        }
+    }
+}
+
 #[proc_macro_derive(Message, attributes(prost))]
 pub fn message(input: TokenStream) -> TokenStream {
     try_message(input).unwrap()
@@ -247,6 +682,26 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
     let input: DeriveInput = syn::parse(input)?;
     let ident = input.ident;
 
+    let mut try_from_i32 = false;
+    let mut prost_path: Option<Path> = None;
+    for attr in field::prost_attrs(input.attrs) {
+        if field::word_attr("try_from_i32", &attr) {
+            field::set_bool(&mut try_from_i32, "duplicate try_from_i32 attribute")?;
+        } else if attr.path().is_ident("prost_path") {
+            let path = match attr {
+                Meta::NameValue(MetaNameValue {
+                    lit: Lit::Str(ref lit),
+                    ..
+                }) => lit.parse::<Path>()?,
+                _ => bail!("invalid prost_path attribute: {:?}", attr),
+            };
+            field::set_option(&mut prost_path, path, "duplicate prost_path attribute")?;
+        } else {
+            bail!("unknown attribute for enumeration {}: {:?}", ident, attr);
+        }
+    }
+    let prost_path = prost_path.unwrap_or_else(|| syn::parse_quote!(::prost));
+
     let generics = &input.generics;
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
@@ -290,6 +745,25 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
     let from = variants.iter().map(
         |&(ref variant, ref value)| quote!(#value => ::core::option::Option::Some(#ident::#variant)),
     );
+    let try_from = if try_from_i32 {
+        let try_from = variants
+            .iter()
+            .map(|(variant, value)| quote!(#value => ::core::result::Result::Ok(#ident::#variant)));
+        quote! {
+            impl #impl_generics ::core::convert::TryFrom::<i32> for #ident #ty_generics #where_clause {
+                type Error = #prost_path::UnknownEnumValue;
+
+                fn try_from(value: i32) -> ::core::result::Result<#ident, #prost_path::UnknownEnumValue> {
+                    match value {
+                        #(#try_from,)*
+                        _ => ::core::result::Result::Err(#prost_path::UnknownEnumValue(value)),
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
 
     let is_valid_doc = format!("Returns `true` if `value` is a variant of `{}`.", ident);
     let from_i32_doc = format!(
@@ -327,6 +801,8 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
                 value as i32
             }
         }
+
+        #try_from
     };
 
     Ok(expanded.into())
@@ -424,6 +900,48 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#ident::#variant_ident(ref value) => #encoded_len)
     });
 
+    let encode_cached = fields.iter().map(|(variant_ident, field)| {
+        let encode = field
+            .encode_cached(quote!(*value))
+            .unwrap_or_else(|| field.encode(quote!(*value)));
+        quote!(#ident::#variant_ident(ref value) => { #encode })
+    });
+
+    let encoded_len_cached = fields.iter().map(|(variant_ident, field)| {
+        let encoded_len = field
+            .encoded_len_cached(quote!(*value))
+            .unwrap_or_else(|| field.encoded_len(quote!(*value)));
+        quote!(#ident::#variant_ident(ref value) => #encoded_len)
+    });
+
+    let exceeds_depth = fields
+        .iter()
+        .flat_map(|(variant_ident, field)| {
+            let exceeds_depth = field.exceeds_depth(quote!(*value))?;
+            Some(quote!(#ident::#variant_ident(ref value) => #exceeds_depth))
+        })
+        .collect::<Vec<_>>();
+
+    let clear_unknown_fields = fields
+        .iter()
+        .flat_map(|(variant_ident, field)| {
+            let clear = field.clear_unknown_fields(quote!(*value))?;
+            Some(quote!(#ident::#variant_ident(ref mut value) => { #clear }))
+        })
+        .collect::<Vec<_>>();
+
+    // Message variants set on both sides are merged, like submessage fields.
+    let merge_from = fields
+        .iter()
+        .filter(|(_, field)| matches!(field, Field::Message(..) | Field::Group(..)))
+        .map(|(variant_ident, _)| {
+            quote! {
+                (#ident::#variant_ident(value), #ident::#variant_ident(other)) => {
+                    ::prost::Message::merge_from(value, other)
+                }
+            }
+        });
+
     let debug = fields.iter().map(|&(ref variant_ident, ref field)| {
         let wrapper = field.debug(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => {
@@ -465,6 +983,54 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     #(#encoded_len,)*
                 }
             }
+
+            /// Encodes the message to a buffer, taking the lengths of submessages from `sizes`.
+            #[doc(hidden)]
+            #[allow(unused_variables)]
+            pub fn encode_cached<B>(&self, buf: &mut B, sizes: &mut ::core::slice::Iter<usize>)
+            where B: ::prost::bytes::BufMut {
+                match *self {
+                    #(#encode_cached,)*
+                }
+            }
+
+            /// Returns the encoded length of the message without a length delimiter, recording
+            /// the lengths of submessages in `sizes`.
+            #[doc(hidden)]
+            #[allow(unused_variables)]
+            pub fn encoded_len_cached(&self, sizes: &mut ::prost::EncodedSizes) -> usize {
+                match *self {
+                    #(#encoded_len_cached,)*
+                }
+            }
+
+            /// Merges `other` into `self` in memory: a message variant set on both is merged, and
+            /// any other variant of `other` replaces `self`.
+            #[allow(unreachable_patterns)]
+            pub fn merge_from(&mut self, other: Self) {
+                match (self, other) {
+                    #(#merge_from,)*
+                    (this, other) => *this = other,
+                }
+            }
+
+            /// Returns whether the submessages of the oneof nest more than `limit` levels deep.
+            #[allow(unused_variables, unreachable_patterns)]
+            pub fn exceeds_depth(&self, limit: u32) -> bool {
+                match *self {
+                    #(#exceeds_depth,)*
+                    _ => false,
+                }
+            }
+
+            /// Clears the unknown fields preserved by the submessages of the oneof.
+            #[allow(unused_variables, unreachable_patterns)]
+            pub fn clear_unknown_fields(&mut self) {
+                match *self {
+                    #(#clear_unknown_fields,)*
+                    _ => (),
+                }
+            }
         }
 
         impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
@@ -0,0 +1,176 @@
+use prost::alloc::format;
+use prost::alloc::vec::Vec;
+use prost::{DecodeError, EncodeError, Name};
+
+use crate::Any;
+
+impl Any {
+    /// Serializes the given message into an `Any`, using [`Name::type_url`] as the type URL.
+    pub fn from_msg<M>(msg: &M) -> Result<Self, EncodeError>
+    where
+        M: Name,
+    {
+        let mut value = Vec::with_capacity(msg.encoded_len());
+        msg.encode(&mut value)?;
+        Ok(Any {
+            type_url: M::type_url(),
+            value,
+        })
+    }
+
+    /// Returns `true` if the type URL of this `Any` names the message type `M`.
+    ///
+    /// Only the fully-qualified type name, i.e. the last segment of the URL's path, is compared,
+    /// so `type.googleapis.com/foo.Bar` and `/foo.Bar` are considered to name the same type.
+    pub fn is<M>(&self) -> bool
+    where
+        M: Name,
+    {
+        type_name(&self.type_url) == Some(&*M::full_name())
+    }
+
+    /// Decodes the contents of this `Any` as the message type `M`.
+    ///
+    /// An error is returned if the type URL does not name `M`, or if the value can not be decoded.
+    pub fn to_msg<M>(&self) -> Result<M, DecodeError>
+    where
+        M: Default + Name + Sized,
+    {
+        if !self.is::<M>() {
+            let mut error = DecodeError::new(format!(
+                "expected type URL: \"{}\" (got: \"{}\")",
+                M::type_url(),
+                self.type_url
+            ));
+            error.push("unexpected type URL", "type_url");
+            return Err(error);
+        }
+        M::decode(self.value.as_slice())
+    }
+}
+
+/// Returns the fully-qualified type name from a type URL, or `None` if the URL is malformed.
+pub(crate) fn type_name(type_url: &str) -> Option<&str> {
+    match type_url.rsplit_once('/') {
+        Some((_, name)) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+/// Dispatches on the type of a `google.protobuf.Any`.
+///
+/// Each arm names a binding and a message type implementing [`prost::Name`]. The arms are tried
+/// in order, and the first one whose type matches the `Any`'s type URL has the value decoded into
+/// its binding. The final `_` arm is evaluated if no type matches.
+///
+/// The macro evaluates to a `Result<T, DecodeError>`, where an error indicates that the value of
+/// a matching type could not be decoded.
+///
+/// ```rust,ignore
+/// let description = prost_types::match_any!(any,
+///     duration: Duration => format!("duration of {}s", duration.seconds),
+///     timestamp: Timestamp => format!("timestamp at {}", timestamp),
+///     _ => format!("unknown type {}", any.type_url),
+/// )?;
+/// ```
+#[macro_export]
+macro_rules! match_any {
+    ($any:expr, $($binding:ident : $ty:ty => $arm:expr,)* _ => $fallback:expr $(,)?) => {{
+        let any: &$crate::Any = &$any;
+        $(
+            if any.is::<$ty>() {
+                match any.to_msg::<$ty>() {
+                    ::core::result::Result::Ok($binding) => ::core::result::Result::Ok($arm),
+                    ::core::result::Result::Err(error) => ::core::result::Result::Err(error),
+                }
+            } else
+        )*
+        {
+            ::core::result::Result::<_, ::prost::DecodeError>::Ok($fallback)
+        }
+    }};
+}
+
+macro_rules! well_known_name {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl Name for crate::$ty {
+                const NAME: &'static str = stringify!($ty);
+                const PACKAGE: &'static str = "google.protobuf";
+
+                fn type_url() -> prost::alloc::string::String {
+                    format!("type.googleapis.com/{}", Self::full_name())
+                }
+            }
+        )*
+    };
+}
+
+well_known_name!(Any, Duration, FieldMask, ListValue, Struct, Timestamp, Value);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Duration, Timestamp};
+
+    #[test]
+    fn check_any_roundtrip() {
+        let duration = Duration {
+            seconds: 3,
+            nanos: 7,
+        };
+        let any = Any::from_msg(&duration).unwrap();
+        assert_eq!(any.type_url, "type.googleapis.com/google.protobuf.Duration");
+        assert!(any.is::<Duration>());
+        assert!(!any.is::<Timestamp>());
+        assert_eq!(any.to_msg::<Duration>().unwrap(), duration);
+        assert!(any.to_msg::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn check_any_type_url_domain() {
+        let any = Any {
+            type_url: "/google.protobuf.Duration".into(),
+            value: Vec::new(),
+        };
+        assert!(any.is::<Duration>());
+
+        let any = Any {
+            type_url: "google.protobuf.Duration".into(),
+            value: Vec::new(),
+        };
+        assert!(!any.is::<Duration>());
+    }
+
+    #[test]
+    fn check_match_any() {
+        let timestamp = Timestamp {
+            seconds: 10,
+            nanos: 0,
+        };
+        let any = Any::from_msg(&timestamp).unwrap();
+
+        let matched = crate::match_any!(any,
+            duration: Duration => duration.seconds,
+            timestamp: Timestamp => timestamp.seconds * 2,
+            _ => -1,
+        );
+        assert_eq!(matched.unwrap(), 20);
+
+        let matched = crate::match_any!(any,
+            duration: Duration => duration.seconds,
+            _ => -1,
+        );
+        assert_eq!(matched.unwrap(), -1);
+
+        let corrupt = Any {
+            type_url: any.type_url.clone(),
+            value: vec![0xFF],
+        };
+        let matched = crate::match_any!(corrupt,
+            timestamp: Timestamp => timestamp.seconds,
+            _ => -1,
+        );
+        assert!(matched.is_err());
+    }
+}
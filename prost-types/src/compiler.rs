@@ -134,17 +134,7 @@ pub mod code_generator_response {
         pub generated_code_info: ::core::option::Option<super::super::GeneratedCodeInfo>,
     }
     /// Sync with code_generator.h.
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Feature {
         None = 0,
@@ -163,11 +153,21 @@ pub mod code_generator_response {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "FEATURE_NONE" => Some(Self::None),
-                "FEATURE_PROTO3_OPTIONAL" => Some(Self::Proto3Optional),
-                _ => None,
-            }
+            let variant = match value {
+                "FEATURE_NONE" => Self::None,
+                "FEATURE_PROTO3_OPTIONAL" => Self::Proto3Optional,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for Feature {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
 }
@@ -0,0 +1,1057 @@
+//! Conversion of `google.protobuf.Any` values to JSON, for debugging and logging, and between
+//! messages and JSON, parsed or not.
+//!
+//! Requires the `json` feature.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use prost::{DecodeError, Message, Name};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::any::type_name;
+use crate::Any;
+
+type ToJson = fn(&[u8]) -> Result<Value, Error>;
+
+/// A registry of the message types which [`any_to_json`] can unpack.
+///
+/// Clones share the registered types until either registers another.
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    types: Arc<BTreeMap<String, ToJson>>,
+}
+
+impl TypeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> TypeRegistry {
+        TypeRegistry::default()
+    }
+
+    /// Registers the message type `M`, under its [`Name::full_name`].
+    pub fn register<M>(&mut self) -> &mut Self
+    where
+        M: Name + Default + Serialize,
+    {
+        Arc::make_mut(&mut self.types).insert(M::full_name(), |value| {
+            let message = M::decode(value)?;
+            Ok(serde_json::to_value(&message)?)
+        });
+        self
+    }
+
+    /// Returns `true` if a message type with the fully-qualified name `full_name` is registered.
+    pub fn contains(&self, full_name: &str) -> bool {
+        self.types.contains_key(full_name)
+    }
+}
+
+impl fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.types.keys()).finish()
+    }
+}
+
+/// An error converting between messages and JSON.
+#[derive(Debug)]
+pub enum Error {
+    /// The value of the `Any` could not be decoded as its registered type.
+    Decode(DecodeError),
+    /// The message could not be serialized to JSON, or deserialized from it.
+    Json(serde_json::Error),
+    /// Unpacking an `Any` required unpacking more nested `Any` values than
+    /// [`JsonOptions::max_any_depth`] allows, which is held.
+    AnyDepthExceeded(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Decode(error) => write!(f, "failed to unpack Any: {}", error),
+            Error::Json(error) => write!(f, "failed to convert JSON: {}", error),
+            Error::AnyDepthExceeded(max_depth) => {
+                write!(f, "Any values nest more than {} levels deep", max_depth)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(error) => Some(error),
+            Error::Json(error) => Some(error),
+            Error::AnyDepthExceeded(_) => None,
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+/// Converts an `Any` to JSON, unpacking its value if its type is registered.
+///
+/// As in the Protobuf JSON mapping, the unpacked message's fields are held by an object along with
+/// an `@type` field holding the type URL. A message which doesn't serialize to a JSON object is
+/// held by a `value` field instead. If the type isn't registered, `value` holds the raw encoded
+/// message in base64.
+///
+/// The `Any` fields of the unpacked message which use the [`any`](mod@any) helpers are unpacked
+/// through `registry` too, up to the default [`JsonOptions::max_any_depth`].
+pub fn any_to_json(any: &Any, registry: &TypeRegistry) -> Result<Value, Error> {
+    any_to_json_with(any, registry, &JsonOptions::new())
+}
+
+/// Converts an `Any` to JSON, as by [`any_to_json`], with `options` applied.
+pub fn any_to_json_with(
+    any: &Any,
+    registry: &TypeRegistry,
+    options: &JsonOptions,
+) -> Result<Value, Error> {
+    struct Restore(Expansion);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            EXPANSION.with(|expansion| {
+                *expansion.borrow_mut() = std::mem::take(&mut self.0);
+            });
+        }
+    }
+
+    let _restore = Restore(EXPANSION.with(|expansion| {
+        expansion.replace(Expansion {
+            registry: Some(registry.clone()),
+            ..Expansion::default()
+        })
+    }));
+    let result = with_options(options, || expand_any(any));
+    // Errors of nested `Any` values reach here through serde, which only keeps their message.
+    match result {
+        Err(Error::Json(_)) if EXPANSION.with(|expansion| expansion.borrow().exceeded) => {
+            Err(Error::AnyDepthExceeded(options.max_any_depth))
+        }
+        result => result,
+    }
+}
+
+/// The `Any` conversion in progress on a thread.
+#[derive(Default)]
+struct Expansion {
+    /// The registry of the conversion, if one is in progress.
+    registry: Option<TypeRegistry>,
+    /// The number of `Any` values being unpacked within each other.
+    depth: u32,
+    /// Whether unpacking failed for exceeding [`JsonOptions::max_any_depth`].
+    exceeded: bool,
+}
+
+/// Converts an `Any` to JSON, unpacking its value if its type is in the registry of the
+/// conversion in progress.
+fn expand_any(any: &Any) -> Result<Value, Error> {
+    let to_json = EXPANSION.with(|expansion| {
+        let expansion = expansion.borrow();
+        let registry = expansion.registry.as_ref()?;
+        type_name(&any.type_url).and_then(|name| registry.types.get(name).copied())
+    });
+    let value = match to_json {
+        Some(to_json) => {
+            let _depth = AnyDepth::enter()?;
+            to_json(&any.value)?
+        }
+        None => Value::String(base64(&any.value)),
+    };
+
+    let mut object = match value {
+        Value::Object(object) if to_json.is_some() => object,
+        value => {
+            let mut object = Map::new();
+            object.insert("value".to_owned(), value);
+            object
+        }
+    };
+    object.insert("@type".to_owned(), Value::String(any.type_url.clone()));
+    Ok(Value::Object(object))
+}
+
+/// Counts an `Any` being unpacked, until dropped.
+struct AnyDepth;
+
+impl AnyDepth {
+    fn enter() -> Result<AnyDepth, Error> {
+        let max_depth = OPTIONS.with(|options| options.get().max_any_depth);
+        EXPANSION.with(|expansion| {
+            let mut expansion = expansion.borrow_mut();
+            if expansion.depth >= max_depth {
+                expansion.exceeded = true;
+                return Err(Error::AnyDepthExceeded(max_depth));
+            }
+            expansion.depth += 1;
+            Ok(AnyDepth)
+        })
+    }
+}
+
+impl Drop for AnyDepth {
+    fn drop(&mut self) {
+        EXPANSION.with(|expansion| expansion.borrow_mut().depth -= 1);
+    }
+}
+
+/// Options for converting between messages and JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JsonOptions {
+    reject_non_finite: bool,
+    max_any_depth: u32,
+}
+
+impl Default for JsonOptions {
+    fn default() -> JsonOptions {
+        JsonOptions {
+            reject_non_finite: false,
+            max_any_depth: 100,
+        }
+    }
+}
+
+impl JsonOptions {
+    /// Creates the default options, which follow the Protobuf JSON mapping.
+    pub fn new() -> JsonOptions {
+        JsonOptions::default()
+    }
+
+    /// Rejects `NaN` and infinite values of fields using the [`float`] helpers, rather than
+    /// representing them as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    ///
+    /// Defaults to `false`.
+    pub fn reject_non_finite(mut self, enabled: bool) -> Self {
+        self.reject_non_finite = enabled;
+        self
+    }
+
+    /// Fails converting an `Any` with [`any_to_json_with`] once more than `max_depth` `Any`
+    /// values would be unpacked within each other, the outermost included.
+    ///
+    /// Each unpacked `Any` may hold messages with `Any` fields of their own, so a malicious value
+    /// could otherwise nest deeply enough to exhaust the stack. The error is
+    /// [`Error::AnyDepthExceeded`]. `Any` values whose type isn't registered aren't unpacked, and
+    /// don't count.
+    ///
+    /// Defaults to `100`.
+    pub fn max_any_depth(mut self, max_depth: u32) -> Self {
+        self.max_any_depth = max_depth;
+        self
+    }
+}
+
+thread_local! {
+    /// The options of the conversion in progress on this thread.
+    static OPTIONS: Cell<JsonOptions> = Cell::new(JsonOptions::new());
+
+    /// The `Any` conversion in progress on this thread.
+    static EXPANSION: RefCell<Expansion> = RefCell::new(Expansion::default());
+}
+
+/// Runs `f` with `options` in effect on this thread.
+fn with_options<T>(options: &JsonOptions, f: impl FnOnce() -> T) -> T {
+    struct Restore(JsonOptions);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OPTIONS.with(|options| options.set(self.0));
+        }
+    }
+
+    let _restore = Restore(OPTIONS.with(|current| current.replace(*options)));
+    f()
+}
+
+/// Converts a message to a JSON value.
+///
+/// The message's serde `Serialize` implementation defines the mapping.
+pub fn to_json_value<M>(message: &M) -> Result<Value, Error>
+where
+    M: Serialize,
+{
+    to_json_value_with(message, &JsonOptions::new())
+}
+
+/// Converts a message to a JSON value, as by [`to_json_value`], with `options` applied.
+pub fn to_json_value_with<M>(message: &M, options: &JsonOptions) -> Result<Value, Error>
+where
+    M: Serialize,
+{
+    Ok(with_options(options, || serde_json::to_value(message))?)
+}
+
+/// Converts a parsed JSON value to a message.
+///
+/// The message's serde `Deserialize` implementation defines the mapping, as it does when parsing
+/// a JSON string. Working on a value avoids rendering JSON which is already parsed, for example
+/// after manipulating it, to a string only to parse it again.
+pub fn from_json_value<M>(value: &Value) -> Result<M, Error>
+where
+    M: DeserializeOwned,
+{
+    from_json_value_with(value, &JsonOptions::new())
+}
+
+/// Converts a parsed JSON value to a message, as by [`from_json_value`], with `options` applied.
+pub fn from_json_value_with<M>(value: &Value, options: &JsonOptions) -> Result<M, Error>
+where
+    M: DeserializeOwned,
+{
+    Ok(with_options(options, || {
+        serde::Deserialize::deserialize(value)
+    })?)
+}
+
+/// Merges a parsed JSON value into a message.
+///
+/// The value is converted as by [`from_json_value`], then merged into `message` as
+/// [`Message::merge`] merges an encoded message: singular fields present in `value` replace those
+/// of `message`, repeated fields are appended, and nested messages are merged recursively. Fields
+/// absent from `value`, or `null`, leave `message` unchanged. Unlike in an encoded message, fields
+/// without presence are present when their key is, so a default value such as `0` or `""`
+/// replaces the field too, as a partial update expects.
+///
+/// Unknown keys are handled as the message's serde `Deserialize` implementation handles them:
+/// they are ignored, unless the message is marked with `#[serde(deny_unknown_fields)]`, which
+/// rejects them.
+pub fn merge_json_value<M>(message: &mut M, value: &Value) -> Result<(), Error>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    let other: M = from_json_value(value)?;
+    message.merge(other.encode_to_vec().as_slice())?;
+
+    // Fields without presence holding their default value aren't encoded, so replace them
+    // through JSON, where their keys are.
+    let mut merged = to_json_value(message)?;
+    replace_scalars(&mut merged, value);
+    *message = from_json_value(&merged)?;
+    Ok(())
+}
+
+/// Parses JSON and merges it into a message, as [`merge_json_value`] merges a parsed value.
+///
+/// This applies partial updates, such as those of `PATCH` requests, to a message.
+pub fn merge_json<M>(message: &mut M, json: &str) -> Result<(), Error>
+where
+    M: Message + Serialize + DeserializeOwned,
+{
+    merge_json_value(message, &serde_json::from_str(json)?)
+}
+
+/// Replaces the values of `target` with the scalars at the same keys of `source`, recursing into
+/// the objects both hold.
+fn replace_scalars(target: &mut Value, source: &Value) {
+    if let (Value::Object(target), Value::Object(source)) = (target, source) {
+        for (key, value) in source {
+            match value {
+                Value::Null | Value::Array(_) => {}
+                Value::Object(_) => {
+                    if let Some(nested) = target.get_mut(key) {
+                        replace_scalars(nested, value);
+                    }
+                }
+                Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+                    target.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Serde helpers for `float` and `double` fields, following the Protobuf JSON mapping.
+///
+/// Finite values are JSON numbers, and non-finite values are the strings `"NaN"`, `"Infinity"` and
+/// `"-Infinity"`. Numbers held in strings, such as `"1.5"`, are accepted when deserializing too.
+/// Unless [`JsonOptions::reject_non_finite`] is set, non-finite values round-trip exactly.
+///
+/// Fields opt in with `#[serde(with = "prost_types::json::float")]`, or the
+/// [`optional`](float::optional) and [`repeated`](float::repeated) modules for `optional` and
+/// `repeated` fields, for example through `prost_build::Config::field_attribute`.
+pub mod float {
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    use serde::de::{self, Deserializer, Unexpected, Visitor};
+    use serde::ser::{self, Serializer};
+    use serde::{Deserialize, Serialize};
+
+    use super::OPTIONS;
+
+    /// A floating point type which the helpers convert: `f32` or `f64`.
+    pub trait Float: Copy + private::Sealed {}
+
+    impl Float for f32 {}
+    impl Float for f64 {}
+
+    mod private {
+        use serde::Serializer;
+
+        pub trait Sealed: Sized {
+            fn to_f64(self) -> f64;
+
+            /// Returns `None` if the finite `value` is out of range.
+            fn from_f64(value: f64) -> Option<Self>;
+
+            fn serialize_finite<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer;
+        }
+
+        impl Sealed for f32 {
+            fn to_f64(self) -> f64 {
+                f64::from(self)
+            }
+
+            fn from_f64(value: f64) -> Option<f32> {
+                let narrowed = value as f32;
+                if narrowed.is_infinite() && value.is_finite() {
+                    None
+                } else {
+                    Some(narrowed)
+                }
+            }
+
+            fn serialize_finite<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_f32(self)
+            }
+        }
+
+        impl Sealed for f64 {
+            fn to_f64(self) -> f64 {
+                self
+            }
+
+            fn from_f64(value: f64) -> Option<f64> {
+                Some(value)
+            }
+
+            fn serialize_finite<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_f64(self)
+            }
+        }
+    }
+
+    fn reject_non_finite() -> bool {
+        OPTIONS.with(|options| options.get().reject_non_finite)
+    }
+
+    /// A float in its JSON representation.
+    struct Json<F>(F);
+
+    impl<F> Serialize for Json<F>
+    where
+        F: Float,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let value = self.0.to_f64();
+            if value.is_finite() {
+                self.0.serialize_finite(serializer)
+            } else if reject_non_finite() {
+                Err(ser::Error::custom(format_args!(
+                    "non-finite float {} is rejected",
+                    value
+                )))
+            } else if value.is_nan() {
+                serializer.serialize_str("NaN")
+            } else if value > 0.0 {
+                serializer.serialize_str("Infinity")
+            } else {
+                serializer.serialize_str("-Infinity")
+            }
+        }
+    }
+
+    impl<'de, F> Deserialize<'de> for Json<F>
+    where
+        F: Float,
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Json<F>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(JsonVisitor(PhantomData))
+        }
+    }
+
+    struct JsonVisitor<F>(PhantomData<F>);
+
+    impl<'de, F> Visitor<'de> for JsonVisitor<F>
+    where
+        F: Float,
+    {
+        type Value = Json<F>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a number, or one of the strings \"NaN\", \"Infinity\" and \"-Infinity\"")
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Json<F>, E>
+        where
+            E: de::Error,
+        {
+            F::from_f64(value)
+                .map(Json)
+                .ok_or_else(|| E::invalid_value(Unexpected::Float(value), &"a number in range"))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Json<F>, E>
+        where
+            E: de::Error,
+        {
+            self.visit_f64(value as f64)
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Json<F>, E>
+        where
+            E: de::Error,
+        {
+            self.visit_f64(value as f64)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Json<F>, E>
+        where
+            E: de::Error,
+        {
+            let non_finite = match value {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                _ => {
+                    return match value.parse::<f64>() {
+                        Ok(number) if number.is_finite() => self.visit_f64(number),
+                        _ => Err(E::invalid_value(Unexpected::Str(value), &self)),
+                    }
+                }
+            };
+            if reject_non_finite() {
+                return Err(E::invalid_value(Unexpected::Str(value), &"a finite number"));
+            }
+            Ok(Json(
+                F::from_f64(non_finite).expect("non-finite values are in range"),
+            ))
+        }
+    }
+
+    /// Serializes a float field.
+    pub fn serialize<F, S>(value: &F, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        F: Float,
+        S: Serializer,
+    {
+        Json(*value).serialize(serializer)
+    }
+
+    /// Deserializes a float field.
+    pub fn deserialize<'de, F, D>(deserializer: D) -> Result<F, D::Error>
+    where
+        F: Float,
+        D: Deserializer<'de>,
+    {
+        Json::deserialize(deserializer).map(|Json(value)| value)
+    }
+
+    /// Serde helpers for `optional` float fields.
+    pub mod optional {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::{Float, Json};
+
+        /// Serializes an `optional` float field.
+        pub fn serialize<F, S>(value: &Option<F>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            F: Float,
+            S: Serializer,
+        {
+            value.map(Json).serialize(serializer)
+        }
+
+        /// Deserializes an `optional` float field.
+        pub fn deserialize<'de, F, D>(deserializer: D) -> Result<Option<F>, D::Error>
+        where
+            F: Float,
+            D: Deserializer<'de>,
+        {
+            Option::<Json<F>>::deserialize(deserializer).map(|value| value.map(|Json(value)| value))
+        }
+    }
+
+    /// Serde helpers for `repeated` float fields.
+    pub mod repeated {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        use super::{Float, Json};
+
+        /// Serializes a `repeated` float field.
+        #[allow(clippy::ptr_arg)]
+        pub fn serialize<F, S>(values: &Vec<F>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            F: Float,
+            S: Serializer,
+        {
+            serializer.collect_seq(values.iter().map(|&value| Json(value)))
+        }
+
+        /// Deserializes a `repeated` float field.
+        pub fn deserialize<'de, F, D>(deserializer: D) -> Result<Vec<F>, D::Error>
+        where
+            F: Float,
+            D: Deserializer<'de>,
+        {
+            Vec::<Json<F>>::deserialize(deserializer)
+                .map(|values| values.into_iter().map(|Json(value)| value).collect())
+        }
+    }
+}
+
+/// Serde helpers for `google.protobuf.Any` fields, converting them to JSON as [`any_to_json`] does.
+///
+/// Within [`any_to_json`] and [`any_to_json_with`], values are unpacked through the registry of
+/// the conversion, so an unpacked message renders the `Any` values it holds as well, up to
+/// [`JsonOptions::max_any_depth`]. Elsewhere, no type is registered, so values hold their raw
+/// encoded message in base64.
+///
+/// Fields opt in with `#[serde(serialize_with = "prost_types::json::any::serialize")]`, or the
+/// `serialize` functions of the [`optional`](any::optional) and [`repeated`](any::repeated)
+/// modules for message and `repeated` fields, for example through
+/// `prost_build::Config::field_attribute`.
+pub mod any {
+    use serde::ser::{self, Serializer};
+    use serde::Serialize;
+
+    use crate::Any;
+
+    /// An `Any` in its JSON representation.
+    struct Json<'a>(&'a Any);
+
+    impl Serialize for Json<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            super::expand_any(self.0)
+                .map_err(ser::Error::custom)?
+                .serialize(serializer)
+        }
+    }
+
+    /// Serializes an `Any` field.
+    pub fn serialize<S>(value: &Any, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Json(value).serialize(serializer)
+    }
+
+    /// Serde helpers for `Any` message fields, which are optional.
+    pub mod optional {
+        use serde::{Serialize, Serializer};
+
+        use super::Json;
+        use crate::Any;
+
+        /// Serializes an `Any` message field.
+        pub fn serialize<S>(value: &Option<Any>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            value.as_ref().map(Json).serialize(serializer)
+        }
+    }
+
+    /// Serde helpers for `repeated` `Any` fields.
+    pub mod repeated {
+        use serde::Serializer;
+
+        use super::Json;
+        use crate::Any;
+
+        /// Serializes a `repeated` `Any` field.
+        #[allow(clippy::ptr_arg)]
+        pub fn serialize<S>(values: &Vec<Any>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(values.iter().map(Json))
+        }
+    }
+}
+
+/// Encodes bytes in standard base64, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (u32::from(byte) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Point {
+        #[prost(int32, tag = "1")]
+        x: i32,
+        #[prost(int32, tag = "2")]
+        y: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Path {
+        #[prost(string, optional, tag = "1")]
+        name: Option<String>,
+        #[prost(message, repeated, tag = "2")]
+        points: Vec<Point>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Reading {
+        #[prost(double, tag = "1")]
+        #[serde(with = "float")]
+        value: f64,
+        #[prost(float, optional, tag = "2")]
+        #[serde(with = "float::optional")]
+        low: Option<f32>,
+        #[prost(float, repeated, tag = "3")]
+        #[serde(with = "float::repeated")]
+        samples: Vec<f32>,
+    }
+
+    impl Name for Point {
+        const NAME: &'static str = "Point";
+        const PACKAGE: &'static str = "test";
+    }
+
+    #[derive(Clone, PartialEq, prost::Message, Serialize)]
+    struct Envelope {
+        #[prost(message, optional, tag = "1")]
+        #[serde(serialize_with = "any::optional::serialize")]
+        payload: Option<Any>,
+        #[prost(message, repeated, tag = "2")]
+        #[serde(serialize_with = "any::repeated::serialize")]
+        attachments: Vec<Any>,
+    }
+
+    impl Name for Envelope {
+        const NAME: &'static str = "Envelope";
+        const PACKAGE: &'static str = "test";
+    }
+
+    #[test]
+    fn check_any_to_json() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Point>();
+        assert!(registry.contains("test.Point"));
+
+        let any = Any::from_msg(&Point { x: 1, y: -2 }).unwrap();
+        assert_eq!(
+            any_to_json(&any, &registry).unwrap(),
+            serde_json::json!({ "@type": "/test.Point", "x": 1, "y": -2 })
+        );
+
+        let corrupt = Any {
+            type_url: any.type_url.clone(),
+            value: vec![0xFF],
+        };
+        assert!(matches!(
+            any_to_json(&corrupt, &registry),
+            Err(Error::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn check_any_to_json_unregistered() {
+        let any = Any {
+            type_url: "type.googleapis.com/test.Unknown".to_owned(),
+            value: b"\x08\x96\x01".to_vec(),
+        };
+        assert_eq!(
+            any_to_json(&any, &TypeRegistry::new()).unwrap(),
+            serde_json::json!({
+                "@type": "type.googleapis.com/test.Unknown",
+                "value": "CJYB",
+            })
+        );
+    }
+
+    #[test]
+    fn check_any_to_json_nested() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Point>().register::<Envelope>();
+
+        let point = Any::from_msg(&Point { x: 1, y: 2 }).unwrap();
+        let inner = Envelope {
+            payload: Some(point.clone()),
+            attachments: vec![point.clone(), point],
+        };
+        let outer = Envelope {
+            payload: Some(Any::from_msg(&inner).unwrap()),
+            attachments: vec![],
+        };
+        let any = Any::from_msg(&outer).unwrap();
+        let point = serde_json::json!({ "@type": "/test.Point", "x": 1, "y": 2 });
+        assert_eq!(
+            any_to_json(&any, &registry).unwrap(),
+            serde_json::json!({
+                "@type": "/test.Envelope",
+                "payload": {
+                    "@type": "/test.Envelope",
+                    "payload": point,
+                    "attachments": [point, point],
+                },
+                "attachments": [],
+            })
+        );
+
+        // The outer envelope, the inner one, and a point nest three `Any` values deep.
+        let options = JsonOptions::new().max_any_depth(3);
+        assert!(any_to_json_with(&any, &registry, &options).is_ok());
+        let options = JsonOptions::new().max_any_depth(2);
+        assert!(matches!(
+            any_to_json_with(&any, &registry, &options),
+            Err(Error::AnyDepthExceeded(2))
+        ));
+
+        // An `Any` nesting itself is bounded by the default depth.
+        let mut any = Any::from_msg(&Envelope::default()).unwrap();
+        for _ in 0..100 {
+            let envelope = Envelope {
+                payload: Some(any),
+                attachments: vec![],
+            };
+            any = Any::from_msg(&envelope).unwrap();
+        }
+        assert!(matches!(
+            any_to_json(&any, &registry),
+            Err(Error::AnyDepthExceeded(100))
+        ));
+
+        // Outside of `any_to_json`, no type is registered.
+        let envelope = Envelope {
+            payload: Some(Any::from_msg(&Point { x: 1, y: 2 }).unwrap()),
+            attachments: vec![],
+        };
+        assert_eq!(
+            to_json_value(&envelope).unwrap(),
+            serde_json::json!({
+                "payload": { "@type": "/test.Point", "value": "CAEQAg==" },
+                "attachments": [],
+            })
+        );
+    }
+
+    #[test]
+    fn check_from_json_value() {
+        let value = serde_json::json!({ "name": "a", "points": [{ "x": 1 }] });
+        assert_eq!(
+            from_json_value::<Path>(&value).unwrap(),
+            Path {
+                name: Some("a".to_owned()),
+                points: vec![Point { x: 1, y: 0 }],
+            }
+        );
+
+        let value = serde_json::json!({ "points": 1 });
+        assert!(matches!(
+            from_json_value::<Path>(&value),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[test]
+    fn check_merge_json_value() {
+        let mut path = Path {
+            name: Some("a".to_owned()),
+            points: vec![Point { x: 1, y: 2 }],
+        };
+        let value = serde_json::json!({ "points": [{ "y": 3 }] });
+        merge_json_value(&mut path, &value).unwrap();
+        assert_eq!(
+            path,
+            Path {
+                name: Some("a".to_owned()),
+                points: vec![Point { x: 1, y: 2 }, Point { x: 0, y: 3 }],
+            }
+        );
+
+        // Default values replace fields without presence, as their keys are present.
+        let mut point = Point { x: 1, y: 2 };
+        merge_json_value(&mut point, &serde_json::json!({ "x": 0, "y": 4 })).unwrap();
+        assert_eq!(point, Point { x: 0, y: 4 });
+    }
+
+    #[derive(Clone, PartialEq, prost::Message, Serialize, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct StrictPoint {
+        #[prost(int32, tag = "1")]
+        x: i32,
+        #[prost(int32, tag = "2")]
+        y: i32,
+    }
+
+    #[test]
+    fn check_merge_json() {
+        let mut path = Path {
+            name: Some("a".to_owned()),
+            points: vec![Point { x: 1, y: 2 }],
+        };
+        merge_json(
+            &mut path,
+            r#"{ "name": "", "points": [{ "x": 0, "y": 3 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            Path {
+                name: Some(String::new()),
+                points: vec![Point { x: 1, y: 2 }, Point { x: 0, y: 3 }],
+            }
+        );
+
+        // Absent and null fields are left untouched.
+        merge_json(&mut path, r#"{ "name": null }"#).unwrap();
+        assert_eq!(path.name.as_deref(), Some(""));
+        assert_eq!(path.points.len(), 2);
+
+        // Unknown keys are ignored, unless the message denies them.
+        let mut point = Point { x: 1, y: 2 };
+        merge_json(&mut point, r#"{ "y": 0, "z": 3 }"#).unwrap();
+        assert_eq!(point, Point { x: 1, y: 0 });
+
+        let mut point = StrictPoint { x: 1, y: 2 };
+        assert!(matches!(
+            merge_json(&mut point, r#"{ "y": 0, "z": 3 }"#),
+            Err(Error::Json(_))
+        ));
+        assert_eq!(point, StrictPoint { x: 1, y: 2 });
+        merge_json(&mut point, r#"{ "y": 0 }"#).unwrap();
+        assert_eq!(point, StrictPoint { x: 1, y: 0 });
+
+        assert!(matches!(merge_json(&mut point, "{"), Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn check_non_finite_floats() {
+        let reading = Reading {
+            value: f64::NAN,
+            low: Some(f32::NEG_INFINITY),
+            samples: vec![1.5, f32::INFINITY],
+        };
+        let value = to_json_value(&reading).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "value": "NaN", "low": "-Infinity", "samples": [1.5, "Infinity"] })
+        );
+
+        let parsed: Reading = from_json_value(&value).unwrap();
+        assert!(parsed.value.is_nan());
+        assert_eq!(parsed.low, Some(f32::NEG_INFINITY));
+        assert_eq!(parsed.samples, [1.5, f32::INFINITY]);
+
+        let value = serde_json::json!({ "value": "2.5", "low": 1, "samples": [] });
+        assert_eq!(
+            from_json_value::<Reading>(&value).unwrap(),
+            Reading {
+                value: 2.5,
+                low: Some(1.0),
+                samples: vec![],
+            }
+        );
+
+        for value in [
+            serde_json::json!({ "value": "inf" }),
+            serde_json::json!({ "value": "1e400" }),
+            serde_json::json!({ "low": 1e39 }),
+        ] {
+            assert!(matches!(
+                from_json_value::<Reading>(&value),
+                Err(Error::Json(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn check_reject_non_finite() {
+        let options = JsonOptions::new().reject_non_finite(true);
+        let reading = Reading {
+            samples: vec![f32::NAN],
+            ..Reading::default()
+        };
+        assert!(matches!(
+            to_json_value_with(&reading, &options),
+            Err(Error::Json(_))
+        ));
+
+        let value = serde_json::json!({ "value": "-Infinity" });
+        assert!(matches!(
+            from_json_value_with::<Reading>(&value, &options),
+            Err(Error::Json(_))
+        ));
+        let finite = serde_json::json!({ "value": 1 });
+        assert_eq!(
+            from_json_value_with::<Reading>(&finite, &options)
+                .unwrap()
+                .value,
+            1.0
+        );
+
+        // The options only apply to the conversion they're passed to.
+        assert_eq!(
+            from_json_value::<Reading>(&value).unwrap().value,
+            f64::NEG_INFINITY
+        );
+    }
+
+    #[test]
+    fn check_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
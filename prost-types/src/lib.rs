@@ -11,9 +11,12 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod any;
 #[rustfmt::skip]
 pub mod compiler;
 mod datetime;
+#[cfg(feature = "json")]
+pub mod json;
 #[rustfmt::skip]
 mod protobuf;
 
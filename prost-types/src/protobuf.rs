@@ -47,6 +47,9 @@ pub struct FileDescriptorProto {
     /// The supported values are "proto2" and "proto3".
     #[prost(string, optional, tag = "12")]
     pub syntax: ::core::option::Option<::prost::alloc::string::String>,
+    /// The edition of the proto file, if the syntax is "editions".
+    #[prost(enumeration = "Edition", optional, tag = "14")]
+    pub edition: ::core::option::Option<i32>,
 }
 /// Describes a message type.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -180,17 +183,7 @@ pub struct FieldDescriptorProto {
 }
 /// Nested message and enum types in `FieldDescriptorProto`.
 pub mod field_descriptor_proto {
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Type {
         /// 0 is reserved for errors.
@@ -255,40 +248,40 @@ pub mod field_descriptor_proto {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "TYPE_DOUBLE" => Some(Self::Double),
-                "TYPE_FLOAT" => Some(Self::Float),
-                "TYPE_INT64" => Some(Self::Int64),
-                "TYPE_UINT64" => Some(Self::Uint64),
-                "TYPE_INT32" => Some(Self::Int32),
-                "TYPE_FIXED64" => Some(Self::Fixed64),
-                "TYPE_FIXED32" => Some(Self::Fixed32),
-                "TYPE_BOOL" => Some(Self::Bool),
-                "TYPE_STRING" => Some(Self::String),
-                "TYPE_GROUP" => Some(Self::Group),
-                "TYPE_MESSAGE" => Some(Self::Message),
-                "TYPE_BYTES" => Some(Self::Bytes),
-                "TYPE_UINT32" => Some(Self::Uint32),
-                "TYPE_ENUM" => Some(Self::Enum),
-                "TYPE_SFIXED32" => Some(Self::Sfixed32),
-                "TYPE_SFIXED64" => Some(Self::Sfixed64),
-                "TYPE_SINT32" => Some(Self::Sint32),
-                "TYPE_SINT64" => Some(Self::Sint64),
-                _ => None,
-            }
+            let variant = match value {
+                "TYPE_DOUBLE" => Self::Double,
+                "TYPE_FLOAT" => Self::Float,
+                "TYPE_INT64" => Self::Int64,
+                "TYPE_UINT64" => Self::Uint64,
+                "TYPE_INT32" => Self::Int32,
+                "TYPE_FIXED64" => Self::Fixed64,
+                "TYPE_FIXED32" => Self::Fixed32,
+                "TYPE_BOOL" => Self::Bool,
+                "TYPE_STRING" => Self::String,
+                "TYPE_GROUP" => Self::Group,
+                "TYPE_MESSAGE" => Self::Message,
+                "TYPE_BYTES" => Self::Bytes,
+                "TYPE_UINT32" => Self::Uint32,
+                "TYPE_ENUM" => Self::Enum,
+                "TYPE_SFIXED32" => Self::Sfixed32,
+                "TYPE_SFIXED64" => Self::Sfixed64,
+                "TYPE_SINT32" => Self::Sint32,
+                "TYPE_SINT64" => Self::Sint64,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
         }
     }
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    impl ::core::hash::Hash for Type {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Label {
         /// 0 is reserved for errors
@@ -310,12 +303,22 @@ pub mod field_descriptor_proto {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "LABEL_OPTIONAL" => Some(Self::Optional),
-                "LABEL_REQUIRED" => Some(Self::Required),
-                "LABEL_REPEATED" => Some(Self::Repeated),
-                _ => None,
-            }
+            let variant = match value {
+                "LABEL_OPTIONAL" => Self::Optional,
+                "LABEL_REQUIRED" => Self::Required,
+                "LABEL_REPEATED" => Self::Repeated,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for Label {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
 }
@@ -552,6 +555,9 @@ pub struct FileOptions {
     /// determining the ruby package.
     #[prost(string, optional, tag = "45")]
     pub ruby_package: ::core::option::Option<::prost::alloc::string::String>,
+    /// Any features defined in the specific edition.
+    #[prost(message, optional, tag = "50")]
+    pub features: ::core::option::Option<FeatureSet>,
     /// The parser stores options it doesn't recognize here.
     /// See the documentation for the "Options" section above.
     #[prost(message, repeated, tag = "999")]
@@ -560,17 +566,7 @@ pub struct FileOptions {
 /// Nested message and enum types in `FileOptions`.
 pub mod file_options {
     /// Generated classes can be optimized for speed or code size.
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum OptimizeMode {
         /// Generate complete code for parsing, serialization,
@@ -596,12 +592,22 @@ pub mod file_options {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "SPEED" => Some(Self::Speed),
-                "CODE_SIZE" => Some(Self::CodeSize),
-                "LITE_RUNTIME" => Some(Self::LiteRuntime),
-                _ => None,
-            }
+            let variant = match value {
+                "SPEED" => Self::Speed,
+                "CODE_SIZE" => Self::CodeSize,
+                "LITE_RUNTIME" => Self::LiteRuntime,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for OptimizeMode {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
 }
@@ -662,6 +668,9 @@ pub struct MessageOptions {
     /// parser.
     #[prost(bool, optional, tag = "7")]
     pub map_entry: ::core::option::Option<bool>,
+    /// Any features defined in the specific edition.
+    #[prost(message, optional, tag = "12")]
+    pub features: ::core::option::Option<FeatureSet>,
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag = "999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
@@ -743,23 +752,19 @@ pub struct FieldOptions {
     /// For Google-internal migration only. Do not use.
     #[prost(bool, optional, tag = "10", default = "false")]
     pub weak: ::core::option::Option<bool>,
+    /// If set to RETENTION_SOURCE, the option will be omitted from the binary.
+    #[prost(enumeration = "field_options::OptionRetention", optional, tag = "17")]
+    pub retention: ::core::option::Option<i32>,
+    /// Any features defined in the specific edition.
+    #[prost(message, optional, tag = "21")]
+    pub features: ::core::option::Option<FeatureSet>,
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag = "999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 /// Nested message and enum types in `FieldOptions`.
 pub mod field_options {
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum CType {
         /// Default mode.
@@ -781,25 +786,25 @@ pub mod field_options {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "STRING" => Some(Self::String),
-                "CORD" => Some(Self::Cord),
-                "STRING_PIECE" => Some(Self::StringPiece),
-                _ => None,
-            }
+            let variant = match value {
+                "STRING" => Self::String,
+                "CORD" => Self::Cord,
+                "STRING_PIECE" => Self::StringPiece,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
         }
     }
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    impl ::core::hash::Hash for CType {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum JsType {
         /// Use the default type.
@@ -823,18 +828,71 @@ pub mod field_options {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "JS_NORMAL" => Some(Self::JsNormal),
-                "JS_STRING" => Some(Self::JsString),
-                "JS_NUMBER" => Some(Self::JsNumber),
-                _ => None,
+            let variant = match value {
+                "JS_NORMAL" => Self::JsNormal,
+                "JS_STRING" => Self::JsString,
+                "JS_NUMBER" => Self::JsNumber,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for JsType {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    /// Whether an option is retained in the descriptors embedded in generated code.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum OptionRetention {
+        RetentionUnknown = 0,
+        RetentionRuntime = 1,
+        RetentionSource = 2,
+    }
+    impl OptionRetention {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                OptionRetention::RetentionUnknown => "RETENTION_UNKNOWN",
+                OptionRetention::RetentionRuntime => "RETENTION_RUNTIME",
+                OptionRetention::RetentionSource => "RETENTION_SOURCE",
             }
         }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "RETENTION_UNKNOWN" => Self::RetentionUnknown,
+                "RETENTION_RUNTIME" => Self::RetentionRuntime,
+                "RETENTION_SOURCE" => Self::RetentionSource,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for OptionRetention {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct OneofOptions {
+    /// Any features defined in the specific edition.
+    #[prost(message, optional, tag = "1")]
+    pub features: ::core::option::Option<FeatureSet>,
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag = "999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
@@ -852,6 +910,9 @@ pub struct EnumOptions {
     /// is a formalization for deprecating enums.
     #[prost(bool, optional, tag = "3", default = "false")]
     pub deprecated: ::core::option::Option<bool>,
+    /// Any features defined in the specific edition.
+    #[prost(message, optional, tag = "7")]
+    pub features: ::core::option::Option<FeatureSet>,
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag = "999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
@@ -907,17 +968,7 @@ pub mod method_options {
     /// Is this method side-effect-free (or safe in HTTP parlance), or idempotent,
     /// or neither? HTTP based RPC implementation may choose GET verb for safe
     /// methods, and PUT verb for idempotent methods instead of the default POST.
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum IdempotencyLevel {
         IdempotencyUnknown = 0,
@@ -940,12 +991,22 @@ pub mod method_options {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "IDEMPOTENCY_UNKNOWN" => Some(Self::IdempotencyUnknown),
-                "NO_SIDE_EFFECTS" => Some(Self::NoSideEffects),
-                "IDEMPOTENT" => Some(Self::Idempotent),
-                _ => None,
-            }
+            let variant = match value {
+                "IDEMPOTENCY_UNKNOWN" => Self::IdempotencyUnknown,
+                "NO_SIDE_EFFECTS" => Self::NoSideEffects,
+                "IDEMPOTENT" => Self::Idempotent,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for IdempotencyLevel {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
 }
@@ -991,6 +1052,264 @@ pub mod uninterpreted_option {
         pub is_extension: bool,
     }
 }
+/// Language-independent features, which control the behavior of fields, messages and enums
+/// in files using protobuf editions.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FeatureSet {
+    #[prost(enumeration = "feature_set::FieldPresence", optional, tag = "1")]
+    pub field_presence: ::core::option::Option<i32>,
+    #[prost(enumeration = "feature_set::EnumType", optional, tag = "2")]
+    pub enum_type: ::core::option::Option<i32>,
+    #[prost(enumeration = "feature_set::RepeatedFieldEncoding", optional, tag = "3")]
+    pub repeated_field_encoding: ::core::option::Option<i32>,
+    #[prost(enumeration = "feature_set::Utf8Validation", optional, tag = "4")]
+    pub utf8_validation: ::core::option::Option<i32>,
+    #[prost(enumeration = "feature_set::MessageEncoding", optional, tag = "5")]
+    pub message_encoding: ::core::option::Option<i32>,
+    #[prost(enumeration = "feature_set::JsonFormat", optional, tag = "6")]
+    pub json_format: ::core::option::Option<i32>,
+}
+/// Nested message and enum types in `FeatureSet`.
+pub mod feature_set {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum FieldPresence {
+        Unknown = 0,
+        Explicit = 1,
+        Implicit = 2,
+        LegacyRequired = 3,
+    }
+    impl FieldPresence {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                FieldPresence::Unknown => "FIELD_PRESENCE_UNKNOWN",
+                FieldPresence::Explicit => "EXPLICIT",
+                FieldPresence::Implicit => "IMPLICIT",
+                FieldPresence::LegacyRequired => "LEGACY_REQUIRED",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "FIELD_PRESENCE_UNKNOWN" => Self::Unknown,
+                "EXPLICIT" => Self::Explicit,
+                "IMPLICIT" => Self::Implicit,
+                "LEGACY_REQUIRED" => Self::LegacyRequired,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for FieldPresence {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum EnumType {
+        Unknown = 0,
+        Open = 1,
+        Closed = 2,
+    }
+    impl EnumType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                EnumType::Unknown => "ENUM_TYPE_UNKNOWN",
+                EnumType::Open => "OPEN",
+                EnumType::Closed => "CLOSED",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "ENUM_TYPE_UNKNOWN" => Self::Unknown,
+                "OPEN" => Self::Open,
+                "CLOSED" => Self::Closed,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for EnumType {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum RepeatedFieldEncoding {
+        Unknown = 0,
+        Packed = 1,
+        Expanded = 2,
+    }
+    impl RepeatedFieldEncoding {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                RepeatedFieldEncoding::Unknown => "REPEATED_FIELD_ENCODING_UNKNOWN",
+                RepeatedFieldEncoding::Packed => "PACKED",
+                RepeatedFieldEncoding::Expanded => "EXPANDED",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "REPEATED_FIELD_ENCODING_UNKNOWN" => Self::Unknown,
+                "PACKED" => Self::Packed,
+                "EXPANDED" => Self::Expanded,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for RepeatedFieldEncoding {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Utf8Validation {
+        Unknown = 0,
+        Verify = 2,
+        None = 3,
+    }
+    impl Utf8Validation {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Utf8Validation::Unknown => "UTF8_VALIDATION_UNKNOWN",
+                Utf8Validation::Verify => "VERIFY",
+                Utf8Validation::None => "NONE",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "UTF8_VALIDATION_UNKNOWN" => Self::Unknown,
+                "VERIFY" => Self::Verify,
+                "NONE" => Self::None,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for Utf8Validation {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum MessageEncoding {
+        Unknown = 0,
+        LengthPrefixed = 1,
+        Delimited = 2,
+    }
+    impl MessageEncoding {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                MessageEncoding::Unknown => "MESSAGE_ENCODING_UNKNOWN",
+                MessageEncoding::LengthPrefixed => "LENGTH_PREFIXED",
+                MessageEncoding::Delimited => "DELIMITED",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "MESSAGE_ENCODING_UNKNOWN" => Self::Unknown,
+                "LENGTH_PREFIXED" => Self::LengthPrefixed,
+                "DELIMITED" => Self::Delimited,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for MessageEncoding {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum JsonFormat {
+        Unknown = 0,
+        Allow = 1,
+        LegacyBestEffort = 2,
+    }
+    impl JsonFormat {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                JsonFormat::Unknown => "JSON_FORMAT_UNKNOWN",
+                JsonFormat::Allow => "ALLOW",
+                JsonFormat::LegacyBestEffort => "LEGACY_BEST_EFFORT",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            let variant = match value {
+                "JSON_FORMAT_UNKNOWN" => Self::Unknown,
+                "ALLOW" => Self::Allow,
+                "LEGACY_BEST_EFFORT" => Self::LegacyBestEffort,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for JsonFormat {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
+        }
+    }
+}
 /// Encapsulates information about the original source file from which a
 /// FileDescriptorProto was generated.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -1171,6 +1490,52 @@ pub mod generated_code_info {
         pub end: ::core::option::Option<i32>,
     }
 }
+/// The full set of known editions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Edition {
+    Unknown = 0,
+    Proto2 = 998,
+    Proto3 = 999,
+    Edition2023 = 1000,
+    Edition2024 = 1001,
+}
+impl Edition {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Edition::Unknown => "EDITION_UNKNOWN",
+            Edition::Proto2 => "EDITION_PROTO2",
+            Edition::Proto3 => "EDITION_PROTO3",
+            Edition::Edition2023 => "EDITION_2023",
+            Edition::Edition2024 => "EDITION_2024",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        let variant = match value {
+            "EDITION_UNKNOWN" => Self::Unknown,
+            "EDITION_PROTO2" => Self::Proto2,
+            "EDITION_PROTO3" => Self::Proto3,
+            "EDITION_2023" => Self::Edition2023,
+            "EDITION_2024" => Self::Edition2024,
+            _ => return ::core::option::Option::None,
+        };
+        ::core::option::Option::Some(variant)
+    }
+}
+impl ::core::hash::Hash for Edition {
+    /// Hashes the `i32` number of the value, as returned by `as i32`.
+    ///
+    /// The hash doesn't depend on the names or order of the values, so it's as stable
+    /// across builds and versions as the hasher, e.g. for use as a shard key.
+    fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+        ::core::hash::Hash::hash(&(*self as i32), state);
+    }
+}
 /// `Any` contains an arbitrary serialized protocol buffer message along with a
 /// URL that describes the type of the serialized message.
 ///
@@ -1373,17 +1738,7 @@ pub struct Field {
 /// Nested message and enum types in `Field`.
 pub mod field {
     /// Basic field types.
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Kind {
         /// Field type unknown.
@@ -1455,42 +1810,42 @@ pub mod field {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "TYPE_UNKNOWN" => Some(Self::TypeUnknown),
-                "TYPE_DOUBLE" => Some(Self::TypeDouble),
-                "TYPE_FLOAT" => Some(Self::TypeFloat),
-                "TYPE_INT64" => Some(Self::TypeInt64),
-                "TYPE_UINT64" => Some(Self::TypeUint64),
-                "TYPE_INT32" => Some(Self::TypeInt32),
-                "TYPE_FIXED64" => Some(Self::TypeFixed64),
-                "TYPE_FIXED32" => Some(Self::TypeFixed32),
-                "TYPE_BOOL" => Some(Self::TypeBool),
-                "TYPE_STRING" => Some(Self::TypeString),
-                "TYPE_GROUP" => Some(Self::TypeGroup),
-                "TYPE_MESSAGE" => Some(Self::TypeMessage),
-                "TYPE_BYTES" => Some(Self::TypeBytes),
-                "TYPE_UINT32" => Some(Self::TypeUint32),
-                "TYPE_ENUM" => Some(Self::TypeEnum),
-                "TYPE_SFIXED32" => Some(Self::TypeSfixed32),
-                "TYPE_SFIXED64" => Some(Self::TypeSfixed64),
-                "TYPE_SINT32" => Some(Self::TypeSint32),
-                "TYPE_SINT64" => Some(Self::TypeSint64),
-                _ => None,
-            }
+            let variant = match value {
+                "TYPE_UNKNOWN" => Self::TypeUnknown,
+                "TYPE_DOUBLE" => Self::TypeDouble,
+                "TYPE_FLOAT" => Self::TypeFloat,
+                "TYPE_INT64" => Self::TypeInt64,
+                "TYPE_UINT64" => Self::TypeUint64,
+                "TYPE_INT32" => Self::TypeInt32,
+                "TYPE_FIXED64" => Self::TypeFixed64,
+                "TYPE_FIXED32" => Self::TypeFixed32,
+                "TYPE_BOOL" => Self::TypeBool,
+                "TYPE_STRING" => Self::TypeString,
+                "TYPE_GROUP" => Self::TypeGroup,
+                "TYPE_MESSAGE" => Self::TypeMessage,
+                "TYPE_BYTES" => Self::TypeBytes,
+                "TYPE_UINT32" => Self::TypeUint32,
+                "TYPE_ENUM" => Self::TypeEnum,
+                "TYPE_SFIXED32" => Self::TypeSfixed32,
+                "TYPE_SFIXED64" => Self::TypeSfixed64,
+                "TYPE_SINT32" => Self::TypeSint32,
+                "TYPE_SINT64" => Self::TypeSint64,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for Kind {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
     /// Whether a field is optional, required, or repeated.
-    #[derive(
-        Clone,
-        Copy,
-        Debug,
-        PartialEq,
-        Eq,
-        Hash,
-        PartialOrd,
-        Ord,
-        ::prost::Enumeration
-    )]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Cardinality {
         /// For fields with unknown cardinality.
@@ -1517,13 +1872,23 @@ pub mod field {
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
         pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-            match value {
-                "CARDINALITY_UNKNOWN" => Some(Self::Unknown),
-                "CARDINALITY_OPTIONAL" => Some(Self::Optional),
-                "CARDINALITY_REQUIRED" => Some(Self::Required),
-                "CARDINALITY_REPEATED" => Some(Self::Repeated),
-                _ => None,
-            }
+            let variant = match value {
+                "CARDINALITY_UNKNOWN" => Self::Unknown,
+                "CARDINALITY_OPTIONAL" => Self::Optional,
+                "CARDINALITY_REQUIRED" => Self::Required,
+                "CARDINALITY_REPEATED" => Self::Repeated,
+                _ => return ::core::option::Option::None,
+            };
+            ::core::option::Option::Some(variant)
+        }
+    }
+    impl ::core::hash::Hash for Cardinality {
+        /// Hashes the `i32` number of the value, as returned by `as i32`.
+        ///
+        /// The hash doesn't depend on the names or order of the values, so it's as stable
+        /// across builds and versions as the hasher, e.g. for use as a shard key.
+        fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+            ::core::hash::Hash::hash(&(*self as i32), state);
         }
     }
 }
@@ -1580,7 +1945,7 @@ pub struct Option {
     pub value: ::core::option::Option<Any>,
 }
 /// The syntax in which a protocol buffer element is defined.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum Syntax {
     /// Syntax `proto2`.
@@ -1601,11 +1966,21 @@ impl Syntax {
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
     pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "SYNTAX_PROTO2" => Some(Self::Proto2),
-            "SYNTAX_PROTO3" => Some(Self::Proto3),
-            _ => None,
-        }
+        let variant = match value {
+            "SYNTAX_PROTO2" => Self::Proto2,
+            "SYNTAX_PROTO3" => Self::Proto3,
+            _ => return ::core::option::Option::None,
+        };
+        ::core::option::Option::Some(variant)
+    }
+}
+impl ::core::hash::Hash for Syntax {
+    /// Hashes the `i32` number of the value, as returned by `as i32`.
+    ///
+    /// The hash doesn't depend on the names or order of the values, so it's as stable
+    /// across builds and versions as the hasher, e.g. for use as a shard key.
+    fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+        ::core::hash::Hash::hash(&(*self as i32), state);
     }
 }
 /// Api is a light-weight descriptor for an API Interface.
@@ -2168,7 +2543,7 @@ pub struct ListValue {
 /// `Value` type union.
 ///
 /// The JSON representation for `NullValue` is JSON `null`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum NullValue {
     /// Null value.
@@ -2186,10 +2561,20 @@ impl NullValue {
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
     pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "NULL_VALUE" => Some(Self::NullValue),
-            _ => None,
-        }
+        let variant = match value {
+            "NULL_VALUE" => Self::NullValue,
+            _ => return ::core::option::Option::None,
+        };
+        ::core::option::Option::Some(variant)
+    }
+}
+impl ::core::hash::Hash for NullValue {
+    /// Hashes the `i32` number of the value, as returned by `as i32`.
+    ///
+    /// The hash doesn't depend on the names or order of the values, so it's as stable
+    /// across builds and versions as the hasher, e.g. for use as a shard key.
+    fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+        ::core::hash::Hash::hash(&(*self as i32), state);
     }
 }
 /// A Timestamp represents a point in time independent of any time zone or local
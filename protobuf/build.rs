@@ -38,6 +38,42 @@ static DATASET_PROTOS: &[&str] = &[
     "google_message4/benchmark_message4_3.proto",
 ];
 
+/// The fields of `TestAllTypesProto3` holding well-known types.
+static WELL_KNOWN_TYPE_FIELDS: &[&str] = &[
+    "optional_bool_wrapper",
+    "optional_int32_wrapper",
+    "optional_int64_wrapper",
+    "optional_uint32_wrapper",
+    "optional_uint64_wrapper",
+    "optional_float_wrapper",
+    "optional_double_wrapper",
+    "optional_string_wrapper",
+    "optional_bytes_wrapper",
+    "repeated_bool_wrapper",
+    "repeated_int32_wrapper",
+    "repeated_int64_wrapper",
+    "repeated_uint32_wrapper",
+    "repeated_uint64_wrapper",
+    "repeated_float_wrapper",
+    "repeated_double_wrapper",
+    "repeated_string_wrapper",
+    "repeated_bytes_wrapper",
+    "optional_duration",
+    "optional_timestamp",
+    "optional_field_mask",
+    "optional_struct",
+    "optional_any",
+    "optional_value",
+    "optional_null_value",
+    "repeated_duration",
+    "repeated_timestamp",
+    "repeated_fieldmask",
+    "repeated_struct",
+    "repeated_any",
+    "repeated_value",
+    "repeated_list_value",
+];
+
 fn main() -> Result<()> {
     let out_dir =
         &PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR environment variable not set"));
@@ -82,8 +118,25 @@ fn main() -> Result<()> {
     // that encode/decode roundtrips can use encoded output for comparison. Otherwise trying to
     // compare based on the Rust PartialEq implementations is difficult, due to presence of NaN
     // values.
-    prost_build::Config::new()
-        .btree_map(["."])
+    let mut config = prost_build::Config::new();
+    config.btree_map(["."]);
+
+    // With the `json` feature, derive serde for the conformance test messages, so that the
+    // conformance runner can handle JSON requests. The well-known types have no serde
+    // implementations, so fields holding them are skipped.
+    if env::var_os("CARGO_FEATURE_JSON").is_some() {
+        config
+            .derive_serde(".protobuf_test_messages")
+            .serde_default(".protobuf_test_messages");
+        for field in WELL_KNOWN_TYPE_FIELDS {
+            config.serde_skip(format!(
+                ".protobuf_test_messages.proto3.TestAllTypesProto3.{}",
+                field
+            ));
+        }
+    }
+
+    config
         .compile_protos(
             &[
                 test_includes.join("test_messages_proto2.proto"),
@@ -0,0 +1,499 @@
+//! Messages which allocate from an arena.
+//!
+//! An arena message, generated by `prost-build` with `Config::arena`, has a lifetime parameter
+//! tying it to a [`Bump`] arena. Its string and bytes fields borrow from the arena, and its
+//! repeated fields are arena [`Vec`]s, so decoding a message performs no allocation from the
+//! global allocator. All of the memory is reclaimed at once when the arena is reset or dropped,
+//! which makes decoding large batches of messages cheap.
+//!
+//! Requires the `arena` feature.
+
+use core::fmt::Debug;
+use core::str;
+
+use ::bytes::{Buf, BufMut};
+
+pub use bumpalo::collections::Vec;
+pub use bumpalo::Bump;
+
+use crate::encoding::{
+    check_wire_type, decode_key, decode_varint, encode_key, encode_varint, encoded_len_varint,
+    key_len, merge_loop, DecodeContext, WireType,
+};
+use crate::{DecodeError, DecodeErrorKind, EncodeError};
+
+/// A Protocol Buffers message which allocates from an arena.
+pub trait ArenaMessage<'arena>: Debug + Sized {
+    /// Creates an empty message, with every field set to its default value.
+    fn new_in(arena: &'arena Bump) -> Self;
+
+    /// Encodes the message to a buffer.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    ///
+    /// Meant to be used only by `ArenaMessage` implementations.
+    #[doc(hidden)]
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut;
+
+    /// Decodes a field from a buffer, and merges it into `self`, allocating from `arena`.
+    ///
+    /// Meant to be used only by `ArenaMessage` implementations.
+    #[doc(hidden)]
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf;
+
+    /// Returns the encoded length of the message without a length delimiter.
+    fn encoded_len(&self) -> usize;
+
+    /// Encodes the message to a buffer.
+    ///
+    /// An error will be returned if the buffer does not have sufficient capacity.
+    fn encode<B>(&self, buf: &mut B) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+    {
+        let required = self.encoded_len();
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+
+        self.encode_raw(buf);
+        Ok(())
+    }
+
+    /// Encodes the message to a newly allocated buffer.
+    fn encode_to_vec(&self) -> alloc::vec::Vec<u8> {
+        let mut buf = alloc::vec::Vec::with_capacity(self.encoded_len());
+        self.encode_raw(&mut buf);
+        buf
+    }
+
+    /// Decodes an instance of the message from a buffer, allocating from `arena`.
+    ///
+    /// The entire buffer will be consumed.
+    fn decode_in<B>(mut buf: B, arena: &'arena Bump) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+    {
+        let mut message = Self::new_in(arena);
+        message.merge_in(&mut buf, arena).map(|_| message)
+    }
+
+    /// Decodes an instance of the message from a buffer, and merges it into `self`, allocating
+    /// from `arena`.
+    ///
+    /// The entire buffer will be consumed.
+    fn merge_in<B>(&mut self, mut buf: B, arena: &'arena Bump) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let ctx = DecodeContext::default();
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            self.merge_field(tag, wire_type, &mut buf, arena, ctx.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/// Merges a repeated scalar field, in either the packed or the unpacked encoding, using `merge`
+/// to decode each value of the expected wire type.
+pub fn merge_repeated_scalar<'arena, T, B, M>(
+    wire_type: WireType,
+    expected: WireType,
+    values: &mut Vec<'arena, T>,
+    buf: &mut B,
+    ctx: DecodeContext,
+    mut merge: M,
+) -> Result<(), DecodeError>
+where
+    T: Default,
+    B: Buf,
+    M: FnMut(WireType, &mut T, &mut B, DecodeContext) -> Result<(), DecodeError>,
+{
+    if wire_type == WireType::LengthDelimited {
+        // Packed.
+        merge_loop(values, buf, ctx, |values, buf, ctx| {
+            let mut value = T::default();
+            merge(expected, &mut value, buf, ctx)?;
+            values.push(value);
+            Ok(())
+        })
+    } else {
+        // Unpacked.
+        check_wire_type(expected, wire_type)?;
+        let mut value = T::default();
+        merge(wire_type, &mut value, buf, ctx)?;
+        values.push(value);
+        Ok(())
+    }
+}
+
+/// Copies a length-delimited value from the buffer into the arena.
+fn merge_slice<'arena, B>(
+    wire_type: WireType,
+    buf: &mut B,
+    arena: &'arena Bump,
+) -> Result<&'arena [u8], DecodeError>
+where
+    B: Buf,
+{
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+    let len = decode_varint(buf)?;
+    if len > buf.remaining() as u64 {
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEof,
+            "buffer underflow",
+        ));
+    }
+
+    let value = arena.alloc_slice_fill_copy(len as usize, 0u8);
+    buf.copy_to_slice(value);
+    Ok(value)
+}
+
+pub mod string {
+    use super::*;
+
+    pub fn encode<B>(tag: u32, value: &str, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        bytes::encode(tag, value.as_bytes(), buf);
+    }
+
+    pub fn merge<'arena, B>(
+        wire_type: WireType,
+        value: &mut &'arena str,
+        buf: &mut B,
+        arena: &'arena Bump,
+        _ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let bytes = merge_slice(wire_type, buf, arena)?;
+        *value = str::from_utf8(bytes).map_err(|_| {
+            DecodeError::with_kind(
+                DecodeErrorKind::InvalidUtf8,
+                "invalid string value: data is not UTF-8 encoded",
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn encode_repeated<B>(tag: u32, values: &[&str], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        for value in values {
+            encode(tag, value, buf);
+        }
+    }
+
+    pub fn merge_repeated<'arena, B>(
+        wire_type: WireType,
+        values: &mut Vec<'arena, &'arena str>,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = "";
+        merge(wire_type, &mut value, buf, arena, ctx)?;
+        values.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &str) -> usize {
+        bytes::encoded_len(tag, value.as_bytes())
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated(tag: u32, values: &[&str]) -> usize {
+        values
+            .iter()
+            .map(|value| encoded_len(tag, value))
+            .sum::<usize>()
+    }
+}
+
+pub mod bytes {
+    use super::*;
+
+    pub fn encode<B>(tag: u32, value: &[u8], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+
+    pub fn merge<'arena, B>(
+        wire_type: WireType,
+        value: &mut &'arena [u8],
+        buf: &mut B,
+        arena: &'arena Bump,
+        _ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        *value = merge_slice(wire_type, buf, arena)?;
+        Ok(())
+    }
+
+    pub fn encode_repeated<B>(tag: u32, values: &[&[u8]], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        for value in values {
+            encode(tag, value, buf);
+        }
+    }
+
+    pub fn merge_repeated<'arena, B>(
+        wire_type: WireType,
+        values: &mut Vec<'arena, &'arena [u8]>,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value: &[u8] = &[];
+        merge(wire_type, &mut value, buf, arena, ctx)?;
+        values.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &[u8]) -> usize {
+        key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated(tag: u32, values: &[&[u8]]) -> usize {
+        values
+            .iter()
+            .map(|value| encoded_len(tag, value))
+            .sum::<usize>()
+    }
+}
+
+pub mod message {
+    use super::*;
+
+    pub fn encode<'arena, M, B>(tag: u32, msg: &M, buf: &mut B)
+    where
+        M: ArenaMessage<'arena>,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(msg.encoded_len() as u64, buf);
+        msg.encode_raw(buf);
+    }
+
+    pub fn merge<'arena, M, B>(
+        wire_type: WireType,
+        msg: &mut M,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: ArenaMessage<'arena>,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.limit_reached()?;
+        merge_loop(
+            msg,
+            buf,
+            ctx.enter_recursion(),
+            |msg: &mut M, buf: &mut B, ctx| {
+                let (tag, wire_type) = decode_key(buf)?;
+                msg.merge_field(tag, wire_type, buf, arena, ctx)
+            },
+        )
+    }
+
+    /// Encodes an optional submessage, writing nothing when it is `None`.
+    pub fn encode_optional<'arena, M, B>(tag: u32, msg: &Option<M>, buf: &mut B)
+    where
+        M: ArenaMessage<'arena>,
+        B: BufMut,
+    {
+        if let Some(msg) = msg {
+            encode(tag, msg, buf);
+        }
+    }
+
+    /// Merges a submessage into an optional field, starting from an empty message when it is
+    /// `None`.
+    pub fn merge_optional<'arena, M, B>(
+        wire_type: WireType,
+        msg: &mut Option<M>,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: ArenaMessage<'arena>,
+        B: Buf,
+    {
+        merge(
+            wire_type,
+            msg.get_or_insert_with(|| M::new_in(arena)),
+            buf,
+            arena,
+            ctx,
+        )
+    }
+
+    pub fn encode_repeated<'arena, M, B>(tag: u32, messages: &[M], buf: &mut B)
+    where
+        M: ArenaMessage<'arena>,
+        B: BufMut,
+    {
+        for msg in messages {
+            encode(tag, msg, buf);
+        }
+    }
+
+    pub fn merge_repeated<'arena, M, B>(
+        wire_type: WireType,
+        messages: &mut Vec<'arena, M>,
+        buf: &mut B,
+        arena: &'arena Bump,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: ArenaMessage<'arena>,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let mut msg = M::new_in(arena);
+        merge(WireType::LengthDelimited, &mut msg, buf, arena, ctx)?;
+        messages.push(msg);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len<'arena, M>(tag: u32, msg: &M) -> usize
+    where
+        M: ArenaMessage<'arena>,
+    {
+        let len = msg.encoded_len();
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    /// Returns the encoded length of an optional submessage, which is zero when it is `None`.
+    #[inline]
+    pub fn encoded_len_optional<'arena, M>(tag: u32, msg: &Option<M>) -> usize
+    where
+        M: ArenaMessage<'arena>,
+    {
+        msg.as_ref().map_or(0, |msg| encoded_len(tag, msg))
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated<'arena, M>(tag: u32, messages: &[M]) -> usize
+    where
+        M: ArenaMessage<'arena>,
+    {
+        messages
+            .iter()
+            .map(|msg| encoded_len(tag, msg))
+            .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_merge_copies_into_arena() {
+        let arena = Bump::new();
+        let mut buf: &[u8] = b"\x05hello";
+        let mut value = "";
+        string::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut buf,
+            &arena,
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(value, "hello");
+        assert!(buf.is_empty());
+
+        let mut encoded = alloc::vec::Vec::new();
+        string::encode(1, value, &mut encoded);
+        assert_eq!(encoded, b"\x0a\x05hello");
+        assert_eq!(string::encoded_len(1, value), encoded.len());
+    }
+
+    #[test]
+    fn string_merge_invalid_utf8() {
+        let arena = Bump::new();
+        let mut buf: &[u8] = b"\x02\xff\xfe";
+        let mut value = "";
+        assert!(string::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut buf,
+            &arena,
+            DecodeContext::default(),
+        )
+        .is_err());
+        assert_eq!(value, "");
+    }
+
+    #[test]
+    fn merge_repeated_scalar_packed_and_unpacked() {
+        let arena = Bump::new();
+        let mut values = Vec::new_in(&arena);
+
+        let mut buf: &[u8] = b"\x03\x01\x02\x03";
+        merge_repeated_scalar(
+            WireType::LengthDelimited,
+            WireType::Varint,
+            &mut values,
+            &mut buf,
+            DecodeContext::default(),
+            crate::encoding::int32::merge,
+        )
+        .unwrap();
+
+        let mut buf: &[u8] = b"\x04";
+        merge_repeated_scalar(
+            WireType::Varint,
+            WireType::Varint,
+            &mut values,
+            &mut buf,
+            DecodeContext::default(),
+            crate::encoding::int32::merge,
+        )
+        .unwrap();
+
+        assert_eq!(values.as_slice(), &[1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,73 @@
+//! Decoding of batches of messages against a shared allocation budget.
+
+use alloc::sync::Arc;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::Buf;
+
+use crate::encoding::{self, decode_key, DecodeContext, DecodeState, WireType};
+use crate::{DecodeError, Message};
+
+/// A byte budget shared by the decodes of a batch of messages.
+///
+/// Each decode charges the memory it allocates to the budget: the messages themselves, the
+/// elements of repeated and map fields, and the contents of string and bytes fields. Decoding
+/// fails once the budget is exhausted. Unlike a limit on the size of each message, this bounds the
+/// memory taken by a whole batch of untrusted input, however it is split between messages.
+///
+/// The accounting is approximate: it counts the size of every decoded value, not the capacity of
+/// the collections holding them. Clones of the context share its budget, and can decode on
+/// different threads.
+#[derive(Clone, Debug)]
+pub struct BatchDecodeContext {
+    remaining: Arc<AtomicUsize>,
+}
+
+impl BatchDecodeContext {
+    /// Creates a context with a budget of `bytes` bytes.
+    pub fn new(bytes: usize) -> BatchDecodeContext {
+        BatchDecodeContext {
+            remaining: Arc::new(AtomicUsize::new(bytes)),
+        }
+    }
+
+    /// Returns the number of bytes left in the budget.
+    pub fn remaining(&self) -> usize {
+        self.remaining.load(Ordering::Relaxed)
+    }
+
+    /// Decodes an instance of the message from a buffer, charging its allocations to the budget.
+    ///
+    /// The entire buffer will be consumed.
+    pub fn decode<M, B>(&self, mut buf: B) -> Result<M, DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        let state = DecodeState::with_byte_budget(self.remaining.clone());
+        let ctx = DecodeContext::with_state(&state);
+        ctx.charge_bytes(mem::size_of::<M>())?;
+        let mut message = M::default();
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            message.merge_field(tag, wire_type, &mut buf, ctx.clone())?;
+        }
+        Ok(message)
+    }
+
+    /// Decodes a length-delimited instance of the message from the buffer, charging its
+    /// allocations to the budget.
+    pub fn decode_length_delimited<M, B>(&self, mut buf: B) -> Result<M, DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        let state = DecodeState::with_byte_budget(self.remaining.clone());
+        let ctx = DecodeContext::with_state(&state);
+        ctx.charge_bytes(mem::size_of::<M>())?;
+        let mut message = M::default();
+        encoding::message::merge(WireType::LengthDelimited, &mut message, &mut buf, ctx)?;
+        Ok(message)
+    }
+}
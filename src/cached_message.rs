@@ -0,0 +1,181 @@
+//! Support for reusing the encoding of unmodified messages.
+
+use core::ops::{Deref, DerefMut};
+use core::slice;
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::encoding::{DecodeContext, WireType};
+use crate::{DecodeError, EncodedSizes, Message};
+
+/// A message stored along with the encoding it was decoded from.
+///
+/// Encoding a `CachedMessage` writes the cached encoding verbatim, as long as the message hasn't
+/// been mutably accessed since it was decoded. Mutable access, through [`get_mut`][1] or
+/// `DerefMut`, discards the cached encoding, so the message is encoded anew afterwards. This
+/// saves the cost of re-encoding in pass-through caches, which rarely modify the messages they
+/// hold.
+///
+/// When decoding from a [`Bytes`] buffer, the cached encoding shares it, so no data is copied.
+///
+/// [1]: CachedMessage::get_mut
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CachedMessage<M> {
+    message: M,
+    encoded: Option<Bytes>,
+}
+
+impl<M> CachedMessage<M>
+where
+    M: Message,
+{
+    /// Creates a `CachedMessage` without a cached encoding.
+    pub fn new(message: M) -> CachedMessage<M> {
+        CachedMessage {
+            message,
+            encoded: None,
+        }
+    }
+
+    /// Decodes a message from a buffer, caching its encoding.
+    ///
+    /// The entire buffer will be consumed.
+    pub fn decode<B>(mut buf: B) -> Result<CachedMessage<M>, DecodeError>
+    where
+        B: Buf,
+        M: Default,
+    {
+        let encoded = buf.copy_to_bytes(buf.remaining());
+        let message = M::decode(encoded.clone())?;
+        Ok(CachedMessage {
+            message,
+            encoded: Some(encoded),
+        })
+    }
+
+    /// Returns a reference to the message.
+    pub fn get(&self) -> &M {
+        &self.message
+    }
+
+    /// Returns a mutable reference to the message, discarding the cached encoding.
+    pub fn get_mut(&mut self) -> &mut M {
+        self.encoded = None;
+        &mut self.message
+    }
+
+    /// Returns the cached encoding of the message, if it hasn't been modified since it was
+    /// decoded.
+    pub fn cached(&self) -> Option<&Bytes> {
+        self.encoded.as_ref()
+    }
+
+    /// Returns the message, discarding the cached encoding.
+    pub fn into_inner(self) -> M {
+        self.message
+    }
+}
+
+impl<M> Deref for CachedMessage<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.message
+    }
+}
+
+impl<M> DerefMut for CachedMessage<M> {
+    fn deref_mut(&mut self) -> &mut M {
+        self.encoded = None;
+        &mut self.message
+    }
+}
+
+impl<M> From<M> for CachedMessage<M>
+where
+    M: Message,
+{
+    fn from(message: M) -> CachedMessage<M> {
+        CachedMessage::new(message)
+    }
+}
+
+impl<M> Message for CachedMessage<M>
+where
+    M: Message,
+{
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        match &self.encoded {
+            Some(encoded) => buf.put_slice(encoded),
+            None => self.message.encode_raw(buf),
+        }
+    }
+
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        self.encoded = None;
+        self.message.merge_field(tag, wire_type, buf, ctx)
+    }
+
+    fn encoded_len(&self) -> usize {
+        match &self.encoded {
+            Some(encoded) => encoded.len(),
+            None => self.message.encoded_len(),
+        }
+    }
+
+    fn encoded_len_cached(&self, sizes: &mut EncodedSizes) -> usize {
+        match &self.encoded {
+            Some(encoded) => encoded.len(),
+            None => self.message.encoded_len_cached(sizes),
+        }
+    }
+
+    fn encode_raw_cached<B>(&self, buf: &mut B, sizes: &mut slice::Iter<'_, usize>)
+    where
+        B: BufMut,
+    {
+        match &self.encoded {
+            Some(encoded) => buf.put_slice(encoded),
+            None => self.message.encode_raw_cached(buf, sizes),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.encoded = None;
+        self.message.clear()
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        self.encoded = None;
+        self.message.merge_from(other.message)
+    }
+
+    fn required_tags(&self) -> &'static [u32] {
+        self.message.required_tags()
+    }
+
+    fn check_required(&self, present: &[bool]) -> Result<(), DecodeError> {
+        self.message.check_required(present)
+    }
+
+    fn exceeds_depth(&self, limit: u32) -> bool {
+        self.message.exceeds_depth(limit)
+    }
+
+    fn clear_unknown_fields(&mut self) {
+        self.encoded = None;
+        self.message.clear_unknown_fields()
+    }
+}
@@ -0,0 +1,170 @@
+//! Schema-less inspection of encoded messages.
+
+use alloc::format;
+use alloc::string::String;
+use core::str;
+
+use bytes::Buf;
+
+use crate::encoding::{decode_key, decode_varint, WireType};
+use crate::{DecodeError, DecodeErrorKind};
+
+/// The maximum nesting depth of length-delimited fields which are printed as messages.
+const MAX_DEPTH: usize = 100;
+
+/// Prints the fields of an encoded message without knowing its type, like
+/// `protoc --decode_raw` does.
+///
+/// Each field is printed on its own line as its field number followed by its value: varints as
+/// unsigned integers, fixed-width values in hexadecimal, and groups as nested blocks.
+/// Length-delimited fields which parse as messages are printed as nested blocks, and the others as
+/// quoted strings, so strings and bytes may occasionally be mistaken for messages.
+///
+/// If the bytes are not a valid encoding, the fields up to the error are printed, followed by a
+/// line describing the error.
+///
+/// # Examples
+///
+/// ```rust
+/// let dump = prost::debug::dump(b"\x08\x96\x01\x12\x07testing\x1a\x02\x08\x01");
+/// assert_eq!(dump, "1: 150\n2: \"testing\"\n3 {\n  1: 1\n}\n");
+/// ```
+pub fn dump(buf: &[u8]) -> String {
+    let mut out = String::new();
+    if let Err(error) = dump_fields(&mut &*buf, 0, None, &mut out) {
+        out.push_str(&format!("error: {}\n", error));
+    }
+    out
+}
+
+/// Prints the fields of `buf` at the given nesting depth, up to the end of the group with field
+/// number `group`, if any.
+fn dump_fields(
+    buf: &mut &[u8],
+    depth: usize,
+    group: Option<u32>,
+    out: &mut String,
+) -> Result<(), DecodeError> {
+    while buf.has_remaining() {
+        let (tag, wire_type) = decode_key(buf)?;
+        match wire_type {
+            WireType::Varint => {
+                let value = decode_varint(buf)?;
+                push_line(out, depth, &format!("{}: {}", tag, value));
+            }
+            WireType::SixtyFourBit => {
+                if buf.remaining() < 8 {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::UnexpectedEof,
+                        "buffer underflow",
+                    ));
+                }
+                let value = buf.get_u64_le();
+                push_line(out, depth, &format!("{}: 0x{:016x}", tag, value));
+            }
+            WireType::ThirtyTwoBit => {
+                if buf.remaining() < 4 {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::UnexpectedEof,
+                        "buffer underflow",
+                    ));
+                }
+                let value = buf.get_u32_le();
+                push_line(out, depth, &format!("{}: 0x{:08x}", tag, value));
+            }
+            WireType::LengthDelimited => {
+                let len = decode_varint(buf)?;
+                if len > buf.remaining() as u64 {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::UnexpectedEof,
+                        "buffer underflow",
+                    ));
+                }
+                let (value, rest) = buf.split_at(len as usize);
+                *buf = rest;
+
+                let mut nested = String::new();
+                if !value.is_empty()
+                    && depth < MAX_DEPTH
+                    && dump_fields(&mut &*value, depth + 1, None, &mut nested).is_ok()
+                {
+                    push_line(out, depth, &format!("{} {{", tag));
+                    out.push_str(&nested);
+                    push_line(out, depth, "}");
+                } else {
+                    push_line(out, depth, &format!("{}: {}", tag, quote(value)));
+                }
+            }
+            WireType::StartGroup => {
+                if depth >= MAX_DEPTH {
+                    return Err(DecodeError::new("recursion limit reached"));
+                }
+                push_line(out, depth, &format!("{} {{", tag));
+                dump_fields(buf, depth + 1, Some(tag), out)?;
+                push_line(out, depth, "}");
+            }
+            WireType::EndGroup => {
+                if group == Some(tag) {
+                    return Ok(());
+                }
+                return Err(DecodeError::new("unexpected end group tag"));
+            }
+        }
+    }
+    match group {
+        Some(_) => Err(DecodeError::new("missing end group tag")),
+        None => Ok(()),
+    }
+}
+
+fn push_line(out: &mut String, depth: usize, line: &str) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Quotes a length-delimited value, escaping it as a string if it is valid UTF-8, or byte by
+/// byte otherwise.
+fn quote(value: &[u8]) -> String {
+    match str::from_utf8(value) {
+        Ok(value) => format!("{:?}", value),
+        Err(_) => {
+            let mut quoted = String::from("\"");
+            for &byte in value {
+                quoted.extend(core::ascii::escape_default(byte).map(char::from));
+            }
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_wire_types() {
+        let buf =
+            b"\x09\x01\x00\x00\x00\x00\x00\x00\x00\x15\xff\x00\x00\x00\x1b\x08\x05\x1c\x22\x00";
+        assert_eq!(
+            dump(buf),
+            "1: 0x0000000000000001\n2: 0x000000ff\n3 {\n  1: 5\n}\n4: \"\"\n"
+        );
+    }
+
+    #[test]
+    fn dump_bytes() {
+        assert_eq!(dump(b"\x0a\x02\xff\x00"), "1: \"\\xff\\x00\"\n");
+    }
+
+    #[test]
+    fn dump_invalid() {
+        assert_eq!(
+            dump(b"\x08\x01\x10"),
+            "1: 1\nerror: failed to decode Protobuf message: invalid varint\n"
+        );
+    }
+}
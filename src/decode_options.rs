@@ -0,0 +1,177 @@
+//! Options for decoding messages, and statistics about the decodes made with them.
+
+use alloc::vec::Vec;
+
+use crate::DecodeError;
+
+/// Options for decoding a message with [`Message::decode_with_options`][1].
+///
+/// The default options decode as [`Message::decode`][2] does. Each option is set with a builder
+/// method, and options combine freely:
+///
+/// ```
+/// # use prost::{DecodeOptions, RequiredFieldPolicy};
+/// let options = DecodeOptions::new()
+///     .recursion_limit(16)
+///     .max_fields(10_000)
+///     .required_field_policy(RequiredFieldPolicy::Warn);
+/// ```
+///
+/// [1]: crate::Message::decode_with_options
+/// [2]: crate::Message::decode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    pub(crate) recursion_limit: Option<u32>,
+    pub(crate) max_fields: Option<usize>,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) required_field_policy: RequiredFieldPolicy,
+    pub(crate) share_input: bool,
+}
+
+impl DecodeOptions {
+    /// Creates the default options, which decode as [`Message::decode`][1] does.
+    ///
+    /// [1]: crate::Message::decode
+    pub fn new() -> DecodeOptions {
+        DecodeOptions::default()
+    }
+
+    /// Fails decoding once messages and groups nest more than `limit` levels deep.
+    ///
+    /// A message whose fields are all scalars decodes with a limit of `0`. A lower limit bounds
+    /// the stack used to decode untrusted input. The limit has no effect if the crate is built
+    /// with the `no-recursion-limit` feature.
+    ///
+    /// Defaults to `100`.
+    pub fn recursion_limit(mut self, limit: u32) -> Self {
+        self.recursion_limit = Some(limit);
+        self
+    }
+
+    /// Fails decoding once more than `max_fields` fields have been decoded.
+    ///
+    /// Every field counts, including each element of a repeated field, whether packed or not, and
+    /// the fields of nested messages, groups, map entries and skipped unknown groups. This bounds
+    /// the work spent on untrusted input made of very many tiny fields.
+    ///
+    /// Defaults to no limit.
+    pub fn max_fields(mut self, max_fields: usize) -> Self {
+        self.max_fields = Some(max_fields);
+        self
+    }
+
+    /// Replaces invalid UTF-8 in string fields with `U+FFFD REPLACEMENT CHARACTER`, as
+    /// `String::from_utf8_lossy` does, rather than failing.
+    ///
+    /// This suits tolerant ingestion, where a lossy string is preferable to dropping the whole
+    /// message. Only strings holding invalid UTF-8 are reallocated, each into a new buffer of its
+    /// own: a scratch buffer provided by the caller can't be reused, as `DecodeOptions` holds no
+    /// references.
+    ///
+    /// Defaults to `false`.
+    pub fn lossy_utf8(mut self, enabled: bool) -> Self {
+        self.lossy_utf8 = enabled;
+        self
+    }
+
+    /// Sets how messages and groups missing any of their proto2 `required` fields are treated.
+    ///
+    /// [`Message::decode`][1] takes missing required fields as their defaults, as
+    /// [`RequiredFieldPolicy::Ignore`] does. The other policies check each message and group in
+    /// the buffer. A submessage split across several occurrences of its field, which encoders
+    /// don't produce, must have its required fields in each occurrence. Messages without required
+    /// fields, such as proto3 messages, decode as usual.
+    ///
+    /// Defaults to [`RequiredFieldPolicy::Ignore`].
+    ///
+    /// [1]: crate::Message::decode
+    pub fn required_field_policy(mut self, policy: RequiredFieldPolicy) -> Self {
+        self.required_field_policy = policy;
+        self
+    }
+
+    /// Fails decoding a message or group if any of its proto2 `required` fields is missing.
+    ///
+    /// This sets the [`required_field_policy`](DecodeOptions::required_field_policy) to
+    /// [`RequiredFieldPolicy::Error`] if `enabled`, and to [`RequiredFieldPolicy::Ignore`]
+    /// otherwise.
+    ///
+    /// Defaults to `false`.
+    pub fn check_required(self, enabled: bool) -> Self {
+        self.required_field_policy(if enabled {
+            RequiredFieldPolicy::Error
+        } else {
+            RequiredFieldPolicy::Ignore
+        })
+    }
+
+    /// Copies the input into a single buffer before decoding, which the [`SharedBytes`][1] and
+    /// [`Bytes`][2] fields of the message are then slices of.
+    ///
+    /// Without this option, such fields are slices of the input only if it is a `Bytes` buffer;
+    /// decoding from any other buffer allocates a copy per field. With it, decoding allocates
+    /// once, for a copy of the input, and not at all for a `Bytes` input.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [1]: crate::SharedBytes
+    /// [2]: bytes::Bytes
+    pub fn share_input(mut self, enabled: bool) -> Self {
+        self.share_input = enabled;
+        self
+    }
+}
+
+/// How decoding treats a message or group missing any of its proto2 `required` fields, as set
+/// with [`DecodeOptions::required_field_policy`].
+///
+/// `Warn` suits migrating to strict validation: the missing fields it reports can be logged until
+/// senders are fixed, and `Error` enforced then.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequiredFieldPolicy {
+    /// Missing required fields take their default values, as with [`Message::decode`][1].
+    ///
+    /// [1]: crate::Message::decode
+    Ignore,
+    /// Missing required fields take their default values, and each is reported in
+    /// [`DecodeStats::missing_required_fields`].
+    ///
+    /// Reporting requires the `std` feature; without it, `Warn` behaves as `Ignore`.
+    Warn,
+    /// Decoding fails with an error of kind
+    /// [`MissingRequiredField`](crate::DecodeErrorKind::MissingRequiredField) naming the first
+    /// required field missing.
+    Error,
+}
+
+impl Default for RequiredFieldPolicy {
+    fn default() -> RequiredFieldPolicy {
+        RequiredFieldPolicy::Ignore
+    }
+}
+
+/// Statistics about a decode, returned by [`Message::decode_with_options`][1].
+///
+/// [1]: crate::Message::decode_with_options
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// The maximum nesting depth reached, counting the nested messages and groups entered, so a
+    /// message whose fields are all scalars has depth `0`.
+    ///
+    /// This is useful to tune recursion limits against real traffic, or to inspect adversarial
+    /// inputs.
+    pub max_depth: u32,
+    /// The number of bytes, keys included, taken by the unknown fields skipped, including those of
+    /// nested messages.
+    ///
+    /// A growing share of unknown bytes indicates that senders use a newer schema than the
+    /// receiver.
+    pub skipped_bytes: usize,
+    /// The required fields missing from the messages and groups decoded, with
+    /// [`RequiredFieldPolicy::Warn`].
+    ///
+    /// Each is an error of kind
+    /// [`MissingRequiredField`](crate::DecodeErrorKind::MissingRequiredField) naming the field
+    /// and the message missing it, in the order the messages end in the buffer.
+    pub missing_required_fields: Vec<DecodeError>,
+}
@@ -0,0 +1,47 @@
+//! Object-safe access to the decoding of individual message fields.
+
+use bytes::Buf;
+
+use crate::encoding::{DecodeContext, WireType};
+use crate::DecodeError;
+use crate::Message;
+
+/// An object-safe extension of [`Message`], implemented for every message type.
+///
+/// [`Message::merge_field`] is generic over the buffer type, so it can't be called through a
+/// `dyn Message`. This trait exposes the same operation for a `&mut dyn Buf`, which allows
+/// driving decodes field-by-field generically, for example to route the fields of an incoming
+/// message to different messages chosen at runtime.
+pub trait DynMessage: Message {
+    /// Decodes a field with the given tag and wire type from a buffer, and merges it into `self`.
+    ///
+    /// The key of the field must already have been consumed from `buf`, for instance with
+    /// [`encoding::decode_key`](crate::encoding::decode_key).
+    fn dyn_merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut dyn Buf,
+    ) -> Result<(), DecodeError>;
+}
+
+impl<M> DynMessage for M
+where
+    M: Message,
+{
+    fn dyn_merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        mut buf: &mut dyn Buf,
+    ) -> Result<(), DecodeError> {
+        self.merge_field(tag, wire_type, &mut buf, DecodeContext::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const _DYN_MESSAGE_IS_OBJECT_SAFE: Option<&dyn DynMessage> = None;
+}
@@ -0,0 +1,61 @@
+//! Incremental encoding of messages into bounded chunks.
+
+use core::cmp;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::Message;
+
+/// An iterator which encodes a message incrementally, yielding chunks of bounded size.
+///
+/// Returned by [`Message::encode_chunks`]. The message is encoded one top-level field at a time,
+/// and only as far as needed to fill the next chunk, so a consumer that sends each chunk before
+/// requesting the next never holds more than one chunk plus the largest top-level field of the
+/// message in memory.
+#[derive(Debug)]
+pub struct EncodeChunks<'a, M> {
+    message: &'a M,
+    chunk_size: usize,
+    part: usize,
+    done: bool,
+    buf: BytesMut,
+}
+
+impl<'a, M> EncodeChunks<'a, M>
+where
+    M: Message,
+{
+    pub(crate) fn new(message: &'a M, chunk_size: usize) -> EncodeChunks<'a, M> {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+        EncodeChunks {
+            message,
+            chunk_size,
+            part: 0,
+            done: false,
+            buf: BytesMut::new(),
+        }
+    }
+}
+
+impl<'a, M> Iterator for EncodeChunks<'a, M>
+where
+    M: Message,
+{
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        while !self.done && self.buf.len() < self.chunk_size {
+            if self.message.encode_raw_part(self.part, &mut self.buf) {
+                self.part += 1;
+            } else {
+                self.done = true;
+            }
+        }
+
+        if self.buf.is_empty() {
+            return None;
+        }
+        let len = cmp::min(self.chunk_size, self.buf.len());
+        Some(self.buf.split_to(len).freeze())
+    }
+}
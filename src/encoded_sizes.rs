@@ -0,0 +1,65 @@
+//! Encoding with precomputed submessage lengths.
+
+use alloc::vec::Vec;
+
+use crate::Message;
+
+/// The encoded lengths of a message and of its submessages, computed in a single pass by
+/// [`Message::encoded_sizes`].
+///
+/// Submessages are prefixed with their length, so [`Message::encode`] computes the length of each
+/// submessage before encoding it, which in turn computes the lengths of the submessages nested in
+/// it. The fields of a message nested `n` levels deep are thus visited `n` times, and encoding
+/// takes time quadratic in the nesting depth. Passing the lengths recorded here to
+/// [`Message::encode_with_sizes`] instead makes encoding linear in the size of the message.
+///
+/// Lengths are recorded for submessage fields, including those in oneofs. The lengths of map
+/// values and groups are still computed as they're encoded.
+///
+/// The lengths are only valid for the message they were computed from, as long as it isn't
+/// modified.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncodedSizes {
+    len: usize,
+    /// The lengths of the submessages, in the order they're encoded.
+    nested: Vec<usize>,
+}
+
+impl EncodedSizes {
+    pub(crate) fn new<M>(message: &M) -> EncodedSizes
+    where
+        M: Message,
+    {
+        let mut sizes = EncodedSizes::default();
+        sizes.len = message.encoded_len_cached(&mut sizes);
+        sizes
+    }
+
+    /// Returns the encoded length of the message without a length delimiter.
+    pub fn encoded_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the lengths of the submessages, in the order they're encoded.
+    pub(crate) fn nested(&self) -> &[usize] {
+        &self.nested
+    }
+
+    /// Reserves the entry for the length of a submessage which is encoded next, returning its
+    /// index. The lengths of the submessages nested in it are recorded after it.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn reserve(&mut self) -> usize {
+        self.nested.push(0);
+        self.nested.len() - 1
+    }
+
+    /// Sets the length of the submessage at the entry with `index`, as returned by `reserve`.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn set(&mut self, index: usize, len: usize) {
+        self.nested[index] = len;
+    }
+}
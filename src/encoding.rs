@@ -7,18 +7,28 @@
 use alloc::collections::{BTreeMap, TryReserveError};
 use alloc::format;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cmp::min;
 use core::convert::TryFrom;
 use core::mem;
+use core::slice;
 use core::str;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use core::u32;
 use core::usize;
 
 use ::bytes::{Buf, BufMut, Bytes};
 
 use crate::DecodeError;
+use crate::DecodeErrorKind;
+use crate::DecodeOptions;
+use crate::DecodeStats;
+use crate::EncodedSizes;
 use crate::Message;
+use crate::RawMessage;
+use crate::RequiredFieldPolicy;
+use crate::SharedBytes;
 
 /// Encodes an integer value into LEB128 variable length format, and writes it to the buffer.
 /// The buffer must have enough remaining space (maximum 10 bytes).
@@ -47,7 +57,10 @@ where
     let bytes = buf.chunk();
     let len = bytes.len();
     if len == 0 {
-        return Err(DecodeError::new("invalid varint"));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEof,
+            "invalid varint",
+        ));
     }
 
     let byte = bytes[0];
@@ -152,7 +165,10 @@ fn decode_varint_slice(bytes: &[u8]) -> Result<(u64, usize), DecodeError> {
 
     // We have overrun the maximum size of a varint (10 bytes) or the final byte caused an overflow.
     // Assume the data is corrupt.
-    Err(DecodeError::new("invalid varint"))
+    Err(DecodeError::with_kind(
+        DecodeErrorKind::InvalidVarint,
+        "invalid varint",
+    ))
 }
 
 /// Decodes a LEB128-encoded variable length integer from the buffer, advancing the buffer as
@@ -168,21 +184,31 @@ where
     B: Buf,
 {
     let mut value = 0;
-    for count in 0..min(10, buf.remaining()) {
+    let limit = min(10, buf.remaining());
+    for count in 0..limit {
         let byte = buf.get_u8();
         value |= u64::from(byte & 0x7F) << (count * 7);
         if byte <= 0x7F {
             // Check for u64::MAX overflow. See [`ConsumeVarint`][1] for details.
             // [1]: https://github.com/protocolbuffers/protobuf-go/blob/v1.27.1/encoding/protowire/wire.go#L358
             if count == 9 && byte >= 0x02 {
-                return Err(DecodeError::new("invalid varint"));
+                return Err(DecodeError::with_kind(
+                    DecodeErrorKind::InvalidVarint,
+                    "invalid varint",
+                ));
             } else {
                 return Ok(value);
             }
         }
     }
 
-    Err(DecodeError::new("invalid varint"))
+    // Running out of input before the varint's final byte is a truncation, not corruption.
+    let kind = if limit < 10 {
+        DecodeErrorKind::UnexpectedEof
+    } else {
+        DecodeErrorKind::InvalidVarint
+    };
+    Err(DecodeError::with_kind(kind, "invalid varint"))
 }
 
 /// Additional information passed to every decode/merge function.
@@ -191,45 +217,205 @@ where
 /// to a function which is decoding a nested object, then use `enter_recursion`.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "no-recursion-limit", derive(Default))]
-pub struct DecodeContext {
+pub struct DecodeContext<'a> {
     /// How many times we can recurse in the current decode stack before we hit
     /// the recursion limit.
     ///
-    /// The recursion limit is defined by `RECURSION_LIMIT` and cannot be
-    /// customized. The recursion limit can be ignored by building the Prost
-    /// crate with the `no-recursion-limit` feature.
+    /// The recursion limit defaults to `RECURSION_LIMIT`, and can be customized
+    /// with `DecodeOptions::recursion_limit`. The recursion limit can be
+    /// ignored by building the Prost crate with the `no-recursion-limit` feature.
     #[cfg(not(feature = "no-recursion-limit"))]
     recurse_count: u32,
+
+    /// How many times we have recursed in the current decode stack.
+    depth: u32,
+
+    /// The state shared by all contexts of a decode made with `DecodeOptions` or a
+    /// `BatchDecodeContext`. It is borrowed, so that plain decodes, which have none, don't pay
+    /// for dropping the context.
+    state: Option<&'a DecodeState>,
+}
+
+/// The state shared by the contexts of a decode, see `DecodeContext::with_state`.
+#[derive(Debug, Default)]
+pub(crate) struct DecodeState {
+    /// The recursion limit, if set with `DecodeOptions::recursion_limit`.
+    #[cfg(not(feature = "no-recursion-limit"))]
+    recursion_limit: Option<u32>,
+
+    /// The maximum depth reached by any decode stack sharing this state.
+    max_depth: AtomicU32,
+
+    /// The number of bytes of unknown fields skipped by any decode stack sharing this state.
+    skipped_bytes: AtomicUsize,
+
+    /// The number of fields decodes sharing this state may still process, if a limit was set
+    /// with `DecodeOptions::max_fields`.
+    remaining_fields: Option<AtomicUsize>,
+
+    /// The number of bytes decodes sharing this state may still allocate, if a budget was set
+    /// with a `BatchDecodeContext`.
+    remaining_bytes: Option<Arc<AtomicUsize>>,
+
+    /// Whether invalid UTF-8 in string fields is replaced, as requested with
+    /// `DecodeOptions::lossy_utf8`.
+    lossy_utf8: bool,
+
+    /// How decoded messages and groups missing required fields are treated, as requested with
+    /// `DecodeOptions::required_field_policy`.
+    required_field_policy: RequiredFieldPolicy,
+
+    /// The required fields found missing by decodes sharing this state, with
+    /// `RequiredFieldPolicy::Warn`.
+    #[cfg(feature = "std")]
+    missing_required_fields: std::sync::Mutex<Vec<DecodeError>>,
+}
+
+impl DecodeState {
+    /// Creates the state of a decode with `options` applied.
+    pub(crate) fn new(options: &DecodeOptions) -> DecodeState {
+        DecodeState {
+            #[cfg(not(feature = "no-recursion-limit"))]
+            recursion_limit: options.recursion_limit,
+            remaining_fields: options.max_fields.map(AtomicUsize::new),
+            lossy_utf8: options.lossy_utf8,
+            required_field_policy: options.required_field_policy,
+            ..DecodeState::default()
+        }
+    }
+
+    /// Creates the state of a decode which charges the bytes it allocates to a shared budget.
+    pub(crate) fn with_byte_budget(remaining_bytes: Arc<AtomicUsize>) -> DecodeState {
+        DecodeState {
+            remaining_bytes: Some(remaining_bytes),
+            ..DecodeState::default()
+        }
+    }
+
+    /// Returns the statistics collected by the decodes which shared this state.
+    pub(crate) fn into_stats(self) -> DecodeStats {
+        DecodeStats {
+            max_depth: self.max_depth.into_inner(),
+            skipped_bytes: self.skipped_bytes.into_inner(),
+            #[cfg(feature = "std")]
+            missing_required_fields: self
+                .missing_required_fields
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            #[cfg(not(feature = "std"))]
+            missing_required_fields: Vec::new(),
+        }
+    }
 }
 
 #[cfg(not(feature = "no-recursion-limit"))]
-impl Default for DecodeContext {
+impl Default for DecodeContext<'_> {
     #[inline]
-    fn default() -> DecodeContext {
+    fn default() -> Self {
         DecodeContext {
             recurse_count: crate::RECURSION_LIMIT,
+            depth: 0,
+            state: None,
         }
     }
 }
 
-impl DecodeContext {
+/// Subtracts `count` from `remaining`, returning `false` if it is less than `count`.
+#[inline]
+fn consume(remaining: &AtomicUsize, count: usize) -> bool {
+    remaining
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+            remaining.checked_sub(count)
+        })
+        .is_ok()
+}
+
+impl<'a> DecodeContext<'a> {
+    /// Creates a context for a decode sharing `state`, which collects its statistics.
+    pub(crate) fn with_state(state: &'a DecodeState) -> DecodeContext<'a> {
+        DecodeContext {
+            #[cfg(not(feature = "no-recursion-limit"))]
+            recurse_count: state.recursion_limit.unwrap_or(crate::RECURSION_LIMIT),
+            state: Some(state),
+            ..DecodeContext::default()
+        }
+    }
+
+    /// Returns `true` if the context was created with a state, in which case decoding goes
+    /// through the accounting which plain decodes skip.
+    #[inline]
+    pub(crate) fn has_state(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Returns `true` if invalid UTF-8 in string fields is to be replaced rather than rejected.
+    #[inline]
+    pub(crate) fn lossy_utf8(&self) -> bool {
+        self.state.map_or(false, |state| state.lossy_utf8)
+    }
+
+    /// Returns how decoded messages and groups missing required fields are treated.
+    #[inline]
+    pub(crate) fn required_field_policy(&self) -> RequiredFieldPolicy {
+        self.state.map_or(RequiredFieldPolicy::Ignore, |state| {
+            state.required_field_policy
+        })
+    }
+
     /// Call this function before recursively decoding.
     ///
     /// There is no `exit` function since this function creates a new `DecodeContext`
     /// to be used at the next level of recursion. Continue to use the old context
     // at the previous level of recursion.
-    #[cfg(not(feature = "no-recursion-limit"))]
     #[inline]
-    pub(crate) fn enter_recursion(&self) -> DecodeContext {
+    pub(crate) fn enter_recursion(&self) -> DecodeContext<'a> {
+        let depth = self.depth + 1;
+        if let Some(state) = self.state {
+            state.max_depth.fetch_max(depth, Ordering::Relaxed);
+        }
         DecodeContext {
+            #[cfg(not(feature = "no-recursion-limit"))]
             recurse_count: self.recurse_count - 1,
+            depth,
+            state: self.state,
         }
     }
 
-    #[cfg(feature = "no-recursion-limit")]
+    /// Charges `bytes` allocated bytes against the byte budget of the decodes sharing this
+    /// context.
+    ///
+    /// Returns `Err<DecodeError>` if the budget set with a `BatchDecodeContext` is exhausted.
+    #[inline]
+    pub(crate) fn charge_bytes(&self, bytes: usize) -> Result<(), DecodeError> {
+        if let Some(remaining_bytes) = self.state.and_then(|state| state.remaining_bytes.as_ref()) {
+            if !consume(remaining_bytes, bytes) {
+                return Err(DecodeError::new("byte budget exhausted"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts `count` fields against the field limit of the decodes sharing this context.
+    ///
+    /// Returns `Err<DecodeError>` if the limit set with `DecodeOptions::max_fields` is exceeded.
+    #[inline]
+    pub(crate) fn count_fields(&self, count: usize) -> Result<(), DecodeError> {
+        if let Some(remaining_fields) = self.state.and_then(|state| state.remaining_fields.as_ref())
+        {
+            if !consume(remaining_fields, count) {
+                return Err(DecodeError::new("field limit reached"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `bytes` to the number of bytes of unknown fields skipped by the decodes sharing this
+    /// context.
     #[inline]
-    pub(crate) fn enter_recursion(&self) -> DecodeContext {
-        DecodeContext {}
+    fn count_skipped(&self, bytes: usize) {
+        if let Some(state) = self.state {
+            state.skipped_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
     }
 
     /// Checks whether the recursion limit has been reached in the stack of
@@ -255,6 +441,17 @@ impl DecodeContext {
     }
 }
 
+/// Returns `true` if the value is equal to its type's default.
+///
+/// Used by generated code as a `skip_serializing_if` predicate for fields without presence.
+#[inline]
+pub fn is_default<T>(value: &T) -> bool
+where
+    T: Default + PartialEq,
+{
+    *value == T::default()
+}
+
 /// Returns the encoded length of the value in LEB128 variable length format.
 /// The returned value will be between 1 and 10, inclusive.
 #[inline]
@@ -290,10 +487,10 @@ impl TryFrom<u64> for WireType {
             3 => Ok(WireType::StartGroup),
             4 => Ok(WireType::EndGroup),
             5 => Ok(WireType::ThirtyTwoBit),
-            _ => Err(DecodeError::new(format!(
-                "invalid wire type value: {}",
-                value
-            ))),
+            _ => Err(DecodeError::with_kind(
+                DecodeErrorKind::InvalidWireType,
+                format!("invalid wire type value: {}", value),
+            )),
         }
     }
 }
@@ -319,13 +516,19 @@ where
 {
     let key = decode_varint(buf)?;
     if key > u64::from(u32::MAX) {
-        return Err(DecodeError::new(format!("invalid key value: {}", key)));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidTag,
+            format!("invalid key value: {}", key),
+        ));
     }
     let wire_type = WireType::try_from(key & 0x07)?;
     let tag = key as u32 >> 3;
 
     if tag < MIN_TAG {
-        return Err(DecodeError::new("invalid tag value: 0"));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidTag,
+            "invalid tag value: 0",
+        ));
     }
 
     Ok((tag, wire_type))
@@ -343,10 +546,10 @@ pub fn key_len(tag: u32) -> usize {
 #[inline]
 pub fn check_wire_type(expected: WireType, actual: WireType) -> Result<(), DecodeError> {
     if expected != actual {
-        return Err(DecodeError::new(format!(
-            "invalid wire type: {:?} (expected {:?})",
-            actual, expected
-        )));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidWireType,
+            format!("invalid wire type: {:?} (expected {:?})", actual, expected),
+        ));
     }
     Ok(())
 }
@@ -366,7 +569,10 @@ where
     let len = decode_varint(buf)?;
     let remaining = buf.remaining();
     if len > remaining as u64 {
-        return Err(DecodeError::new("buffer underflow"));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEof,
+            "buffer underflow",
+        ));
     }
 
     let limit = remaining - len as usize;
@@ -380,6 +586,78 @@ where
     Ok(())
 }
 
+/// Records which required fields of a message were decoded, if the decode context checks them.
+pub(crate) struct RequiredFields {
+    tags: &'static [u32],
+    present: Vec<bool>,
+    policy: RequiredFieldPolicy,
+}
+
+impl RequiredFields {
+    pub(crate) fn new<M>(msg: &M, ctx: &DecodeContext) -> RequiredFields
+    where
+        M: Message,
+    {
+        let policy = ctx.required_field_policy();
+        let tags = match policy {
+            RequiredFieldPolicy::Ignore => &[],
+            RequiredFieldPolicy::Warn | RequiredFieldPolicy::Error => msg.required_tags(),
+        };
+        RequiredFields {
+            tags,
+            present: alloc::vec![false; tags.len()],
+            policy,
+        }
+    }
+
+    /// Records that a field with `tag` was decoded.
+    #[inline]
+    pub(crate) fn mark(&mut self, tag: u32) {
+        if let Some(index) = self.tags.iter().position(|&required| required == tag) {
+            self.present[index] = true;
+        }
+    }
+
+    /// Returns an error naming the first required field of `msg` which wasn't decoded, or with
+    /// `RequiredFieldPolicy::Warn`, reports each such field to the state of `ctx`.
+    ///
+    /// Reporting needs a mutex, so without the `std` feature the missing fields are not reported.
+    pub(crate) fn check<M>(&self, msg: &M, ctx: &DecodeContext) -> Result<(), DecodeError>
+    where
+        M: Message,
+    {
+        if self.tags.is_empty() {
+            return Ok(());
+        }
+        match (self.policy, ctx.state) {
+            #[cfg(feature = "std")]
+            (RequiredFieldPolicy::Warn, Some(state)) => {
+                // `check_required` names the first missing field, so ask about each in turn.
+                let mut present = alloc::vec![true; self.present.len()];
+                let mut missing = Vec::new();
+                for index in (0..present.len()).filter(|&index| !self.present[index]) {
+                    present[index] = false;
+                    if let Err(error) = msg.check_required(&present) {
+                        missing.push(error);
+                    }
+                    present[index] = true;
+                }
+                if !missing.is_empty() {
+                    state
+                        .missing_required_fields
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .append(&mut missing);
+                }
+                Ok(())
+            }
+            #[cfg(not(feature = "std"))]
+            (RequiredFieldPolicy::Warn, Some(_)) => Ok(()),
+            _ => msg.check_required(&self.present),
+        }
+    }
+}
+
 pub fn skip_field<B>(
     wire_type: WireType,
     tag: u32,
@@ -390,6 +668,7 @@ where
     B: Buf,
 {
     ctx.limit_reached()?;
+    let remaining = buf.remaining();
     let len = match wire_type {
         WireType::Varint => decode_varint(buf).map(|_| 0)?,
         WireType::ThirtyTwoBit => 4,
@@ -397,6 +676,7 @@ where
         WireType::LengthDelimited => decode_varint(buf)?,
         WireType::StartGroup => loop {
             let (inner_tag, inner_wire_type) = decode_key(buf)?;
+            ctx.count_fields(1)?;
             match inner_wire_type {
                 WireType::EndGroup => {
                     if inner_tag != tag {
@@ -411,10 +691,22 @@ where
     };
 
     if len > buf.remaining() as u64 {
-        return Err(DecodeError::new("buffer underflow"));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEof,
+            "buffer underflow",
+        ));
     }
 
     buf.advance(len as usize);
+
+    if ctx.has_state() {
+        let value_len = match wire_type {
+            // The fields within a group count themselves as they are skipped, leaving its end key.
+            WireType::StartGroup => key_len(tag),
+            _ => remaining - buf.remaining(),
+        };
+        ctx.count_skipped(key_len(tag) + value_len);
+    }
     Ok(())
 }
 
@@ -448,9 +740,14 @@ macro_rules! merge_repeated_numeric {
             B: Buf,
         {
             if wire_type == WireType::LengthDelimited {
-                // Packed.
+                // Packed. The field's key was counted as its first element.
+                let start = values.len();
                 merge_loop(values, buf, ctx, |values, buf, ctx| {
+                    if values.len() > start {
+                        ctx.count_fields(1)?;
+                    }
                     let mut value = Default::default();
+                    ctx.charge_bytes(mem::size_of::<$ty>())?;
                     $merge($wire_type, &mut value, buf, ctx)?;
                     values.try_reserve(1)?;
                     values.push(value);
@@ -459,6 +756,7 @@ macro_rules! merge_repeated_numeric {
             } else {
                 // Unpacked.
                 check_wire_type($wire_type, wire_type)?;
+                ctx.charge_bytes(mem::size_of::<$ty>())?;
                 let mut value = Default::default();
                 $merge(wire_type, &mut value, buf, ctx)?;
                 values.try_reserve(1)?;
@@ -630,7 +928,10 @@ macro_rules! fixed_width {
             {
                 check_wire_type($wire_type, wire_type)?;
                 if buf.remaining() < $width {
-                    return Err(DecodeError::new("buffer underflow"));
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::UnexpectedEof,
+                        "buffer underflow",
+                    ));
                 }
                 *value = buf.$get();
                 Ok(())
@@ -655,7 +956,49 @@ macro_rules! fixed_width {
                 }
             }
 
-            merge_repeated_numeric!($ty, $wire_type, merge, merge_repeated);
+            pub fn merge_repeated<B>(
+                wire_type: WireType,
+                values: &mut Vec<$ty>,
+                buf: &mut B,
+                ctx: DecodeContext,
+            ) -> Result<(), DecodeError>
+            where
+                B: Buf,
+            {
+                if wire_type != WireType::LengthDelimited {
+                    // Unpacked.
+                    check_wire_type($wire_type, wire_type)?;
+                    ctx.charge_bytes(mem::size_of::<$ty>())?;
+                    let mut value = Default::default();
+                    merge(wire_type, &mut value, buf, ctx)?;
+                    values.try_reserve(1)?;
+                    values.push(value);
+                    return Ok(());
+                }
+
+                // Packed. The elements have a fixed width, so the length must be a multiple of it.
+                let len = decode_varint(buf)?;
+                if len > buf.remaining() as u64 {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::UnexpectedEof,
+                        "buffer underflow",
+                    ));
+                }
+                if len % $width != 0 {
+                    return Err(DecodeError::new(
+                        "packed field length not a multiple of element size",
+                    ));
+                }
+                let count = (len / $width) as usize;
+                // The field's key was counted as its first element.
+                ctx.count_fields(count.saturating_sub(1))?;
+                ctx.charge_bytes(len as usize)?;
+                values.try_reserve(count)?;
+                for _ in 0..count {
+                    values.push(buf.$get());
+                }
+                Ok(())
+            }
 
             #[inline]
             pub fn encoded_len(tag: u32, _: &$ty) -> usize {
@@ -756,35 +1099,134 @@ fixed_width!(
     get_i64_le
 );
 
+/// Encoding functions for integer fields represented as `NonZero*` types, which reject zero values
+/// when decoding.
+///
+/// Singular fields are held in an `Option`, as they may be absent. The values are encoded and
+/// decoded as their underlying integers, by the functions of the module for the same type.
+pub mod nonzero {
+    use core::num::{NonZeroI32, NonZeroI64, NonZeroU32, NonZeroU64};
+
+    use super::*;
+
+    fn zero_value() -> DecodeError {
+        DecodeError::new("zero value in non-zero field")
+    }
+
+    /// Macro which emits a module containing a set of encoding functions for a `NonZero*` type,
+    /// in terms of those of the module for its underlying integer type.
+    macro_rules! nonzero {
+        ($nonzero_ty:ty, $ty:ty, $proto_ty:ident) => {
+            pub mod $proto_ty {
+                use super::*;
+
+                /// Returns the values as their underlying integers.
+                fn get(values: &[$nonzero_ty]) -> &[$ty] {
+                    // SAFETY: the `NonZero*` types have the same layout as their integer types,
+                    // and their values are valid integers.
+                    unsafe { slice::from_raw_parts(values.as_ptr() as *const $ty, values.len()) }
+                }
+
+                pub fn encode<B>(tag: u32, value: &$nonzero_ty, buf: &mut B)
+                where
+                    B: BufMut,
+                {
+                    super::super::$proto_ty::encode(tag, &value.get(), buf);
+                }
+
+                pub fn merge<B>(
+                    wire_type: WireType,
+                    value: &mut Option<$nonzero_ty>,
+                    buf: &mut B,
+                    ctx: DecodeContext,
+                ) -> Result<(), DecodeError>
+                where
+                    B: Buf,
+                {
+                    let mut raw = 0;
+                    super::super::$proto_ty::merge(wire_type, &mut raw, buf, ctx)?;
+                    *value = Some(<$nonzero_ty>::new(raw).ok_or_else(zero_value)?);
+                    Ok(())
+                }
+
+                pub fn encode_repeated<B>(tag: u32, values: &[$nonzero_ty], buf: &mut B)
+                where
+                    B: BufMut,
+                {
+                    super::super::$proto_ty::encode_repeated(tag, get(values), buf);
+                }
+
+                pub fn encode_packed<B>(tag: u32, values: &[$nonzero_ty], buf: &mut B)
+                where
+                    B: BufMut,
+                {
+                    super::super::$proto_ty::encode_packed(tag, get(values), buf);
+                }
+
+                pub fn merge_repeated<B>(
+                    wire_type: WireType,
+                    values: &mut Vec<$nonzero_ty>,
+                    buf: &mut B,
+                    ctx: DecodeContext,
+                ) -> Result<(), DecodeError>
+                where
+                    B: Buf,
+                {
+                    if wire_type != WireType::LengthDelimited {
+                        // Unpacked.
+                        ctx.charge_bytes(mem::size_of::<$nonzero_ty>())?;
+                        let mut value = None;
+                        merge(wire_type, &mut value, buf, ctx)?;
+                        values.try_reserve(1)?;
+                        values.extend(value);
+                        return Ok(());
+                    }
+
+                    // Packed. The elements are decoded as integers, and then checked.
+                    let mut raw = Vec::new();
+                    super::super::$proto_ty::merge_repeated(wire_type, &mut raw, buf, ctx)?;
+                    values.try_reserve(raw.len())?;
+                    for value in raw {
+                        values.push(<$nonzero_ty>::new(value).ok_or_else(zero_value)?);
+                    }
+                    Ok(())
+                }
+
+                #[inline]
+                pub fn encoded_len(tag: u32, value: &$nonzero_ty) -> usize {
+                    super::super::$proto_ty::encoded_len(tag, &value.get())
+                }
+
+                #[inline]
+                pub fn encoded_len_repeated(tag: u32, values: &[$nonzero_ty]) -> usize {
+                    super::super::$proto_ty::encoded_len_repeated(tag, get(values))
+                }
+
+                #[inline]
+                pub fn encoded_len_packed(tag: u32, values: &[$nonzero_ty]) -> usize {
+                    super::super::$proto_ty::encoded_len_packed(tag, get(values))
+                }
+            }
+        };
+    }
+
+    nonzero!(NonZeroI32, i32, int32);
+    nonzero!(NonZeroI64, i64, int64);
+    nonzero!(NonZeroU32, u32, uint32);
+    nonzero!(NonZeroU64, u64, uint64);
+    nonzero!(NonZeroI32, i32, sint32);
+    nonzero!(NonZeroI64, i64, sint64);
+    nonzero!(NonZeroU32, u32, fixed32);
+    nonzero!(NonZeroU64, u64, fixed64);
+    nonzero!(NonZeroI32, i32, sfixed32);
+    nonzero!(NonZeroI64, i64, sfixed64);
+}
+
 /// Macro which emits encoding functions for a length-delimited type.
 macro_rules! length_delimited {
     ($ty:ty) => {
         encode_repeated!($ty);
 
-        pub fn merge_repeated<B>(
-            wire_type: WireType,
-            values: &mut Vec<$ty>,
-            buf: &mut B,
-            ctx: DecodeContext,
-        ) -> Result<(), DecodeError>
-        where
-            B: Buf,
-        {
-            check_wire_type(WireType::LengthDelimited, wire_type)?;
-            let mut value = Default::default();
-            merge(wire_type, &mut value, buf, ctx)?;
-
-            // Explicitly reserve before pushing so we can return an error instead of panicking from
-            // and out-of-memory exception.
-            //
-            // Note: Reserving a single element will still cause ammortizied growth of the Vec like
-            // pushing does, so there is no performance impact of reserving early.
-            values.try_reserve(1)?;
-            values.push(value);
-
-            Ok(())
-        }
-
         #[inline]
         pub fn encoded_len(tag: u32, value: &$ty) -> usize {
             key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
@@ -818,6 +1260,53 @@ pub mod string {
         buf: &mut B,
         ctx: DecodeContext,
     ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        merge_utf8(wire_type, value, buf, ctx, false)
+    }
+
+    /// Merges a string like `merge`, but always replaces invalid UTF-8 sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER` rather than failing, whether or not the context is lossy.
+    pub fn merge_lossy<B>(
+        wire_type: WireType,
+        value: &mut String,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        merge_utf8(wire_type, value, buf, ctx, true)
+    }
+
+    /// Merges a repeated string like `merge_repeated`, replacing invalid UTF-8 as `merge_lossy`
+    /// does.
+    pub fn merge_repeated_lossy<B>(
+        wire_type: WireType,
+        values: &mut Vec<String>,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<String>())?;
+        let mut value = String::new();
+        merge_lossy(wire_type, &mut value, buf, ctx)?;
+        values.try_reserve(1)?;
+        values.push(value);
+        Ok(())
+    }
+
+    fn merge_utf8<B>(
+        wire_type: WireType,
+        value: &mut String,
+        buf: &mut B,
+        ctx: DecodeContext,
+        lossy: bool,
+    ) -> Result<(), DecodeError>
     where
         B: Buf,
     {
@@ -844,20 +1333,81 @@ pub mod string {
             }
 
             let drop_guard = DropGuard(value.as_mut_vec());
-            bytes::merge_one_copy(wire_type, drop_guard.0, buf, ctx)?;
+            bytes::merge_one_copy(wire_type, drop_guard.0, buf, ctx.clone())?;
             match str::from_utf8(drop_guard.0) {
                 Ok(_) => {
                     // Success; do not clear the bytes.
                     mem::forget(drop_guard);
                     Ok(())
                 }
-                Err(_) => Err(DecodeError::new(
+                Err(_) if lossy || ctx.lossy_utf8() => {
+                    // Replace the invalid sequences in a new buffer, which then holds well-formed
+                    // utf-8 and can be swapped in. It becomes the string's own buffer, so there is
+                    // no scratch buffer to reuse across strings.
+                    let mut valid = Vec::with_capacity(drop_guard.0.len());
+                    push_lossy(drop_guard.0, &mut valid);
+                    *drop_guard.0 = valid;
+                    mem::forget(drop_guard);
+                    Ok(())
+                }
+                Err(_) => Err(DecodeError::with_kind(
+                    DecodeErrorKind::InvalidUtf8,
                     "invalid string value: data is not UTF-8 encoded",
                 )),
             }
         }
     }
 
+    /// Appends `input` to `out`, replacing each invalid utf-8 sequence with `U+FFFD`.
+    fn push_lossy(mut input: &[u8], out: &mut Vec<u8>) {
+        loop {
+            match str::from_utf8(input) {
+                Ok(valid) => {
+                    out.extend_from_slice(valid.as_bytes());
+                    return;
+                }
+                Err(error) => {
+                    let (valid, rest) = input.split_at(error.valid_up_to());
+                    out.extend_from_slice(valid);
+                    out.extend_from_slice(
+                        char::REPLACEMENT_CHARACTER
+                            .encode_utf8(&mut [0; 4])
+                            .as_bytes(),
+                    );
+                    match error.error_len() {
+                        Some(len) => input = &rest[len..],
+                        None => return,
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn merge_repeated<B>(
+        wire_type: WireType,
+        values: &mut Vec<String>,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<String>())?;
+        let mut value = String::new();
+        merge(wire_type, &mut value, buf, ctx)?;
+
+        // Explicitly reserve before pushing so we can return an error instead of panicking from
+        // and out-of-memory exception.
+        //
+        // Note: Reserving a single element will still cause ammortizied growth of the Vec like
+        // pushing does, so there is no performance impact of reserving early.
+        values.try_reserve(1)?;
+        values.push(value);
+
+        Ok(())
+    }
+
     length_delimited!(String);
 
     #[cfg(test)]
@@ -956,40 +1506,88 @@ impl sealed::BytesAdapter for Vec<u8> {
     }
 }
 
-pub mod bytes {
-    use super::*;
+impl BytesAdapter for RawMessage {}
 
-    pub fn encode<A, B>(tag: u32, value: &A, buf: &mut B)
-    where
-        A: BytesAdapter,
-        B: BufMut,
-    {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(value.len() as u64, buf);
-        value.append_to(buf);
+impl sealed::BytesAdapter for RawMessage {
+    fn len(&self) -> usize {
+        self.0.len()
     }
 
-    pub fn merge<A, B>(
-        wire_type: WireType,
-        value: &mut A,
-        buf: &mut B,
-        _ctx: DecodeContext,
-    ) -> Result<(), DecodeError>
+    fn replace_with<B>(&mut self, buf: B) -> Result<(), TryReserveError>
     where
-        A: BytesAdapter,
         B: Buf,
     {
-        check_wire_type(WireType::LengthDelimited, wire_type)?;
-        let len = decode_varint(buf)?;
-        if len > buf.remaining() as u64 {
-            return Err(DecodeError::new("buffer underflow"));
-        }
-        let len = len as usize;
+        sealed::BytesAdapter::replace_with(&mut self.0, buf)
+    }
 
-        // Clear the existing value. This follows from the following rule in the encoding guide[1]:
-        //
-        // > Normally, an encoded message would never have more than one instance of a non-repeated
-        // > field. However, parsers are expected to handle the case in which they do. For numeric
+    fn append_to<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        sealed::BytesAdapter::append_to(&self.0, buf)
+    }
+}
+
+impl BytesAdapter for SharedBytes {}
+
+impl sealed::BytesAdapter for SharedBytes {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn replace_with<B>(&mut self, buf: B) -> Result<(), TryReserveError>
+    where
+        B: Buf,
+    {
+        sealed::BytesAdapter::replace_with(&mut self.0, buf)
+    }
+
+    fn append_to<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        sealed::BytesAdapter::append_to(&self.0, buf)
+    }
+}
+
+pub mod bytes {
+    use super::*;
+
+    pub fn encode<A, B>(tag: u32, value: &A, buf: &mut B)
+    where
+        A: BytesAdapter,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        value.append_to(buf);
+    }
+
+    pub fn merge<A, B>(
+        wire_type: WireType,
+        value: &mut A,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        A: BytesAdapter,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        if len > buf.remaining() as u64 {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::UnexpectedEof,
+                "buffer underflow",
+            ));
+        }
+        let len = len as usize;
+        ctx.charge_bytes(len)?;
+
+        // Clear the existing value. This follows from the following rule in the encoding guide[1]:
+        //
+        // > Normally, an encoded message would never have more than one instance of a non-repeated
+        // > field. However, parsers are expected to handle the case in which they do. For numeric
         // > types and strings, if the same field appears multiple times, the parser accepts the
         // > last value it sees.
         //
@@ -1006,7 +1604,7 @@ pub mod bytes {
         wire_type: WireType,
         value: &mut A,
         buf: &mut B,
-        _ctx: DecodeContext,
+        ctx: DecodeContext,
     ) -> Result<(), DecodeError>
     where
         A: BytesAdapter,
@@ -1015,15 +1613,65 @@ pub mod bytes {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
         let len = decode_varint(buf)?;
         if len > buf.remaining() as u64 {
-            return Err(DecodeError::new("buffer underflow"));
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::UnexpectedEof,
+                "buffer underflow",
+            ));
         }
         let len = len as usize;
+        ctx.charge_bytes(len)?;
 
         // If we must copy, make sure to copy only once.
         value.replace_with(buf.take(len))?;
         Ok(())
     }
 
+    /// Like `merge_repeated`, but merges each value with `merge_one_copy`.
+    pub fn merge_repeated_one_copy<A, B>(
+        wire_type: WireType,
+        values: &mut Vec<A>,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        A: BytesAdapter,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<A>())?;
+        let mut value = A::default();
+        merge_one_copy(wire_type, &mut value, buf, ctx)?;
+        values.try_reserve(1)?;
+        values.push(value);
+        Ok(())
+    }
+
+    pub fn merge_repeated<A, B>(
+        wire_type: WireType,
+        values: &mut Vec<A>,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        A: BytesAdapter,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<A>())?;
+        let mut value = A::default();
+        merge(wire_type, &mut value, buf, ctx)?;
+
+        // Explicitly reserve before pushing so we can return an error instead of panicking from
+        // and out-of-memory exception.
+        //
+        // Note: Reserving a single element will still cause ammortizied growth of the Vec like
+        // pushing does, so there is no performance impact of reserving early.
+        values.try_reserve(1)?;
+        values.push(value);
+
+        Ok(())
+    }
+
     length_delimited!(impl BytesAdapter);
 
     #[cfg(test)]
@@ -1040,6 +1688,12 @@ pub mod bytes {
                                                             encode, merge, encoded_len)?;
             }
 
+            #[test]
+            fn check_vec_one_copy(value: Vec<u8>, tag in MIN_TAG..=MAX_TAG) {
+                super::test::check_type::<Vec<u8>, Vec<u8>>(value, tag, WireType::LengthDelimited,
+                                                            encode, merge_one_copy, encoded_len)?;
+            }
+
             #[test]
             fn check_bytes(value: Vec<u8>, tag in MIN_TAG..=MAX_TAG) {
                 let value = Bytes::from(value);
@@ -1054,6 +1708,13 @@ pub mod bytes {
                                                    encoded_len_repeated)?;
             }
 
+            #[test]
+            fn check_repeated_vec_one_copy(value: Vec<Vec<u8>>, tag in MIN_TAG..=MAX_TAG) {
+                super::test::check_collection_type(value, tag, WireType::LengthDelimited,
+                                                   encode_repeated, merge_repeated_one_copy,
+                                                   encoded_len_repeated)?;
+            }
+
             #[test]
             fn check_repeated_bytes(value: Vec<Vec<u8>>, tag in MIN_TAG..=MAX_TAG) {
                 let value = value.into_iter().map(Bytes::from).collect();
@@ -1090,15 +1751,57 @@ pub mod message {
     {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
         ctx.limit_reached()?;
+        if !ctx.has_state() {
+            return merge_loop(
+                msg,
+                buf,
+                ctx.enter_recursion(),
+                |msg: &mut M, buf: &mut B, ctx| {
+                    let (tag, wire_type) = decode_key(buf)?;
+                    msg.merge_field(tag, wire_type, buf, ctx)
+                },
+            );
+        }
+
+        let mut required = RequiredFields::new(msg, &ctx);
         merge_loop(
             msg,
             buf,
             ctx.enter_recursion(),
             |msg: &mut M, buf: &mut B, ctx| {
                 let (tag, wire_type) = decode_key(buf)?;
+                ctx.count_fields(1)?;
+                required.mark(tag);
                 msg.merge_field(tag, wire_type, buf, ctx)
             },
-        )
+        )?;
+        required.check(msg, &ctx)
+    }
+
+    /// Encodes an optional submessage, writing nothing when it is `None`.
+    pub fn encode_optional<M, B>(tag: u32, msg: &Option<M>, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        if let Some(msg) = msg {
+            encode(tag, msg, buf);
+        }
+    }
+
+    /// Merges a submessage into an optional field, starting from the default value when it is
+    /// `None`.
+    pub fn merge_optional<M, B>(
+        wire_type: WireType,
+        msg: &mut Option<M>,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        merge(wire_type, msg.get_or_insert_with(M::default), buf, ctx)
     }
 
     pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
@@ -1122,6 +1825,7 @@ pub mod message {
         B: Buf,
     {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<M>())?;
         let mut msg = M::default();
         merge(WireType::LengthDelimited, &mut msg, buf, ctx)?;
 
@@ -1145,6 +1849,15 @@ pub mod message {
         key_len(tag) + encoded_len_varint(len as u64) + len
     }
 
+    /// Returns the encoded length of an optional submessage, which is zero when it is `None`.
+    #[inline]
+    pub fn encoded_len_optional<M>(tag: u32, msg: &Option<M>) -> usize
+    where
+        M: Message,
+    {
+        msg.as_ref().map_or(0, |msg| encoded_len(tag, msg))
+    }
+
     #[inline]
     pub fn encoded_len_repeated<M>(tag: u32, messages: &[M]) -> usize
     where
@@ -1157,6 +1870,88 @@ pub mod message {
                 .map(|len| len + encoded_len_varint(len as u64))
                 .sum::<usize>()
     }
+
+    /// Encodes a submessage, taking its length and those of its submessages from `sizes`, as
+    /// recorded by `encoded_len_cached`. Lengths missing from `sizes` are computed.
+    pub fn encode_cached<M, B>(tag: u32, msg: &M, buf: &mut B, sizes: &mut slice::Iter<'_, usize>)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        let len = sizes.next().copied().unwrap_or_else(|| msg.encoded_len());
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(len as u64, buf);
+        msg.encode_raw_cached(buf, sizes);
+    }
+
+    pub fn encode_optional_cached<M, B>(
+        tag: u32,
+        msg: &Option<M>,
+        buf: &mut B,
+        sizes: &mut slice::Iter<'_, usize>,
+    ) where
+        M: Message,
+        B: BufMut,
+    {
+        if let Some(msg) = msg {
+            encode_cached(tag, msg, buf, sizes);
+        }
+    }
+
+    pub fn encode_repeated_cached<M, B>(
+        tag: u32,
+        messages: &[M],
+        buf: &mut B,
+        sizes: &mut slice::Iter<'_, usize>,
+    ) where
+        M: Message,
+        B: BufMut,
+    {
+        for msg in messages {
+            encode_cached(tag, msg, buf, sizes);
+        }
+    }
+
+    /// Returns the encoded length of a submessage, recording its length and those of its
+    /// submessages in `sizes`, in the order `encode_cached` takes them.
+    #[inline]
+    pub fn encoded_len_cached<M>(tag: u32, msg: &M, sizes: &mut EncodedSizes) -> usize
+    where
+        M: Message,
+    {
+        let index = sizes.reserve();
+        let len = msg.encoded_len_cached(sizes);
+        sizes.set(index, len);
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    #[inline]
+    pub fn encoded_len_optional_cached<M>(
+        tag: u32,
+        msg: &Option<M>,
+        sizes: &mut EncodedSizes,
+    ) -> usize
+    where
+        M: Message,
+    {
+        msg.as_ref()
+            .map_or(0, |msg| encoded_len_cached(tag, msg, sizes))
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated_cached<M>(
+        tag: u32,
+        messages: &[M],
+        sizes: &mut EncodedSizes,
+    ) -> usize
+    where
+        M: Message,
+    {
+        messages
+            .iter()
+            .map(|msg| encoded_len_cached(tag, msg, sizes))
+            .sum()
+    }
 }
 
 pub mod group {
@@ -1186,15 +1981,23 @@ pub mod group {
         check_wire_type(WireType::StartGroup, wire_type)?;
 
         ctx.limit_reached()?;
+        let mut required = ctx.has_state().then(|| RequiredFields::new(msg, &ctx));
         loop {
             let (field_tag, field_wire_type) = decode_key(buf)?;
             if field_wire_type == WireType::EndGroup {
                 if field_tag != tag {
                     return Err(DecodeError::new("unexpected end group tag"));
                 }
-                return Ok(());
+                return match &required {
+                    Some(required) => required.check(msg, &ctx),
+                    None => Ok(()),
+                };
             }
 
+            if let Some(required) = &mut required {
+                ctx.count_fields(1)?;
+                required.mark(field_tag);
+            }
             M::merge_field(msg, field_tag, field_wire_type, buf, ctx.enter_recursion())?;
         }
     }
@@ -1221,6 +2024,7 @@ pub mod group {
         B: Buf,
     {
         check_wire_type(WireType::StartGroup, wire_type)?;
+        ctx.charge_bytes(mem::size_of::<M>())?;
         let mut msg = M::default();
         merge(tag, WireType::StartGroup, &mut msg, buf, ctx)?;
 
@@ -1252,6 +2056,53 @@ pub mod group {
     }
 }
 
+/// A conversion of map keys to the type they're encoded as, along with the encoding functions of
+/// that type.
+pub trait KeyConversion<K> {
+    /// The type the keys are encoded as.
+    type Encoded: Default + PartialEq;
+
+    /// Converts a key to the type it's encoded as.
+    fn convert(&self, key: &K) -> Self::Encoded;
+
+    /// Encodes a converted key.
+    fn encode<B>(&self, tag: u32, key: &Self::Encoded, buf: &mut B)
+    where
+        B: BufMut;
+
+    /// Returns the encoded length of a converted key.
+    fn encoded_len(&self, tag: u32, key: &Self::Encoded) -> usize;
+}
+
+/// Encodes enumeration map keys as `int32`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnumerationKey;
+
+impl<E> KeyConversion<E> for EnumerationKey
+where
+    E: Copy + Into<i32>,
+{
+    type Encoded = i32;
+
+    #[inline]
+    fn convert(&self, key: &E) -> i32 {
+        (*key).into()
+    }
+
+    #[inline]
+    fn encode<B>(&self, tag: u32, key: &i32, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        int32::encode(tag, key, buf);
+    }
+
+    #[inline]
+    fn encoded_len(&self, tag: u32, key: &i32) -> usize {
+        int32::encoded_len(tag, key)
+    }
+}
+
 /// Rust doesn't have a `Map` trait, so macros are currently the best way to be
 /// generic over `HashMap` and `BTreeMap`.
 macro_rules! map {
@@ -1384,12 +2235,14 @@ macro_rules! map {
             let mut key = Default::default();
             let mut val = val_default;
             ctx.limit_reached()?;
+            ctx.charge_bytes(mem::size_of::<(K, V)>())?;
             merge_loop(
                 &mut (&mut key, &mut val),
                 buf,
                 ctx.enter_recursion(),
                 |&mut (ref mut key, ref mut val), buf, ctx| {
                     let (tag, wire_type) = decode_key(buf)?;
+                    ctx.count_fields(1)?;
                     match tag {
                         1 => key_merge(wire_type, key, buf, ctx),
                         2 => val_merge(wire_type, val, buf, ctx),
@@ -1436,6 +2289,125 @@ macro_rules! map {
                     })
                     .sum::<usize>()
         }
+
+        /// Generic protobuf map encode function for keys which are converted to another type to
+        /// be encoded, such as enumerations encoded as `int32`.
+        pub fn encode_with_key_conversion<K, V, B, KC, VE, VL>(
+            key_conversion: KC,
+            val_encode: VE,
+            val_encoded_len: VL,
+            val_default: &V,
+            tag: u32,
+            values: &$map_ty<K, V>,
+            buf: &mut B,
+        ) where
+            K: Eq + Hash + Ord,
+            V: PartialEq,
+            B: BufMut,
+            KC: KeyConversion<K>,
+            VE: Fn(u32, &V, &mut B),
+            VL: Fn(u32, &V) -> usize,
+        {
+            for (key, val) in values.iter() {
+                let key = key_conversion.convert(key);
+                let skip_key = key == KC::Encoded::default();
+                let skip_val = val == val_default;
+
+                let len = (if skip_key {
+                    0
+                } else {
+                    key_conversion.encoded_len(1, &key)
+                }) + (if skip_val { 0 } else { val_encoded_len(2, val) });
+
+                encode_key(tag, WireType::LengthDelimited, buf);
+                encode_varint(len as u64, buf);
+                if !skip_key {
+                    key_conversion.encode(1, &key, buf);
+                }
+                if !skip_val {
+                    val_encode(2, val, buf);
+                }
+            }
+        }
+
+        /// Generic protobuf map merge function for keys which are converted from another type
+        /// after being decoded, such as enumerations decoded as `int32`.
+        ///
+        /// Returns an error for an entry whose key can't be converted, rather than losing it.
+        pub fn merge_with_key_conversion<K, R, V, B, KC, KM, VM>(
+            key_convert: KC,
+            key_merge: KM,
+            val_merge: VM,
+            val_default: V,
+            values: &mut $map_ty<K, V>,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError>
+        where
+            K: Eq + Hash + Ord,
+            R: Default,
+            B: Buf,
+            KC: Fn(R) -> Option<K>,
+            KM: Fn(WireType, &mut R, &mut B, DecodeContext) -> Result<(), DecodeError>,
+            VM: Fn(WireType, &mut V, &mut B, DecodeContext) -> Result<(), DecodeError>,
+        {
+            let mut key = R::default();
+            let mut val = val_default;
+            ctx.limit_reached()?;
+            ctx.charge_bytes(mem::size_of::<(K, V)>())?;
+            merge_loop(
+                &mut (&mut key, &mut val),
+                buf,
+                ctx.enter_recursion(),
+                |&mut (ref mut key, ref mut val), buf, ctx| {
+                    let (tag, wire_type) = decode_key(buf)?;
+                    ctx.count_fields(1)?;
+                    match tag {
+                        1 => key_merge(wire_type, key, buf, ctx),
+                        2 => val_merge(wire_type, val, buf, ctx),
+                        _ => skip_field(wire_type, tag, buf, ctx),
+                    }
+                },
+            )?;
+            let key = key_convert(key)
+                .ok_or_else(|| DecodeError::new("invalid enumeration value for map key"))?;
+            values.insert(key, val);
+            Ok(())
+        }
+
+        /// Generic protobuf map encoded length function for keys which are converted to another
+        /// type to be encoded, such as enumerations encoded as `int32`.
+        pub fn encoded_len_with_key_conversion<K, V, KC, VL>(
+            key_conversion: KC,
+            val_encoded_len: VL,
+            val_default: &V,
+            tag: u32,
+            values: &$map_ty<K, V>,
+        ) -> usize
+        where
+            K: Eq + Hash + Ord,
+            V: PartialEq,
+            KC: KeyConversion<K>,
+            VL: Fn(u32, &V) -> usize,
+        {
+            key_len(tag) * values.len()
+                + values
+                    .iter()
+                    .map(|(key, val)| {
+                        let key = key_conversion.convert(key);
+                        let len = (if key == KC::Encoded::default() {
+                            0
+                        } else {
+                            key_conversion.encoded_len(1, &key)
+                        }) + (if val == val_default {
+                            0
+                        } else {
+                            val_encoded_len(2, val)
+                        });
+                        encoded_len_varint(len as u64) + len
+                    })
+                    .sum::<usize>()
+        }
     };
 }
 
@@ -1614,6 +2586,107 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn message_optional() {
+        let mut buf = Vec::new();
+        message::encode_optional::<String, _>(1, &None, &mut buf);
+        assert!(buf.is_empty());
+        assert_eq!(message::encoded_len_optional::<String>(1, &None), 0);
+
+        let msg = Some("hello".to_string());
+        message::encode_optional(1, &msg, &mut buf);
+        assert_eq!(buf.len(), message::encoded_len_optional(1, &msg));
+
+        let mut buf = &buf[..];
+        let (tag, wire_type) = decode_key(&mut buf).unwrap();
+        assert_eq!(tag, 1);
+        let mut roundtrip = None;
+        message::merge_optional(
+            wire_type,
+            &mut roundtrip,
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(roundtrip, msg);
+    }
+
+    #[test]
+    fn string_merge_lossy_utf8() {
+        let state = DecodeState::new(&DecodeOptions::new().lossy_utf8(true));
+        let mut s = String::new();
+        let buf = b"\x06a\x80b\xF0\x9F\x98";
+
+        string::merge(
+            WireType::LengthDelimited,
+            &mut s,
+            &mut &buf[..],
+            DecodeContext::with_state(&state),
+        )
+        .unwrap();
+        assert_eq!(s, "a\u{FFFD}b\u{FFFD}");
+    }
+
+    #[test]
+    fn fixed_width_packed_length() {
+        let mut values = Vec::new();
+        let mut buf = &b"\x08\x01\x00\x00\x00\x02\x00\x00\x00"[..];
+        fixed32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut values,
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(values, [1, 2]);
+        assert!(buf.is_empty());
+
+        // A trailing partial element is rejected, rather than read from the following bytes.
+        let mut values = Vec::new();
+        let mut buf = &b"\x05\x01\x00\x00\x00\x02\x00\x00\x00"[..];
+        let error = fixed32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut values,
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed to decode Protobuf message: packed field length not a multiple of element size"
+        );
+        assert!(values.is_empty());
+
+        let mut buf = &[0x0c; 13][..];
+        assert!(fixed64::merge_repeated(
+            WireType::LengthDelimited,
+            &mut Vec::new(),
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn decode_context_is_send() {
+        fn assert_send<T: Send + Sync>() {}
+        assert_send::<DecodeContext>();
+    }
+
+    #[test]
+    fn skip_field_skipped_bytes() {
+        let state = DecodeState::new(&DecodeOptions::new());
+        let ctx = DecodeContext::with_state(&state);
+        // A varint field, then a group holding a length-delimited field, without their keys.
+        let mut buf = &b"\x96\x01\x12\x01a\x0c"[..];
+
+        skip_field(WireType::Varint, 1, &mut buf, ctx.clone()).unwrap();
+        assert_eq!(state.skipped_bytes.load(Ordering::Relaxed), 3);
+        skip_field(WireType::StartGroup, 1, &mut buf, ctx.clone()).unwrap();
+        assert_eq!(state.skipped_bytes.load(Ordering::Relaxed), 8);
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn string_merge_invalid_utf8() {
         let mut s = String::new();
@@ -1625,10 +2698,50 @@ mod test {
             &mut &buf[..],
             DecodeContext::default(),
         );
-        r.expect_err("must be an error");
+        let error = r.expect_err("must be an error");
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidUtf8);
         assert!(s.is_empty());
     }
 
+    #[test]
+    fn decode_error_kind() {
+        let kind = |mut buf: &[u8]| decode_key(&mut buf).unwrap_err().kind();
+
+        assert_eq!(
+            kind(b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff"),
+            DecodeErrorKind::InvalidVarint
+        );
+        assert_eq!(kind(b"\x80\x80"), DecodeErrorKind::UnexpectedEof);
+        assert_eq!(kind(b""), DecodeErrorKind::UnexpectedEof);
+        assert_eq!(kind(b"\x00"), DecodeErrorKind::InvalidTag);
+        assert_eq!(
+            kind(b"\x80\x80\x80\x80\x80\x01"),
+            DecodeErrorKind::InvalidTag
+        );
+        assert_eq!(kind(b"\x0e"), DecodeErrorKind::InvalidWireType);
+        assert_eq!(
+            check_wire_type(WireType::Varint, WireType::LengthDelimited)
+                .unwrap_err()
+                .kind(),
+            DecodeErrorKind::InvalidWireType
+        );
+        assert_eq!(
+            skip_field(
+                WireType::SixtyFourBit,
+                1,
+                &mut &b"\x01"[..],
+                DecodeContext::default()
+            )
+            .unwrap_err()
+            .kind(),
+            DecodeErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            DecodeError::new("invalid enumeration value").kind(),
+            DecodeErrorKind::Other
+        );
+    }
+
     #[test]
     fn varint() {
         fn check(value: u64, mut encoded: &[u8]) {
@@ -1717,6 +2830,30 @@ mod test {
             .expect_err("slow decoding u64::MAX + 1 succeeded");
     }
 
+    #[test]
+    fn key_max_tag() {
+        let mut buf = Vec::new();
+        encode_key(MAX_TAG, WireType::Varint, &mut buf);
+        assert_eq!(buf.len(), 5);
+        assert_eq!(key_len(MAX_TAG), 5);
+
+        let mut slice = &buf[..];
+        assert_eq!(decode_key(&mut slice).unwrap(), (MAX_TAG, WireType::Varint));
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn key_tag_out_of_range() {
+        // Field number 2^29 with wire type varint, which does not fit in a 32-bit key.
+        let mut buf = Vec::new();
+        encode_varint(u64::from(MAX_TAG + 1) << 3, &mut buf);
+        decode_key(&mut &buf[..]).expect_err("decoding tag 2^29 succeeded");
+
+        let mut buf = Vec::new();
+        encode_varint(u64::MAX, &mut buf);
+        decode_key(&mut &buf[..]).expect_err("decoding key u64::MAX succeeded");
+    }
+
     /// This big bowl o' macro soup generates an encoding property test for each combination of map
     /// type, scalar map key, and value type.
     /// TODO: these tests take a long time to compile, can this be improved?
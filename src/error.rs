@@ -3,6 +3,7 @@
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use alloc::collections::TryReserveError;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 
 use core::fmt;
@@ -19,12 +20,16 @@ pub struct DecodeError {
 
 #[derive(Clone, PartialEq, Eq)]
 struct Inner {
+    /// The kind of problem, for classifying errors without matching on the description.
+    kind: DecodeErrorKind,
     /// A 'best effort' root cause description.
     description: Cow<'static, str>,
     /// A stack of (message, field) name pairs, which identify the specific
     /// message type and field where decoding failed. The stack contains an
     /// entry per level of nesting.
     stack: Vec<(&'static str, &'static str)>,
+    /// The number of bytes of the input consumed when decoding failed, if known.
+    offset: Option<usize>,
 }
 
 impl DecodeError {
@@ -34,14 +39,65 @@ impl DecodeError {
     #[doc(hidden)]
     #[cold]
     pub fn new(description: impl Into<Cow<'static, str>>) -> DecodeError {
+        DecodeError::with_kind(DecodeErrorKind::Other, description)
+    }
+
+    /// Creates a new `DecodeError` of the given kind with a 'best effort' root cause description.
+    #[cold]
+    pub(crate) fn with_kind(
+        kind: DecodeErrorKind,
+        description: impl Into<Cow<'static, str>>,
+    ) -> DecodeError {
         DecodeError {
             inner: Box::new(Inner {
+                kind,
                 description: description.into(),
                 stack: Vec::new(),
+                offset: None,
             }),
         }
     }
 
+    /// Creates a `DecodeError` reporting a missing required field, to be located with `push`.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    #[cold]
+    pub fn missing_required_field() -> DecodeError {
+        DecodeError::with_kind(
+            DecodeErrorKind::MissingRequiredField,
+            "missing required field",
+        )
+    }
+
+    /// Returns the kind of problem which caused decoding to fail.
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.inner.kind
+    }
+
+    /// Returns the number of bytes of the input which were consumed when decoding failed, if
+    /// known.
+    ///
+    /// The offset is reported on a 'best effort' basis, by [`Message::decode`] and the other
+    /// decoding methods built on [`Message::merge`], as well as
+    /// [`Message::merge_length_delimited`]. It counts from the start of the buffer passed to the
+    /// method, and locates the end of the key, length or value whose decoding revealed the
+    /// problem; the corruption itself may lie a little before it.
+    ///
+    /// [`Message::decode`]: crate::Message::decode
+    /// [`Message::merge`]: crate::Message::merge
+    /// [`Message::merge_length_delimited`]: crate::Message::merge_length_delimited
+    pub fn offset(&self) -> Option<usize> {
+        self.inner.offset
+    }
+
+    /// Records the number of bytes of the input consumed when decoding failed.
+    #[cold]
+    pub(crate) fn at_offset(mut self, offset: usize) -> DecodeError {
+        self.inner.offset = Some(offset);
+        self
+    }
+
     /// Pushes a (message, field) name location pair on to the location stack.
     ///
     /// Meant to be used only by `Message` implementations.
@@ -54,8 +110,10 @@ impl DecodeError {
 impl fmt::Debug for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("DecodeError")
+            .field("kind", &self.inner.kind)
             .field("description", &self.inner.description)
             .field("stack", &self.inner.stack)
+            .field("offset", &self.inner.offset)
             .finish()
     }
 }
@@ -79,6 +137,31 @@ impl From<TryReserveError> for DecodeError {
 #[cfg(feature = "std")]
 impl std::error::Error for DecodeError {}
 
+/// The kind of problem a [`DecodeError`] reports.
+///
+/// Kinds classify the structural problems common to all messages; anything else, such as an
+/// invalid enumeration value or an out of range well-known type, is [`Other`](Self::Other).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeErrorKind {
+    /// A varint is longer than ten bytes, or overflows 64 bits.
+    InvalidVarint,
+    /// A field key holds a tag of zero or beyond the maximum field number.
+    InvalidTag,
+    /// A field key holds an unknown wire type, or one which doesn't match the field's type.
+    InvalidWireType,
+    /// The input ends before a complete value, including in the middle of a varint.
+    UnexpectedEof,
+    /// A string field holds data which is not valid UTF-8.
+    InvalidUtf8,
+    /// A required proto2 field is missing, as reported when decoding with
+    /// [`RequiredFieldPolicy::Error`](crate::RequiredFieldPolicy::Error), or collected with
+    /// [`RequiredFieldPolicy::Warn`](crate::RequiredFieldPolicy::Warn).
+    MissingRequiredField,
+    /// Any other problem; see the error's description.
+    Other,
+}
+
 #[cfg(feature = "std")]
 impl From<DecodeError> for std::io::Error {
     fn from(error: DecodeError) -> std::io::Error {
@@ -88,13 +171,19 @@ impl From<DecodeError> for std::io::Error {
 
 /// A Protobuf message encoding error.
 ///
-/// `EncodeError` always indicates that a message failed to encode because the
-/// provided buffer had insufficient capacity. Message encoding is otherwise
+/// `EncodeError` indicates that a message failed to encode because the
+/// provided buffer had insufficient capacity, or, for the checked methods such as
+/// [`Message::try_encode`](crate::Message::try_encode), because the message
+/// nests submessages beyond the recursion limit, or, for
+/// [`Message::encode_length_delimited_with`](crate::Message::encode_length_delimited_with),
+/// because the frame is too long for its length prefix. Message encoding is otherwise
 /// infallible.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct EncodeError {
     required: usize,
     remaining: usize,
+    recursion_limit_reached: bool,
+    length_exceeds_prefix: bool,
 }
 
 impl EncodeError {
@@ -103,6 +192,29 @@ impl EncodeError {
         EncodeError {
             required,
             remaining,
+            recursion_limit_reached: false,
+            length_exceeds_prefix: false,
+        }
+    }
+
+    /// Creates an `EncodeError` for a message nesting submessages beyond the recursion limit.
+    #[cfg(not(feature = "no-recursion-limit"))]
+    pub(crate) fn recursion_limit_reached() -> EncodeError {
+        EncodeError {
+            required: 0,
+            remaining: 0,
+            recursion_limit_reached: true,
+            length_exceeds_prefix: false,
+        }
+    }
+
+    /// Creates an `EncodeError` for a frame of `length` bytes, which its length prefix can't hold.
+    pub(crate) fn length_exceeds_prefix(length: usize) -> EncodeError {
+        EncodeError {
+            required: length,
+            remaining: 0,
+            recursion_limit_reached: false,
+            length_exceeds_prefix: true,
         }
     }
 
@@ -115,10 +227,33 @@ impl EncodeError {
     pub fn remaining(&self) -> usize {
         self.remaining
     }
+
+    /// Returns `true` if the message failed to encode because it nests submessages beyond the
+    /// recursion limit, rather than because of insufficient buffer capacity.
+    pub fn is_recursion_limit_reached(&self) -> bool {
+        self.recursion_limit_reached
+    }
+
+    /// Returns `true` if the message failed to encode because its frame is too long for the
+    /// length prefix, in which case [`required_capacity`](EncodeError::required_capacity) is the
+    /// length of the frame.
+    pub fn is_length_exceeding_prefix(&self) -> bool {
+        self.length_exceeds_prefix
+    }
 }
 
 impl fmt::Display for EncodeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.recursion_limit_reached {
+            return f.write_str("failed to encode Protobuf message; recursion limit reached");
+        }
+        if self.length_exceeds_prefix {
+            return write!(
+                f,
+                "failed to encode Protobuf message; length exceeds the length prefix (length: {})",
+                self.required
+            );
+        }
         write!(
             f,
             "failed to encode Protobuf message; insufficient buffer capacity (required: {}, remaining: {})",
@@ -136,3 +271,49 @@ impl From<EncodeError> for std::io::Error {
         std::io::Error::new(std::io::ErrorKind::InvalidInput, error)
     }
 }
+
+/// An error returned when parsing an enumeration from a string fails.
+///
+/// `ParseEnumError` indicates that the string is not the name of any value of the
+/// enumeration, as it appears in the Protobuf definition.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseEnumError {
+    _private: (),
+}
+
+impl ParseEnumError {
+    /// Creates a new `ParseEnumError`.
+    ///
+    /// Meant to be used only by generated `FromStr` implementations.
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> ParseEnumError {
+        ParseEnumError { _private: () }
+    }
+}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to parse Protobuf enumeration: unknown value name")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseEnumError {}
+
+/// An error returned when converting an `i32` to an enumeration fails.
+///
+/// `UnknownEnumValue` holds the original value, which is not the number of any value of the
+/// enumeration, e.g. because a newer version of the Protobuf definition added it. Enumeration
+/// fields of messages are `i32`s, so such values are kept when decoding and encoding messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnknownEnumValue(pub i32);
+
+impl fmt::Display for UnknownEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown Protobuf enumeration value: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownEnumValue {}
@@ -0,0 +1,14 @@
+//! Statistics about the fields of decoded messages.
+
+/// Statistics about the occurrences of a field in an encoded message.
+///
+/// Returned by [`Message::decode_with_stats`][1].
+///
+/// [1]: crate::Message::decode_with_stats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FieldStats {
+    /// The number of times the field occurred.
+    pub count: usize,
+    /// The number of bytes taken by all occurrences of the field, including their keys.
+    pub bytes: usize,
+}
@@ -0,0 +1,116 @@
+//! Support for transforming encoded messages within length-delimited framing.
+
+use alloc::borrow::Cow;
+
+use bytes::{Buf, BufMut};
+
+use crate::encoding::{encode_varint, encoded_len_varint};
+use crate::{decode_length_delimiter, DecodeError, DecodeErrorKind};
+
+/// The format of the length prefix of a length-delimited frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LengthPrefix {
+    /// A varint, the standard Protobuf length delimiter.
+    Varint,
+    /// A little-endian `u32`.
+    U32Le,
+    /// A little-endian `u64`.
+    U64Le,
+}
+
+impl LengthPrefix {
+    /// Returns the encoded length of a prefix holding `length`.
+    pub fn encoded_len(self, length: usize) -> usize {
+        match self {
+            LengthPrefix::Varint => encoded_len_varint(length as u64),
+            LengthPrefix::U32Le => 4,
+            LengthPrefix::U64Le => 8,
+        }
+    }
+
+    /// Returns `true` if a prefix of this format can hold `length`.
+    pub fn can_hold(self, length: usize) -> bool {
+        match self {
+            LengthPrefix::U32Le => u32::try_from(length).is_ok(),
+            LengthPrefix::Varint | LengthPrefix::U64Le => true,
+        }
+    }
+
+    /// Encodes a prefix holding `length` to the buffer.
+    ///
+    /// This method will panic if the buffer has insufficient capacity, or if the prefix can't
+    /// hold `length`, see [`can_hold`](LengthPrefix::can_hold).
+    pub fn encode<B>(self, length: usize, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        match self {
+            LengthPrefix::Varint => encode_varint(length as u64, buf),
+            LengthPrefix::U32Le => {
+                let length = u32::try_from(length).expect("length exceeds u32 prefix");
+                buf.put_u32_le(length);
+            }
+            LengthPrefix::U64Le => buf.put_u64_le(length as u64),
+        }
+    }
+
+    /// Decodes a prefix from the buffer, returning the length it holds.
+    pub fn decode<B>(self, mut buf: B) -> Result<usize, DecodeError>
+    where
+        B: Buf,
+    {
+        let length = match self {
+            LengthPrefix::Varint => return decode_length_delimiter(buf),
+            LengthPrefix::U32Le if buf.remaining() >= 4 => u64::from(buf.get_u32_le()),
+            LengthPrefix::U64Le if buf.remaining() >= 8 => buf.get_u64_le(),
+            _ => {
+                return Err(DecodeError::with_kind(
+                    DecodeErrorKind::UnexpectedEof,
+                    "buffer underflow",
+                ))
+            }
+        };
+        if length > usize::MAX as u64 {
+            return Err(DecodeError::new(
+                "length delimiter exceeds maximum usize value",
+            ));
+        }
+        Ok(length as usize)
+    }
+}
+
+/// A transformation applied to encoded messages inside length-delimited frames, such as
+/// compression.
+///
+/// Used with [`Message::encode_length_delimited_with`][1] and
+/// [`Message::decode_length_delimited_with`][2]: a frame consists of the length of the
+/// transformed message followed by the transformed message itself. The length is a varint by
+/// default, and the transformation defaults to the identity, so implementations only need to
+/// override what they change. [`LengthPrefix`] itself implements `Framing`, to frame untransformed
+/// messages with a given prefix format.
+///
+/// [1]: crate::Message::encode_length_delimited_with
+/// [2]: crate::Message::decode_length_delimited_with
+pub trait Framing {
+    /// Returns the format of the length prefix of frames.
+    fn length_prefix(&self) -> LengthPrefix {
+        LengthPrefix::Varint
+    }
+
+    /// Transforms an encoded message into the contents of a frame.
+    fn compress<'a>(&self, encoded: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(encoded)
+    }
+
+    /// Recovers an encoded message from the contents of a frame, reversing
+    /// [`compress`](Framing::compress).
+    fn decompress<'a>(&self, frame: &'a [u8]) -> Result<Cow<'a, [u8]>, DecodeError> {
+        Ok(Cow::Borrowed(frame))
+    }
+}
+
+impl Framing for LengthPrefix {
+    fn length_prefix(&self) -> LengthPrefix {
+        *self
+    }
+}
@@ -9,19 +9,59 @@ pub extern crate alloc;
 // Re-export the bytes crate for use within derived code.
 pub use bytes;
 
+// Re-export the log crate for use within derived code tracing field access.
+#[cfg(feature = "log")]
+#[doc(hidden)]
+pub use log;
+
+mod batch_decode;
+mod cached_message;
+mod decode_options;
+mod dyn_message;
+mod encode_chunks;
+mod encoded_sizes;
 mod error;
+mod field_stats;
+mod framing;
 mod message;
+mod name;
+mod raw_message;
+mod shared_bytes;
 mod types;
+mod unknown_fields;
 
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod debug;
 #[doc(hidden)]
 pub mod encoding;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "std")]
+pub mod stream;
 
-pub use crate::error::{DecodeError, EncodeError};
+pub use crate::batch_decode::BatchDecodeContext;
+pub use crate::cached_message::CachedMessage;
+pub use crate::decode_options::{DecodeOptions, DecodeStats, RequiredFieldPolicy};
+pub use crate::dyn_message::DynMessage;
+pub use crate::encode_chunks::EncodeChunks;
+pub use crate::encoded_sizes::EncodedSizes;
+pub use crate::error::{
+    DecodeError, DecodeErrorKind, EncodeError, ParseEnumError, UnknownEnumValue,
+};
+pub use crate::field_stats::FieldStats;
+pub use crate::framing::{Framing, LengthPrefix};
 pub use crate::message::Message;
+pub use crate::name::Name;
+pub use crate::raw_message::RawMessage;
+pub use crate::shared_bytes::SharedBytes;
+pub use crate::unknown_fields::UnknownFields;
 
 use bytes::{Buf, BufMut};
 
-use crate::encoding::{decode_varint, encode_varint, encoded_len_varint};
+use crate::encoding::{
+    decode_key, decode_varint, encode_varint, encoded_len_varint, skip_field, DecodeContext,
+};
 
 // See `encoding::DecodeContext` for more info.
 // 100 is the default recursion limit in the C++ implementation.
@@ -80,6 +120,55 @@ where
     Ok(length as usize)
 }
 
+/// Reads the length delimiter at the front of a buffer holding a partially received frame.
+///
+/// Returns the length of the message following the delimiter, as written by
+/// [Message.encode_length_delimited], or `None` if the buffer ends before the delimiter does. The
+/// buffer is not consumed, and the message itself need not have been received yet. This is
+/// useful to decide how much input to buffer before decoding a frame. Use
+/// [`length_delimiter_len`] to find where the message starts.
+///
+/// An error is returned if the delimiter is invalid.
+pub fn probe_length_delimited(buf: &[u8]) -> Result<Option<usize>, DecodeError> {
+    // A varint ends with the first byte whose continuation bit is clear.
+    match buf.iter().take(10).position(|&byte| byte < 0x80) {
+        Some(end) => decode_length_delimiter(&buf[..=end]).map(Some),
+        None if buf.len() < 10 => Ok(None),
+        None => Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidVarint,
+            "invalid varint",
+        )),
+    }
+}
+
+/// Decodes the elements of a repeated message field from an encoded message, passing each
+/// element to `f` instead of collecting them.
+///
+/// `tag` is the field number of the repeated field within the encoded message. Other fields of
+/// the message are skipped. This allows aggregating over a large repeated field in a single
+/// pass, holding only one element in memory at a time.
+///
+/// The entire buffer will be consumed.
+pub fn decode_repeated_with<M, B, F>(mut buf: B, tag: u32, mut f: F) -> Result<(), DecodeError>
+where
+    M: Message + Default,
+    B: Buf,
+    F: FnMut(M),
+{
+    let ctx = DecodeContext::default();
+    while buf.has_remaining() {
+        let (field_tag, wire_type) = decode_key(&mut buf)?;
+        if field_tag == tag {
+            let mut element = M::default();
+            encoding::message::merge(wire_type, &mut element, &mut buf, ctx.clone())?;
+            f(element);
+        } else {
+            skip_field(wire_type, field_tag, &mut buf, ctx.clone())?;
+        }
+    }
+    Ok(())
+}
+
 // Re-export #[derive(Message, Enumeration, Oneof)].
 // Based on serde's equivalent re-export [1], but enabled by default.
 //
@@ -1,16 +1,29 @@
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 
 use core::fmt::Debug;
+use core::hash::Hasher;
+use core::slice;
 use core::usize;
 
+use bytes::buf::UninitSlice;
 use bytes::{Buf, BufMut};
 
+use crate::decode_length_delimiter;
 use crate::encoding::{
-    decode_key, encode_varint, encoded_len_varint, message, DecodeContext, WireType,
+    decode_key, encode_varint, encoded_len_varint, message, skip_field, DecodeContext, DecodeState,
+    RequiredFields, WireType,
 };
 use crate::DecodeError;
+use crate::DecodeErrorKind;
+use crate::DecodeOptions;
+use crate::DecodeStats;
+use crate::EncodeChunks;
 use crate::EncodeError;
+use crate::EncodedSizes;
+use crate::FieldStats;
+use crate::Framing;
 
 /// A Protocol Buffers message.
 pub trait Message: Debug + Send + Sync {
@@ -25,6 +38,27 @@ pub trait Message: Debug + Send + Sync {
         B: BufMut,
         Self: Sized;
 
+    /// Encodes the part of the message with index `part` to a buffer, returning `false` without
+    /// encoding anything if the message has no such part.
+    ///
+    /// Encoding each part in turn, starting from zero, is equivalent to `encode_raw`. The default
+    /// implementation encodes the whole message as a single part.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn encode_raw_part<B>(&self, part: usize, buf: &mut B) -> bool
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        if part == 0 {
+            self.encode_raw(buf);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Decodes a field from a buffer, and merges it into `self`.
     ///
     /// Meant to be used only by `Message` implementations.
@@ -43,9 +77,109 @@ pub trait Message: Debug + Send + Sync {
     /// Returns the encoded length of the message without a length delimiter.
     fn encoded_len(&self) -> usize;
 
+    /// Returns the encoded length of the message without a length delimiter, recording the
+    /// lengths of its submessages in `sizes` in the order they're encoded.
+    ///
+    /// The default implementation records nothing, and is paired with the default implementation
+    /// of `encode_raw_cached`.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn encoded_len_cached(&self, sizes: &mut EncodedSizes) -> usize {
+        let _ = sizes;
+        self.encoded_len()
+    }
+
+    /// Encodes the message to a buffer, taking the lengths of its submessages from `sizes`, as
+    /// recorded by `encoded_len_cached`.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn encode_raw_cached<B>(&self, buf: &mut B, sizes: &mut slice::Iter<'_, usize>)
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        let _ = sizes;
+        self.encode_raw(buf)
+    }
+
+    /// Returns the tags of the required fields of the message.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn required_tags(&self) -> &'static [u32] {
+        &[]
+    }
+
+    /// Returns an error naming the first required field which is missing, where `present` holds
+    /// whether each of the fields returned by `required_tags` was decoded.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn check_required(&self, present: &[bool]) -> Result<(), DecodeError> {
+        let _ = present;
+        Ok(())
+    }
+
+    /// Returns whether the message nests submessages more than `limit` levels deep.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn exceeds_depth(&self, limit: u32) -> bool {
+        let _ = limit;
+        false
+    }
+
+    /// Clears the unknown fields preserved by the message and its submessages, leaving their known
+    /// fields as they are.
+    ///
+    /// Only messages generated with `prost_build::Config::preserve_unknown_fields` preserve
+    /// unknown fields; this does nothing to others.
+    fn clear_unknown_fields(&mut self) {}
+
+    /// Returns `true` if the known fields of the message and its submessages equal those of
+    /// `other`, ignoring the unknown fields either preserves.
+    ///
+    /// `PartialEq` compares preserved unknown fields too, so messages with the same known fields
+    /// differ if they were decoded from senders using different schemas. This compares copies of
+    /// the messages with their unknown fields cleared.
+    fn eq_known_fields(&self, other: &Self) -> bool
+    where
+        Self: Sized + Clone + PartialEq,
+    {
+        let mut this = self.clone();
+        let mut other = other.clone();
+        this.clear_unknown_fields();
+        other.clear_unknown_fields();
+        this == other
+    }
+
+    /// Returns the encoded length of the message without a length delimiter, or an error if the
+    /// message nests submessages beyond the recursion limit.
+    ///
+    /// Computing the length and encoding recurse into submessages, so a pathologically deep
+    /// message, such as one built in memory from untrusted input, can overflow the stack. The
+    /// nesting depth is checked against the same limit which applies to decoding before the
+    /// length is computed. Pair this with [`try_encode`](Message::try_encode).
+    fn try_encoded_len(&self) -> Result<usize, EncodeError> {
+        #[cfg(not(feature = "no-recursion-limit"))]
+        if self.exceeds_depth(crate::RECURSION_LIMIT) {
+            return Err(EncodeError::recursion_limit_reached());
+        }
+        Ok(self.encoded_len())
+    }
+
     /// Encodes the message to a buffer.
     ///
     /// An error will be returned if the buffer does not have sufficient capacity.
+    ///
+    /// The capacity is checked against [`BufMut::remaining_mut`] before anything is written, and
+    /// exactly [`encoded_len`](Message::encoded_len) bytes are written on success, so the buffer
+    /// need not be able to grow. This makes fixed-size buffers, such as a `&mut [u8]` or a region
+    /// of a memory-mapped file, suitable targets. The buffer is left untouched on error.
     fn encode<B>(&self, buf: &mut B) -> Result<(), EncodeError>
     where
         B: BufMut,
@@ -61,6 +195,53 @@ pub trait Message: Debug + Send + Sync {
         Ok(())
     }
 
+    /// Computes the encoded lengths of the message and of its submessages in a single pass, to
+    /// encode the message with [`encode_with_sizes`](Message::encode_with_sizes).
+    fn encoded_sizes(&self) -> EncodedSizes
+    where
+        Self: Sized,
+    {
+        EncodedSizes::new(self)
+    }
+
+    /// Encodes the message to a buffer, taking the lengths of the message and of its submessages
+    /// from `sizes` rather than computing them again.
+    ///
+    /// This writes the same bytes as [`encode`](Message::encode), in time linear in the size of
+    /// the message, however deeply its submessages nest. `sizes` must have been computed by
+    /// [`encoded_sizes`](Message::encoded_sizes) from this message, which mustn't have been
+    /// modified since. Otherwise, the encoding is garbled, or this panics if the buffer runs out
+    /// of capacity.
+    ///
+    /// An error will be returned if the buffer does not have sufficient capacity.
+    fn encode_with_sizes<B>(&self, sizes: &EncodedSizes, buf: &mut B) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        let required = sizes.encoded_len();
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+        self.encode_raw_cached(buf, &mut sizes.nested().iter());
+        Ok(())
+    }
+
+    /// Encodes the message to a buffer, or returns an error if the message nests submessages
+    /// beyond the recursion limit.
+    ///
+    /// See [`try_encoded_len`](Message::try_encoded_len). An error will also be returned if the
+    /// buffer does not have sufficient capacity.
+    fn try_encode<B>(&self, buf: &mut B) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        self.try_encoded_len()?;
+        self.encode(buf)
+    }
+
     /// Encodes the message to a newly allocated buffer.
     fn encode_to_vec(&self) -> Vec<u8>
     where
@@ -72,6 +253,76 @@ pub trait Message: Debug + Send + Sync {
         buf
     }
 
+    /// Encodes the message into `buf`, replacing its previous contents.
+    ///
+    /// The buffer is cleared before encoding and its existing capacity is reused, which avoids an
+    /// allocation per call when encoding many messages in a loop.
+    fn encode_to_vec_in(&self, buf: &mut Vec<u8>)
+    where
+        Self: Sized,
+    {
+        buf.clear();
+        buf.reserve(self.encoded_len());
+        self.encode_raw(buf);
+    }
+
+    /// Encodes the message into the start of `buf`, returning the written prefix.
+    ///
+    /// This is the slice counterpart of [`encode_to_vec`](Message::encode_to_vec), suited to
+    /// stack buffers and `no_std` targets. An error reporting the required and available sizes is
+    /// returned if `buf` is too small, in which case `buf` is left untouched.
+    fn encode_to_slice<'a>(&self, buf: &'a mut [u8]) -> Result<&'a [u8], EncodeError>
+    where
+        Self: Sized,
+    {
+        let required = self.encoded_len();
+        if required > buf.len() {
+            return Err(EncodeError::new(required, buf.len()));
+        }
+
+        let (written, _) = buf.split_at_mut(required);
+        let mut cursor = &mut *written;
+        self.encode_raw(&mut cursor);
+        Ok(written)
+    }
+
+    /// Returns an iterator which encodes the message incrementally, yielding chunks of
+    /// `chunk_size` bytes, except for the last chunk, which may be shorter.
+    ///
+    /// The message is encoded only as far as needed to produce the next chunk, which allows
+    /// sending a large message over a flow-controlled transport without first encoding all of it.
+    /// An empty message yields no chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    fn encode_chunks(&self, chunk_size: usize) -> EncodeChunks<'_, Self>
+    where
+        Self: Sized,
+    {
+        EncodeChunks::new(self, chunk_size)
+    }
+
+    /// Feeds the encoded message to `hasher`, without allocating a buffer to hold the encoding.
+    ///
+    /// The hash only depends on the encoded message, so it is stable across runs and processes
+    /// for stable hashers, making it suitable as a cache key. `HashMap` fields are encoded in
+    /// their iteration order, which varies between runs, so messages used this way should have
+    /// their map fields generated as `BTreeMap`s.
+    fn content_hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+        Self: Sized,
+    {
+        let mut buf = HashBuf {
+            hasher,
+            buf: [0; 256],
+            len: 0,
+        };
+        self.encode_raw(&mut buf);
+        buf.flush();
+    }
+
     /// Encodes the message with a length-delimiter to a buffer.
     ///
     /// An error will be returned if the buffer does not have sufficient capacity.
@@ -91,6 +342,40 @@ pub trait Message: Debug + Send + Sync {
         Ok(())
     }
 
+    /// Encodes the message with a length-delimiter to a buffer, transforming the encoded message
+    /// with `framing`, e.g. to compress it.
+    ///
+    /// The length-delimiter, in the format given by [`Framing::length_prefix`], holds the length
+    /// of the transformed message. An error will be returned if the buffer does not have
+    /// sufficient capacity, or if the length-delimiter can't hold the length, such as a
+    /// [`LengthPrefix::U32Le`](crate::LengthPrefix::U32Le) delimiter for 4 GiB or more.
+    fn encode_length_delimited_with<F, B>(
+        &self,
+        framing: &F,
+        buf: &mut B,
+    ) -> Result<(), EncodeError>
+    where
+        F: Framing + ?Sized,
+        B: BufMut,
+        Self: Sized,
+    {
+        let encoded = self.encode_to_vec();
+        let frame = framing.compress(&encoded);
+        let prefix = framing.length_prefix();
+        let len = frame.len();
+        if !prefix.can_hold(len) {
+            return Err(EncodeError::length_exceeds_prefix(len));
+        }
+        let required = len + prefix.encoded_len(len);
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+        prefix.encode(len, buf);
+        buf.put_slice(&frame);
+        Ok(())
+    }
+
     /// Encodes the message with a length-delimiter to a newly allocated buffer.
     fn encode_length_delimited_to_vec(&self) -> Vec<u8>
     where
@@ -116,6 +401,92 @@ pub trait Message: Debug + Send + Sync {
         Self::merge(&mut message, &mut buf).map(|_| message)
     }
 
+    /// Decodes an instance of the message from a buffer with `options` applied, returning the
+    /// message along with statistics about the decode.
+    ///
+    /// With the default options, this decodes as [`Message::decode`] does. The options can limit
+    /// the nesting depth and the number of fields of untrusted input, decode invalid UTF-8 lossily,
+    /// check for missing required fields, and decode bytes fields as slices of a single buffer;
+    /// see [`DecodeOptions`] for each of them.
+    ///
+    /// The entire buffer will be consumed.
+    fn decode_with_options<B>(
+        buf: B,
+        options: &DecodeOptions,
+    ) -> Result<(Self, DecodeStats), DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let mut message = Self::default();
+        let stats = message.merge_with_options(buf, options)?;
+        Ok((message, stats))
+    }
+
+    /// Decodes an instance of the message from the file at `path`.
+    ///
+    /// With the `mmap` feature, the file is memory-mapped and decoded from the mapping, rather
+    /// than read into a heap buffer first. The file is decoded from a `Bytes`, so `bytes` fields
+    /// represented as `Bytes` share the mapping instead of copying it, and keep it alive. Without
+    /// the feature, or if the file can't be mapped, its contents are read into a buffer, which
+    /// such fields share in the same way.
+    ///
+    /// The entire file will be consumed.
+    ///
+    /// # Safety
+    ///
+    /// If the file is mapped, the decoded message reads from it until decoding ends, and its
+    /// `Bytes` fields until they are dropped. The caller must ensure that the file is neither
+    /// modified nor truncated meanwhile, by this or any other process: modifying a mapped file is
+    /// undefined behavior, and reading a truncated one raises `SIGBUS` on Unix.
+    #[cfg(feature = "std")]
+    unsafe fn decode_file<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+        Self: Default,
+    {
+        let mut file = std::fs::File::open(path)?;
+        #[cfg(feature = "mmap")]
+        {
+            // SAFETY: the caller ensures the file isn't modified or truncated while the mapping,
+            // which is owned by the buffer and the fields sharing it, is alive.
+            if let Ok(mmap) = unsafe { memmap2::Mmap::map(&file) } {
+                return Ok(Self::decode(bytes::Bytes::from_owner(mmap))?);
+            }
+        }
+        let mut buf = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut buf)?;
+        Ok(Self::decode(bytes::Bytes::from(buf))?)
+    }
+
+    /// Decodes an instance of the message from a buffer, returning the message along with
+    /// statistics about each of its top-level fields, keyed by field number.
+    ///
+    /// The statistics show how many times each field occurred and how many bytes it took, which
+    /// is useful to find out which fields dominate the size of a payload. Unknown fields are
+    /// included.
+    ///
+    /// The entire buffer will be consumed.
+    fn decode_with_stats<B>(mut buf: B) -> Result<(Self, BTreeMap<u32, FieldStats>), DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let ctx = DecodeContext::default();
+        let mut message = Self::default();
+        let mut stats = BTreeMap::<u32, FieldStats>::new();
+        while buf.has_remaining() {
+            let remaining = buf.remaining();
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            message.merge_field(tag, wire_type, &mut buf, ctx.clone())?;
+
+            let field = stats.entry(tag).or_default();
+            field.count += 1;
+            field.bytes += remaining - buf.remaining();
+        }
+        Ok((message, stats))
+    }
+
     /// Decodes a length-delimited instance of the message from the buffer.
     fn decode_length_delimited<B>(buf: B) -> Result<Self, DecodeError>
     where
@@ -127,6 +498,117 @@ pub trait Message: Debug + Send + Sync {
         Ok(message)
     }
 
+    /// Decodes a length-delimited instance of the message from the buffer, reversing the
+    /// transformation applied by [`Message::encode_length_delimited_with`] with `framing`.
+    fn decode_length_delimited_with<F, B>(framing: &F, mut buf: B) -> Result<Self, DecodeError>
+    where
+        F: Framing + ?Sized,
+        B: Buf,
+        Self: Default,
+    {
+        let len = framing.length_prefix().decode(&mut buf)?;
+        if len > buf.remaining() {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::UnexpectedEof,
+                "buffer underflow",
+            ));
+        }
+        let frame = buf.copy_to_bytes(len);
+        let encoded = framing.decompress(&frame)?;
+        Self::decode(&*encoded)
+    }
+
+    /// Decodes a length-delimited instance of the message from the front of a slice, returning
+    /// the message and the remaining unconsumed bytes.
+    ///
+    /// Protobuf messages are not self-delimiting, so messages concatenated without framing can not
+    /// be told apart; this expects each message to be prefixed with its length, as written by
+    /// [`Message::encode_length_delimited`]. This makes it convenient to parse a stream of
+    /// back-to-back messages held in a single slice.
+    fn decode_prefix(buf: &[u8]) -> Result<(Self, &[u8]), DecodeError>
+    where
+        Self: Default,
+    {
+        let mut rest = buf;
+        let len = decode_length_delimiter(&mut rest)?;
+        if len > rest.len() {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::UnexpectedEof,
+                "buffer underflow",
+            ));
+        }
+        let (message, rest) = rest.split_at(len);
+        Ok((Self::decode(message)?, rest))
+    }
+
+    /// Decodes exactly one length-delimited instance of the message from the entire slice.
+    ///
+    /// Like [`Message::decode_prefix`], this expects the message to be prefixed with its length,
+    /// as written by [`Message::encode_length_delimited`], but an error is returned if any bytes
+    /// remain after the message, e.g. junk, padding or a second message. This makes "exactly one
+    /// message, no more" an explicit contract for strict endpoints. Note that [`Message::decode`]
+    /// consumes an unframed slice entirely, so it has no trailing bytes to reject.
+    fn decode_exact(buf: &[u8]) -> Result<Self, DecodeError>
+    where
+        Self: Default,
+    {
+        let (message, rest) = Self::decode_prefix(buf)?;
+        if !rest.is_empty() {
+            return Err(
+                DecodeError::new("unexpected trailing bytes").at_offset(buf.len() - rest.len())
+            );
+        }
+        Ok(message)
+    }
+
+    /// Decodes an instance of the message from a buffer on a best-effort basis, returning the
+    /// message along with the errors encountered.
+    ///
+    /// Unlike [`Message::decode`], an undecodable field does not abort decoding. If the field is
+    /// well-formed on the wire but its value is invalid, it is skipped. If the field itself is
+    /// malformed, e.g. it has an invalid wire type or is truncated, decoding resumes at the next
+    /// offset where a field begins with a valid key and, unless it starts a group, a complete
+    /// value. Each skipped field is reported as an error.
+    ///
+    /// This is intended for recovering data from damaged messages; the resulting message may be
+    /// missing fields, or contain fields decoded from misaligned bytes.
+    fn decode_lenient(buf: &[u8]) -> (Self, Vec<DecodeError>)
+    where
+        Self: Default,
+    {
+        let ctx = DecodeContext::default();
+        let mut message = Self::default();
+        let mut errors = Vec::new();
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let len = match field_len(buf, ctx.clone()) {
+                Ok(len) => len,
+                Err(error) => {
+                    errors.push(error);
+                    // Resynchronize on the next offset where a field starts. Candidates are
+                    // checked without descending into groups, so the scan stays linear.
+                    match (1..buf.len()).find(|&offset| starts_field(&buf[offset..], ctx.clone())) {
+                        Some(offset) => {
+                            buf = &buf[offset..];
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+            };
+
+            let (mut field, rest) = buf.split_at(len);
+            buf = rest;
+            let result = decode_key(&mut field).and_then(|(tag, wire_type)| {
+                message.merge_field(tag, wire_type, &mut field, ctx.clone())
+            });
+            if let Err(error) = result {
+                errors.push(error);
+            }
+        }
+        (message, errors)
+    }
+
     /// Decodes an instance of the message from a buffer, and merges it into `self`.
     ///
     /// The entire buffer will be consumed.
@@ -136,13 +618,74 @@ pub trait Message: Debug + Send + Sync {
         Self: Sized,
     {
         let ctx = DecodeContext::default();
+        let len = buf.remaining();
         while buf.has_remaining() {
-            let (tag, wire_type) = decode_key(&mut buf)?;
-            self.merge_field(tag, wire_type, &mut buf, ctx.clone())?;
+            let result = decode_key(&mut buf).and_then(|(tag, wire_type)| {
+                self.merge_field(tag, wire_type, &mut buf, ctx.clone())
+            });
+            if let Err(error) = result {
+                return Err(error.at_offset(len - buf.remaining()));
+            }
         }
         Ok(())
     }
 
+    /// Decodes an instance of the message from a buffer, replacing the contents of `self`.
+    ///
+    /// This is equivalent to calling [`clear`](Message::clear) followed by
+    /// [`merge`](Message::merge). Clearing keeps the capacity of the repeated, string and bytes
+    /// fields of `self`, so overwriting a message reuses its allocations, which makes it suitable
+    /// for pooling messages. If an error is returned, `self` holds the fields decoded before it.
+    ///
+    /// The entire buffer will be consumed.
+    fn overwrite<B>(&mut self, buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
+        self.clear();
+        self.merge(buf)
+    }
+
+    /// Decodes an instance of the message from a buffer with `options` applied, and merges it into
+    /// `self`, returning statistics about the decode.
+    ///
+    /// See [`Message::decode_with_options`].
+    ///
+    /// The entire buffer will be consumed.
+    fn merge_with_options<B>(
+        &mut self,
+        mut buf: B,
+        options: &DecodeOptions,
+    ) -> Result<DecodeStats, DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
+        if options.share_input {
+            // `copy_to_bytes` doesn't copy a `Bytes` input, and copies any other input once.
+            let input = buf.copy_to_bytes(buf.remaining());
+            return self.merge_with_options(input, &options.share_input(false));
+        }
+
+        let state = DecodeState::new(options);
+        let ctx = DecodeContext::with_state(&state);
+        let mut required = RequiredFields::new(self, &ctx);
+        let len = buf.remaining();
+        while buf.has_remaining() {
+            let result = decode_key(&mut buf).and_then(|(tag, wire_type)| {
+                ctx.count_fields(1)?;
+                required.mark(tag);
+                self.merge_field(tag, wire_type, &mut buf, ctx.clone())
+            });
+            if let Err(error) = result {
+                return Err(error.at_offset(len - buf.remaining()));
+            }
+        }
+        required.check(self, &ctx)?;
+        Ok(state.into_stats())
+    }
+
     /// Decodes a length-delimited instance of the message from buffer, and
     /// merges it into `self`.
     fn merge_length_delimited<B>(&mut self, mut buf: B) -> Result<(), DecodeError>
@@ -150,18 +693,90 @@ pub trait Message: Debug + Send + Sync {
         B: Buf,
         Self: Sized,
     {
+        let len = buf.remaining();
         message::merge(
             WireType::LengthDelimited,
             self,
             &mut buf,
             DecodeContext::default(),
         )
+        .map_err(|error| error.at_offset(len - buf.remaining()))
     }
 
+    /// Merges `other` into `self` in memory, as merging the encoding of `other` would.
+    ///
+    /// Singular fields set in `other` overwrite those of `self`, repeated fields are
+    /// concatenated, map entries of `other` replace those with the same keys, and submessages
+    /// set on both sides are merged recursively. This suits layering configuration messages,
+    /// without encoding and decoding them.
+    ///
+    /// Derived implementations merge field by field, without a recursion limit.
+    fn merge_from(&mut self, other: Self)
+    where
+        Self: Sized;
+
     /// Clears the message, resetting all fields to their default.
     fn clear(&mut self);
 }
 
+/// Returns the length of the encoded field, including its key, at the front of the buffer.
+fn field_len(mut buf: &[u8], ctx: DecodeContext) -> Result<usize, DecodeError> {
+    let len = buf.len();
+    let (tag, wire_type) = decode_key(&mut buf)?;
+    skip_field(wire_type, tag, &mut buf, ctx)?;
+    Ok(len - buf.len())
+}
+
+/// Returns whether the buffer starts with a valid key followed by a complete value. A group is
+/// only checked for its key, as its fields may span the rest of the buffer.
+fn starts_field(mut buf: &[u8], ctx: DecodeContext) -> bool {
+    match decode_key(&mut buf) {
+        Ok((_, WireType::StartGroup)) => true,
+        Ok((tag, wire_type)) => skip_field(wire_type, tag, &mut buf, ctx).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// A buffer which feeds the bytes written to it to a hasher in fixed-size blocks.
+struct HashBuf<'a, H> {
+    hasher: &'a mut H,
+    buf: [u8; 256],
+    len: usize,
+}
+
+impl<H> HashBuf<'_, H>
+where
+    H: Hasher,
+{
+    fn flush(&mut self) {
+        self.hasher.write(&self.buf[..self.len]);
+        self.len = 0;
+    }
+}
+
+unsafe impl<H> BufMut for HashBuf<'_, H>
+where
+    H: Hasher,
+{
+    fn remaining_mut(&self) -> usize {
+        usize::MAX
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.len += cnt;
+        // Flush a full block, so that `chunk_mut` always has space to return.
+        if self.len == self.buf.len() {
+            self.flush();
+        }
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        let chunk = &mut self.buf[self.len..];
+        // Safety: the chunk is a valid, initialized slice.
+        unsafe { UninitSlice::from_raw_parts_mut(chunk.as_mut_ptr(), chunk.len()) }
+    }
+}
+
 impl<M> Message for Box<M>
 where
     M: Message,
@@ -172,6 +787,12 @@ where
     {
         (**self).encode_raw(buf)
     }
+    fn encode_raw_part<B>(&self, part: usize, buf: &mut B) -> bool
+    where
+        B: BufMut,
+    {
+        (**self).encode_raw_part(part, buf)
+    }
     fn merge_field<B>(
         &mut self,
         tag: u32,
@@ -187,9 +808,33 @@ where
     fn encoded_len(&self) -> usize {
         (**self).encoded_len()
     }
+    fn encoded_len_cached(&self, sizes: &mut EncodedSizes) -> usize {
+        (**self).encoded_len_cached(sizes)
+    }
+    fn encode_raw_cached<B>(&self, buf: &mut B, sizes: &mut slice::Iter<'_, usize>)
+    where
+        B: BufMut,
+    {
+        (**self).encode_raw_cached(buf, sizes)
+    }
+    fn required_tags(&self) -> &'static [u32] {
+        (**self).required_tags()
+    }
+    fn check_required(&self, present: &[bool]) -> Result<(), DecodeError> {
+        (**self).check_required(present)
+    }
+    fn exceeds_depth(&self, limit: u32) -> bool {
+        (**self).exceeds_depth(limit)
+    }
+    fn clear_unknown_fields(&mut self) {
+        (**self).clear_unknown_fields()
+    }
     fn clear(&mut self) {
         (**self).clear()
     }
+    fn merge_from(&mut self, other: Self) {
+        (**self).merge_from(*other)
+    }
 }
 
 #[cfg(test)]
@@ -197,4 +842,21 @@ mod tests {
     use super::*;
 
     const _MESSAGE_IS_OBJECT_SAFE: Option<&dyn Message> = None;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn decode_file() {
+        let path = std::env::temp_dir().join(alloc::format!(
+            "prost-decode-file-{}.bin",
+            std::process::id()
+        ));
+        let value = bytes::Bytes::from_static(b"mapped");
+        std::fs::write(&path, value.encode_to_vec()).unwrap();
+        // SAFETY: the file is private to this test, and is only removed once the message is
+        // dropped.
+        let decoded = unsafe { bytes::Bytes::decode_file(&path) }.unwrap();
+        assert_eq!(decoded, value);
+        drop(decoded);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,31 @@
+//! Support for associating type name information with a [`Message`].
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::Message;
+
+/// Associate a type name with a [`Message`] type.
+pub trait Name: Message {
+    /// Simple name for this [`Message`], as it appears in the source `.proto` file, e.g. `FooBar`.
+    const NAME: &'static str;
+
+    /// Package name this message type is contained in, e.g. `google.protobuf`.
+    const PACKAGE: &'static str;
+
+    /// Fully-qualified unique name for this [`Message`].
+    ///
+    /// By default, this is the package name followed by the message name, e.g.
+    /// `google.rpc.BadRequest`.
+    fn full_name() -> String {
+        format!("{}.{}", Self::PACKAGE, Self::NAME)
+    }
+
+    /// Type URL for this [`Message`], used when packing the message into a `google.protobuf.Any`.
+    ///
+    /// By default, this is the full name with a leading slash, but it may also include a domain
+    /// name, e.g. `type.googleapis.com/google.protobuf.Duration`.
+    fn type_url() -> String {
+        format!("/{}", Self::full_name())
+    }
+}
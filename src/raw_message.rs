@@ -0,0 +1,53 @@
+//! Support for passing through already-encoded submessages.
+
+use core::ops::Deref;
+
+use bytes::Bytes;
+
+/// An encoded message embedded in another message without being decoded.
+///
+/// A `RawMessage` field holds the encoded contents of a submessage. Encoding writes the contents
+/// verbatim as a length-delimited field, and decoding captures them without inspecting them; when
+/// decoding from a [`Bytes`] buffer, no data is copied. This allows routing layers to forward
+/// submessages they don't need to look into.
+///
+/// Fields of this type are declared with `#[prost(bytes = "raw")]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RawMessage(pub Bytes);
+
+impl RawMessage {
+    /// Creates a `RawMessage` from the encoded contents of a message.
+    pub fn new(bytes: Bytes) -> RawMessage {
+        RawMessage(bytes)
+    }
+
+    /// Returns the encoded contents of the message.
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+
+    /// Clears the contents, leaving an encoded empty message.
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+}
+
+impl Deref for RawMessage {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Bytes> for RawMessage {
+    fn from(bytes: Bytes) -> RawMessage {
+        RawMessage(bytes)
+    }
+}
+
+impl From<&'static [u8]> for RawMessage {
+    fn from(bytes: &'static [u8]) -> RawMessage {
+        RawMessage(Bytes::from_static(bytes))
+    }
+}
@@ -0,0 +1,407 @@
+//! Serde helpers for generated messages, giving fields their Protobuf JSON representation.
+//!
+//! Requires the `serde` feature. Code generated with `prost_build::Config::derive_serde` refers
+//! to these helpers: `bytes` fields are serialized as base64 strings, and enumeration fields,
+//! which are generated as `i32`, are serialized by the name of their value.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, Deserialize, Deserializer, Unexpected, Visitor};
+use ::serde::ser::{Serialize, Serializer};
+
+use crate::alloc::string::String;
+use crate::alloc::vec::Vec;
+
+/// An enumeration whose values can be looked up by their names in the Protobuf definition.
+///
+/// `prost-build` implements this for the enumerations matched by `derive_serde`.
+pub trait NamedEnum {
+    /// Returns the name of `value`, or `None` if it isn't a known value.
+    fn name(value: i32) -> Option<&'static str>;
+
+    /// Returns the value named `name`, or `None` if there is no such value.
+    fn value(name: &str) -> Option<i32>;
+}
+
+/// An enumeration value, serialized by name.
+struct EnumValue<E>(i32, PhantomData<E>);
+
+impl<E> Serialize for EnumValue<E>
+where
+    E: NamedEnum,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Unknown values keep their number, as in the Protobuf JSON mapping.
+        match E::name(self.0) {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_i32(self.0),
+        }
+    }
+}
+
+impl<'de, E> Deserialize<'de> for EnumValue<E>
+where
+    E: NamedEnum,
+{
+    fn deserialize<D>(deserializer: D) -> Result<EnumValue<E>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(EnumVisitor(PhantomData))
+    }
+}
+
+struct EnumVisitor<E>(PhantomData<E>);
+
+impl<'de, E> Visitor<'de> for EnumVisitor<E>
+where
+    E: NamedEnum,
+{
+    type Value = EnumValue<E>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the name or number of an enumeration value")
+    }
+
+    fn visit_i64<Error>(self, value: i64) -> Result<EnumValue<E>, Error>
+    where
+        Error: de::Error,
+    {
+        i32::try_from(value)
+            .map(|value| EnumValue(value, PhantomData))
+            .map_err(|_| Error::invalid_value(Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<Error>(self, value: u64) -> Result<EnumValue<E>, Error>
+    where
+        Error: de::Error,
+    {
+        i32::try_from(value)
+            .map(|value| EnumValue(value, PhantomData))
+            .map_err(|_| Error::invalid_value(Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_str<Error>(self, value: &str) -> Result<EnumValue<E>, Error>
+    where
+        Error: de::Error,
+    {
+        E::value(value)
+            .map(|value| EnumValue(value, PhantomData))
+            .ok_or_else(|| Error::invalid_value(Unexpected::Str(value), &self))
+    }
+}
+
+/// Serde helpers for enumeration fields, which name the enumeration `E` explicitly, e.g.
+/// `#[serde(serialize_with = "prost::serde::enumeration::serialize::<Kind, _>")]`.
+pub mod enumeration {
+    use core::marker::PhantomData;
+
+    use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{EnumValue, NamedEnum};
+
+    /// Serializes an enumeration field.
+    pub fn serialize<E, S>(value: &i32, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        E: NamedEnum,
+        S: Serializer,
+    {
+        EnumValue::<E>(*value, PhantomData).serialize(serializer)
+    }
+
+    /// Deserializes an enumeration field.
+    pub fn deserialize<'de, E, D>(deserializer: D) -> Result<i32, D::Error>
+    where
+        E: NamedEnum,
+        D: Deserializer<'de>,
+    {
+        EnumValue::<E>::deserialize(deserializer).map(|EnumValue(value, _)| value)
+    }
+
+    /// Serde helpers for `optional` enumeration fields.
+    pub mod optional {
+        use core::marker::PhantomData;
+
+        use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use crate::serde::{EnumValue, NamedEnum};
+
+        /// Serializes an `optional` enumeration field.
+        pub fn serialize<E, S>(value: &Option<i32>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            E: NamedEnum,
+            S: Serializer,
+        {
+            value
+                .map(|value| EnumValue::<E>(value, PhantomData))
+                .serialize(serializer)
+        }
+
+        /// Deserializes an `optional` enumeration field.
+        pub fn deserialize<'de, E, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+        where
+            E: NamedEnum,
+            D: Deserializer<'de>,
+        {
+            Option::<EnumValue<E>>::deserialize(deserializer)
+                .map(|value| value.map(|EnumValue(value, _)| value))
+        }
+    }
+
+    /// Serde helpers for `repeated` enumeration fields.
+    pub mod repeated {
+        use core::marker::PhantomData;
+
+        use ::serde::{Deserialize, Deserializer, Serializer};
+
+        use crate::alloc::vec::Vec;
+        use crate::serde::{EnumValue, NamedEnum};
+
+        /// Serializes a `repeated` enumeration field.
+        #[allow(clippy::ptr_arg)]
+        pub fn serialize<E, S>(values: &Vec<i32>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            E: NamedEnum,
+            S: Serializer,
+        {
+            serializer.collect_seq(
+                values
+                    .iter()
+                    .map(|&value| EnumValue::<E>(value, PhantomData)),
+            )
+        }
+
+        /// Deserializes a `repeated` enumeration field.
+        pub fn deserialize<'de, E, D>(deserializer: D) -> Result<Vec<i32>, D::Error>
+        where
+            E: NamedEnum,
+            D: Deserializer<'de>,
+        {
+            Vec::<EnumValue<E>>::deserialize(deserializer).map(|values| {
+                values
+                    .into_iter()
+                    .map(|EnumValue(value, _)| value)
+                    .collect()
+            })
+        }
+    }
+}
+
+/// A `bytes` value, serialized as a base64 string.
+struct Base64<T>(T);
+
+impl<T> Serialize for Base64<T>
+where
+    T: AsRef<[u8]>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&encode_base64(self.0.as_ref()))
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Base64<T>
+where
+    T: From<Vec<u8>>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Base64<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(Base64Visitor(PhantomData))
+    }
+}
+
+struct Base64Visitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for Base64Visitor<T>
+where
+    T: From<Vec<u8>>,
+{
+    type Value = Base64<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a base64 string")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Base64<T>, E>
+    where
+        E: de::Error,
+    {
+        decode_base64(value)
+            .map(|bytes| Base64(T::from(bytes)))
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+    }
+}
+
+/// Serde helpers for `bytes` fields, of any type which the field can be generated with.
+pub mod bytes {
+    use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Base64;
+    use crate::alloc::vec::Vec;
+
+    /// Serializes a `bytes` field.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        Base64(value).serialize(serializer)
+    }
+
+    /// Deserializes a `bytes` field.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        Base64::deserialize(deserializer).map(|Base64(value)| value)
+    }
+
+    /// Serde helpers for `optional` `bytes` fields.
+    pub mod optional {
+        use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use crate::alloc::vec::Vec;
+        use crate::serde::Base64;
+
+        /// Serializes an `optional` `bytes` field.
+        pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: AsRef<[u8]>,
+            S: Serializer,
+        {
+            value.as_ref().map(Base64).serialize(serializer)
+        }
+
+        /// Deserializes an `optional` `bytes` field.
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            T: From<Vec<u8>>,
+            D: Deserializer<'de>,
+        {
+            Option::<Base64<T>>::deserialize(deserializer)
+                .map(|value| value.map(|Base64(value)| value))
+        }
+    }
+
+    /// Serde helpers for `repeated` `bytes` fields.
+    pub mod repeated {
+        use ::serde::{Deserialize, Deserializer, Serializer};
+
+        use crate::alloc::vec::Vec;
+        use crate::serde::Base64;
+
+        /// Serializes a `repeated` `bytes` field.
+        #[allow(clippy::ptr_arg)]
+        pub fn serialize<T, S>(values: &Vec<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: AsRef<[u8]>,
+            S: Serializer,
+        {
+            serializer.collect_seq(values.iter().map(Base64))
+        }
+
+        /// Deserializes a `repeated` `bytes` field.
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+        where
+            T: From<Vec<u8>>,
+            D: Deserializer<'de>,
+        {
+            Vec::<Base64<T>>::deserialize(deserializer)
+                .map(|values| values.into_iter().map(|Base64(value)| value).collect())
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes in standard base64, with padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (u32::from(byte) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes standard or URL-safe base64, with or without padding, as the Protobuf JSON mapping
+/// requires. Returns `None` if `encoded` isn't valid base64.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let unpadded = encoded
+        .strip_suffix("==")
+        .or_else(|| encoded.strip_suffix('='))
+        .unwrap_or(encoded);
+    if unpadded.len() % 4 == 1 || (unpadded.len() != encoded.len() && encoded.len() % 4 != 0) {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(unpadded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut len = 0;
+    for byte in unpadded.bytes() {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(sextet);
+        len += 6;
+        if len >= 8 {
+            len -= 8;
+            decoded.push((bits >> len) as u8);
+            bits &= (1 << len) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_base64() {
+        let cases: [(&[u8], &str); 6] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xfb\xff", "+/8="),
+        ];
+        for (bytes, encoded) in cases {
+            assert_eq!(encode_base64(bytes), encoded);
+            assert_eq!(decode_base64(encoded).as_deref(), Some(bytes));
+        }
+
+        // URL-safe and unpadded input is accepted too.
+        assert_eq!(decode_base64("-_8").as_deref(), Some(&b"\xfb\xff"[..]));
+        assert_eq!(decode_base64("Zg").as_deref(), Some(&b"f"[..]));
+
+        assert_eq!(decode_base64("Z"), None);
+        assert_eq!(decode_base64("Zg="), None);
+        assert_eq!(decode_base64("Zm9v!"), None);
+    }
+}
@@ -0,0 +1,167 @@
+//! Support for `bytes` fields sharing the buffer they were decoded from.
+
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::mem;
+use core::ops::Deref;
+
+use bytes::Bytes;
+
+/// A `bytes` field value which shares the buffer it was decoded from, used like a `Vec<u8>`.
+///
+/// A `SharedBytes` is an offset and a length into a reference-counted buffer. Decoding a message
+/// from a [`Bytes`] buffer, or with [`DecodeOptions::share_input`][1], makes each of its
+/// `SharedBytes` fields a slice of the single buffer holding the input, where `Vec<u8>` fields
+/// each allocate their own copy. This suits parsing a large input into many small byte fields.
+///
+/// Like a `Vec<u8>`, a `SharedBytes` dereferences to `[u8]`, converts from and into `Vec<u8>`,
+/// and can be extended or truncated. Extending a value which shares its buffer copies it out of
+/// the buffer first.
+///
+/// Fields of this type are declared with `#[prost(bytes = "shared")]`.
+///
+/// [1]: crate::DecodeOptions::share_input
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SharedBytes(pub(crate) Bytes);
+
+impl SharedBytes {
+    /// Creates an empty `SharedBytes`.
+    pub fn new() -> SharedBytes {
+        SharedBytes::default()
+    }
+
+    /// Returns the bytes as a slice.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the bytes as a `Vec<u8>`, which reuses the buffer if nothing else shares it.
+    pub fn into_vec(self) -> Vec<u8> {
+        Vec::from(self.0)
+    }
+
+    /// Returns the bytes as a [`Bytes`] slice of the shared buffer.
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+
+    /// Removes all bytes, releasing the shared buffer.
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Shortens the bytes to the first `len`, doing nothing if there are fewer.
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    /// Appends a byte.
+    pub fn push(&mut self, byte: u8) {
+        self.modify(|vec| vec.push(byte))
+    }
+
+    /// Appends the bytes of `other`.
+    pub fn extend_from_slice(&mut self, other: &[u8]) {
+        self.modify(|vec| vec.extend_from_slice(other))
+    }
+
+    /// Modifies the bytes as a `Vec<u8>`, copying them out of the shared buffer if necessary.
+    fn modify<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let mut vec = Vec::from(mem::take(&mut self.0));
+        f(&mut vec);
+        self.0 = Bytes::from(vec);
+    }
+}
+
+impl fmt::Debug for SharedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for SharedBytes {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Extend<u8> for SharedBytes {
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = u8>,
+    {
+        self.modify(|vec| vec.extend(iter))
+    }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(vec: Vec<u8>) -> SharedBytes {
+        SharedBytes(Bytes::from(vec))
+    }
+}
+
+impl From<&'static [u8]> for SharedBytes {
+    fn from(bytes: &'static [u8]) -> SharedBytes {
+        SharedBytes(Bytes::from_static(bytes))
+    }
+}
+
+impl From<Bytes> for SharedBytes {
+    fn from(bytes: Bytes) -> SharedBytes {
+        SharedBytes(bytes)
+    }
+}
+
+impl From<SharedBytes> for Vec<u8> {
+    fn from(bytes: SharedBytes) -> Vec<u8> {
+        bytes.into_vec()
+    }
+}
+
+impl From<SharedBytes> for Bytes {
+    fn from(bytes: SharedBytes) -> Bytes {
+        bytes.0
+    }
+}
+
+impl PartialEq<[u8]> for SharedBytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl PartialEq<&[u8]> for SharedBytes {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
+impl PartialEq<Vec<u8>> for SharedBytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl PartialEq<SharedBytes> for Vec<u8> {
+    fn eq(&self, other: &SharedBytes) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
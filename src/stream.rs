@@ -0,0 +1,190 @@
+//! Decoding of length-delimited messages from a stream of bytes.
+//!
+//! Requires the `std` feature.
+
+use core::fmt;
+use core::marker::PhantomData;
+use std::io::{self, Read};
+
+use alloc::vec::Vec;
+
+use crate::{length_delimiter_len, probe_length_delimited, DecodeError, DecodeErrorKind, Message};
+
+/// The number of bytes requested from the source by each read.
+const READ_SIZE: usize = 8 * 1024;
+
+/// An iterator over the messages of a stream of length-delimited frames, as written by
+/// [`Message::encode_length_delimited`], which are read from a `Read` source.
+///
+/// Input is read in chunks and buffered, so frames may be split across reads in any way. The
+/// iterator ends when the source ends between two frames. If the source ends within a frame,
+/// or a frame can't be decoded, or reading fails, the error is yielded once and the iterator
+/// ends. Reads interrupted by a signal are retried.
+///
+/// Wrapping the source in a `BufReader` is unnecessary, as the decoder buffers its input.
+#[derive(Debug)]
+pub struct LengthDelimitedDecoder<R, M> {
+    reader: R,
+    buf: Vec<u8>,
+    /// The offset of the next frame in `buf`.
+    start: usize,
+    /// Set once the iterator has ended.
+    done: bool,
+    message: PhantomData<fn() -> M>,
+}
+
+impl<R, M> LengthDelimitedDecoder<R, M> {
+    /// Creates a decoder of the frames read from `reader`.
+    pub fn new(reader: R) -> LengthDelimitedDecoder<R, M> {
+        LengthDelimitedDecoder {
+            reader,
+            buf: Vec::new(),
+            start: 0,
+            done: false,
+            message: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the source.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns the source. Input which has been read from it but not decoded yet is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, M> LengthDelimitedDecoder<R, M>
+where
+    R: Read,
+    M: Message + Default,
+{
+    /// Decodes the next frame, or returns `None` if the source ended between frames.
+    fn next_frame(&mut self) -> Result<Option<M>, Error> {
+        let len = loop {
+            if let Some(len) = probe_length_delimited(&self.buf[self.start..])? {
+                break len;
+            }
+            if !self.fill()? {
+                return if self.start == self.buf.len() {
+                    Ok(None)
+                } else {
+                    Err(truncated().into())
+                };
+            }
+        };
+
+        let delimiter_len = length_delimiter_len(len);
+        let frame_len = delimiter_len
+            .checked_add(len)
+            .ok_or_else(|| DecodeError::new("length delimiter exceeds maximum usize value"))?;
+        while self.buf.len() - self.start < frame_len {
+            if !self.fill()? {
+                return Err(truncated().into());
+            }
+        }
+
+        let message = M::decode(&self.buf[self.start + delimiter_len..self.start + frame_len])?;
+        self.start += frame_len;
+        Ok(Some(message))
+    }
+
+    /// Reads more input into the buffer, returning `false` if the source has ended.
+    fn fill(&mut self) -> io::Result<bool> {
+        // Drop the frames decoded already, rather than growing the buffer indefinitely.
+        self.buf.drain(..self.start);
+        self.start = 0;
+
+        let len = self.buf.len();
+        self.buf.resize(len + READ_SIZE, 0);
+        loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(read) => {
+                    self.buf.truncate(len + read);
+                    return Ok(read > 0);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.buf.truncate(len);
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+impl<R, M> Iterator for LengthDelimitedDecoder<R, M>
+where
+    R: Read,
+    M: Message + Default,
+{
+    type Item = Result<M, Error>;
+
+    fn next(&mut self) -> Option<Result<M, Error>> {
+        if self.done {
+            return None;
+        }
+        let frame = self.next_frame();
+        if !matches!(frame, Ok(Some(_))) {
+            self.done = true;
+        }
+        frame.transpose()
+    }
+}
+
+fn truncated() -> DecodeError {
+    DecodeError::with_kind(
+        DecodeErrorKind::UnexpectedEof,
+        "stream ended within a length-delimited frame",
+    )
+}
+
+/// An error decoding a stream of length-delimited messages.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from the source failed.
+    Io(io::Error),
+    /// A frame couldn't be decoded, or the source ended within a frame.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "failed to read message stream: {}", error),
+            Error::Decode(error) => write!(f, "failed to decode message stream: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Decode(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode(error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Io(error) => error,
+            Error::Decode(error) => error.into(),
+        }
+    }
+}
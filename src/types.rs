@@ -5,15 +5,20 @@
 //! the `prost-types` crate in order to avoid a cyclic dependency between `prost` and
 //! `prost-build`.
 
+use alloc::borrow::Cow;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use ::bytes::{Buf, BufMut, Bytes};
 
 use crate::{
     encoding::{
-        bool, bytes, double, float, int32, int64, skip_field, string, uint32, uint64,
-        DecodeContext, WireType,
+        bool, bytes, double, encode_key, encode_varint, encoded_len_varint, float, int32, int64,
+        key_len, skip_field, string, uint32, uint64, DecodeContext, WireType,
     },
     DecodeError, Message,
 };
@@ -54,6 +59,11 @@ impl Message for bool {
     fn clear(&mut self) {
         *self = false;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.UInt32Value`
@@ -92,6 +102,11 @@ impl Message for u32 {
     fn clear(&mut self) {
         *self = 0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.UInt64Value`
@@ -130,6 +145,11 @@ impl Message for u64 {
     fn clear(&mut self) {
         *self = 0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.Int32Value`
@@ -168,6 +188,11 @@ impl Message for i32 {
     fn clear(&mut self) {
         *self = 0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.Int64Value`
@@ -206,6 +231,11 @@ impl Message for i64 {
     fn clear(&mut self) {
         *self = 0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.FloatValue`
@@ -244,6 +274,11 @@ impl Message for f32 {
     fn clear(&mut self) {
         *self = 0.0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0.0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.DoubleValue`
@@ -282,6 +317,11 @@ impl Message for f64 {
     fn clear(&mut self) {
         *self = 0.0;
     }
+    fn merge_from(&mut self, other: Self) {
+        if other != 0.0 {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.StringValue`
@@ -320,6 +360,11 @@ impl Message for String {
     fn clear(&mut self) {
         self.clear();
     }
+    fn merge_from(&mut self, other: Self) {
+        if !other.is_empty() {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.BytesValue`
@@ -358,6 +403,11 @@ impl Message for Vec<u8> {
     fn clear(&mut self) {
         self.clear();
     }
+    fn merge_from(&mut self, other: Self) {
+        if !other.is_empty() {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.BytesValue`
@@ -396,6 +446,118 @@ impl Message for Bytes {
     fn clear(&mut self) {
         self.clear();
     }
+    fn merge_from(&mut self, other: Self) {
+        if !other.is_empty() {
+            *self = other;
+        }
+    }
+}
+
+/// Encodes a non-empty `value` of a `Cow` wrapper as its length-delimited field.
+fn encode_cow_value<B>(value: &[u8], buf: &mut B)
+where
+    B: BufMut,
+{
+    if !value.is_empty() {
+        encode_key(1, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+}
+
+/// Returns the encoded length of the `value` of a `Cow` wrapper.
+fn encoded_len_cow_value(value: &[u8]) -> usize {
+    if !value.is_empty() {
+        key_len(1) + encoded_len_varint(value.len() as u64) + value.len()
+    } else {
+        0
+    }
+}
+
+/// `google.protobuf.StringValue`
+///
+/// Borrowed strings are encoded without copying them into a `String` first. Decoding makes the
+/// value owned.
+impl Message for Cow<'_, str> {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_cow_value(self.as_bytes(), buf)
+    }
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        if tag == 1 {
+            string::merge(wire_type, self.to_mut(), buf, ctx)
+        } else {
+            skip_field(wire_type, tag, buf, ctx)
+        }
+    }
+    fn encoded_len(&self) -> usize {
+        encoded_len_cow_value(self.as_bytes())
+    }
+    fn clear(&mut self) {
+        match self {
+            Cow::Owned(value) => value.clear(),
+            Cow::Borrowed(_) => *self = Cow::Borrowed(""),
+        }
+    }
+    fn merge_from(&mut self, other: Self) {
+        if !other.is_empty() {
+            *self = other;
+        }
+    }
+}
+
+/// `google.protobuf.BytesValue`
+///
+/// Borrowed bytes are encoded without copying them into a `Vec` first. Decoding makes the value
+/// owned.
+impl Message for Cow<'_, [u8]> {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_cow_value(self, buf)
+    }
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        if tag == 1 {
+            bytes::merge_one_copy(wire_type, self.to_mut(), buf, ctx)
+        } else {
+            skip_field(wire_type, tag, buf, ctx)
+        }
+    }
+    fn encoded_len(&self) -> usize {
+        encoded_len_cow_value(self)
+    }
+    fn clear(&mut self) {
+        match self {
+            Cow::Owned(value) => value.clear(),
+            Cow::Borrowed(_) => *self = Cow::Borrowed(&[]),
+        }
+    }
+    fn merge_from(&mut self, other: Self) {
+        if !other.is_empty() {
+            *self = other;
+        }
+    }
 }
 
 /// `google.protobuf.Empty`
@@ -421,4 +583,195 @@ impl Message for () {
         0
     }
     fn clear(&mut self) {}
+    fn merge_from(&mut self, _other: Self) {}
+}
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+/// Encodes the `seconds` and `nanos` fields shared by `Duration` and `Timestamp`.
+fn encode_seconds_nanos<B>(seconds: i64, nanos: i32, buf: &mut B)
+where
+    B: BufMut,
+{
+    if seconds != 0 {
+        int64::encode(1, &seconds, buf);
+    }
+    if nanos != 0 {
+        int32::encode(2, &nanos, buf);
+    }
+}
+
+/// Returns the encoded length of the `seconds` and `nanos` fields shared by `Duration` and
+/// `Timestamp`.
+fn seconds_nanos_encoded_len(seconds: i64, nanos: i32) -> usize {
+    (if seconds != 0 {
+        int64::encoded_len(1, &seconds)
+    } else {
+        0
+    }) + (if nanos != 0 {
+        int32::encoded_len(2, &nanos)
+    } else {
+        0
+    })
+}
+
+/// Merges a `seconds` or `nanos` field into the parts of a `Duration` or `Timestamp`, checking
+/// that `nanos` is normalized.
+fn merge_seconds_nanos<B>(
+    seconds: &mut i64,
+    nanos: &mut i32,
+    tag: u32,
+    wire_type: WireType,
+    buf: &mut B,
+    ctx: DecodeContext,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    match tag {
+        1 => int64::merge(wire_type, seconds, buf, ctx),
+        2 => {
+            int32::merge(wire_type, nanos, buf, ctx)?;
+            if !(0..NANOS_PER_SECOND).contains(nanos) {
+                return Err(DecodeError::new("invalid nanos: out of range"));
+            }
+            Ok(())
+        }
+        _ => skip_field(wire_type, tag, buf, ctx),
+    }
+}
+
+/// Returns the whole seconds of a `Duration`, saturating at the largest encodable value.
+fn duration_seconds(duration: &Duration) -> i64 {
+    i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+}
+
+/// `google.protobuf.Duration`
+///
+/// Negative durations can't be represented, and fail to decode. Durations longer than
+/// `i64::MAX` seconds are encoded as `i64::MAX` seconds.
+impl Message for Duration {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_seconds_nanos(duration_seconds(self), self.subsec_nanos() as i32, buf)
+    }
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut seconds = duration_seconds(self);
+        let mut nanos = self.subsec_nanos() as i32;
+        merge_seconds_nanos(&mut seconds, &mut nanos, tag, wire_type, buf, ctx)?;
+        let seconds = u64::try_from(seconds).map_err(|_| {
+            DecodeError::new("invalid duration: negative durations are not supported")
+        })?;
+        *self = Duration::new(seconds, nanos as u32);
+        Ok(())
+    }
+    fn encoded_len(&self) -> usize {
+        seconds_nanos_encoded_len(duration_seconds(self), self.subsec_nanos() as i32)
+    }
+    fn clear(&mut self) {
+        *self = Duration::default();
+    }
+    fn merge_from(&mut self, other: Self) {
+        let seconds = match other.as_secs() {
+            0 => self.as_secs(),
+            seconds => seconds,
+        };
+        let nanos = match other.subsec_nanos() {
+            0 => self.subsec_nanos(),
+            nanos => nanos,
+        };
+        *self = Duration::new(seconds, nanos);
+    }
+}
+
+/// Returns the seconds and normalized nanos of a `SystemTime` relative to the Unix epoch.
+#[cfg(feature = "std")]
+fn timestamp_parts(time: &SystemTime) -> (i64, i32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (duration_seconds(&since), since.subsec_nanos() as i32),
+        Err(error) => {
+            let before = error.duration();
+            let seconds = -duration_seconds(&before);
+            match before.subsec_nanos() as i32 {
+                0 => (seconds, 0),
+                nanos => (seconds.saturating_sub(1), NANOS_PER_SECOND - nanos),
+            }
+        }
+    }
+}
+
+/// Returns the `SystemTime` `seconds` and normalized `nanos` after the Unix epoch, if the platform
+/// can represent it.
+#[cfg(feature = "std")]
+fn timestamp_from_parts(seconds: i64, nanos: i32) -> Option<SystemTime> {
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::new(seconds as u64, nanos as u32))
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+            .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+    }
+}
+
+/// `google.protobuf.Timestamp`
+///
+/// Timestamps which the platform's `SystemTime` can't represent fail to decode. As `SystemTime`
+/// doesn't implement `Default`, it is decoded by merging into an existing value, such as
+/// `UNIX_EPOCH`, rather than with `Message::decode`.
+#[cfg(feature = "std")]
+impl Message for SystemTime {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        let (seconds, nanos) = timestamp_parts(self);
+        encode_seconds_nanos(seconds, nanos, buf)
+    }
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let (mut seconds, mut nanos) = timestamp_parts(self);
+        merge_seconds_nanos(&mut seconds, &mut nanos, tag, wire_type, buf, ctx)?;
+        *self = timestamp_from_parts(seconds, nanos)
+            .ok_or_else(|| DecodeError::new("invalid timestamp: out of range"))?;
+        Ok(())
+    }
+    fn encoded_len(&self) -> usize {
+        let (seconds, nanos) = timestamp_parts(self);
+        seconds_nanos_encoded_len(seconds, nanos)
+    }
+    fn clear(&mut self) {
+        *self = UNIX_EPOCH;
+    }
+    fn merge_from(&mut self, other: Self) {
+        let (seconds, nanos) = timestamp_parts(self);
+        let (other_seconds, other_nanos) = timestamp_parts(&other);
+        let seconds = if other_seconds != 0 {
+            other_seconds
+        } else {
+            seconds
+        };
+        let nanos = if other_nanos != 0 { other_nanos } else { nanos };
+        // Combining the parts can only leave the platform's range at its very ends, where
+        // `other` is kept whole.
+        *self = timestamp_from_parts(seconds, nanos).unwrap_or(other);
+    }
 }
@@ -0,0 +1,178 @@
+//! Support for preserving the unknown fields of decoded messages.
+
+use alloc::vec::Vec;
+
+use bytes::{Buf, BufMut};
+
+use crate::encoding::{decode_key, encode_key, DecodeContext, WireType};
+use crate::{DecodeError, DecodeErrorKind};
+
+/// The fields of a decoded message which its type doesn't declare.
+///
+/// Decoding keeps each unknown field in its encoded form, rather than skipping it, and encoding
+/// writes the fields again after the declared fields of the message. A proxy can thus decode a
+/// message, change a field it knows about, and encode the message again without dropping the
+/// fields added by newer versions of its schema. The fields are kept in the order they were
+/// decoded in, and the values of groups are copied as is, so that they survive a round trip byte
+/// for byte. Only the key of each field is encoded again, in its canonical form.
+///
+/// Fields of this type are declared with `#[prost(unknown_fields)]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UnknownFields {
+    fields: Vec<UnknownField>,
+}
+
+/// An unknown field, key included.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct UnknownField {
+    tag: u32,
+    encoded: Vec<u8>,
+}
+
+impl UnknownFields {
+    /// Creates an empty set of unknown fields.
+    pub fn new() -> UnknownFields {
+        UnknownFields::default()
+    }
+
+    /// Returns the number of unknown fields, counting each occurrence of a tag.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if there are no unknown fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns an iterator over the tags and encoded forms, keys included, of the unknown fields,
+    /// in the order they were decoded in.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.fields
+            .iter()
+            .map(|field| (field.tag, field.encoded.as_slice()))
+    }
+
+    /// Removes all unknown fields.
+    pub fn clear(&mut self) {
+        self.fields.clear()
+    }
+
+    /// Appends the unknown fields of `other`, as decoding them after those of `self` would.
+    pub fn merge_from(&mut self, other: UnknownFields) {
+        self.fields.extend(other.fields);
+    }
+
+    /// Decodes the value of an unknown field from the buffer, and keeps the field.
+    ///
+    /// The key of the field, holding `tag` and `wire_type`, must already have been decoded.
+    pub fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut encoded = Vec::new();
+        encode_key(tag, wire_type, &mut encoded);
+        copy_value(wire_type, tag, buf, &mut encoded, ctx.clone())?;
+        ctx.charge_bytes(encoded.len())?;
+        self.fields.push(UnknownField { tag, encoded });
+        Ok(())
+    }
+
+    /// Encodes the unknown fields to the buffer.
+    pub fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        for field in &self.fields {
+            buf.put_slice(&field.encoded);
+        }
+    }
+
+    /// Returns the encoded length of the unknown fields.
+    pub fn encoded_len(&self) -> usize {
+        self.fields.iter().map(|field| field.encoded.len()).sum()
+    }
+}
+
+/// Copies the value of a field from `buf` to `out` byte for byte, as `skip_field` would skip it.
+fn copy_value<B>(
+    wire_type: WireType,
+    tag: u32,
+    buf: &mut B,
+    out: &mut Vec<u8>,
+    ctx: DecodeContext,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    ctx.limit_reached()?;
+    let len = match wire_type {
+        WireType::Varint => copy_varint(buf, out).map(|_| 0)?,
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        WireType::LengthDelimited => copy_varint(buf, out)?,
+        WireType::StartGroup => loop {
+            // Copy the key as is, then decode it from the copy.
+            let start = out.len();
+            copy_varint(buf, out)?;
+            let (inner_tag, inner_wire_type) = decode_key(&mut &out[start..])?;
+            ctx.count_fields(1)?;
+            match inner_wire_type {
+                WireType::EndGroup => {
+                    if inner_tag != tag {
+                        return Err(DecodeError::new("unexpected end group tag"));
+                    }
+                    break 0;
+                }
+                _ => copy_value(inner_wire_type, inner_tag, buf, out, ctx.enter_recursion())?,
+            }
+        },
+        WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
+    };
+
+    if len > buf.remaining() as u64 {
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEof,
+            "buffer underflow",
+        ));
+    }
+
+    out.put((&mut *buf).take(len as usize));
+    Ok(())
+}
+
+/// Copies a varint from `buf` to `out` byte for byte, returning its value.
+fn copy_varint<B>(buf: &mut B, out: &mut Vec<u8>) -> Result<u64, DecodeError>
+where
+    B: Buf,
+{
+    let mut value = 0;
+    for count in 0..10 {
+        if !buf.has_remaining() {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::UnexpectedEof,
+                "invalid varint",
+            ));
+        }
+        let byte = buf.get_u8();
+        out.push(byte);
+        value |= u64::from(byte & 0x7F) << (count * 7);
+        if byte < 0x80 {
+            // The tenth byte may only hold the last bit of a 64-bit value.
+            if count == 9 && byte > 1 {
+                break;
+            }
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::with_kind(
+        DecodeErrorKind::InvalidVarint,
+        "invalid varint",
+    ))
+}
@@ -40,6 +40,33 @@ fn main() {
         "Foo.Custom.OneOfAttrs.Msg.field",
         "#[derive(Eq, PartialOrd, Ord)]",
     );
+    config.oneof_case("Foo.Custom.OneOfAttrs.Msg.field");
+    config.oneof_from(".recursive_oneof");
+    config.oneof_from(".no_implicit_prelude");
+    config.columnar(".no_implicit_prelude.Outer");
+    config.transparent(".no_implicit_prelude.Id");
+    config.transparent(".no_implicit_prelude.Name");
+    config.transparent(".no_implicit_prelude.Blob");
+    config.bytes([".no_implicit_prelude.Blob.value"]);
+    config.enum_map_keys(
+        ".no_implicit_prelude.Palette.names",
+        ".no_implicit_prelude.Color",
+    );
+    config.open_enums(".no_implicit_prelude.Shade");
+    config.enum_map_keys(
+        ".no_implicit_prelude.Swatch.names",
+        ".no_implicit_prelude.Shade",
+    );
+    config.enum_string_impls();
+    config.collection_wrappers();
+    cfg_if! {
+        if #[cfg(feature = "edition-2015")] {
+            const DOMAIN_COLOR: &str = "::DomainColor";
+        } else {
+            const DOMAIN_COLOR: &str = "crate::DomainColor";
+        }
+    };
+    config.enum_extern(".no_implicit_prelude.Color", DOMAIN_COLOR);
     config.field_attribute("Foo.Custom.Attrs.AnotherEnum.C", "/// The C docs");
     config.field_attribute("Foo.Custom.Attrs.AnotherEnum.D", "/// The D docs");
     config.field_attribute("Foo.Custom.Attrs.Msg.field.a", "/// Oneof A docs");
@@ -54,6 +81,7 @@ fn main() {
         .compile_protos(&[src.join("ident_conversion.proto")], includes)
         .unwrap();
 
+    config.boxed_map_value(".nesting.E.children");
     config
         .compile_protos(&[src.join("nesting.proto")], includes)
         .unwrap();
@@ -74,6 +102,7 @@ fn main() {
         .compile_protos(&[src.join("no_unused_results.proto")], includes)
         .unwrap();
 
+    config.enum_try_from(".default_enum_value.PrivacyLevel");
     config
         .compile_protos(&[src.join("default_enum_value.proto")], includes)
         .unwrap();
@@ -82,6 +111,10 @@ fn main() {
         .compile_protos(&[src.join("groups.proto")], includes)
         .unwrap();
 
+    config
+        .compile_protos(&[src.join("required_fields.proto")], includes)
+        .unwrap();
+
     config
         .compile_protos(&[src.join("deprecated_field.proto")], includes)
         .unwrap();
@@ -90,11 +123,25 @@ fn main() {
         .compile_protos(&[src.join("default_string_escape.proto")], includes)
         .unwrap();
 
+    config
+        .compile_protos(&[src.join("no_implicit_prelude.proto")], includes)
+        .unwrap();
+
     prost_build::Config::new()
         .protoc_arg("--experimental_allow_proto3_optional")
+        .option_accessors(".proto3.presence.Accessors")
+        .field_accessors(".proto3.presence.Patch")
         .compile_protos(&[src.join("proto3_presence.proto")], includes)
         .unwrap();
 
+    prost_build::Config::new()
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .btree_map(["."])
+        .derive_serde(".")
+        .serde_default(".")
+        .compile_protos(&[src.join("derive_serde.proto")], includes)
+        .unwrap();
+
     {
         let mut config = prost_build::Config::new();
         config.disable_comments(["."]);
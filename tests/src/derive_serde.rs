@@ -0,0 +1,118 @@
+use alloc::string::ToString;
+
+mod derive_serde {
+    include!(concat!(env!("OUT_DIR"), "/derive_serde.rs"));
+}
+
+use self::derive_serde::{all_types, AllTypes, Color, Nested};
+
+fn sample() -> AllTypes {
+    AllTypes {
+        int32: -1,
+        int64: 1 << 40,
+        uint64: 7,
+        double: 0.5,
+        bool: true,
+        string: "text".to_string(),
+        bytes: b"\x00\x01\xfe".to_vec(),
+        color: Color::Red as i32,
+        nested: Some(Nested { value: 3 }),
+        optional_bytes: Some(b"foo".to_vec()),
+        optional_color: Some(Color::Unspecified as i32),
+        strings: vec!["a".to_string(), "b".to_string()],
+        repeated_bytes: vec![b"f".to_vec(), vec![]],
+        colors: vec![Color::Green as i32, 5],
+        nesteds: vec![Nested { value: 1 }, Nested::default()],
+        counts: vec![("one".to_string(), 1)].into_iter().collect(),
+        choice: Some(all_types::Choice::Data(b"fo".to_vec())),
+    }
+}
+
+#[test]
+fn test_derive_serde_round_trip() {
+    let message = sample();
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "int32": -1,
+            "int64": 1_i64 << 40,
+            "uint64": 7,
+            "double": 0.5,
+            "bool": true,
+            "string": "text",
+            "bytes": "AAH+",
+            "color": "COLOR_RED",
+            "nested": { "value": 3 },
+            "optional_bytes": "Zm9v",
+            "optional_color": "COLOR_UNSPECIFIED",
+            "strings": ["a", "b"],
+            "repeated_bytes": ["Zg==", ""],
+            "colors": ["COLOR_GREEN", 5],
+            "nesteds": [{ "value": 1 }, {}],
+            "counts": { "one": 1 },
+            "data": "Zm8=",
+        })
+    );
+    assert_eq!(serde_json::from_value::<AllTypes>(json).unwrap(), message);
+
+    // Fields holding their defaults are omitted, and taken as their defaults when absent.
+    let json = serde_json::to_string(&AllTypes::default()).unwrap();
+    assert_eq!(json, "{}");
+    assert_eq!(
+        serde_json::from_str::<AllTypes>(&json).unwrap(),
+        AllTypes::default()
+    );
+}
+
+#[test]
+fn test_derive_serde_oneof() {
+    for (choice, json) in vec![
+        (
+            all_types::Choice::Name("n".to_string()),
+            serde_json::json!({ "name": "n" }),
+        ),
+        (
+            all_types::Choice::Shade(Color::Green as i32),
+            serde_json::json!({ "shade": "COLOR_GREEN" }),
+        ),
+        (
+            all_types::Choice::Child(Nested { value: 2 }),
+            serde_json::json!({ "child": { "value": 2 } }),
+        ),
+    ] {
+        let message = AllTypes {
+            choice: Some(choice),
+            ..AllTypes::default()
+        };
+        assert_eq!(serde_json::to_value(&message).unwrap(), json);
+        assert_eq!(serde_json::from_value::<AllTypes>(json).unwrap(), message);
+    }
+}
+
+#[test]
+fn test_derive_serde_lenient_input() {
+    // Enumeration values may be given by number, and bytes in URL-safe base64 without padding.
+    let message = serde_json::from_str::<AllTypes>(
+        r#"{"color": 2, "bytes": "-_8", "colors": [1, "COLOR_RED"]}"#,
+    )
+    .unwrap();
+    assert_eq!(message.color, Color::Green as i32);
+    assert_eq!(message.bytes, b"\xfb\xff".to_vec());
+    assert_eq!(message.colors, vec![Color::Red as i32; 2]);
+
+    assert!(serde_json::from_str::<AllTypes>(r#"{"color": "COLOR_BLUE"}"#).is_err());
+    assert!(serde_json::from_str::<AllTypes>(r#"{"bytes": "!"}"#).is_err());
+}
+
+#[test]
+fn test_derive_serde_enum() {
+    assert_eq!(
+        serde_json::to_string(&Color::Green).unwrap(),
+        r#""COLOR_GREEN""#
+    );
+    assert_eq!(
+        serde_json::from_str::<Color>(r#""COLOR_RED""#).unwrap(),
+        Color::Red
+    );
+}
@@ -36,6 +36,8 @@ mod bootstrap;
 mod debug;
 #[cfg(test)]
 mod deprecated_field;
+#[cfg(all(test, feature = "std", not(feature = "edition-2015")))]
+mod derive_serde;
 #[cfg(test)]
 mod generic_derive;
 #[cfg(test)]
@@ -90,6 +92,10 @@ pub mod groups {
     include!(concat!(env!("OUT_DIR"), "/groups.rs"));
 }
 
+pub mod required_fields {
+    include!(concat!(env!("OUT_DIR"), "/required_fields.rs"));
+}
+
 pub mod proto3 {
     pub mod presence {
         include!(concat!(env!("OUT_DIR"), "/proto3.presence.rs"));
@@ -106,6 +112,20 @@ pub mod default_string_escape {
     include!(concat!(env!("OUT_DIR"), "/default_string_escape.rs"));
 }
 
+/// Generated code must only use fully-qualified paths, so that it can be included in modules
+/// without the implicit prelude.
+#[no_implicit_prelude]
+pub mod no_implicit_prelude {
+    include!(concat!(env!("OUT_DIR"), "/no_implicit_prelude.rs"));
+}
+
+/// A hand-written enum with generated conversions to and from `no_implicit_prelude::Color`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DomainColor {
+    Red,
+    Green,
+}
+
 use alloc::vec::Vec;
 
 use anyhow::anyhow;
@@ -185,6 +205,13 @@ where
         return RoundtripResult::Error(error.into());
     }
     let buf3 = roundtrip.encode_to_vec();
+    let mut buf4 = Vec::from([0xFF; 8]);
+    roundtrip.encode_to_vec_in(&mut buf4);
+    let mut buf5 = vec![0xFF; encoded_len + 8];
+    let buf5 = match roundtrip.encode_to_slice(&mut buf5) {
+        Ok(written) => written,
+        Err(error) => return RoundtripResult::Error(error.into()),
+    };
 
     /*
     // Useful for debugging:
@@ -203,6 +230,18 @@ where
         ));
     }
 
+    if buf1 != buf4 {
+        return RoundtripResult::Error(anyhow!(
+            "roundtripped encoded buffers do not match with `encode_to_vec_in`"
+        ));
+    }
+
+    if buf1 != buf5 {
+        return RoundtripResult::Error(anyhow!(
+            "roundtripped encoded buffers do not match with `encode_to_slice`"
+        ));
+    }
+
     RoundtripResult::Ok(buf1)
 }
 
@@ -217,6 +256,13 @@ where
     msg.encode(&mut buf).unwrap();
     assert_eq!(expected_len, buf.len());
 
+    // Encoding with precomputed sizes writes the same bytes.
+    let sizes = msg.encoded_sizes();
+    assert_eq!(expected_len, sizes.encoded_len());
+    let mut sized_buf = Vec::new();
+    msg.encode_with_sizes(&sizes, &mut sized_buf).unwrap();
+    assert_eq!(buf, sized_buf);
+
     let mut buf = &*buf;
     let roundtrip = M::decode(&mut buf).unwrap();
 
@@ -371,6 +417,22 @@ mod tests {
         set2.insert(msg2.field);
     }
 
+    #[test]
+    fn test_oneof_case() {
+        use crate::oneof_attributes::msg::{Field, FieldCase};
+        use crate::oneof_attributes::Msg;
+
+        let mut msg = Msg::default();
+        assert_eq!(msg.which_field(), None);
+
+        msg.field = Some(Field::A("a".to_string()));
+        assert_eq!(msg.which_field(), Some(FieldCase::A));
+
+        msg.field = Some(Field::B(vec![1, 2, 3]));
+        assert_eq!(msg.which_field(), Some(FieldCase::B));
+        assert_eq!(msg.field.as_ref().map(Field::case), Some(FieldCase::B));
+    }
+
     #[test]
     fn test_nesting() {
         use crate::nesting::{A, B};
@@ -384,6 +446,90 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_btree_map_message_values() {
+        use crate::nesting::D;
+
+        let d = |entries: Vec<(&str, D)>| D {
+            m: entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        };
+
+        let msg = d(vec![
+            ("c", d(vec![("z", D::default())])),
+            ("a", D::default()),
+            ("b", d(vec![("y", D::default()), ("x", D::default())])),
+        ]);
+        check_message(&msg);
+
+        // Entries are encoded in key order, regardless of insertion order.
+        let expected = msg
+            .m
+            .iter()
+            .flat_map(|(key, value)| d(vec![(key.as_str(), value.clone())]).encode_to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(msg.encode_to_vec(), expected);
+
+        // The last entry for a duplicated key replaces earlier entries, rather than being merged.
+        let first = d(vec![("a", d(vec![("x", D::default())]))]);
+        let last = d(vec![("a", d(vec![("y", D::default())]))]);
+        let mut buf = first.encode_to_vec();
+        buf.extend(last.encode_to_vec());
+        assert_eq!(D::decode(&*buf).unwrap(), last);
+    }
+
+    #[test]
+    fn test_recursive_default() {
+        use crate::nesting::Tree;
+
+        let tree = Tree::default();
+        assert_eq!(tree.value, 0);
+        assert_eq!(tree.left, None);
+        assert_eq!(tree.right, None);
+
+        let leaf = |value| {
+            Some(Box::new(Tree {
+                value,
+                ..Tree::default()
+            }))
+        };
+        let tree = Tree {
+            value: 1,
+            left: leaf(2),
+            right: Some(Box::new(Tree {
+                value: 3,
+                left: leaf(4),
+                right: None,
+            })),
+        };
+        check_message(&tree);
+        assert_eq!(
+            Tree::decode(&*Tree::default().encode_to_vec()).unwrap(),
+            Tree::default()
+        );
+    }
+
+    #[test]
+    fn test_boxed_map_value() {
+        use crate::nesting::E;
+
+        let leaf: Box<E> = Box::default();
+        let msg = E {
+            children: vec![(
+                "a".to_string(),
+                Box::new(E {
+                    children: vec![("b".to_string(), leaf)].into_iter().collect(),
+                }),
+            )]
+            .into_iter()
+            .collect(),
+        };
+        check_message(&msg);
+        assert_eq!(msg.children["a"].children["b"], Box::default());
+    }
+
     #[test]
     fn test_deep_nesting() {
         fn build_and_roundtrip(depth: usize) -> Result<(), prost::DecodeError> {
@@ -398,13 +544,78 @@ mod tests {
 
             let mut buf = Vec::new();
             a.encode(&mut buf).unwrap();
-            A::decode(&*buf).map(|_| ())
+            let decoded = A::decode(&*buf).map(|_| ());
+            // Encoding checks the nesting depth against the same limit as decoding.
+            assert_eq!(a.try_encoded_len().is_ok(), decoded.is_ok());
+            decoded
         }
 
         assert!(build_and_roundtrip(100).is_ok());
         assert!(build_and_roundtrip(101).is_err());
     }
 
+    #[test]
+    fn test_decode_max_depth() {
+        use crate::nesting::{A, C};
+        use prost::DecodeOptions;
+
+        let mut a = Box::new(A::default());
+        for _ in 0..10 {
+            let mut next = Box::new(A::default());
+            next.a = Some(a);
+            a = next;
+        }
+        let options = DecodeOptions::new();
+        let (decoded, stats) = A::decode_with_options(&*a.encode_to_vec(), &options).unwrap();
+        assert_eq!(decoded, *a);
+        assert_eq!(stats.max_depth, 10);
+
+        let (_, stats) = A::decode_with_options(&[][..], &options).unwrap();
+        assert_eq!(stats.max_depth, 0);
+
+        // The depth is the maximum over all branches of the message.
+        let mut c = C::default();
+        c.r.push(C::default());
+        c.r.push(C {
+            r: vec![C {
+                r: vec![C::default()],
+            }],
+        });
+        let (_, stats) = C::decode_with_options(&*c.encode_to_vec(), &options).unwrap();
+        assert_eq!(stats.max_depth, 3);
+    }
+
+    #[test]
+    fn test_decode_recursion_limit() {
+        use crate::nesting::{A, C};
+        use prost::DecodeOptions;
+
+        fn decode<M: Message + Default>(buf: &[u8], limit: u32) -> Result<M, prost::DecodeError> {
+            let options = DecodeOptions::new().recursion_limit(limit);
+            M::decode_with_options(buf, &options).map(|(msg, _)| msg)
+        }
+
+        let mut a = Box::new(A::default());
+        for _ in 0..10 {
+            let mut next = Box::new(A::default());
+            next.a = Some(a);
+            a = next;
+        }
+        let buf = a.encode_to_vec();
+        assert_eq!(decode::<A>(&buf, 10).unwrap(), *a);
+        let error = decode::<A>(&buf, 9).unwrap_err();
+        assert!(error.to_string().contains("recursion limit reached"));
+
+        // The limit applies to each branch on its own, not to the number of nested messages.
+        let c = C {
+            r: vec![C::default(); 5],
+        };
+        let buf = c.encode_to_vec();
+        assert_eq!(decode::<C>(&buf, 1).unwrap(), c);
+        assert!(decode::<C>(&buf, 0).is_err());
+        assert_eq!(decode::<C>(&[], 0).unwrap(), C::default());
+    }
+
     #[test]
     fn test_deep_nesting_oneof() {
         fn build_and_roundtrip(depth: usize) -> Result<(), prost::DecodeError> {
@@ -421,7 +632,10 @@ mod tests {
 
             let mut buf = Vec::new();
             a.encode(&mut buf).unwrap();
-            A::decode(&*buf).map(|_| ())
+            let decoded = A::decode(&*buf).map(|_| ());
+            // Encoding checks the nesting depth against the same limit as decoding.
+            assert_eq!(a.try_encoded_len().is_ok(), decoded.is_ok());
+            decoded
         }
 
         assert!(build_and_roundtrip(99).is_ok());
@@ -444,7 +658,10 @@ mod tests {
 
             let mut buf = Vec::new();
             a.encode(&mut buf).unwrap();
-            NestedGroup2::decode(&*buf).map(|_| ())
+            let decoded = NestedGroup2::decode(&*buf).map(|_| ());
+            // Encoding checks the nesting depth against the same limit as decoding.
+            assert_eq!(a.try_encoded_len().is_ok(), decoded.is_ok());
+            decoded
         }
 
         assert!(build_and_roundtrip(50).is_ok());
@@ -465,7 +682,10 @@ mod tests {
 
             let mut buf = Vec::new();
             c.encode(&mut buf).unwrap();
-            C::decode(&*buf).map(|_| ())
+            let decoded = C::decode(&*buf).map(|_| ());
+            // Encoding checks the nesting depth against the same limit as decoding.
+            assert_eq!(c.try_encoded_len().is_ok(), decoded.is_ok());
+            decoded
         }
 
         assert!(build_and_roundtrip(100).is_ok());
@@ -486,7 +706,10 @@ mod tests {
 
             let mut buf = Vec::new();
             d.encode(&mut buf).unwrap();
-            D::decode(&*buf).map(|_| ())
+            let decoded = D::decode(&*buf).map(|_| ());
+            // Encoding checks the nesting depth against the same limit as decoding.
+            assert_eq!(d.try_encoded_len().is_ok(), decoded.is_ok());
+            decoded
         }
 
         assert!(build_and_roundtrip(50).is_ok());
@@ -505,6 +728,25 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_oneof_from() {
+        use crate::recursive_oneof::{a, A, B, C};
+
+        // Boxed payloads are boxed by the conversion.
+        let b = B {
+            a: Some(Box::new(A::default())),
+        };
+        let msg = A {
+            kind: Some(b.clone().into()),
+        };
+        assert_eq!(msg.kind, Some(a::Kind::B(Box::new(b))));
+
+        let msg = A {
+            kind: Some(C {}.into()),
+        };
+        assert_eq!(msg.kind, Some(a::Kind::C(C {})));
+    }
+
     #[test]
     fn test_267_regression() {
         // Checks that skip_field will error appropriately when given a big stack of StartGroup
@@ -584,6 +826,236 @@ mod tests {
         assert_eq!(msg.name, r#"["unknown"]"#);
     }
 
+    #[test]
+    fn test_enum_accessors() {
+        use crate::no_implicit_prelude::{Color, Outer};
+
+        let mut msg = Outer::default();
+        msg.set_color(Color::Red);
+        assert_eq!(msg.color, Some(Color::Red as i32));
+        assert_eq!(msg.color(), Color::Red);
+
+        // Values which aren't in the enum read as the default.
+        msg.color = Some(99);
+        assert_eq!(msg.color(), Color::Green);
+
+        // Invalid repeated values are skipped.
+        msg.colors = vec![Color::Red as i32, 99, Color::Green as i32];
+        assert_eq!(msg.colors().collect::<Vec<_>>(), [Color::Red, Color::Green]);
+    }
+
+    #[test]
+    fn test_no_implicit_prelude() {
+        use crate::no_implicit_prelude::{outer, Color, Inner, Outer};
+
+        let mut msg = Outer::default();
+        assert_eq!(msg.name(), "name");
+        assert_eq!(msg.data(), b"data");
+        assert_eq!(msg.color(), Color::Green);
+        assert_eq!(Color::from_str_name("RED"), Some(Color::Red));
+        assert_eq!(Color::from_str_name("BLUE"), None);
+        assert_eq!("GREEN".parse(), Ok(Color::Green));
+        assert!("Green".parse::<Color>().is_err());
+        assert_eq!(Color::Green.to_string(), "GREEN");
+
+        msg.push_colors(Color::Red);
+        msg.insert_color_map("red".to_string(), Color::Red);
+        msg.required_inner = Inner { value: Some(1) };
+        msg.kind = Some(outer::Kind::KindColor(Color::Green as i32));
+        check_message(&msg);
+        msg.kind = Some(Inner { value: Some(2) }.into());
+        assert_eq!(msg.kind, Some(outer::Kind::Inner(Inner { value: Some(2) })));
+
+        msg.clear();
+        assert_eq!(msg, Outer::default());
+    }
+
+    #[test]
+    fn test_transparent() {
+        use crate::no_implicit_prelude::Id;
+
+        assert_eq!(core::mem::size_of::<Id>(), core::mem::size_of::<u64>());
+        let id = Id(7);
+        assert_eq!(id.encode_to_vec(), [0x08, 7]);
+        assert_eq!(Id::decode(&[0x08, 7][..]).unwrap(), id);
+    }
+
+    #[test]
+    fn test_transparent_str_eq() {
+        use crate::no_implicit_prelude::Name;
+
+        let name = Name("x".to_string());
+        assert!(name == "x");
+        assert!(name == *"x");
+        assert!(name != "y");
+    }
+
+    #[test]
+    fn test_transparent_buf() {
+        use crate::no_implicit_prelude::Blob;
+        use prost::bytes::Bytes;
+
+        let mut blob = Blob(Bytes::from_static(b"abcd"));
+        assert_eq!(blob.remaining(), 4);
+        assert_eq!(blob.get_u8(), b'a');
+        assert_eq!(blob.copy_to_bytes(2), Bytes::from_static(b"bc"));
+        assert_eq!(blob.chunk(), b"d");
+    }
+
+    #[test]
+    fn test_enum_map_keys() {
+        use crate::no_implicit_prelude::{Color, Palette};
+
+        let mut palette = Palette::default();
+        palette.names.insert(Color::Red, "red".to_string());
+        palette.names.insert(Color::Green, "green".to_string());
+        check_message(&palette);
+
+        // Keys are encoded as `int32`, and an entry with an unknown key fails decoding.
+        let buf = [0x0A, 7, 0x08, 1, 0x12, 3, b'r', b'e', b'd'];
+        let decoded = Palette::decode(&buf[..]).unwrap();
+        assert_eq!(decoded.names.len(), 1);
+        assert_eq!(decoded.names[&Color::Red], "red");
+        let buf = [
+            0x0A, 7, 0x08, 1, 0x12, 3, b'r', b'e', b'd', 0x0A, 6, 0x08, 5, 0x12, 2, b'h', b'i',
+        ];
+        let error = Palette::decode(&buf[..]).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("invalid enumeration value for map key"));
+    }
+
+    #[test]
+    fn test_enum_try_from() {
+        use crate::default_enum_value::{PrivacyLevel, Test};
+        use core::convert::TryFrom;
+
+        assert_eq!(PrivacyLevel::try_from(2), Ok(PrivacyLevel::Two));
+        assert_eq!(PrivacyLevel::try_from(9), Err(prost::UnknownEnumValue(9)));
+
+        // Values unknown to the schema survive a round trip, as the field is an `i32`.
+        let buf = [0x08, 9];
+        let test = Test::decode(&buf[..]).unwrap();
+        assert_eq!(test.privacy_level_1, Some(9));
+        assert_eq!(test.privacy_level_1(), PrivacyLevel::One);
+        assert_eq!(
+            PrivacyLevel::try_from(test.privacy_level_1.unwrap()),
+            Err(prost::UnknownEnumValue(9))
+        );
+        assert_eq!(test.encode_to_vec(), buf);
+    }
+
+    #[test]
+    fn test_open_enum() {
+        use crate::no_implicit_prelude::{Shade, Swatch};
+
+        assert_eq!(Shade::from(1), Shade::Dark);
+        assert_eq!(Shade::from(7), Shade::Unknown(7));
+        assert_eq!(i32::from(Shade::Unknown(7)), 7);
+        assert_eq!(Shade::Unknown(1), Shade::Dark);
+        assert!(Shade::Dark < Shade::Unknown(7));
+        assert!(!Shade::is_valid(7));
+        assert_eq!(Shade::default(), Shade::Light);
+
+        // Unknown values are returned by the getters, and re-encoded unchanged.
+        let buf = [0x08, 7, 0x10, 9, 0x1a, 5, 0x08, 11, 0x12, 1, b'x'];
+        let swatch = Swatch::decode(&buf[..]).unwrap();
+        assert_eq!(swatch.shade(), Shade::Unknown(7));
+        assert_eq!(swatch.shades().collect::<Vec<_>>(), [Shade::Unknown(9)]);
+        assert_eq!(swatch.names[&Shade::Unknown(11)], "x");
+        assert_eq!(swatch.encode_to_vec(), buf);
+
+        let mut swatch = Swatch::default();
+        swatch.set_shade(Shade::Unknown(7));
+        assert_eq!(swatch.shade, Some(7));
+    }
+
+    #[test]
+    fn test_enum_extern() {
+        use crate::no_implicit_prelude::Color;
+        use core::convert::TryFrom;
+
+        assert_eq!(DomainColor::from(Color::Red), DomainColor::Red);
+        assert_eq!(Color::from(DomainColor::Green), Color::Green);
+        assert_eq!(i32::from(DomainColor::Green), Color::Green as i32);
+        assert_eq!(DomainColor::try_from(1).unwrap(), DomainColor::Red);
+        assert!(DomainColor::try_from(3).is_err());
+    }
+
+    #[test]
+    fn test_enum_hash() {
+        use crate::no_implicit_prelude::Color;
+        use core::hash::{Hash, Hasher};
+
+        /// Records the bytes hashed.
+        #[derive(Default)]
+        struct Recorder(Vec<u8>);
+
+        impl Hasher for Recorder {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, bytes: &[u8]) {
+                self.0.extend_from_slice(bytes);
+            }
+        }
+
+        // Enums hash exactly like their numbers.
+        let mut color = Recorder::default();
+        Color::Green.hash(&mut color);
+        let mut number = Recorder::default();
+        2i32.hash(&mut number);
+        assert_eq!(color.0, number.0);
+    }
+
+    #[test]
+    fn test_collection_wrappers() {
+        use crate::nesting::C;
+
+        let mut c: C = (0..3).map(|_| C::default()).collect();
+        c.extend(vec![C::default()]);
+        assert_eq!(c.r.len(), 4);
+        assert_eq!((&c).into_iter().count(), 4);
+        assert_eq!(c.into_iter().collect::<Vec<_>>(), vec![C::default(); 4]);
+    }
+
+    #[test]
+    fn test_columnar() {
+        use crate::no_implicit_prelude::{outer, Color, Inner, Outer, OuterColumns};
+
+        let first = Outer {
+            required_inner: Inner { value: Some(1) },
+            colors: vec![Color::Red as i32],
+            kind: Some(outer::Kind::Text("first".to_string())),
+            ..Outer::default()
+        };
+        let second = Outer {
+            name: Some("second".to_string()),
+            required_inner: Inner { value: Some(2) },
+            ..Outer::default()
+        };
+
+        let mut columns = OuterColumns::default();
+        assert!(columns.is_empty());
+        columns.push_decoded(&*first.encode_to_vec()).unwrap();
+        columns.push(second.clone());
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.name, vec![None, Some("second".to_string())]);
+        assert_eq!(
+            columns.required_inner,
+            vec![first.required_inner.clone(), second.required_inner.clone()]
+        );
+        assert_eq!(columns.colors, vec![first.colors.clone(), Vec::new()]);
+        assert_eq!(columns.kind, vec![first.kind.clone(), None]);
+
+        // A message which fails to decode leaves the columns unchanged.
+        let mut invalid = second.encode_to_vec();
+        invalid.push(0x0F);
+        assert!(columns.push_decoded(&*invalid).is_err());
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.name.len(), 2);
+    }
+
     #[test]
     fn test_group() {
         // optional group
@@ -661,6 +1133,115 @@ mod tests {
         check_message(&groups::OneofGroup::default());
     }
 
+    #[test]
+    fn test_decode_check_required() {
+        use crate::required_fields::{Inner, Outer};
+        use prost::DecodeOptions;
+
+        fn decode<M: Message + Default>(buf: &[u8]) -> Result<M, prost::DecodeError> {
+            let options = DecodeOptions::new().check_required(true);
+            M::decode_with_options(buf, &options).map(|(msg, _)| msg)
+        }
+
+        let inner = Inner {
+            name: "inner".to_string(),
+            value: None,
+        };
+        let msg = Outer {
+            id: 1,
+            inner: inner.clone(),
+            inners: vec![inner.clone()],
+            optional_inner: Some(inner.clone()),
+        };
+        assert_eq!(decode::<Outer>(&msg.encode_to_vec()), Ok(msg));
+
+        // A top-level required field is missing.
+        let mut buf = Vec::new();
+        prost::encoding::int32::encode(1, &1, &mut buf);
+        let error = decode::<Outer>(&buf).unwrap_err();
+        assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+        assert_eq!(
+            error.to_string(),
+            "failed to decode Protobuf message: Outer.inner: missing required field"
+        );
+        assert_eq!(Outer::decode(&*buf).unwrap().inner, Inner::default());
+
+        // A required field is missing from a nested message, which is checked even when the
+        // message itself is optional or repeated.
+        let mut incomplete = Vec::new();
+        prost::encoding::int32::encode(2, &5, &mut incomplete);
+        for &tag in &[2, 3, 4] {
+            let mut buf = Vec::new();
+            prost::encoding::int32::encode(1, &1, &mut buf);
+            prost::encoding::message::encode(2, &inner, &mut buf);
+            prost::encoding::bytes::encode(tag, &incomplete, &mut buf);
+            let error = decode::<Outer>(&buf).unwrap_err();
+            assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+            assert!(error.to_string().contains("Inner.name: Outer."));
+            assert!(Outer::decode(&*buf).is_ok());
+        }
+
+        // Required fields of groups are checked too.
+        let buf = [0x13, 0x14];
+        let error = decode::<groups::OneofGroup>(&buf).unwrap_err();
+        assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+
+        // Proto3 messages have no required fields.
+        let msg = decode::<proto3::presence::A>(&[]).unwrap();
+        assert_eq!(msg, proto3::presence::A::default());
+    }
+
+    #[test]
+    fn test_decode_required_field_policy() {
+        use crate::required_fields::{Inner, Outer};
+        use prost::{DecodeOptions, RequiredFieldPolicy};
+
+        // The top-level message misses both of its required fields, and a nested one its name.
+        let mut incomplete = Vec::new();
+        prost::encoding::int32::encode(2, &5, &mut incomplete);
+        let mut buf = Vec::new();
+        prost::encoding::bytes::encode(3, &incomplete, &mut buf);
+
+        let options = DecodeOptions::new().required_field_policy(RequiredFieldPolicy::Warn);
+        let (msg, stats) = Outer::decode_with_options(&*buf, &options).unwrap();
+        assert_eq!(msg, Outer::decode(&*buf).unwrap());
+        let missing = stats
+            .missing_required_fields
+            .iter()
+            .map(|error| {
+                assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+                error.to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            missing,
+            [
+                "failed to decode Protobuf message: Inner.name: missing required field",
+                "failed to decode Protobuf message: Outer.id: missing required field",
+                "failed to decode Protobuf message: Outer.inner: missing required field",
+            ]
+        );
+
+        let options = DecodeOptions::new().required_field_policy(RequiredFieldPolicy::Error);
+        let error = Outer::decode_with_options(&*buf, &options).unwrap_err();
+        assert!(error.to_string().contains("Inner.name: Outer.inners"));
+
+        let options = DecodeOptions::new().required_field_policy(RequiredFieldPolicy::Ignore);
+        let (_, stats) = Outer::decode_with_options(&*buf, &options).unwrap();
+        assert!(stats.missing_required_fields.is_empty());
+
+        // A complete message reports nothing.
+        let msg = Outer {
+            id: 1,
+            inner: Inner::default(),
+            inners: vec![],
+            optional_inner: None,
+        };
+        let options = DecodeOptions::new().required_field_policy(RequiredFieldPolicy::Warn);
+        let (_, stats) = Outer::decode_with_options(&*msg.encode_to_vec(), &options).unwrap();
+        assert!(stats.missing_required_fields.is_empty());
+    }
+
     #[test]
     fn test_proto3_presence() {
         let msg = proto3::presence::A {
@@ -671,6 +1252,68 @@ mod tests {
         check_message(&msg);
     }
 
+    #[test]
+    fn test_proto3_presence_default_values() {
+        // Optional fields explicitly set to their default value are present on the wire.
+        let msg = proto3::presence::A {
+            b: Some(0),
+            foo: None,
+        };
+        let buf = msg.encode_to_vec();
+        assert_eq!(buf, [0x08, 0x00]);
+        assert_eq!(proto3::presence::A::decode(buf.as_slice()).unwrap(), msg);
+
+        let msg = proto3::presence::Accessors {
+            number: Some(0),
+            text: Some("".to_string()),
+            data: Some(vec![]),
+        };
+        let buf = msg.encode_to_vec();
+        assert_eq!(buf, [0x08, 0x00, 0x12, 0x00, 0x1a, 0x00]);
+        assert_eq!(
+            proto3::presence::Accessors::decode(buf.as_slice()).unwrap(),
+            msg
+        );
+    }
+
+    #[test]
+    fn test_option_accessors() {
+        let mut msg = proto3::presence::Accessors::default();
+        assert_eq!(msg.number_opt(), None);
+        assert_eq!(msg.text_opt(), None);
+        assert_eq!(msg.data_opt(), None);
+        assert_eq!(msg.text(), "");
+
+        msg.number = Some(0);
+        msg.text = Some("foo".to_string());
+        msg.data = Some(vec![1, 2, 3]);
+        assert_eq!(msg.number_opt(), Some(0));
+        assert_eq!(msg.text_opt(), Some("foo"));
+        assert_eq!(msg.data_opt(), Some(&[1u8, 2, 3][..]));
+    }
+
+    #[test]
+    fn test_field_accessors() {
+        let mut msg = proto3::presence::Patch::default();
+        assert!(!msg.has_number());
+        assert!(!msg.has_child());
+        assert!(!msg.has_choice());
+
+        msg.number = Some(0);
+        msg.names = vec!["a".to_string()];
+        msg.child = Some(proto3::presence::Accessors::default());
+        msg.choice = Some(proto3::presence::patch::Choice::Count(1));
+        assert!(msg.has_number());
+        assert!(msg.has_child());
+        assert!(msg.has_choice());
+
+        msg.clear_number();
+        msg.clear_names();
+        msg.clear_child();
+        msg.clear_choice();
+        assert_eq!(msg, proto3::presence::Patch::default());
+    }
+
     #[test]
     fn test_file_descriptor_set_path() {
         let file_descriptor_set_bytes =
@@ -1,6 +1,20 @@
-use prost::alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use core::hash::Hasher;
+use core::mem;
+use core::num::{NonZeroI32, NonZeroI64, NonZeroU32};
+
+use prost::alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 use prost::bytes::Bytes;
-use prost::{Enumeration, Message, Oneof};
+use prost::{
+    BatchDecodeContext, CachedMessage, DecodeError, DecodeErrorKind, DecodeOptions, DynMessage,
+    Enumeration, Framing, LengthPrefix, Message, Oneof, RawMessage, SharedBytes,
+};
 
 use crate::check_message;
 use crate::check_serialize_equivalent;
@@ -26,11 +40,1262 @@ fn check_repeated_floats() {
     });
 }
 
+#[test]
+fn check_overwrite() {
+    let first = RepeatedFloats {
+        single_float: 1.0,
+        repeated_float: vec![1.0, 2.0, 3.0, 4.0],
+    }
+    .encode_to_vec();
+    let second = RepeatedFloats {
+        single_float: 0.0,
+        repeated_float: vec![5.0],
+    };
+
+    let mut msg = RepeatedFloats::decode(first.as_slice()).unwrap();
+    let capacity = msg.repeated_float.capacity();
+    msg.overwrite(second.encode_to_vec().as_slice()).unwrap();
+    assert_eq!(msg, second);
+    assert_eq!(msg.repeated_float.capacity(), capacity);
+}
+
+#[test]
+fn check_encode_to_fixed_size_slice() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let len = msg.encoded_len();
+
+    // Encoding to a slice one byte too short fails without writing anything.
+    let mut storage = vec![0xAA; len + 1];
+    let mut buf = &mut storage[..len - 1];
+    assert!(msg.encode(&mut buf).is_err());
+    assert_eq!(buf.len(), len - 1);
+    assert!(storage.iter().all(|&byte| byte == 0xAA));
+
+    // Encoding to a slice of exactly the encoded length fills it, and writes nothing beyond.
+    let mut buf = &mut storage[..len];
+    msg.encode(&mut buf).unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(&storage[..len], msg.encode_to_vec().as_slice());
+    assert_eq!(storage[len], 0xAA);
+}
+
+#[test]
+fn check_decode_skipped_bytes() {
+    let msg = RepeatedFloats {
+        single_float: 1.0,
+        repeated_float: vec![2.0],
+    };
+    let options = DecodeOptions::new();
+    let mut buf = msg.encode_to_vec();
+    let (_, stats) = RepeatedFloats::decode_with_options(&*buf, &options).unwrap();
+    assert_eq!(stats.skipped_bytes, 0);
+
+    // An unknown varint field, and an unknown length-delimited field.
+    buf.extend_from_slice(b"\x08\x96\x01\x12\x02ab");
+    let mut decoded = RepeatedFloats::default();
+    let stats = decoded.merge_with_options(&*buf, &options).unwrap();
+    assert_eq!(stats.skipped_bytes, 7);
+    assert_eq!(decoded, msg);
+}
+
+#[test]
+fn check_decode_max_fields() {
+    fn decode<M: Message + Default>(buf: &[u8], max_fields: usize) -> Result<M, DecodeError> {
+        let options = DecodeOptions::new().max_fields(max_fields);
+        M::decode_with_options(buf, &options).map(|(msg, _)| msg)
+    }
+
+    // One scalar field, and three packed elements.
+    let msg = RepeatedFloats {
+        single_float: 1.0,
+        repeated_float: vec![2.0, 3.0, 4.0],
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(decode::<RepeatedFloats>(&buf, 4), Ok(msg));
+    assert!(decode::<RepeatedFloats>(&buf, 3).is_err());
+
+    // Two top-level fields, and two fields of a nested message.
+    let msg = DecodedEnvelope {
+        route: "route".to_owned(),
+        payload: Some(MaxFieldNumber {
+            first: 1,
+            last: "last".to_owned(),
+        }),
+        extra: Vec::new(),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(decode::<DecodedEnvelope>(&buf, 4), Ok(msg));
+    assert!(decode::<DecodedEnvelope>(&buf, 3).is_err());
+}
+
+#[test]
+fn check_decode_with_combined_options() {
+    // The route is invalid UTF-8, and an unknown field follows the nested message.
+    let mut buf = vec![0x0A, 3, b'a', 0xFF, b'b'];
+    buf.extend_from_slice(&[0x1A, 2, 0x08, 1]);
+    buf.extend_from_slice(&[0x20, 2]);
+
+    let options = DecodeOptions::new()
+        .lossy_utf8(true)
+        .max_fields(4)
+        .recursion_limit(1);
+    let (msg, stats) = DecodedEnvelope::decode_with_options(&*buf, &options).unwrap();
+    assert_eq!(msg.route, "a\u{FFFD}b");
+    assert_eq!(msg.extra[0].first, 1);
+    assert_eq!(stats.max_depth, 1);
+    assert_eq!(stats.skipped_bytes, 2);
+
+    // Each option still applies when combined with the others.
+    assert!(DecodedEnvelope::decode_with_options(&*buf, &options.max_fields(3)).is_err());
+    assert!(DecodedEnvelope::decode_with_options(&*buf, &options.recursion_limit(0)).is_err());
+    assert!(DecodedEnvelope::decode_with_options(&*buf, &options.lossy_utf8(false)).is_err());
+
+    // Errors report the offset of the field which failed.
+    let error = DecodedEnvelope::decode_with_options(&*buf, &DecodeOptions::new()).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::InvalidUtf8);
+    assert_eq!(error.offset(), Some(5));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_decode_file() {
+    let msg = RepeatedFloats {
+        single_float: 1.0,
+        repeated_float: vec![2.0, 3.0, 4.0],
+    };
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("floats.bin");
+    std::fs::write(&path, msg.encode_to_vec()).unwrap();
+    // SAFETY: the files are private to this test, and aren't modified while they are decoded.
+    let decode_file = |path: &std::path::Path| unsafe { RepeatedFloats::decode_file(path) };
+    assert_eq!(decode_file(&path).unwrap(), msg);
+
+    // An empty file holds the default message.
+    let empty = tempdir.path().join("empty.bin");
+    std::fs::write(&empty, b"").unwrap();
+    assert_eq!(decode_file(&empty).unwrap(), RepeatedFloats::default());
+
+    // Decode errors are reported as invalid data.
+    std::fs::write(&path, b"\x0d\x00").unwrap();
+    let error = decode_file(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    let missing = tempdir.path().join("missing.bin");
+    let error = decode_file(&missing).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn check_encode_to_slice() {
+    let msg = RepeatedFloats {
+        single_float: 1.0,
+        repeated_float: vec![2.0, 3.0, 4.0],
+    };
+    let encoded = msg.encode_to_vec();
+
+    let mut buf = [0xFF; 64];
+    let written = msg.encode_to_slice(&mut buf).unwrap();
+    assert_eq!(written, &*encoded);
+    assert_eq!(buf[encoded.len()], 0xFF);
+
+    // A slice which is too small reports the sizes, and is left untouched.
+    let mut buf = vec![0xFF; encoded.len() - 1];
+    let error = msg.encode_to_slice(&mut buf).unwrap_err();
+    assert_eq!(error.required_capacity(), encoded.len());
+    assert_eq!(error.remaining(), encoded.len() - 1);
+    assert!(buf.iter().all(|&byte| byte == 0xFF));
+}
+
+/// A message with each kind of field, to merge in memory.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct Layered {
+    #[prost(int32, tag = "1")]
+    pub plain: i32,
+    #[prost(string, optional, tag = "2")]
+    pub optional: Option<String>,
+    #[prost(uint32, repeated, tag = "3")]
+    pub repeated: Vec<u32>,
+    #[prost(message, optional, boxed, tag = "4")]
+    pub child: Option<Box<Layered>>,
+    #[prost(btree_map = "string, int32", tag = "5")]
+    pub map: ::alloc::collections::BTreeMap<String, i32>,
+    #[prost(oneof = "LayeredKind", tags = "6, 7")]
+    pub kind: Option<LayeredKind>,
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Oneof)]
+pub enum LayeredKind {
+    #[prost(string, tag = "6")]
+    Name(String),
+    #[prost(message, tag = "7")]
+    Floats(RepeatedFloats),
+}
+
+#[test]
+fn check_merge_from() {
+    let base = Layered {
+        plain: 1,
+        optional: Some("base".to_owned()),
+        repeated: vec![1],
+        child: Some(Box::new(Layered {
+            plain: 2,
+            ..Layered::default()
+        })),
+        map: vec![("a".to_owned(), 1), ("b".to_owned(), 2)]
+            .into_iter()
+            .collect(),
+        kind: Some(LayeredKind::Floats(RepeatedFloats {
+            single_float: 1.0,
+            repeated_float: vec![1.0],
+        })),
+    };
+    let overlay = Layered {
+        plain: 0,
+        optional: None,
+        repeated: vec![2, 3],
+        child: Some(Box::new(Layered {
+            optional: Some("child".to_owned()),
+            ..Layered::default()
+        })),
+        map: vec![("b".to_owned(), 3)].into_iter().collect(),
+        kind: Some(LayeredKind::Floats(RepeatedFloats {
+            single_float: 0.0,
+            repeated_float: vec![2.0],
+        })),
+    };
+
+    let mut merged = base.clone();
+    merged.merge_from(overlay.clone());
+    assert_eq!(merged.plain, 1);
+    assert_eq!(merged.optional.as_deref(), Some("base"));
+    assert_eq!(merged.repeated, [1, 2, 3]);
+    assert_eq!(
+        merged.child.as_deref(),
+        Some(&Layered {
+            plain: 2,
+            optional: Some("child".to_owned()),
+            ..Layered::default()
+        })
+    );
+    assert_eq!(merged.map["b"], 3);
+    assert_eq!(
+        merged.kind,
+        Some(LayeredKind::Floats(RepeatedFloats {
+            single_float: 1.0,
+            repeated_float: vec![1.0, 2.0],
+        }))
+    );
+
+    // Merging in memory matches merging the encoding, in both directions.
+    for (dst, src) in [(&base, &overlay), (&overlay, &base)] {
+        let mut in_memory = dst.clone();
+        in_memory.merge_from(src.clone());
+        let mut encoded = dst.clone();
+        encoded.merge(&*src.encode_to_vec()).unwrap();
+        assert_eq!(in_memory, encoded);
+    }
+
+    // A different oneof variant replaces the current one.
+    let mut merged = base;
+    merged.merge_from(Layered {
+        kind: Some(LayeredKind::Name("name".to_owned())),
+        ..Layered::default()
+    });
+    assert_eq!(merged.kind, Some(LayeredKind::Name("name".to_owned())));
+}
+
+#[test]
+fn check_merge_from_wrappers() {
+    fn check<M>(dst: M, src: M)
+    where
+        M: Message + Clone + PartialEq,
+    {
+        let mut in_memory = dst.clone();
+        in_memory.merge_from(src.clone());
+        let mut encoded = dst;
+        encoded.merge(&*src.encode_to_vec()).unwrap();
+        assert_eq!(in_memory, encoded);
+    }
+
+    check(true, false);
+    check(5u32, 0);
+    check(-5i64, 7);
+    check(1.5f64, 0.0);
+    check("base".to_owned(), String::new());
+    check(b"base".to_vec(), b"other".to_vec());
+    check(
+        core::time::Duration::new(5, 10),
+        core::time::Duration::new(0, 20),
+    );
+    check(
+        core::time::Duration::new(5, 10),
+        core::time::Duration::new(7, 0),
+    );
+}
+
+#[test]
+fn check_encode_with_sizes() {
+    // Nest submessages in fields, boxed fields and oneofs, some of them empty.
+    let mut msg = Layered::default();
+    for depth in 0..50 {
+        msg = Layered {
+            plain: depth,
+            repeated: vec![depth as u32; depth as usize % 3],
+            child: Some(Box::new(msg)),
+            kind: Some(LayeredKind::Floats(RepeatedFloats {
+                single_float: depth as f32,
+                repeated_float: vec![],
+            })),
+            ..Layered::default()
+        };
+    }
+
+    let sizes = msg.encoded_sizes();
+    assert_eq!(sizes.encoded_len(), msg.encoded_len());
+    let mut buf = Vec::new();
+    msg.encode_with_sizes(&sizes, &mut buf).unwrap();
+    assert_eq!(buf, msg.encode_to_vec());
+
+    let mut short = vec![0; buf.len() - 1];
+    let error = msg
+        .encode_with_sizes(&sizes, &mut short.as_mut_slice())
+        .unwrap_err();
+    assert_eq!(error.required_capacity(), buf.len());
+
+    let msg = Compound {
+        required_message: Basic {
+            oneof: Some(BasicOneof::Int(1)),
+            ..Basic::default()
+        },
+        repeated_message: vec![Basic::default(); 3],
+        ..Compound::default()
+    };
+    let sizes = msg.encoded_sizes();
+    let mut buf = Vec::new();
+    msg.encode_with_sizes(&sizes, &mut buf).unwrap();
+    assert_eq!(buf, msg.encode_to_vec());
+}
+
+/// A message with both strict and lossy string fields.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct MixedStrings {
+    #[prost(string, tag = "1")]
+    pub strict: String,
+    #[prost(string, lossy, tag = "2")]
+    pub lossy: String,
+    #[prost(string, repeated, lossy, tag = "3")]
+    pub lossy_list: Vec<String>,
+}
+
+#[test]
+fn check_lossy_string_fields() {
+    // Invalid UTF-8 in the lossy fields is replaced.
+    let buf = b"\x0a\x02ok\x12\x03a\x80b\x1a\x01\xff";
+    let expected = MixedStrings {
+        strict: "ok".to_owned(),
+        lossy: "a\u{FFFD}b".to_owned(),
+        lossy_list: vec!["\u{FFFD}".to_owned()],
+    };
+    assert_eq!(MixedStrings::decode(&buf[..]), Ok(expected.clone()));
+
+    // Invalid UTF-8 in the strict field is rejected, unless the whole message is lossy.
+    let buf = b"\x0a\x03a\x80b";
+    let error = MixedStrings::decode(&buf[..]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::InvalidUtf8);
+    let options = DecodeOptions::new().lossy_utf8(true);
+    let (decoded, _) = MixedStrings::decode_with_options(&buf[..], &options).unwrap();
+    assert_eq!(decoded.strict, "a\u{FFFD}b");
+}
+
+/// A message with integer fields represented by `NonZero*` types.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct NonZeroFields {
+    #[prost(uint32, nonzero, tag = "1")]
+    pub id: Option<NonZeroU32>,
+    #[prost(sint64, repeated, nonzero, tag = "2")]
+    pub parents: Vec<NonZeroI64>,
+    #[prost(sfixed32, optional, nonzero, tag = "3")]
+    pub fixed: Option<NonZeroI32>,
+}
+
+#[test]
+fn check_nonzero_fields() {
+    let msg = NonZeroFields {
+        id: NonZeroU32::new(7),
+        parents: vec![NonZeroI64::new(-1).unwrap(), NonZeroI64::new(300).unwrap()],
+        fixed: NonZeroI32::new(-5),
+    };
+    check_message(&msg);
+    check_message(&NonZeroFields::default());
+
+    // A zero value is rejected, whether singular or packed.
+    let buf = b"\x08\x00";
+    let error = NonZeroFields::decode(&buf[..]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::Other);
+    let buf = b"\x12\x02\x02\x00";
+    assert!(NonZeroFields::decode(&buf[..]).is_err());
+    let buf = b"\x1d\x00\x00\x00\x00";
+    assert!(NonZeroFields::decode(&buf[..]).is_err());
+}
+
+/// A message whose length-delimited fields all have presence.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct EmptyFields {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub bytes: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "2")]
+    pub string: Option<String>,
+    #[prost(message, optional, tag = "3")]
+    pub message: Option<MaxFieldNumber>,
+    #[prost(string, repeated, tag = "4")]
+    pub strings: Vec<String>,
+}
+
+#[test]
+fn check_zero_length_fields() {
+    // Every field is present with a length of zero.
+    let buf = b"\x0a\x00\x12\x00\x1a\x00\x22\x00";
+    let expected = EmptyFields {
+        bytes: Some(Vec::new()),
+        string: Some(String::new()),
+        message: Some(MaxFieldNumber::default()),
+        strings: vec![String::new()],
+    };
+    assert_eq!(EmptyFields::decode(&buf[..]), Ok(expected.clone()));
+    assert_eq!(expected.encode_to_vec(), buf);
+    check_message(&expected);
+
+    // Without presence, zero-length fields decode to their default value.
+    assert_eq!(
+        DecodedEnvelope::decode(&b"\x0a\x00\x12\x00"[..]),
+        Ok(DecodedEnvelope {
+            route: String::new(),
+            payload: Some(MaxFieldNumber::default()),
+            extra: Vec::new(),
+        })
+    );
+}
+
+#[test]
+fn check_batch_decode_context() {
+    let msg = DecodedEnvelope {
+        route: "route".to_owned(),
+        payload: None,
+        extra: vec![MaxFieldNumber::default(); 2],
+    };
+    let buf = msg.encode_to_vec();
+
+    // The budget covers the message, the elements of its repeated field, and its string.
+    let size = mem::size_of::<DecodedEnvelope>() + 2 * mem::size_of::<MaxFieldNumber>() + 5;
+    let batch = BatchDecodeContext::new(2 * size);
+    assert_eq!(batch.decode::<DecodedEnvelope, _>(&*buf), Ok(msg.clone()));
+    assert_eq!(batch.remaining(), size);
+    let delimited = msg.encode_length_delimited_to_vec();
+    assert_eq!(
+        batch.decode_length_delimited::<DecodedEnvelope, _>(&*delimited),
+        Ok(msg)
+    );
+    assert_eq!(batch.remaining(), 0);
+
+    // The budget is shared, so the next message of the batch exhausts it.
+    assert!(batch.decode::<DecodedEnvelope, _>(&*buf).is_err());
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct MaxFieldNumber {
+    #[prost(int32, tag = "1")]
+    pub first: i32,
+    #[prost(string, tag = "536870911")]
+    pub last: String,
+}
+
+#[test]
+fn check_max_field_number() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    check_message(&msg);
+
+    // Key for field 536870911 with wire type length-delimited is a 5 byte varint.
+    let buf = msg.encode_to_vec();
+    assert_eq!(&buf[2..7], &[0xFA, 0xFF, 0xFF, 0xFF, 0x0F]);
+
+    // Field number 2^29 is out of range and must be rejected.
+    let out_of_range: &[u8] = &[0x80, 0x80, 0x80, 0x80, 0x10, 0x00];
+    assert!(MaxFieldNumber::decode(out_of_range).is_err());
+}
+
+#[test]
+fn check_length_delimited() {
+    let first = MaxFieldNumber {
+        first: 1,
+        last: "first".to_owned(),
+    };
+    let second = MaxFieldNumber::default();
+    let mut buf = Vec::new();
+    first.encode_length_delimited(&mut buf).unwrap();
+    second.encode_length_delimited(&mut buf).unwrap();
+    assert_eq!(buf.len(), 1 + first.encoded_len() + 1);
+    assert_eq!(
+        buf[..first.encoded_len() + 1],
+        first.encode_length_delimited_to_vec()[..]
+    );
+
+    // Messages are read off the front of a stream, one at a time.
+    let mut stream = &buf[..];
+    assert_eq!(
+        MaxFieldNumber::decode_length_delimited(&mut stream).unwrap(),
+        first
+    );
+    assert_eq!(
+        MaxFieldNumber::decode_length_delimited(&mut stream).unwrap(),
+        second
+    );
+    assert!(stream.is_empty());
+
+    // A buffer holding fewer bytes than the delimiter declares is an error rather than a partial
+    // decode.
+    let error = MaxFieldNumber::decode_length_delimited(&buf[..first.encoded_len()]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::UnexpectedEof);
+
+    let mut short = [0; 4];
+    assert!(first.encode_length_delimited(&mut &mut short[..]).is_err());
+}
+
+/// A reader which returns at most `chunk` bytes per read, interrupting every other read, and
+/// then fails with `error` if set.
+#[cfg(feature = "std")]
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    interrupt: bool,
+    error: Option<std::io::ErrorKind>,
+}
+
+#[cfg(feature = "std")]
+impl ChunkedReader<'_> {
+    fn new(data: &[u8], chunk: usize, error: Option<std::io::ErrorKind>) -> ChunkedReader<'_> {
+        ChunkedReader {
+            data,
+            chunk,
+            interrupt: false,
+            error,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            return Err(std::io::ErrorKind::Interrupted.into());
+        }
+        if self.data.is_empty() {
+            if let Some(kind) = self.error {
+                return Err(kind.into());
+            }
+        }
+        let len = self.chunk.min(buf.len()).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_length_delimited_decoder() {
+    use prost::stream::{Error, LengthDelimitedDecoder};
+
+    let messages = (0..20)
+        .map(|i| MaxFieldNumber {
+            first: i,
+            last: "x".repeat(i as usize * 15),
+        })
+        .collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    for msg in &messages {
+        msg.encode_length_delimited(&mut buf).unwrap();
+    }
+
+    // Frames split across reads anywhere, even within delimiters, are reassembled.
+    for chunk in [1, 2, 3, 7, 100, buf.len()] {
+        let mut decoder =
+            LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(&buf, chunk, None));
+        for msg in &messages {
+            assert_eq!(&decoder.next().unwrap().unwrap(), msg);
+        }
+        assert!(decoder.next().is_none());
+        assert!(decoder.next().is_none());
+    }
+
+    // An empty stream holds no messages.
+    let mut decoder =
+        LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(&[], 1, None));
+    assert!(decoder.next().is_none());
+
+    // A stream ending within a frame, or within its delimiter, yields an error once.
+    for truncated in [&buf[..buf.len() - 1], &[0x80][..]] {
+        let mut decoder = LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(
+            truncated, 5, None,
+        ));
+        let error = decoder.by_ref().find_map(Result::err).unwrap();
+        match error {
+            Error::Decode(error) => assert_eq!(error.kind(), DecodeErrorKind::UnexpectedEof),
+            Error::Io(error) => panic!("unexpected IO error: {}", error),
+        }
+        assert!(decoder.next().is_none());
+    }
+
+    // Read errors are reported apart from decode errors.
+    let mut decoder = LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(
+        &buf,
+        4,
+        Some(std::io::ErrorKind::ConnectionReset),
+    ));
+    assert_eq!(
+        decoder.by_ref().take_while(Result::is_ok).count(),
+        messages.len()
+    );
+    assert!(decoder.next().is_none());
+
+    let mut decoder = LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(
+        &[],
+        4,
+        Some(std::io::ErrorKind::ConnectionReset),
+    ));
+    match decoder.next() {
+        Some(Err(Error::Io(error))) => {
+            assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset)
+        }
+        other => panic!("expected an IO error, got {:?}", other),
+    }
+
+    // Frames which can't be decoded are decode errors.
+    let mut decoder = LengthDelimitedDecoder::<_, MaxFieldNumber>::new(ChunkedReader::new(
+        b"\x02\x0d\x00",
+        1,
+        None,
+    ));
+    assert!(matches!(decoder.next(), Some(Err(Error::Decode(_)))));
+    assert!(decoder.next().is_none());
+}
+
+#[test]
+fn check_decode_error_offset() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let buf = msg.encode_to_vec();
+
+    // The string is truncated: its length is read after the two bytes of `first`, its five byte
+    // key and its one byte length.
+    let error = MaxFieldNumber::decode(&buf[..buf.len() - 1]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::UnexpectedEof);
+    assert_eq!(error.offset(), Some(8));
+
+    // The second field has tag 1 but a 32-bit wire type, found once its key is read.
+    let error = MaxFieldNumber::decode(&b"\x08\x01\x0D\x00\x00\x00\x00"[..]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::InvalidWireType);
+    assert_eq!(error.offset(), Some(3));
+
+    // The offset of a length-delimited message includes its delimiter.
+    let delimited = msg.encode_length_delimited_to_vec();
+    let error =
+        MaxFieldNumber::decode_length_delimited(&delimited[..delimited.len() - 1]).unwrap_err();
+    assert_eq!(error.offset(), Some(1));
+
+    assert_eq!(DecodeError::new("error").offset(), None);
+}
+
+#[test]
+fn check_decode_prefix() {
+    let first = MaxFieldNumber {
+        first: 1,
+        last: "first".to_owned(),
+    };
+    let second = MaxFieldNumber {
+        first: 2,
+        last: "second".to_owned(),
+    };
+    let mut buf = first.encode_length_delimited_to_vec();
+    let first_len = buf.len();
+    buf.extend(second.encode_length_delimited_to_vec());
+
+    let (decoded, rest) = MaxFieldNumber::decode_prefix(&buf).unwrap();
+    assert_eq!(decoded, first);
+    let (decoded, rest) = MaxFieldNumber::decode_prefix(rest).unwrap();
+    assert_eq!(decoded, second);
+    assert!(rest.is_empty());
+
+    // A truncated message is an error rather than a partial decode.
+    assert!(MaxFieldNumber::decode_prefix(&buf[..first_len - 1]).is_err());
+}
+
+#[test]
+fn check_decode_exact() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let buf = msg.encode_length_delimited_to_vec();
+    assert_eq!(MaxFieldNumber::decode_exact(&buf).unwrap(), msg);
+
+    // Trailing junk, including a second message, is rejected at the end of the first message.
+    let second = msg.encode_length_delimited_to_vec();
+    for junk in vec![
+        &[0x00][..],
+        &[0x00, 0x00, 0x00],
+        &[0xFF],
+        &[0x08],
+        &second[..],
+    ] {
+        let mut junked = buf.clone();
+        junked.extend_from_slice(junk);
+        let error = MaxFieldNumber::decode_exact(&junked).unwrap_err();
+        assert!(error.to_string().ends_with("unexpected trailing bytes"));
+        assert_eq!(error.offset(), Some(buf.len()));
+    }
+
+    // A truncated message is rejected.
+    let error = MaxFieldNumber::decode_exact(&buf[..buf.len() - 1]).unwrap_err();
+    assert_eq!(error.kind(), DecodeErrorKind::UnexpectedEof);
+
+    // Unframed input is read as a length prefix, which doesn't match the message.
+    assert!(MaxFieldNumber::decode_exact(&msg.encode_to_vec()).is_err());
+}
+
+#[test]
+fn check_decode_lossy_utf8() {
+    // The route is invalid UTF-8, as is the string of a nested message.
+    let mut buf = vec![0x0A, 3, b'a', 0xFF, b'b'];
+    let nested = [0xFA, 0xFF, 0xFF, 0xFF, 0x0F, 2, 0xC3, b'c'];
+    buf.extend_from_slice(&[0x1A, nested.len() as u8]);
+    buf.extend_from_slice(&nested);
+    assert!(DecodedEnvelope::decode(&*buf).is_err());
+
+    let options = DecodeOptions::new().lossy_utf8(true);
+    let (msg, _) = DecodedEnvelope::decode_with_options(&*buf, &options).unwrap();
+    assert_eq!(msg.route, "a\u{FFFD}b");
+    assert_eq!(msg.extra[0].last, "\u{FFFD}c");
+
+    // Valid strings are unaffected.
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let (decoded, _) =
+        MaxFieldNumber::decode_with_options(&*msg.encode_to_vec(), &options).unwrap();
+    assert_eq!(decoded, msg);
+}
+
+#[test]
+fn check_dyn_merge_field() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let buf = msg.encode_to_vec();
+
+    // Route each field to a different message, chosen at runtime by tag.
+    let mut firsts = MaxFieldNumber::default();
+    let mut lasts = MaxFieldNumber::default();
+    let mut buf = &*buf;
+    while !buf.is_empty() {
+        let (tag, wire_type) = prost::encoding::decode_key(&mut buf).unwrap();
+        let target: &mut dyn DynMessage = if tag == 1 { &mut firsts } else { &mut lasts };
+        target.dyn_merge_field(tag, wire_type, &mut buf).unwrap();
+    }
+    assert_eq!(firsts.first, 1);
+    assert!(firsts.last.is_empty());
+    assert_eq!(lasts.first, 0);
+    assert_eq!(lasts.last, "last");
+}
+
+#[test]
+fn check_cached_message() {
+    // A non-canonical encoding, with the fields out of order, is reproduced verbatim.
+    let mut buf = MaxFieldNumber {
+        first: 0,
+        last: "last".to_owned(),
+    }
+    .encode_to_vec();
+    buf.extend_from_slice(&[0x08, 0x01]);
+    let buf = Bytes::from(buf);
+
+    let mut cached = CachedMessage::<MaxFieldNumber>::decode(buf.clone()).unwrap();
+    assert_eq!(cached.first, 1);
+    assert_eq!(cached.cached(), Some(&buf));
+    assert_eq!(cached.encoded_len(), buf.len());
+    assert_eq!(cached.encode_to_vec(), buf);
+
+    // Mutable access discards the cached encoding.
+    cached.first = 2;
+    assert_eq!(cached.cached(), None);
+    let expected = MaxFieldNumber {
+        first: 2,
+        last: "last".to_owned(),
+    };
+    assert_eq!(cached.encode_to_vec(), expected.encode_to_vec());
+    assert_eq!(cached.into_inner(), expected);
+}
+
+/// A hasher which records the bytes written to it.
+#[derive(Default)]
+struct RecordingHasher(Vec<u8>);
+
+impl Hasher for RecordingHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+#[test]
+fn check_content_hash() {
+    for len in [0, 10, 1000] {
+        let msg = MaxFieldNumber {
+            first: 1,
+            last: "x".repeat(len),
+        };
+        let mut hasher = RecordingHasher::default();
+        msg.content_hash(&mut hasher);
+        assert_eq!(hasher.0, msg.encode_to_vec());
+    }
+}
+
+/// A framing which inverts the bits of encoded messages, behind a marker byte.
+struct Inverted;
+
+impl Framing for Inverted {
+    fn compress<'a>(&self, encoded: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut frame = vec![0xFF];
+        frame.extend(encoded.iter().map(|byte| !byte));
+        Cow::Owned(frame)
+    }
+
+    fn decompress<'a>(&self, frame: &'a [u8]) -> Result<Cow<'a, [u8]>, DecodeError> {
+        match frame.split_first() {
+            Some((0xFF, rest)) => Ok(Cow::Owned(rest.iter().map(|byte| !byte).collect())),
+            _ => Err(DecodeError::new("missing marker byte")),
+        }
+    }
+}
+
+#[test]
+fn check_length_delimited_with_framing() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let mut buf = Vec::new();
+    msg.encode_length_delimited_with(&Inverted, &mut buf)
+        .unwrap();
+    msg.encode_length_delimited_with(&Inverted, &mut buf)
+        .unwrap();
+    // The delimiter holds the length of the transformed message.
+    assert_eq!(buf[0] as usize, msg.encoded_len() + 1);
+
+    let mut rest = &buf[..];
+    for _ in 0..2 {
+        let decoded = MaxFieldNumber::decode_length_delimited_with(&Inverted, &mut rest);
+        assert_eq!(decoded.unwrap(), msg);
+    }
+    assert!(rest.is_empty());
+
+    // Frames written without the transformation are rejected.
+    let plain = msg.encode_length_delimited_to_vec();
+    assert!(MaxFieldNumber::decode_length_delimited_with(&Inverted, &*plain).is_err());
+
+    // The default transformation is the identity.
+    struct Identity;
+    impl Framing for Identity {}
+    let mut buf = Vec::new();
+    msg.encode_length_delimited_with(&Identity, &mut buf)
+        .unwrap();
+    assert_eq!(buf, plain);
+    assert_eq!(
+        MaxFieldNumber::decode_length_delimited_with(&Identity, &*buf).unwrap(),
+        msg
+    );
+}
+
+#[test]
+fn check_probe_length_delimited() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "x".repeat(200),
+    };
+    let len = msg.encoded_len();
+    assert!(len >= 128);
+    let buf = msg.encode_length_delimited_to_vec();
+
+    // The two-byte delimiter is needed, but the message is not.
+    assert_eq!(prost::probe_length_delimited(&buf[..0]), Ok(None));
+    assert_eq!(prost::probe_length_delimited(&buf[..1]), Ok(None));
+    assert_eq!(prost::probe_length_delimited(&buf[..2]), Ok(Some(len)));
+    assert_eq!(prost::probe_length_delimited(&buf), Ok(Some(len)));
+    assert_eq!(prost::length_delimiter_len(len), 2);
+
+    assert!(prost::probe_length_delimited(&[0xFF; 10]).is_err());
+}
+
+#[test]
+fn check_length_delimited_with_fixed_prefix() {
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let len = msg.encoded_len();
+
+    for (prefix, width) in [(LengthPrefix::U32Le, 4), (LengthPrefix::U64Le, 8)] {
+        let mut buf = Vec::new();
+        msg.encode_length_delimited_with(&prefix, &mut buf).unwrap();
+        assert_eq!(buf.len(), width + len);
+        assert_eq!(buf[0] as usize, len);
+        assert!(buf[1..width].iter().all(|&byte| byte == 0));
+        assert_eq!(
+            MaxFieldNumber::decode_length_delimited_with(&prefix, &*buf).unwrap(),
+            msg
+        );
+
+        // A truncated prefix is rejected.
+        assert!(MaxFieldNumber::decode_length_delimited_with(&prefix, &buf[..width - 1]).is_err());
+    }
+}
+
+/// A framing which replaces encoded messages with 4 GiB of zeros, too long for a `u32` prefix.
+#[cfg(target_pointer_width = "64")]
+struct Oversized;
+
+#[cfg(target_pointer_width = "64")]
+impl Framing for Oversized {
+    fn length_prefix(&self) -> LengthPrefix {
+        LengthPrefix::U32Le
+    }
+
+    fn compress<'a>(&self, _encoded: &'a [u8]) -> Cow<'a, [u8]> {
+        // The zeroed allocation is not written to, so it takes no memory in practice.
+        Cow::Owned(vec![0; 1 << 32])
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn check_length_delimited_exceeding_prefix() {
+    assert!(LengthPrefix::U32Le.can_hold(u32::MAX as usize));
+    assert!(!LengthPrefix::U32Le.can_hold(1 << 32));
+    assert!(LengthPrefix::U64Le.can_hold(1 << 32));
+
+    let mut buf = Vec::new();
+    let error = MaxFieldNumber::default()
+        .encode_length_delimited_with(&Oversized, &mut buf)
+        .unwrap_err();
+    assert!(error.is_length_exceeding_prefix());
+    assert_eq!(error.required_capacity(), 1 << 32);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn check_decode_lenient() {
+    let mut buf = vec![0x08, 0x01];
+    // Field `last` holding invalid UTF-8 is well-formed on the wire, so only it is skipped.
+    buf.extend_from_slice(&[0xFA, 0xFF, 0xFF, 0xFF, 0x0F, 0x01, 0xFF]);
+    // A key with an invalid wire type is skipped up to the next well-formed field.
+    buf.extend_from_slice(&[0x0F, 0x08, 0x09]);
+    // A truncated trailing field has nothing to resynchronize on.
+    buf.extend_from_slice(&[0x12, 0x05, 0x01]);
+
+    assert!(MaxFieldNumber::decode(&*buf).is_err());
+
+    let (decoded, errors) = MaxFieldNumber::decode_lenient(&buf);
+    assert_eq!(
+        decoded,
+        MaxFieldNumber {
+            first: 9,
+            last: String::new(),
+        }
+    );
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0].to_string().contains("MaxFieldNumber.last"));
+
+    // A valid message decodes without errors.
+    let msg = MaxFieldNumber {
+        first: 1,
+        last: "last".to_owned(),
+    };
+    let (decoded, errors) = MaxFieldNumber::decode_lenient(&msg.encode_to_vec());
+    assert_eq!(decoded, msg);
+    assert!(errors.is_empty());
+
+    // Unterminated groups are skipped one key at a time, recovering the field which follows.
+    let mut buf = vec![0x0B; 10_000];
+    buf.extend_from_slice(&[0x08, 0x01]);
+    let (decoded, errors) = MaxFieldNumber::decode_lenient(&buf);
+    assert_eq!(decoded.first, 1);
+    assert_eq!(errors.len(), 10_000);
+}
+
+#[test]
+fn check_encode_chunks() {
+    let msg = MaxFieldNumber {
+        first: 150,
+        last: "x".repeat(100),
+    };
+    let encoded = msg.encode_to_vec();
+
+    for chunk_size in [1, 3, 64, 1000] {
+        let chunks = msg.encode_chunks(chunk_size).collect::<Vec<_>>();
+        let (last, init) = chunks.split_last().unwrap();
+        assert!(init.iter().all(|chunk| chunk.len() == chunk_size));
+        assert!(!last.is_empty() && last.len() <= chunk_size);
+        assert_eq!(chunks.concat(), encoded);
+    }
+
+    // An empty message yields no chunks.
+    assert_eq!(MaxFieldNumber::default().encode_chunks(16).count(), 0);
+}
+
+/// Passes through the submessages of [`DecodedEnvelope`] without decoding them.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct RawEnvelope {
+    #[prost(string, tag = "1")]
+    pub route: String,
+    #[prost(bytes = "raw", optional, tag = "2")]
+    pub payload: Option<RawMessage>,
+    #[prost(bytes = "raw", repeated, tag = "3")]
+    pub extra: Vec<RawMessage>,
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct DecodedEnvelope {
+    #[prost(string, tag = "1")]
+    pub route: String,
+    #[prost(message, optional, tag = "2")]
+    pub payload: Option<MaxFieldNumber>,
+    #[prost(message, repeated, tag = "3")]
+    pub extra: Vec<MaxFieldNumber>,
+}
+
+#[test]
+fn check_raw_message() {
+    let payload = MaxFieldNumber {
+        first: 1,
+        last: "payload".to_owned(),
+    };
+    let decoded = DecodedEnvelope {
+        route: "route".to_owned(),
+        payload: Some(payload.clone()),
+        extra: vec![MaxFieldNumber::default(), payload.clone()],
+    };
+    let buf = Bytes::from(decoded.encode_to_vec());
+
+    let raw = RawEnvelope::decode(buf.clone()).unwrap();
+    assert_eq!(raw.route, "route");
+    assert_eq!(raw.payload(), &payload.encode_to_vec()[..]);
+    assert_eq!(raw.extra.len(), 2);
+    assert!(raw.extra[0].is_empty());
+    assert_eq!(
+        MaxFieldNumber::decode(raw.extra[1].clone().into_bytes()).unwrap(),
+        payload
+    );
+
+    // Raw submessages are written back verbatim.
+    assert_eq!(raw.encode_to_vec(), buf);
+    check_message(&raw);
+
+    // An empty submessage is still present.
+    let empty = RawEnvelope {
+        payload: Some(RawMessage::default()),
+        ..RawEnvelope::default()
+    };
+    assert_eq!(
+        DecodedEnvelope::decode(&*empty.encode_to_vec()).unwrap(),
+        DecodedEnvelope {
+            payload: Some(MaxFieldNumber::default()),
+            ..DecodedEnvelope::default()
+        }
+    );
+}
+
+/// A message with a hand-written `Default`, which would conflict with a derived one.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+#[prost(skip_default)]
+pub struct SkipDefault {
+    #[prost(int32, tag = "1")]
+    pub value: i32,
+}
+
+impl Default for SkipDefault {
+    fn default() -> Self {
+        SkipDefault { value: 42 }
+    }
+}
+
+#[test]
+fn check_skip_default() {
+    assert_eq!(SkipDefault::default().value, 42);
+    check_message(&SkipDefault { value: 7 });
+}
+
+/// A message which encodes its fields in declaration order rather than by tag.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+#[prost(declaration_order)]
+pub struct DeclarationOrder {
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(int32, tag = "1")]
+    pub id: i32,
+}
+
+#[test]
+fn check_declaration_order() {
+    let msg = DeclarationOrder {
+        name: "a".to_string(),
+        id: 1,
+    };
+    assert_eq!(msg.encode_to_vec(), b"\x12\x01a\x08\x01");
+    check_message(&msg);
+}
+
+/// A message whose getters log each access in debug builds.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+#[prost(option_accessors, trace_field_access)]
+pub struct TraceFieldAccess {
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+    #[prost(enumeration = "BasicEnumeration", tag = "2")]
+    pub kind: i32,
+}
+
+#[test]
+fn check_trace_field_access() {
+    let msg = TraceFieldAccess {
+        name: Some("a".to_string()),
+        kind: BasicEnumeration::TWO as i32,
+    };
+    assert_eq!(msg.name(), "a");
+    assert_eq!(msg.name_opt(), Some("a"));
+    assert_eq!(msg.kind(), BasicEnumeration::TWO);
+    check_message(&msg);
+}
+
+/// A message with methods extending its repeated fields.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+#[prost(extend_methods)]
+pub struct ExtendMethods {
+    #[prost(int32, repeated, tag = "1")]
+    pub numbers: Vec<i32>,
+    #[prost(message, repeated, tag = "2")]
+    pub envelopes: Vec<DecodedEnvelope>,
+}
+
+#[test]
+fn check_extend_methods() {
+    let mut msg = ExtendMethods::default();
+    msg.extend_numbers(1..3);
+    msg.extend_numbers(vec![5]);
+    msg.extend_envelopes(["a", "b"].iter().map(|route| DecodedEnvelope {
+        route: route.to_string(),
+        ..DecodedEnvelope::default()
+    }));
+    assert_eq!(msg.numbers, vec![1, 2, 5]);
+    assert_eq!(msg.envelopes.len(), 2);
+    assert_eq!(msg.envelopes[1].route, "b");
+}
+
 #[test]
 fn check_scalar_types() {
     check_message(&ScalarTypes::default());
 }
 
+#[test]
+fn check_bytes_share_input_buffer() {
+    let msg = ScalarTypes {
+        bytes_buf: Bytes::from_static(b"first"),
+        repeated_bytes_buf: vec![Bytes::from_static(b"second"), Bytes::from_static(b"third")],
+        ..ScalarTypes::default()
+    };
+    let input = Bytes::from(msg.encode_to_vec());
+    let decoded = ScalarTypes::decode(input.clone()).unwrap();
+    assert_eq!(decoded, msg);
+
+    // `Bytes` fields decoded from a `Bytes` buffer are views into it rather than copies, so
+    // decoding many of them needs no allocation per field.
+    let input = input.as_ptr_range();
+    assert!(input.contains(&decoded.bytes_buf.as_ptr()));
+    for bytes in &decoded.repeated_bytes_buf {
+        assert!(input.contains(&bytes.as_ptr()));
+    }
+}
+
+/// A message whose bytes fields share the buffer they were decoded from.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct SharedBytesFields {
+    #[prost(bytes = "shared", tag = "1")]
+    pub plain: SharedBytes,
+    #[prost(bytes = "shared", optional, tag = "2", default = "b\"default\"")]
+    pub optional: Option<SharedBytes>,
+    #[prost(bytes = "shared", repeated, tag = "3")]
+    pub repeated: Vec<SharedBytes>,
+}
+
+#[test]
+fn check_shared_bytes() {
+    let msg = SharedBytesFields {
+        plain: SharedBytes::from(b"first".to_vec()),
+        optional: None,
+        repeated: vec![SharedBytes::from(&b"second"[..]), SharedBytes::new()],
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(SharedBytesFields::decode(&*buf).unwrap(), msg);
+    assert_eq!(msg.optional(), b"default");
+
+    // With `share_input`, the fields are slices of one copy of the input, lying as far apart as
+    // they do in the input.
+    let options = DecodeOptions::new().share_input(true);
+    let (decoded, _) = SharedBytesFields::decode_with_options(&*buf, &options).unwrap();
+    assert_eq!(decoded, msg);
+    assert!(!buf.as_ptr_range().contains(&decoded.plain.as_ptr()));
+    let distance = decoded.repeated[0].as_ptr() as usize - decoded.plain.as_ptr() as usize;
+    assert_eq!(distance, b"first\x1a\x06".len());
+
+    // A `Bytes` input is shared as is.
+    let input = Bytes::from(buf.clone());
+    let decoded = SharedBytesFields::decode(input.clone()).unwrap();
+    assert!(input.as_ptr_range().contains(&decoded.plain.as_ptr()));
+
+    // Values are used like `Vec<u8>`, copying out of the shared buffer when modified.
+    let mut plain = decoded.plain.clone();
+    plain.extend_from_slice(b"!");
+    plain.push(b'!');
+    assert_eq!(plain, b"first!!".to_vec());
+    assert_eq!(decoded.plain, &b"first"[..]);
+    plain.truncate(5);
+    assert_eq!(Vec::from(plain), b"first");
+}
+
 /// A protobuf message which contains all scalar types.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, Message)]
@@ -332,6 +1597,25 @@ pub enum BasicEnumeration {
     THREE = 3,
 }
 
+/// A protobuf enum implementing `TryFrom<i32>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+#[prost(try_from_i32, prost_path = "::prost")]
+pub enum TryFromEnumeration {
+    Zero = 0,
+    One = 1,
+}
+
+#[test]
+fn check_enumeration_try_from() {
+    use core::convert::TryFrom;
+
+    assert_eq!(TryFromEnumeration::try_from(1), Ok(TryFromEnumeration::One));
+    assert_eq!(
+        TryFromEnumeration::try_from(2),
+        Err(prost::UnknownEnumValue(2))
+    );
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, Message)]
 pub struct Basic {
@@ -374,6 +1658,7 @@ pub struct Basic {
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, Message)]
+#[prost(reserve_methods)]
 pub struct Compound {
     #[prost(message, optional, tag = "1")]
     pub optional_message: Option<Basic>,
@@ -392,6 +1677,116 @@ pub struct Compound {
     pub message_btree_map: prost::alloc::collections::BTreeMap<i32, Basic>,
 }
 
+#[test]
+fn check_decode_repeated_with() {
+    let basic = |int32| Basic {
+        int32,
+        ..Basic::default()
+    };
+    let msg = Compound {
+        required_message: basic(10),
+        repeated_message: vec![basic(1), basic(2), basic(3)],
+        ..Compound::default()
+    };
+    let buf = msg.encode_to_vec();
+
+    let mut elements = Vec::new();
+    prost::decode_repeated_with(&*buf, 3, |element: Basic| elements.push(element.int32)).unwrap();
+    assert_eq!(elements, [1, 2, 3]);
+
+    // An element with the wrong wire type is an error.
+    assert!(prost::decode_repeated_with(&[0x18, 0x01][..], 3, |_: Basic| ()).is_err());
+}
+
+#[test]
+fn check_decode_with_stats() {
+    let basic = |int32| Basic {
+        int32,
+        ..Basic::default()
+    };
+    let msg = Compound {
+        required_message: basic(10),
+        repeated_message: vec![basic(1), basic(2), basic(3)],
+        ..Compound::default()
+    };
+    let buf = msg.encode_to_vec();
+
+    let (decoded, stats) = Compound::decode_with_stats(&*buf).unwrap();
+    assert_eq!(decoded, msg);
+    assert_eq!(stats.keys().copied().collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(stats[&2].count, 1);
+    assert_eq!(stats[&3].count, 3);
+    // Each element of `repeated_message` takes a key, a length and an `int32` field.
+    assert_eq!(stats[&3].bytes, 3 * 4);
+    assert_eq!(
+        stats.values().map(|field| field.bytes).sum::<usize>(),
+        buf.len()
+    );
+}
+
+#[test]
+fn check_reserve() {
+    let mut msg = Compound::default();
+    msg.reserve_repeated_message(8);
+    assert!(msg.repeated_message.capacity() >= 8);
+
+    #[cfg(feature = "std")]
+    {
+        msg.reserve_message_map(8);
+        assert!(msg.message_map.capacity() >= 8);
+    }
+}
+
+/// A message with maps keyed by each of the fixed-width integer types.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct FixedKeyMaps {
+    #[prost(btree_map = "fixed32, string", tag = "1")]
+    pub fixed32: prost::alloc::collections::BTreeMap<u32, String>,
+    #[prost(btree_map = "fixed64, string", tag = "2")]
+    pub fixed64: prost::alloc::collections::BTreeMap<u64, String>,
+    #[prost(btree_map = "sfixed32, string", tag = "3")]
+    pub sfixed32: prost::alloc::collections::BTreeMap<i32, String>,
+    #[prost(btree_map = "sfixed64, string", tag = "4")]
+    pub sfixed64: prost::alloc::collections::BTreeMap<i64, String>,
+}
+
+#[test]
+fn check_fixed_key_maps() {
+    let mut msg = FixedKeyMaps::default();
+    for key in [0, 1, u32::MAX] {
+        msg.fixed32.insert(key, key.to_string());
+    }
+    for key in [0, 1, u64::MAX] {
+        msg.fixed64.insert(key, key.to_string());
+    }
+    for key in [i32::MIN, -1, 0, i32::MAX] {
+        msg.sfixed32.insert(key, key.to_string());
+    }
+    for key in [i64::MIN, -1, 0, i64::MAX] {
+        msg.sfixed64.insert(key, key.to_string());
+    }
+    check_message(&msg);
+
+    // Each map entry is a message whose key field is encoded with the fixed-width wire type
+    // (5 for 32-bit keys, 1 for 64-bit keys) rather than as a varint.
+    let mut msg = FixedKeyMaps::default();
+    msg.fixed32.insert(0x0403_0201, "a".to_string());
+    msg.fixed64.insert(0x0807_0605_0403_0201, "b".to_string());
+    msg.sfixed32.insert(-2, "c".to_string());
+    msg.sfixed64.insert(-2, "d".to_string());
+    assert_eq!(
+        msg.encode_to_vec(),
+        [
+            &[0x0A, 8, 0x0D, 0x01, 0x02, 0x03, 0x04, 0x12, 1, b'a'][..],
+            &[0x12, 12, 0x09, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x12, 1, b'b'],
+            &[0x1A, 8, 0x0D, 0xFE, 0xFF, 0xFF, 0xFF, 0x12, 1, b'c'],
+            &[0x22, 12, 0x09, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x12, 1, b'd'],
+        ]
+        .concat()
+    );
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, Oneof)]
 pub enum BasicOneof {
@@ -400,3 +1795,185 @@ pub enum BasicOneof {
     #[prost(string, tag = "9")]
     String(String),
 }
+
+macro_rules! wide_message {
+    ($($field:ident)*) => {
+        /// A message with a `uint64` field for each of the tags 1 to 200, and a packed repeated
+        /// field with tag 201.
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, Message)]
+        pub struct Wide {
+            $(
+                #[prost(uint64)]
+                pub $field: u64,
+            )*
+            #[prost(uint32, repeated)]
+            pub values: Vec<u32>,
+        }
+    };
+}
+
+wide_message! {
+    f1 f2 f3 f4 f5 f6 f7 f8 f9 f10
+    f11 f12 f13 f14 f15 f16 f17 f18 f19 f20
+    f21 f22 f23 f24 f25 f26 f27 f28 f29 f30
+    f31 f32 f33 f34 f35 f36 f37 f38 f39 f40
+    f41 f42 f43 f44 f45 f46 f47 f48 f49 f50
+    f51 f52 f53 f54 f55 f56 f57 f58 f59 f60
+    f61 f62 f63 f64 f65 f66 f67 f68 f69 f70
+    f71 f72 f73 f74 f75 f76 f77 f78 f79 f80
+    f81 f82 f83 f84 f85 f86 f87 f88 f89 f90
+    f91 f92 f93 f94 f95 f96 f97 f98 f99 f100
+    f101 f102 f103 f104 f105 f106 f107 f108 f109 f110
+    f111 f112 f113 f114 f115 f116 f117 f118 f119 f120
+    f121 f122 f123 f124 f125 f126 f127 f128 f129 f130
+    f131 f132 f133 f134 f135 f136 f137 f138 f139 f140
+    f141 f142 f143 f144 f145 f146 f147 f148 f149 f150
+    f151 f152 f153 f154 f155 f156 f157 f158 f159 f160
+    f161 f162 f163 f164 f165 f166 f167 f168 f169 f170
+    f171 f172 f173 f174 f175 f176 f177 f178 f179 f180
+    f181 f182 f183 f184 f185 f186 f187 f188 f189 f190
+    f191 f192 f193 f194 f195 f196 f197 f198 f199 f200
+}
+
+#[test]
+fn check_wide_message_out_of_order() {
+    let mut buf = Vec::new();
+    prost::encoding::uint32::encode_packed(201, &[1, 2], &mut buf);
+    for tag in (1..=200).rev() {
+        prost::encoding::uint64::encode(tag, &u64::from(tag), &mut buf);
+    }
+    // Later occurrences of singular fields replace earlier ones, and repeated fields append.
+    prost::encoding::uint64::encode(7, &700, &mut buf);
+    prost::encoding::uint32::encode_packed(201, &[3], &mut buf);
+
+    let msg = Wide::decode(buf.as_slice()).unwrap();
+    assert_eq!(msg.f1, 1);
+    assert_eq!(msg.f7, 700);
+    assert_eq!(msg.f100, 100);
+    assert_eq!(msg.f200, 200);
+    assert_eq!(msg.values, [1, 2, 3]);
+    check_message(&msg);
+}
+
+/// A message which keeps the fields it doesn't declare.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct PreservesUnknown {
+    #[prost(int32, tag = "1")]
+    pub id: i32,
+    #[prost(string, tag = "5")]
+    pub name: String,
+    #[prost(unknown_fields)]
+    pub unknown_fields: prost::UnknownFields,
+}
+
+#[test]
+fn check_unknown_fields() {
+    let mut buf = Vec::new();
+    prost::encoding::int32::encode(1, &7, &mut buf);
+    prost::encoding::string::encode(5, &"name".to_owned(), &mut buf);
+    // An unknown varint, padded to three bytes, and unknown 32-bit, length-delimited and group
+    // fields.
+    buf.extend_from_slice(b"\x10\x81\x80\x00");
+    prost::encoding::fixed32::encode(3, &4, &mut buf);
+    prost::encoding::bytes::encode(4, &b"abc".to_vec(), &mut buf);
+    buf.extend_from_slice(b"\x33\x08\x01\x34");
+
+    let mut msg = PreservesUnknown::decode(&*buf).unwrap();
+    assert_eq!(msg.id, 7);
+    assert_eq!(msg.name, "name");
+    let tags = msg.unknown_fields.iter().map(|(tag, _)| tag);
+    assert_eq!(tags.collect::<Vec<_>>(), vec![2, 3, 4, 6]);
+
+    // Unknown fields survive a round trip byte for byte, after the known fields.
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(msg.encode_to_vec(), buf);
+    msg.id = 8;
+    let decoded = PreservesUnknown::decode(&*msg.encode_to_vec()).unwrap();
+    assert_eq!(decoded, msg);
+
+    // Merging keeps the unknown fields of both messages, in the order they were decoded in.
+    let mut merged = msg.clone();
+    merged.merge_from(msg.clone());
+    let mut encoded = msg.clone();
+    encoded.merge(&*msg.encode_to_vec()).unwrap();
+    assert_eq!(merged, encoded);
+    let tags = merged.unknown_fields.iter().map(|(tag, _)| tag);
+    assert_eq!(tags.collect::<Vec<_>>(), vec![2, 3, 4, 6, 2, 3, 4, 6]);
+
+    msg.clear();
+    assert!(msg.unknown_fields.is_empty());
+    assert_eq!(msg, PreservesUnknown::default());
+
+    // A truncated unknown group is an error.
+    assert!(PreservesUnknown::decode(&buf[..buf.len() - 1]).is_err());
+
+    // Unknown fields out of tag order keep their order, and a group keeps the padded keys within
+    // it.
+    let buf = b"\x30\x00\x1d\x04\x00\x00\x00\x13\x88\x00\x01\x94\x00\x10\x05";
+    let msg = PreservesUnknown::decode(&buf[..]).unwrap();
+    let tags = msg.unknown_fields.iter().map(|(tag, _)| tag);
+    assert_eq!(tags.collect::<Vec<_>>(), vec![6, 3, 2, 2]);
+    assert_eq!(msg.encode_to_vec(), buf);
+}
+
+/// A message holding messages which keep unknown fields, in each kind of field.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Message)]
+pub struct HoldsUnknown {
+    #[prost(message, optional, tag = "1")]
+    pub single: Option<PreservesUnknown>,
+    #[prost(message, repeated, tag = "2")]
+    pub repeated: Vec<PreservesUnknown>,
+    #[prost(btree_map = "int32, message", tag = "3")]
+    pub map: prost::alloc::collections::BTreeMap<i32, PreservesUnknown>,
+    #[prost(oneof = "HoldsUnknownChoice", tags = "4")]
+    pub choice: Option<HoldsUnknownChoice>,
+    #[prost(unknown_fields)]
+    pub unknown_fields: prost::UnknownFields,
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, Oneof)]
+pub enum HoldsUnknownChoice {
+    #[prost(message, tag = "4")]
+    Nested(PreservesUnknown),
+}
+
+#[test]
+fn check_eq_known_fields() {
+    let mut nested = PreservesUnknown {
+        id: 1,
+        ..PreservesUnknown::default()
+    };
+    let plain = HoldsUnknown {
+        single: Some(nested.clone()),
+        repeated: vec![nested.clone()],
+        map: vec![(1, nested.clone())].into_iter().collect(),
+        choice: Some(HoldsUnknownChoice::Nested(nested.clone())),
+        ..HoldsUnknown::default()
+    };
+
+    // Each message, nested ones included, carries an unknown field.
+    nested.merge(&[0x10, 0x01][..]).unwrap();
+    let mut msg = HoldsUnknown {
+        single: Some(nested.clone()),
+        repeated: vec![nested.clone()],
+        map: vec![(1, nested.clone())].into_iter().collect(),
+        choice: Some(HoldsUnknownChoice::Nested(nested)),
+        ..HoldsUnknown::default()
+    };
+    msg.merge(&[0x48, 0x01][..]).unwrap();
+    assert_ne!(msg, plain);
+    assert!(msg.eq_known_fields(&plain));
+    assert!(plain.eq_known_fields(&msg));
+
+    // Known fields still count.
+    let mut other = plain.clone();
+    other.map.get_mut(&1).unwrap().name = "name".to_owned();
+    assert!(!msg.eq_known_fields(&other));
+
+    msg.clear_unknown_fields();
+    assert_eq!(msg, plain);
+}
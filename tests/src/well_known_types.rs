@@ -23,6 +23,28 @@ fn test_well_known_types() {
 }
 
 #[cfg(feature = "std")]
+#[test]
+fn test_bytes_field_shares_input() {
+    use prost::bytes::Bytes;
+    use prost::Message;
+
+    let msg = Test {
+        bytes: Bytes::from_static(b"payload"),
+        ..Test::default()
+    };
+    let input = Bytes::from(msg.encode_to_vec());
+
+    // Decoding from a `Bytes` buffer slices the field out of it, without copying.
+    let decoded = Test::decode(input.clone()).unwrap();
+    assert_eq!(decoded, msg);
+    assert!(input.as_ptr_range().contains(&decoded.bytes.as_ptr()));
+
+    // Decoding from any other buffer copies the field.
+    let decoded = Test::decode(&*input).unwrap();
+    assert_eq!(decoded, msg);
+    assert!(!input.as_ptr_range().contains(&decoded.bytes.as_ptr()));
+}
+
 #[test]
 fn test_timestamp() {
     use std::collections::HashSet;
@@ -69,3 +91,82 @@ mod include {
         "/wellknown_include/wellknown_include.rs"
     ));
 }
+
+#[test]
+fn test_duration_message() {
+    use core::time::Duration;
+    use prost::Message;
+
+    let duration = Duration::new(99, 42);
+    let expected = ::prost_types::Duration {
+        seconds: 99,
+        nanos: 42,
+    };
+    assert_eq!(duration.encode_to_vec(), expected.encode_to_vec());
+    assert_eq!(
+        Duration::decode(&*expected.encode_to_vec()).unwrap(),
+        duration
+    );
+    crate::check_message(&duration);
+
+    // Negative durations can't be represented.
+    let negative = ::prost_types::Duration {
+        seconds: -1,
+        nanos: 0,
+    };
+    assert!(Duration::decode(&*negative.encode_to_vec()).is_err());
+}
+
+#[test]
+fn test_cow_messages() {
+    use alloc::borrow::Cow;
+    use prost::Message;
+
+    let borrowed: Cow<'static, str> = Cow::Borrowed("hello");
+    let expected = alloc::string::String::from("hello").encode_to_vec();
+    assert_eq!(borrowed.encode_to_vec(), expected);
+    assert_eq!(borrowed.encoded_len(), expected.len());
+    let decoded = Cow::<str>::decode(&*expected).unwrap();
+    assert!(matches!(decoded, Cow::Owned(_)));
+    assert_eq!(decoded, "hello");
+    crate::check_message(&borrowed);
+
+    let borrowed: Cow<'static, [u8]> = Cow::Borrowed(b"\x00\x01");
+    let expected = alloc::vec![0u8, 1].encode_to_vec();
+    assert_eq!(borrowed.encode_to_vec(), expected);
+    assert_eq!(Cow::<[u8]>::decode(&*expected).unwrap(), borrowed);
+    crate::check_message(&borrowed);
+
+    // Empty values are omitted, as with the other wrappers.
+    assert!(Cow::<str>::Borrowed("").encode_to_vec().is_empty());
+    assert!(Cow::<[u8]>::Borrowed(&[]).encode_to_vec().is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_system_time_message() {
+    use prost::Message;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    for (time, expected) in [
+        (
+            UNIX_EPOCH + Duration::new(99, 42),
+            ::prost_types::Timestamp {
+                seconds: 99,
+                nanos: 42,
+            },
+        ),
+        (
+            UNIX_EPOCH - Duration::new(1, 500_000_000),
+            ::prost_types::Timestamp {
+                seconds: -2,
+                nanos: 500_000_000,
+            },
+        ),
+    ] {
+        assert_eq!(time.encode_to_vec(), expected.encode_to_vec());
+        let mut decoded = UNIX_EPOCH;
+        decoded.merge(&*expected.encode_to_vec()).unwrap();
+        assert_eq!(decoded, time);
+    }
+}